@@ -99,6 +99,10 @@ pub enum ErrorCode {
     InvalidAmountTransferred,
     #[msg("Integer overflow occured")]
     IntegerOverlow,
+    #[msg("Given duration is negative")]
+    NegativeDuration,
+    #[msg("Given amount is zero")]
+    ZeroAmount,
 }
 
 #[program]
@@ -117,6 +121,9 @@ pub mod pool {
         let now = ctx.accounts.clock.unix_timestamp;
 
         require!(topup_duration <= lockup_duration, TopupLongerThanLockup);
+        require!(topup_duration >= 0 && lockup_duration >= 0, NegativeDuration);
+        require!(target_amount > 0 && reward_amount > 0, ZeroAmount);
+        require!(now.checked_add(lockup_duration).is_some(), IntegerOverlow);
 
         let pool = &mut ctx.accounts.pool;
 
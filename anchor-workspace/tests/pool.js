@@ -254,4 +254,50 @@ describe('pool', () => {
       assert.ok(true);
     }
   });
+
+  it('Rejects a pool with a negative duration', async () => {
+    const administrator = anchor.web3.Keypair.generate();
+    const pool = anchor.web3.Keypair.generate();
+
+    const stakeMintToken = await poolClient.utils.createMint(provider);
+    const stakeMint = stakeMintToken.publicKey;
+
+    const [poolAuthority, bump] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        pool.publicKey.toBuffer(),
+        administrator.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+
+    const stakeVault = await poolClient.utils.createTokenAccount(
+      provider,
+      stakeMint,
+      poolAuthority
+    );
+
+    await assert.rejects(
+      program.rpc.initializePool(
+        bump,
+        new anchor.BN(-6),
+        new anchor.BN(-3),
+        new anchor.BN(10000),
+        new anchor.BN(100),
+        {
+          accounts: {
+            administratorAuthority: administrator.publicKey,
+            poolAuthority,
+            pool: pool.publicKey,
+            stakeMint,
+            stakeVault,
+            clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
+          },
+          signers: [administrator, pool],
+          instructions: [
+            await program.account.pool.createInstruction(pool),
+          ],
+        }),
+      (err) => err.code === 313
+    );
+  });
 });
@@ -0,0 +1,211 @@
+//! Accounts of the anchor build of the pool program (`anchor-workspace/programs/pool`), decoded
+//! without the anchor runtime.
+//!
+//! Anchor starts account data with a discriminator, the first 8 bytes of
+//! `sha256("account:<name>")`, followed by the borsh-serialized fields. Accounts may be larger
+//! than their fields, the rest is ignored like anchor does.
+
+use borsh::BorshDeserialize;
+use sha2::{Digest, Sha256};
+use solana_api_types::Pubkey;
+
+pub const DISCRIMINATOR_SIZE: usize = 8;
+
+/// Discriminator of the anchor account type `name`.
+pub fn discriminator(name: &str) -> [u8; DISCRIMINATOR_SIZE] {
+    let hash = Sha256::digest(format!("account:{}", name).as_bytes());
+    let mut discriminator = [0; DISCRIMINATOR_SIZE];
+    discriminator.copy_from_slice(&hash[..DISCRIMINATOR_SIZE]);
+    discriminator
+}
+
+/// The fields of account `data` of type `name`.
+fn strip_discriminator<'a>(data: &'a [u8], name: &str) -> anyhow::Result<&'a [u8]> {
+    if data.len() < DISCRIMINATOR_SIZE || data[..DISCRIMINATOR_SIZE] != discriminator(name) {
+        anyhow::bail!("not an anchor {} account", name);
+    }
+
+    Ok(&data[DISCRIMINATOR_SIZE..])
+}
+
+#[derive(BorshDeserialize)]
+struct RawPool {
+    administrator_authority: [u8; 32],
+    bump: u8,
+    genesis: i64,
+    topup_duration: i64,
+    lockup_duration: i64,
+    stake_acquired_amount: u64,
+    stake_target_amount: u64,
+    reward_amount: u64,
+    deposited_reward_amount: u64,
+    stake_mint: [u8; 32],
+    stake_vault: [u8; 32],
+}
+
+#[derive(BorshDeserialize)]
+struct RawTicket {
+    authority: [u8; 32],
+    pool: [u8; 32],
+    staked_amount: u64,
+    bump: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorPool {
+    pub administrator_authority: Pubkey,
+    /// Bump seed of the pool authority.
+    pub bump: u8,
+    pub genesis: i64,
+    pub topup_duration: i64,
+    pub lockup_duration: i64,
+    pub stake_acquired_amount: u64,
+    pub stake_target_amount: u64,
+    pub reward_amount: u64,
+    pub deposited_reward_amount: u64,
+    pub stake_mint: Pubkey,
+    pub stake_vault: Pubkey,
+}
+
+impl AnchorPool {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let raw = RawPool::deserialize(&mut strip_discriminator(data, "Pool")?)?;
+
+        Ok(Self {
+            administrator_authority: Pubkey::new(raw.administrator_authority),
+            bump: raw.bump,
+            genesis: raw.genesis,
+            topup_duration: raw.topup_duration,
+            lockup_duration: raw.lockup_duration,
+            stake_acquired_amount: raw.stake_acquired_amount,
+            stake_target_amount: raw.stake_target_amount,
+            reward_amount: raw.reward_amount,
+            deposited_reward_amount: raw.deposited_reward_amount,
+            stake_mint: Pubkey::new(raw.stake_mint),
+            stake_vault: Pubkey::new(raw.stake_vault),
+        })
+    }
+
+    /// Whether stakes are still accepted at `now`.
+    pub fn can_topup(&self, now: i64) -> bool {
+        now < self.genesis.saturating_add(self.topup_duration)
+    }
+
+    /// Whether rewards can be claimed at `now`.
+    pub fn is_expired(&self, now: i64) -> bool {
+        now > self.genesis.saturating_add(self.lockup_duration)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorTicket {
+    pub authority: Pubkey,
+    pub pool: Pubkey,
+    pub staked_amount: u64,
+    /// Bump seed of the ticket address, derived from the pool and the staker.
+    pub bump: u8,
+}
+
+impl AnchorTicket {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let raw = RawTicket::deserialize(&mut strip_discriminator(data, "Ticket")?)?;
+
+        Ok(Self {
+            authority: Pubkey::new(raw.authority),
+            pool: Pubkey::new(raw.pool),
+            staked_amount: raw.staked_amount,
+            bump: raw.bump,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_api_types::Pubkey;
+
+    use super::{discriminator, AnchorPool, AnchorTicket};
+
+    /// A pool created at 1630000000 with a 200s topup and a 1000s lockup, as laid out by anchor.
+    fn pool_blob() -> Vec<u8> {
+        [
+            &[241, 154, 109, 4, 17, 177, 109, 188][..],
+            &[1; 32],
+            &[254],
+            &1_630_000_000i64.to_le_bytes(),
+            &200i64.to_le_bytes(),
+            &1000i64.to_le_bytes(),
+            &150u64.to_le_bytes(),
+            &10_000u64.to_le_bytes(),
+            &1000u64.to_le_bytes(),
+            &400u64.to_le_bytes(),
+            &[2; 32],
+            &[3; 32],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn anchor_pool_test() {
+        assert_eq!(discriminator("Pool"), [241, 154, 109, 4, 17, 177, 109, 188]);
+
+        let pool = AnchorPool::decode(&pool_blob()).unwrap();
+        assert_eq!(
+            pool,
+            AnchorPool {
+                administrator_authority: Pubkey::new([1; 32]),
+                bump: 254,
+                genesis: 1_630_000_000,
+                topup_duration: 200,
+                lockup_duration: 1000,
+                stake_acquired_amount: 150,
+                stake_target_amount: 10_000,
+                reward_amount: 1000,
+                deposited_reward_amount: 400,
+                stake_mint: Pubkey::new([2; 32]),
+                stake_vault: Pubkey::new([3; 32]),
+            }
+        );
+
+        assert!(pool.can_topup(1_630_000_199));
+        assert!(!pool.can_topup(1_630_000_200));
+        assert!(!pool.is_expired(1_630_001_000));
+        assert!(pool.is_expired(1_630_001_001));
+
+        // space allocated past the fields is ignored
+        let mut padded = pool_blob();
+        padded.resize(padded.len() + 64, 0);
+        assert_eq!(AnchorPool::decode(&padded).unwrap(), pool);
+
+        let blob = pool_blob();
+        assert!(AnchorPool::decode(&blob[..blob.len() - 1]).is_err());
+        assert!(AnchorTicket::decode(&blob).is_err());
+        assert!(AnchorPool::decode(&[]).is_err());
+    }
+
+    #[test]
+    fn anchor_ticket_test() {
+        let blob = [
+            &[41, 228, 24, 165, 78, 90, 235, 200][..],
+            &[4; 32],
+            &[5; 32],
+            &150u64.to_le_bytes(),
+            &[253],
+        ]
+        .concat();
+        assert_eq!(
+            discriminator("Ticket"),
+            [41, 228, 24, 165, 78, 90, 235, 200]
+        );
+
+        assert_eq!(
+            AnchorTicket::decode(&blob).unwrap(),
+            AnchorTicket {
+                authority: Pubkey::new([4; 32]),
+                pool: Pubkey::new([5; 32]),
+                staked_amount: 150,
+                bump: 253,
+            }
+        );
+        assert!(AnchorPool::decode(&blob).is_err());
+    }
+}
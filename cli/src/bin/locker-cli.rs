@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use cli::{
+    config::CliConfig,
+    format_relative,
+    store::{ClientStore, DEFAULT_STORE_PATH},
+    Cluster, SolanaClient, SolanaClientConfig,
+};
+use solana_api_types::{Account, CommitmentConfig, CommitmentLevel, Pubkey};
+use solar::{account::AccountFields, spl::WalletAccount, time::SolTimestamp};
+use structopt::StructOpt;
+use token_locker::TokenLockEntity;
+use zeroize::Zeroizing;
+
+#[derive(Debug, StructOpt)]
+struct Opts {
+    /// JSON file with defaults for the options below, see `cli::config`.
+    #[structopt(long, env = cli::config::CONFIG_PATH_ENV)]
+    config: Option<PathBuf>,
+    /// Locker program id.
+    #[structopt(long, env = "X5_LOCKER_PROGRAM_ID")]
+    program_id: Option<Pubkey>,
+    /// Cluster to connect to, devnet by default.
+    #[structopt(long, env = "X5_CLUSTER")]
+    cluster: Option<Cluster>,
+    /// Commitment of reads and blockhashes, finalized by default.
+    #[structopt(long, parse(try_from_str = cli::config::parse_commitment))]
+    commitment: Option<CommitmentLevel>,
+    /// Path to the local client store, `store.json` by default.
+    #[structopt(long)]
+    store: Option<PathBuf>,
+    /// Passphrase the store is encrypted with, the store is kept in plaintext if not set.
+    #[structopt(long, env = "X5_STORE_PASSPHRASE", hide_env_values = true)]
+    store_passphrase: Option<String>,
+    /// Simulate transactions and print their logs instead of sending them.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Send transactions without the node's preflight simulation, for endpoints where it fails
+    /// spuriously.
+    #[structopt(long)]
+    skip_preflight: bool,
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Print the state of every locker in the client store.
+    ListLockers,
+}
+
+async fn list_lockers(
+    client: &SolanaClient,
+    program_id: &Pubkey,
+    store: &ClientStore,
+) -> anyhow::Result<()> {
+    if store.lockers.is_empty() {
+        println!("no lockers in store");
+        return Ok(());
+    }
+
+    let clock = client.clock().await?;
+    let now = SolTimestamp::from(clock.unix_timestamp);
+
+    let lockers = client
+        .load_many::<TokenLockEntity<Box<Account>>>(&store.lockers, None)
+        .await?;
+    let lockers: Vec<_> = store
+        .lockers
+        .iter()
+        .zip(lockers)
+        .filter_map(|(key, locker)| match locker {
+            Ok(locker) if locker.account().owner() == program_id => Some((key, locker)),
+            Ok(_) => {
+                println!("locker {}: not owned by program {}", key, program_id);
+                None
+            }
+            Err(err) => {
+                println!("locker {}: failed to load: {}", key, err);
+                None
+            }
+        })
+        .collect();
+
+    let vault_keys: Vec<_> = lockers.iter().map(|(_, locker)| locker.vault).collect();
+    let vaults = client
+        .load_many::<WalletAccount<Box<Account>>>(&vault_keys, None)
+        .await?;
+
+    for ((key, locker), vault) in lockers.into_iter().zip(vaults) {
+        let vault = vault?;
+
+        println!("locker {}", key);
+        println!("  owner:        {}", locker.owner);
+        println!("  mint:         {}", locker.mint);
+        println!("  vault:        {}", locker.vault);
+        println!("  balance:      {}", vault.amount().value());
+        println!(
+            "  unlock date:  {} ({})",
+            locker.release_date,
+            format_relative(now, locker.release_date)
+        );
+        println!(
+            "  withdrawable: {}",
+            if locker.can_withdraw(clock.unix_timestamp.into()) {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut opts = Opts::from_args();
+    let passphrase = opts.store_passphrase.take().map(Zeroizing::new);
+
+    let config = CliConfig::load(opts.config.as_deref())?;
+    let program_id = opts
+        .program_id
+        .or(config.locker_program_id)
+        .ok_or_else(|| anyhow::anyhow!("no locker program id, pass --program-id"))?;
+    let store = opts
+        .store
+        .or(config.store)
+        .unwrap_or_else(|| DEFAULT_STORE_PATH.into());
+
+    let mut client_config = SolanaClientConfig {
+        skip_preflight: opts.skip_preflight,
+        ..Default::default()
+    };
+    if let Some(commitment) = opts.commitment.or(config.commitment) {
+        client_config.commitment = CommitmentConfig { commitment };
+    }
+    let client = SolanaClient::with_cluster(opts.cluster.or(config.cluster).unwrap_or_default())
+        .with_config(client_config)
+        .dry_run(opts.dry_run);
+    let store = ClientStore::load_with(&store, passphrase.as_deref().map(String::as_str))?;
+
+    match opts.cmd {
+        Command::ListLockers => list_lockers(&client, &program_id, &store).await?,
+    }
+
+    Ok(())
+}
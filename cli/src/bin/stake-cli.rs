@@ -0,0 +1,718 @@
+use std::path::PathBuf;
+
+use cli::{
+    builder::PoolTransactionBuilder,
+    config::CliConfig,
+    default_keypair_path, read_keypair_file,
+    store::{ClientStore, TicketRecord, DEFAULT_STORE_PATH},
+    summary::KnownPrograms,
+    Cluster, SolanaClient, SolanaClientConfig,
+};
+use fixed::types::U64F64;
+use solana_api_types::{
+    system::create_account, Account, CommitmentConfig, CommitmentLevel, Keypair, Pubkey, Signer,
+};
+use solar::spl::{create_mint, mint_to, mint_to_cap, MintAccount};
+use structopt::StructOpt;
+use x5margin_program::{
+    data::AccountType,
+    instructions::{AddRewardAccounts, SetAdministratorAccounts, StakeAccounts, WithdrawAccounts},
+    simple_stake::{
+        self, derive_pool_authority, InitializeArgs, StakePoolEntity, StakerTicket,
+        POOL_FLAG_FEE_ON_TRANSFER, POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE,
+    },
+};
+use zeroize::Zeroizing;
+
+#[derive(Debug, StructOpt)]
+struct Opts {
+    /// JSON file with defaults for the options below, see `cli::config`.
+    #[structopt(long, env = cli::config::CONFIG_PATH_ENV)]
+    config: Option<PathBuf>,
+    /// Stake program id.
+    #[structopt(long, env = "X5_STAKE_PROGRAM_ID")]
+    program_id: Option<Pubkey>,
+    /// Cluster to connect to, devnet by default.
+    #[structopt(long, env = "X5_CLUSTER")]
+    cluster: Option<Cluster>,
+    /// Commitment of reads and blockhashes, finalized by default.
+    #[structopt(long, parse(try_from_str = cli::config::parse_commitment))]
+    commitment: Option<CommitmentLevel>,
+    /// Path to the local client store, `store.json` by default.
+    #[structopt(long)]
+    store: Option<PathBuf>,
+    /// Passphrase the store is encrypted with, the store is kept in plaintext if not set.
+    #[structopt(long, env = "X5_STORE_PASSPHRASE", hide_env_values = true)]
+    store_passphrase: Option<String>,
+    /// Fee payer and signing authority, defaults to the Solana CLI keypair.
+    #[structopt(long, env = "X5_PAYER")]
+    payer: Option<PathBuf>,
+    /// Simulate transactions and print their logs instead of sending them.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Send transactions without the node's preflight simulation, for endpoints where it fails
+    /// spuriously.
+    #[structopt(long)]
+    skip_preflight: bool,
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create a token mint with the payer as its mint authority, e.g. the stake mint of a pool.
+    CreateMint {
+        /// Decimals of the mint, 9 matches native SOL.
+        #[structopt(long, default_value = "6")]
+        decimals: u8,
+        /// Authority allowed to freeze wallets of the mint, none if not set.
+        #[structopt(long)]
+        freeze_authority: Option<Pubkey>,
+        /// Keypair of the mint account, a fresh one if not set.
+        #[structopt(long)]
+        mint_keypair: Option<PathBuf>,
+    },
+    /// Mint tokens of a mint the payer is the authority of into `target_wallet`.
+    MintTokens {
+        #[structopt(long)]
+        mint: Pubkey,
+        #[structopt(long)]
+        target_wallet: Pubkey,
+        #[structopt(long)]
+        amount: u64,
+        /// Mint only what keeps the supply within `cap`, so running the command again doesn't
+        /// over-issue.
+        #[structopt(long)]
+        cap: Option<u64>,
+    },
+    /// Create a new stake pool administered by the payer.
+    Initialize {
+        #[structopt(long)]
+        stake_mint: Pubkey,
+        #[structopt(long)]
+        lockup_duration: i64,
+        #[structopt(long)]
+        topup_duration: i64,
+        #[structopt(long)]
+        reward_amount: u64,
+        #[structopt(long)]
+        target_amount: u64,
+        /// Credit stakes with what reaches the vault, for mints taking a fee on transfer.
+        #[structopt(long)]
+        fee_on_transfer: bool,
+        /// Reject stakes until the whole reward has been deposited.
+        #[structopt(long)]
+        require_funded_before_stake: bool,
+        /// Keypair of the pool account, a fresh one if not set.
+        #[structopt(long)]
+        pool_keypair: Option<PathBuf>,
+        /// Keypair of the stake vault, a fresh one if not set. A vault left behind by an earlier
+        /// run for the same pool keypair is reused rather than created again.
+        #[structopt(long)]
+        stake_vault_keypair: Option<PathBuf>,
+    },
+    /// Stake tokens from `source_wallet` into the pool.
+    Stake {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        source_wallet: Pubkey,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Withdraw staked tokens into `target_wallet` while the pool is still open for top-ups.
+    Unstake {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        target_wallet: Pubkey,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Like `unstake`, but withdraws `bps` basis points of the stake, 10000 withdraws all of it.
+    UnstakePercent {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        target_wallet: Pubkey,
+        #[structopt(long)]
+        bps: u16,
+    },
+    /// Deposit reward tokens from `source_wallet` into the pool.
+    AddReward {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        source_wallet: Pubkey,
+        #[structopt(long)]
+        amount: u64,
+    },
+    /// Withdraw the stake and its share of the reward into `target_wallet` after the lockup ends.
+    ClaimReward {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        target_wallet: Pubkey,
+    },
+    /// Hand the pool administered by the payer over to `new_administrator`.
+    SetAdministrator {
+        #[structopt(long)]
+        pool: Pubkey,
+        #[structopt(long)]
+        new_administrator: Pubkey,
+    },
+    /// Show the pool parameters and its reward rate.
+    PoolInfo {
+        #[structopt(long)]
+        pool: Pubkey,
+    },
+}
+
+struct StakeCli {
+    client: SolanaClient,
+    program_id: Pubkey,
+    payer: Keypair,
+    store: ClientStore,
+}
+
+impl StakeCli {
+    async fn load_pool(&self, pool: &Pubkey) -> anyhow::Result<StakePoolEntity<Box<Account>>> {
+        let account = self.client.load::<Box<Account>>(pool, None).await?;
+        StakePoolEntity::load(&self.program_id, account)
+            .map_err(|err| anyhow::anyhow!("failed to load pool {}: {}", pool, err))
+    }
+
+    async fn create_mint(
+        &self,
+        decimals: u8,
+        freeze_authority: Option<Pubkey>,
+        mint: Option<Keypair>,
+    ) -> anyhow::Result<()> {
+        let authority = self.payer.pubkey();
+        let mint = mint.unwrap_or_else(Keypair::new);
+        self.client
+            .ensure_accounts_missing(&[mint.pubkey()])
+            .await?;
+
+        let instructions = create_mint(
+            &authority,
+            &mint.pubkey(),
+            &authority,
+            freeze_authority.as_ref(),
+            decimals,
+        );
+        let signature = self
+            .client
+            .send_instructions(&instructions, &authority, &[&self.payer, &mint])
+            .await?;
+
+        println!(
+            "mint:             {} ({} decimals)",
+            mint.pubkey(),
+            decimals
+        );
+        if let Some(freeze_authority) = freeze_authority {
+            println!("freeze authority: {}", freeze_authority);
+        }
+        println!("signature:        {}", signature);
+
+        Ok(())
+    }
+
+    async fn mint_tokens(
+        &self,
+        mint: Pubkey,
+        target_wallet: Pubkey,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> anyhow::Result<()> {
+        let authority = self.payer.pubkey();
+        let instruction = match cap {
+            Some(cap) => {
+                let state = self
+                    .client
+                    .load::<MintAccount<Box<Account>>>(&mint, None)
+                    .await?;
+                match mint_to_cap(&mint, &state, &target_wallet, &authority, amount, cap) {
+                    Some(instruction) => instruction,
+                    None => {
+                        println!("supply of {} already at its cap of {}", mint, cap);
+                        return Ok(());
+                    }
+                }
+            }
+            None => mint_to(&mint, &target_wallet, &authority, amount),
+        };
+
+        let signature = self
+            .client
+            .send_instructions(&[instruction], &authority, &[&self.payer])
+            .await?;
+
+        println!("signature: {}", signature);
+
+        Ok(())
+    }
+
+    async fn initialize(
+        &mut self,
+        args: InitializeArgs,
+        stake_mint: Pubkey,
+        pool: Option<Keypair>,
+        stake_vault: Option<Keypair>,
+    ) -> anyhow::Result<()> {
+        let administrator = self.payer.pubkey();
+        let pool = pool.unwrap_or_else(Keypair::new);
+        let (program_authority, _) =
+            derive_pool_authority(&self.program_id, &pool.pubkey(), &administrator);
+
+        let builder = PoolTransactionBuilder::new(self.program_id, administrator, stake_mint, args)
+            .pool_key(pool);
+        let builder = match stake_vault {
+            Some(stake_vault) => {
+                if self
+                    .client
+                    .wallet_exists(&stake_vault.pubkey(), &stake_mint, &program_authority)
+                    .await?
+                {
+                    println!("reusing stake vault {}", stake_vault.pubkey());
+                    builder.existing_stake_vault(stake_vault.pubkey())
+                } else {
+                    builder.stake_vault_key(stake_vault)
+                }
+            }
+            None => builder,
+        };
+        let transaction = builder.build();
+
+        // the transaction signs for every account it creates
+        let created: Vec<_> = transaction.signers.iter().map(Signer::pubkey).collect();
+        self.client.ensure_accounts_missing(&created).await?;
+
+        let mut signers: Vec<&dyn Signer> = vec![&self.payer];
+        signers.extend(
+            transaction
+                .signers
+                .iter()
+                .map(|signer| signer as &dyn Signer),
+        );
+
+        let signature = self
+            .client
+            .send_instructions(&transaction.instructions, &administrator, &signers)
+            .await?;
+
+        self.store.pools.push(transaction.pool);
+
+        println!("pool:        {}", transaction.pool);
+        println!("stake vault: {}", transaction.stake_vault);
+        println!("signature:   {}", signature);
+
+        Ok(())
+    }
+
+    async fn stake(
+        &mut self,
+        pool: Pubkey,
+        source_wallet: Pubkey,
+        amount: u64,
+    ) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let staker = self.payer.pubkey();
+
+        let mut instructions = vec![];
+        let mut new_ticket = None;
+
+        let ticket = match self.store.find_ticket(&pool, &staker) {
+            Some(record) => record.ticket,
+            None => {
+                // tickets are initialized by the program on first stake, so they only need to
+                // be allocated here
+                let ticket = Keypair::new();
+                instructions.push(create_account(
+                    &staker,
+                    &ticket.pubkey(),
+                    StakerTicket::default_lamports(),
+                    StakerTicket::default_size() as u64,
+                    &self.program_id,
+                ));
+
+                let key = ticket.pubkey();
+                new_ticket = Some(ticket);
+                key
+            }
+        };
+
+        instructions.push(x5margin_program::instructions::stake(
+            self.program_id,
+            &StakeAccounts {
+                pool,
+                staker,
+                ticket,
+                stake_vault: entity.stake_vault,
+                source_authority: staker,
+                source_wallet,
+            },
+            amount.into(),
+        ));
+
+        let mut signers: Vec<&dyn Signer> = vec![&self.payer];
+        if let Some(ticket) = &new_ticket {
+            signers.push(ticket);
+        }
+
+        // a ticket is only remembered once the transaction creating it is confirmed, a dropped
+        // or failed one leaves nothing behind
+        let signature = self
+            .client
+            .send_and_confirm_instructions(
+                &instructions,
+                &staker,
+                &signers,
+                self.client.config().commitment,
+            )
+            .await?;
+
+        if new_ticket.is_some() {
+            self.store.tickets.push(TicketRecord {
+                pool,
+                staker,
+                ticket,
+            });
+        }
+
+        println!("ticket:    {}", ticket);
+        println!("signature: {}", signature);
+
+        Ok(())
+    }
+
+    /// `Unstake` and `ClaimReward` share the same account layout.
+    async fn withdraw(
+        &mut self,
+        pool: Pubkey,
+        target_wallet: Pubkey,
+        method: simple_stake::Method,
+    ) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let staker = self.payer.pubkey();
+        let ticket = self
+            .store
+            .find_ticket(&pool, &staker)
+            .ok_or_else(|| anyhow::anyhow!("no ticket for {} in pool {}", staker, pool))?
+            .ticket;
+
+        let accounts = WithdrawAccounts {
+            pool,
+            ticket,
+            staker,
+            program_authority: entity.program_authority,
+            stake_vault: entity.stake_vault,
+            target_wallet,
+        };
+        let instruction = match method {
+            simple_stake::Method::Unstake { amount } => {
+                x5margin_program::instructions::unstake(self.program_id, &accounts, amount)
+            }
+            simple_stake::Method::UnstakePercent { bps } => {
+                x5margin_program::instructions::unstake_percent(self.program_id, &accounts, bps)
+            }
+            simple_stake::Method::ClaimReward => {
+                x5margin_program::instructions::claim_reward(self.program_id, &accounts)
+            }
+            method => anyhow::bail!("{} is not a withdrawal", method),
+        };
+
+        let signature = self
+            .client
+            .send_and_confirm_instructions(
+                &[instruction],
+                &staker,
+                &[&self.payer],
+                self.client.config().commitment,
+            )
+            .await?;
+
+        // a full unstake or claim closes the ticket, the next stake has to create a new one
+        if !self.client.is_dry_run() && !self.client.account_exists(&ticket).await? {
+            self.store.tickets.retain(|record| record.ticket != ticket);
+        }
+
+        println!("signature: {}", signature);
+
+        Ok(())
+    }
+
+    /// Print what claiming the reward of the payer's ticket would transfer right now.
+    async fn preview_claim(&self, pool: Pubkey) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let staker = self.payer.pubkey();
+        let record = self
+            .store
+            .find_ticket(&pool, &staker)
+            .ok_or_else(|| anyhow::anyhow!("no ticket for {} in pool {}", staker, pool))?;
+
+        let ticket = self
+            .client
+            .load::<Box<Account>>(&record.ticket, None)
+            .await?;
+        let ticket = entity
+            .load_ticket(ticket)
+            .map_err(|err| anyhow::anyhow!("failed to load ticket {}: {}", record.ticket, err))?;
+        let now = self.client.clock().await?.unix_timestamp;
+
+        match ticket.claimable(&entity, now.into()) {
+            Some((principal, reward)) => println!(
+                "claiming {} staked + {} reward",
+                principal.value(),
+                reward.value()
+            ),
+            None => println!("the pool is still locked, the claim is expected to fail"),
+        }
+
+        Ok(())
+    }
+
+    async fn add_reward(
+        &mut self,
+        pool: Pubkey,
+        source_wallet: Pubkey,
+        amount: u64,
+    ) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let authority = self.payer.pubkey();
+
+        let instruction = x5margin_program::instructions::add_reward(
+            self.program_id,
+            &AddRewardAccounts {
+                pool,
+                stake_vault: entity.stake_vault,
+                source_authority: authority,
+                source_wallet,
+            },
+            amount.into(),
+        );
+
+        let signature = self
+            .client
+            .send_instructions(&[instruction], &authority, &[&self.payer])
+            .await?;
+
+        println!("signature: {}", signature);
+
+        Ok(())
+    }
+
+    async fn set_administrator(
+        &mut self,
+        pool: Pubkey,
+        new_administrator: Pubkey,
+    ) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let administrator = self.payer.pubkey();
+
+        if entity.administrator_authority != administrator {
+            anyhow::bail!(
+                "pool {} is administered by {}",
+                pool,
+                entity.administrator_authority
+            );
+        }
+
+        let instruction = x5margin_program::instructions::set_administrator(
+            self.program_id,
+            &SetAdministratorAccounts {
+                pool,
+                administrator,
+                new_administrator,
+            },
+        );
+
+        let signature = self
+            .client
+            .send_instructions(&[instruction], &administrator, &[&self.payer])
+            .await?;
+
+        println!("signature: {}", signature);
+
+        Ok(())
+    }
+
+    async fn pool_info(&self, pool: Pubkey) -> anyhow::Result<()> {
+        let entity = self.load_pool(&pool).await?;
+        let now = self.client.clock().await?;
+        let percent = |rate: Option<U64F64>| match rate {
+            Some(rate) => format!("{:.2}%", rate.to_num::<f64>() * 100.0),
+            None => "n/a".to_string(),
+        };
+
+        println!("administrator: {}", entity.administrator_authority);
+        println!("stake mint:    {}", entity.stake_mint);
+        println!("stake vault:   {}", entity.stake_vault);
+        println!(
+            "staked:        {} of {}",
+            entity.stake_acquired_amount.value(),
+            entity.stake_target_amount.value()
+        );
+        println!(
+            "reward:        {} ({} deposited)",
+            entity.reward_amount.value(),
+            entity.deposited_reward_amount.value()
+        );
+        println!("topup:         {}s", entity.topup_duration().value());
+        println!("lockup:        {}s", entity.lockup_duration().value());
+        println!(
+            "state:         {}",
+            entity.activation_state(now.unix_timestamp.into())
+        );
+        println!("apr:           {}", percent(entity.apr()));
+        println!("apy:           {}", percent(entity.apy()));
+
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut opts = Opts::from_args();
+    let passphrase = opts.store_passphrase.take().map(Zeroizing::new);
+
+    let config = CliConfig::load(opts.config.as_deref())?;
+    let program_id = opts
+        .program_id
+        .or(config.stake_program_id)
+        .ok_or_else(|| anyhow::anyhow!("no stake program id, pass --program-id"))?;
+    let payer = opts
+        .payer
+        .or(config.keypair_path)
+        .unwrap_or_else(default_keypair_path);
+    let store = opts
+        .store
+        .or(config.store)
+        .unwrap_or_else(|| DEFAULT_STORE_PATH.into());
+
+    let mut client_config = SolanaClientConfig {
+        skip_preflight: opts.skip_preflight,
+        ..Default::default()
+    };
+    if let Some(commitment) = opts.commitment.or(config.commitment) {
+        client_config.commitment = CommitmentConfig { commitment };
+    }
+
+    let mut cli = StakeCli {
+        client: SolanaClient::with_cluster(opts.cluster.or(config.cluster).unwrap_or_default())
+            .with_config(client_config)
+            .dry_run(opts.dry_run)
+            .known_programs(KnownPrograms {
+                stake: Some(program_id),
+                locker: None,
+            }),
+        program_id,
+        payer: read_keypair_file(payer)?,
+        store: ClientStore::load_with(&store, passphrase.as_deref().map(String::as_str))?,
+    };
+
+    match opts.cmd {
+        Command::CreateMint {
+            decimals,
+            freeze_authority,
+            mint_keypair,
+        } => {
+            let mint = mint_keypair.map(read_keypair_file).transpose()?;
+            cli.create_mint(decimals, freeze_authority, mint).await?
+        }
+        Command::MintTokens {
+            mint,
+            target_wallet,
+            amount,
+            cap,
+        } => cli.mint_tokens(mint, target_wallet, amount, cap).await?,
+        Command::Initialize {
+            stake_mint,
+            lockup_duration,
+            topup_duration,
+            reward_amount,
+            target_amount,
+            fee_on_transfer,
+            require_funded_before_stake,
+            pool_keypair,
+            stake_vault_keypair,
+        } => {
+            let mut flags = 0;
+            if fee_on_transfer {
+                flags |= POOL_FLAG_FEE_ON_TRANSFER;
+            }
+            if require_funded_before_stake {
+                flags |= POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE;
+            }
+
+            let args = InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: lockup_duration.into(),
+                topup_duration: topup_duration.into(),
+                reward_amount: reward_amount.into(),
+                target_amount: target_amount.into(),
+                flags,
+                reward_tranches: vec![],
+            };
+
+            let pool = pool_keypair.map(read_keypair_file).transpose()?;
+            let stake_vault = stake_vault_keypair.map(read_keypair_file).transpose()?;
+            cli.initialize(args, stake_mint, pool, stake_vault).await?
+        }
+        Command::Stake {
+            pool,
+            source_wallet,
+            amount,
+        } => cli.stake(pool, source_wallet, amount).await?,
+        Command::Unstake {
+            pool,
+            target_wallet,
+            amount,
+        } => {
+            let method = simple_stake::Method::Unstake {
+                amount: amount.into(),
+            };
+
+            cli.withdraw(pool, target_wallet, method).await?
+        }
+        Command::UnstakePercent {
+            pool,
+            target_wallet,
+            bps,
+        } => {
+            cli.withdraw(
+                pool,
+                target_wallet,
+                simple_stake::Method::UnstakePercent { bps },
+            )
+            .await?
+        }
+        Command::AddReward {
+            pool,
+            source_wallet,
+            amount,
+        } => cli.add_reward(pool, source_wallet, amount).await?,
+        Command::ClaimReward {
+            pool,
+            target_wallet,
+        } => {
+            cli.preview_claim(pool).await?;
+            cli.withdraw(pool, target_wallet, simple_stake::Method::ClaimReward)
+                .await?
+        }
+        Command::SetAdministrator {
+            pool,
+            new_administrator,
+        } => cli.set_administrator(pool, new_administrator).await?,
+        Command::PoolInfo { pool } => cli.pool_info(pool).await?,
+    }
+
+    // nothing was created in a dry run, so there is nothing to remember
+    if !cli.client.is_dry_run() {
+        cli.store
+            .save_with(&store, passphrase.as_deref().map(String::as_str))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,623 @@
+//! Builders for the transactions creating pools and lockers.
+//!
+//! They derive the program authorities, allocate the accounts the programs expect to find and
+//! order the instructions, so callers only have to sign and send the result.
+//!
+//! With `auto_compute_budget`, building through `build_budgeted` also simulates the transaction
+//! and prepends a compute unit limit sized from the units the simulation consumed.
+
+use parity_scale_codec::Encode;
+use solana_api_types::{
+    compute_budget::{self, MAX_COMPUTE_UNIT_LIMIT},
+    system::create_account,
+    AccountMeta, Client, ClientError, ClientErrorKind, Instruction, Keypair, Pubkey,
+    RpcSimulateTransactionConfig, Signer, Transaction,
+};
+use solar::{spl, time::SolTimestamp};
+use token_locker::{data::AccountType as _, derive_locker_authority, TokenLock};
+use x5margin_program::{
+    data::AccountType as _,
+    instructions::{self, CreatePoolAccounts},
+    simple_stake::{derive_pool_authority, InitializeArgs, StakePool},
+};
+
+use crate::units_consumed_from_logs;
+
+/// Prepend a `set_compute_unit_limit` instruction to `instructions`, sized from the compute
+/// units a simulation of them consumes plus `margin_percent`.
+///
+/// The simulation skips signature verification and replaces the blockhash, so the instructions
+/// don't have to be signed yet.
+pub async fn with_compute_unit_limit<C: Client>(
+    client: &C,
+    payer: &Pubkey,
+    instructions: Vec<Instruction>,
+    margin_percent: u32,
+) -> Result<Vec<Instruction>, ClientError> {
+    let transaction = Transaction::new_with_payer(&instructions, Some(payer));
+    let config = RpcSimulateTransactionConfig {
+        replace_recent_blockhash: true,
+        ..Default::default()
+    };
+    let result = client.simulate_transaction(&transaction, config).await?;
+
+    if let Some(err) = result.err {
+        return Err(ClientErrorKind::TransactionError(err).into());
+    }
+
+    let consumed = result
+        .units_consumed
+        .or_else(|| result.logs.as_deref().and_then(units_consumed_from_logs))
+        .ok_or_else(|| {
+            ClientErrorKind::Custom("simulation did not report the compute units consumed".into())
+        })?;
+    let units = (consumed.saturating_mul(100 + margin_percent as u64) / 100)
+        .min(MAX_COMPUTE_UNIT_LIMIT as u64);
+
+    let mut budgeted = vec![compute_budget::set_compute_unit_limit(units as u32)];
+    budgeted.extend(instructions);
+    Ok(budgeted)
+}
+
+/// Token wallet a built transaction deposits into.
+enum Vault {
+    /// Created by the transaction, the keypair signs for it.
+    Create(Keypair),
+    /// Already a wallet of the mint held by the program authority, see
+    /// [`SolanaClient::wallet_exists`](crate::SolanaClient::wallet_exists).
+    Existing(Pubkey),
+}
+
+impl Vault {
+    fn pubkey(&self) -> Pubkey {
+        match self {
+            Vault::Create(vault) => vault.pubkey(),
+            Vault::Existing(vault) => *vault,
+        }
+    }
+
+    /// Instructions creating the vault if it doesn't exist yet, and the keypair signing them.
+    fn create(
+        self,
+        payer: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+    ) -> (Vec<Instruction>, Option<Keypair>) {
+        match self {
+            Vault::Create(vault) => (
+                spl::create_wallet(payer, &vault.pubkey(), mint, authority).to_vec(),
+                Some(vault),
+            ),
+            Vault::Existing(_) => (vec![], None),
+        }
+    }
+}
+
+/// Instructions creating a stake pool, as returned by [`PoolTransactionBuilder::build`].
+pub struct PoolTransaction {
+    pub instructions: Vec<Instruction>,
+    /// Keypairs of the accounts the transaction creates, they sign it along with the payer.
+    pub signers: Vec<Keypair>,
+    pub pool: Pubkey,
+    pub stake_vault: Pubkey,
+    pub program_authority: Pubkey,
+}
+
+/// Creates the pool account and its stake vault, then initializes the pool.
+pub struct PoolTransactionBuilder {
+    program_id: Pubkey,
+    payer: Pubkey,
+    administrator: Pubkey,
+    stake_mint: Pubkey,
+    args: InitializeArgs,
+    pool: Option<Keypair>,
+    stake_vault: Option<Vault>,
+    compute_margin: Option<u32>,
+}
+
+impl PoolTransactionBuilder {
+    /// `payer` funds the new accounts and administers the pool unless
+    /// [`PoolTransactionBuilder::administrator`] says otherwise. The salt in `args` is replaced
+    /// with the one derived for the pool.
+    pub fn new(
+        program_id: Pubkey,
+        payer: Pubkey,
+        stake_mint: Pubkey,
+        args: InitializeArgs,
+    ) -> Self {
+        Self {
+            program_id,
+            payer,
+            administrator: payer,
+            stake_mint,
+            args,
+            pool: None,
+            stake_vault: None,
+            compute_margin: None,
+        }
+    }
+
+    pub fn administrator(mut self, administrator: Pubkey) -> Self {
+        self.administrator = administrator;
+        self
+    }
+
+    /// Create the pool at `pool` instead of a fresh key.
+    pub fn pool_key(mut self, pool: Keypair) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Create the stake vault at `stake_vault` instead of a fresh key.
+    pub fn stake_vault_key(mut self, stake_vault: Keypair) -> Self {
+        self.stake_vault = Some(Vault::Create(stake_vault));
+        self
+    }
+
+    /// Use `stake_vault`, already a wallet of the stake mint held by the program authority,
+    /// instead of creating one.
+    pub fn existing_stake_vault(mut self, stake_vault: Pubkey) -> Self {
+        self.stake_vault = Some(Vault::Existing(stake_vault));
+        self
+    }
+
+    /// Size the compute budget from a simulation plus `margin_percent` when built with
+    /// [`PoolTransactionBuilder::build_budgeted`].
+    pub fn auto_compute_budget(mut self, margin_percent: u32) -> Self {
+        self.compute_margin = Some(margin_percent);
+        self
+    }
+
+    /// Like [`PoolTransactionBuilder::build`], but prepends a compute unit limit if
+    /// [`PoolTransactionBuilder::auto_compute_budget`] was asked for.
+    pub async fn build_budgeted<C: Client>(
+        self,
+        client: &C,
+    ) -> Result<PoolTransaction, ClientError> {
+        let payer = self.payer;
+        let compute_margin = self.compute_margin;
+        let mut transaction = self.build();
+
+        if let Some(margin_percent) = compute_margin {
+            transaction.instructions =
+                with_compute_unit_limit(client, &payer, transaction.instructions, margin_percent)
+                    .await?;
+        }
+
+        Ok(transaction)
+    }
+
+    pub fn build(self) -> PoolTransaction {
+        let pool = self.pool.unwrap_or_else(Keypair::new);
+        let stake_vault = self
+            .stake_vault
+            .unwrap_or_else(|| Vault::Create(Keypair::new()));
+        let stake_vault_key = stake_vault.pubkey();
+        let (program_authority, salt) =
+            derive_pool_authority(&self.program_id, &pool.pubkey(), &self.administrator);
+
+        let mut instructions = vec![create_account(
+            &self.payer,
+            &pool.pubkey(),
+            StakePool::default_lamports(),
+            StakePool::default_size() as u64,
+            &self.program_id,
+        )];
+        let (create_vault, vault_signer) =
+            stake_vault.create(&self.payer, &self.stake_mint, &program_authority);
+        instructions.extend(create_vault);
+        instructions.push(instructions::create_pool(
+            self.program_id,
+            &CreatePoolAccounts {
+                administrator: self.administrator,
+                program_authority,
+                pool: pool.pubkey(),
+                stake_mint: self.stake_mint,
+                stake_vault: stake_vault_key,
+            },
+            InitializeArgs {
+                program_authority_salt: salt,
+                ..self.args
+            },
+        ));
+
+        let pool_key = pool.pubkey();
+        let mut signers = vec![pool];
+        signers.extend(vault_signer);
+
+        PoolTransaction {
+            instructions,
+            pool: pool_key,
+            stake_vault: stake_vault_key,
+            program_authority,
+            signers,
+        }
+    }
+}
+
+/// Instructions creating a locker, as returned by [`LockerTransactionBuilder::build`].
+pub struct LockerTransaction {
+    pub instructions: Vec<Instruction>,
+    /// Keypairs of the accounts the transaction creates, they sign it along with the payer and
+    /// the source authority.
+    pub signers: Vec<Keypair>,
+    pub locker: Pubkey,
+    pub vault: Pubkey,
+    pub program_authority: Pubkey,
+}
+
+/// Creates the locker vault and account, then locks `amount` tokens of `source_wallet` in it.
+pub struct LockerTransactionBuilder {
+    program_id: Pubkey,
+    payer: Pubkey,
+    owner: Pubkey,
+    mint: Pubkey,
+    source_wallet: Pubkey,
+    source_authority: Pubkey,
+    unlock_date: SolTimestamp,
+    amount: token_locker::TokenAmount,
+    locker: Option<Keypair>,
+    vault: Option<Vault>,
+    compute_margin: Option<u32>,
+}
+
+impl LockerTransactionBuilder {
+    /// `payer` funds the new accounts, owns the locker and signs for `source_wallet` unless
+    /// told otherwise.
+    pub fn new(
+        program_id: Pubkey,
+        payer: Pubkey,
+        mint: Pubkey,
+        source_wallet: Pubkey,
+        unlock_date: SolTimestamp,
+        amount: token_locker::TokenAmount,
+    ) -> Self {
+        Self {
+            program_id,
+            payer,
+            owner: payer,
+            mint,
+            source_wallet,
+            source_authority: payer,
+            unlock_date,
+            amount,
+            locker: None,
+            vault: None,
+            compute_margin: None,
+        }
+    }
+
+    /// Withdraw authority of the locker.
+    pub fn owner(mut self, owner: Pubkey) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn source_authority(mut self, source_authority: Pubkey) -> Self {
+        self.source_authority = source_authority;
+        self
+    }
+
+    /// Create the locker at `locker` instead of a fresh key.
+    pub fn locker_key(mut self, locker: Keypair) -> Self {
+        self.locker = Some(locker);
+        self
+    }
+
+    /// Create the vault at `vault` instead of a fresh key.
+    pub fn vault_key(mut self, vault: Keypair) -> Self {
+        self.vault = Some(Vault::Create(vault));
+        self
+    }
+
+    /// Use `vault`, already a wallet of the mint held by the program authority, instead of
+    /// creating one.
+    pub fn existing_vault(mut self, vault: Pubkey) -> Self {
+        self.vault = Some(Vault::Existing(vault));
+        self
+    }
+
+    /// Size the compute budget from a simulation plus `margin_percent` when built with
+    /// [`LockerTransactionBuilder::build_budgeted`].
+    pub fn auto_compute_budget(mut self, margin_percent: u32) -> Self {
+        self.compute_margin = Some(margin_percent);
+        self
+    }
+
+    /// Like [`LockerTransactionBuilder::build`], but prepends a compute unit limit if
+    /// [`LockerTransactionBuilder::auto_compute_budget`] was asked for.
+    pub async fn build_budgeted<C: Client>(
+        self,
+        client: &C,
+    ) -> Result<Option<LockerTransaction>, ClientError> {
+        let payer = self.payer;
+        let compute_margin = self.compute_margin;
+        let mut transaction = match self.build() {
+            Some(transaction) => transaction,
+            None => return Ok(None),
+        };
+
+        if let Some(margin_percent) = compute_margin {
+            transaction.instructions =
+                with_compute_unit_limit(client, &payer, transaction.instructions, margin_percent)
+                    .await?;
+        }
+
+        Ok(Some(transaction))
+    }
+
+    /// `None` if the locker key given to [`LockerTransactionBuilder::locker_key`] has no
+    /// program authority, see [`derive_locker_authority`]. Fresh keys are drawn until one has.
+    pub fn build(self) -> Option<LockerTransaction> {
+        let (locker, program_authority) = match self.locker {
+            Some(locker) => {
+                let program_authority =
+                    derive_locker_authority(&self.program_id, &locker.pubkey(), &self.owner)?;
+                (locker, program_authority)
+            }
+            None => loop {
+                let locker = Keypair::new();
+                if let Some(program_authority) =
+                    derive_locker_authority(&self.program_id, &locker.pubkey(), &self.owner)
+                {
+                    break (locker, program_authority);
+                }
+            },
+        };
+        let vault = self.vault.unwrap_or_else(|| Vault::Create(Keypair::new()));
+        let vault_key = vault.pubkey();
+
+        let (mut instructions, vault_signer) =
+            vault.create(&self.payer, &self.mint, &program_authority);
+        instructions.push(create_account(
+            &self.payer,
+            &locker.pubkey(),
+            TokenLock::default_lamports(),
+            TokenLock::default_size() as u64,
+            &self.program_id,
+        ));
+        instructions.push(Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(locker.pubkey(), false),
+                AccountMeta::new(self.source_wallet, false),
+                AccountMeta::new_readonly(self.source_authority, true),
+                AccountMeta::new(vault_key, false),
+                AccountMeta::new_readonly(program_authority, false),
+                AccountMeta::new_readonly(self.owner, false),
+            ],
+            data: token_locker::Method::CreateLock {
+                unlock_date: self.unlock_date,
+                amount: self.amount,
+            }
+            .encode(),
+        });
+
+        let locker_key = locker.pubkey();
+        let mut signers = vec![locker];
+        signers.extend(vault_signer);
+
+        Some(LockerTransaction {
+            instructions,
+            locker: locker_key,
+            vault: vault_key,
+            program_authority,
+            signers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_scale_codec::Encode;
+    use solana_api_types::{
+        compute_budget::{self, MAX_COMPUTE_UNIT_LIMIT},
+        system::create_account,
+        AccountMeta, Instruction, Keypair, Pubkey, Signer,
+    };
+    use solar::spl;
+    use token_locker::{data::AccountType as _, derive_locker_authority, TokenLock};
+    use x5margin_program::{
+        data::AccountType as _,
+        instructions::{self, CreatePoolAccounts},
+        simple_stake::{derive_pool_authority, InitializeArgs, StakePool},
+    };
+
+    use super::{LockerTransactionBuilder, PoolTransactionBuilder};
+    use crate::mock::CountingClient;
+
+    #[test]
+    fn pool_transaction_test() {
+        let program_id = Pubkey::new_unique();
+        let administrator = Pubkey::new_unique();
+        let stake_mint = Pubkey::new_unique();
+        let pool = Keypair::new();
+        let stake_vault = Keypair::new();
+        let args = InitializeArgs {
+            program_authority_salt: 0,
+            lockup_duration: 1000.into(),
+            topup_duration: 200.into(),
+            reward_amount: 1000.into(),
+            target_amount: 10000.into(),
+            flags: 0,
+            reward_tranches: vec![],
+        };
+
+        let built =
+            PoolTransactionBuilder::new(program_id, administrator, stake_mint, args.clone())
+                .pool_key(pool.clone())
+                .stake_vault_key(stake_vault.clone())
+                .build();
+
+        // what `stake-cli initialize` assembled by hand
+        let (program_authority, salt) =
+            derive_pool_authority(&program_id, &pool.pubkey(), &administrator);
+        let mut expected = vec![create_account(
+            &administrator,
+            &pool.pubkey(),
+            StakePool::default_lamports(),
+            StakePool::default_size() as u64,
+            &program_id,
+        )];
+        expected.extend(spl::create_wallet(
+            &administrator,
+            &stake_vault.pubkey(),
+            &stake_mint,
+            &program_authority,
+        ));
+        expected.push(instructions::create_pool(
+            program_id,
+            &CreatePoolAccounts {
+                administrator,
+                program_authority,
+                pool: pool.pubkey(),
+                stake_mint,
+                stake_vault: stake_vault.pubkey(),
+            },
+            InitializeArgs {
+                program_authority_salt: salt,
+                ..args
+            },
+        ));
+
+        assert_eq!(built.instructions, expected);
+        assert_eq!(built.pool, pool.pubkey());
+        assert_eq!(built.stake_vault, stake_vault.pubkey());
+        assert_eq!(built.program_authority, program_authority);
+        let signers: Vec<_> = built.signers.iter().map(Signer::pubkey).collect();
+        assert_eq!(signers, vec![pool.pubkey(), stake_vault.pubkey()]);
+    }
+
+    #[test]
+    fn locker_transaction_test() {
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let source_wallet = Pubkey::new_unique();
+        let vault = Keypair::new();
+
+        let (locker, without_authority): (Vec<_>, Vec<_>) = (0..64)
+            .map(|_| Keypair::new())
+            .partition(|key| derive_locker_authority(&program_id, &key.pubkey(), &owner).is_some());
+        let locker = locker[0].clone();
+        let program_authority =
+            derive_locker_authority(&program_id, &locker.pubkey(), &owner).unwrap();
+
+        let builder = |locker: Keypair| {
+            LockerTransactionBuilder::new(
+                program_id,
+                payer,
+                mint,
+                source_wallet,
+                1_700_000_000.into(),
+                500.into(),
+            )
+            .owner(owner)
+            .locker_key(locker)
+            .vault_key(vault.clone())
+        };
+
+        let built = builder(locker.clone()).build().unwrap();
+
+        let mut expected = vec![];
+        expected.extend(spl::create_wallet(
+            &payer,
+            &vault.pubkey(),
+            &mint,
+            &program_authority,
+        ));
+        expected.push(create_account(
+            &payer,
+            &locker.pubkey(),
+            TokenLock::default_lamports(),
+            TokenLock::default_size() as u64,
+            &program_id,
+        ));
+        expected.push(Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(locker.pubkey(), false),
+                AccountMeta::new(source_wallet, false),
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(vault.pubkey(), false),
+                AccountMeta::new_readonly(program_authority, false),
+                AccountMeta::new_readonly(owner, false),
+            ],
+            data: token_locker::Method::CreateLock {
+                unlock_date: 1_700_000_000.into(),
+                amount: 500.into(),
+            }
+            .encode(),
+        });
+
+        assert_eq!(built.instructions, expected);
+        assert_eq!(built.locker, locker.pubkey());
+        assert_eq!(built.program_authority, program_authority);
+        let signers: Vec<_> = built.signers.iter().map(Signer::pubkey).collect();
+        assert_eq!(signers, vec![locker.pubkey(), vault.pubkey()]);
+
+        // keys without a program authority can't hold a locker
+        assert!(builder(without_authority[0].clone()).build().is_none());
+    }
+
+    #[tokio::test]
+    async fn compute_budget_test() {
+        let program_id = Pubkey::new_unique();
+        let administrator = Pubkey::new_unique();
+        let pool = Keypair::new();
+        let stake_vault = Keypair::new();
+        let builder = || {
+            PoolTransactionBuilder::new(
+                program_id,
+                administrator,
+                Pubkey::new_unique(),
+                InitializeArgs {
+                    program_authority_salt: 0,
+                    lockup_duration: 1000.into(),
+                    topup_duration: 200.into(),
+                    reward_amount: 1000.into(),
+                    target_amount: 10000.into(),
+                    flags: 0,
+                    reward_tranches: vec![],
+                },
+            )
+            .pool_key(pool.clone())
+            .stake_vault_key(stake_vault.clone())
+        };
+        let unbudgeted = builder().build().instructions;
+
+        // nothing is simulated unless asked for
+        let client = CountingClient::with_units_consumed(50_000);
+        let built = builder().build_budgeted(&client).await.unwrap();
+        assert_eq!(built.instructions, unbudgeted);
+        assert!(client.simulated.borrow().is_empty());
+
+        let built = builder()
+            .auto_compute_budget(20)
+            .build_budgeted(&client)
+            .await
+            .unwrap();
+        assert_eq!(
+            compute_budget::compute_unit_limit(&built.instructions[0]),
+            Some(60_000)
+        );
+        assert_eq!(built.instructions[1..], unbudgeted[..]);
+
+        let simulated = client.simulated.borrow();
+        assert_eq!(simulated.len(), 1);
+        assert_eq!(simulated[0].fee_payer(), Some(&administrator));
+
+        // the limit can't go past what a transaction may request
+        let client = CountingClient::with_units_consumed(1_300_000);
+        let built = builder()
+            .auto_compute_budget(20)
+            .build_budgeted(&client)
+            .await
+            .unwrap();
+        assert_eq!(
+            built.instructions[0],
+            compute_budget::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use solana_api_types::{Account, Pubkey};
+
+/// Tunables of the account cache of [`SolanaClient`](crate::SolanaClient).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccountCacheConfig {
+    /// Accounts kept at most, the least recently used one is evicted to make room.
+    pub capacity: usize,
+    /// How long a fetched account is served from the cache.
+    pub ttl: Duration,
+}
+
+impl Default for AccountCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 256,
+            ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+struct Entry {
+    account: Account,
+    fetched_at: Instant,
+    last_used: u64,
+}
+
+/// Least recently used accounts, each of them valid for `ttl` after it was fetched.
+pub struct AccountCache {
+    config: AccountCacheConfig,
+    entries: HashMap<Pubkey, Entry>,
+    clock: u64,
+}
+
+impl AccountCache {
+    pub fn new(config: AccountCacheConfig) -> Self {
+        Self {
+            config,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn config(&self) -> &AccountCacheConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Cached copy of `pubkey`, `None` if it was never fetched or has expired.
+    pub fn get(&mut self, pubkey: &Pubkey) -> Option<Account> {
+        self.clock += 1;
+
+        let entry = self.entries.get_mut(pubkey)?;
+        if entry.fetched_at.elapsed() >= self.config.ttl {
+            self.entries.remove(pubkey);
+            return None;
+        }
+
+        entry.last_used = self.clock;
+        Some(entry.account.clone())
+    }
+
+    pub fn insert(&mut self, account: Account) {
+        if self.config.capacity == 0 {
+            return;
+        }
+
+        self.clock += 1;
+
+        if !self.entries.contains_key(&account.pubkey) && self.entries.len() >= self.config.capacity
+        {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(pubkey, _)| *pubkey);
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(
+            account.pubkey,
+            Entry {
+                account,
+                fetched_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Drop `pubkey`, e.g. because a transaction writing to it was sent.
+    pub fn invalidate(&mut self, pubkey: &Pubkey) {
+        self.entries.remove(pubkey);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use solana_api_types::{Account, Pubkey};
+
+    use super::{AccountCache, AccountCacheConfig};
+
+    fn account(pubkey: Pubkey) -> Account {
+        Account {
+            pubkey,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lru_eviction_test() {
+        let mut cache = AccountCache::new(AccountCacheConfig {
+            capacity: 2,
+            ttl: Duration::from_secs(60),
+        });
+        let keys: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+        cache.insert(account(keys[0]));
+        cache.insert(account(keys[1]));
+        assert!(cache.get(&keys[0]).is_some());
+
+        // the second key was used least recently
+        cache.insert(account(keys[2]));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&keys[1]).is_none());
+        assert!(cache.get(&keys[0]).is_some());
+        assert!(cache.get(&keys[2]).is_some());
+
+        cache.invalidate(&keys[0]);
+        assert!(cache.get(&keys[0]).is_none());
+    }
+
+    #[test]
+    fn ttl_test() {
+        let mut cache = AccountCache::new(AccountCacheConfig {
+            capacity: 2,
+            ttl: Duration::from_millis(0),
+        });
+        let key = Pubkey::new_unique();
+
+        cache.insert(account(key));
+        assert!(cache.get(&key).is_none());
+        assert!(cache.is_empty());
+    }
+}
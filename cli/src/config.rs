@@ -0,0 +1,152 @@
+//! Settings shared by every run of the CLIs, so switching between clusters doesn't take repeating
+//! every flag.
+//!
+//! The file is JSON, e.g.
+//!
+//! ```json
+//! {
+//!     "cluster": "localnet",
+//!     "commitment": "confirmed",
+//!     "stake_program_id": "6Bho5aBGh5ppwxXYfLQGsNbVn2tnpCmcrvVpDbMvTtvD",
+//!     "keypair_path": "/home/user/.config/solana/localnet.json"
+//! }
+//! ```
+//!
+//! Every field is optional. Flags and their environment variables take precedence over the file,
+//! the built-in defaults apply to what neither sets.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use solana_api_types::{CommitmentLevel, Pubkey};
+
+use crate::Cluster;
+
+/// Environment variable the config path is read from if `--config` isn't passed.
+pub const CONFIG_PATH_ENV: &str = "X5_CONFIG";
+
+/// The config file as written, before its keys and cluster names are parsed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    cluster: Option<String>,
+    commitment: Option<CommitmentLevel>,
+    stake_program_id: Option<String>,
+    locker_program_id: Option<String>,
+    keypair_path: Option<PathBuf>,
+    store: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CliConfig {
+    pub cluster: Option<Cluster>,
+    pub commitment: Option<CommitmentLevel>,
+    pub stake_program_id: Option<Pubkey>,
+    pub locker_program_id: Option<Pubkey>,
+    /// Fee payer and signing authority.
+    pub keypair_path: Option<PathBuf>,
+    /// Path to the local client store.
+    pub store: Option<PathBuf>,
+}
+
+impl CliConfig {
+    /// Read the config at `path`, an empty config if there is none.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        match path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)?;
+                Self::from_json(&contents).map_err(|err| {
+                    anyhow::anyhow!("invalid config file {}: {}", path.display(), err)
+                })
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let raw: RawConfig = serde_json::from_str(json)?;
+        let pubkey = |key: Option<String>| {
+            key.map(|key| {
+                Pubkey::from_str(&key)
+                    .map_err(|err| anyhow::anyhow!("invalid pubkey {}: {}", key, err))
+            })
+            .transpose()
+        };
+
+        Ok(Self {
+            cluster: raw
+                .cluster
+                .map(|cluster| Cluster::from_str(&cluster))
+                .transpose()
+                .map_err(anyhow::Error::msg)?,
+            commitment: raw.commitment,
+            stake_program_id: pubkey(raw.stake_program_id)?,
+            locker_program_id: pubkey(raw.locker_program_id)?,
+            keypair_path: raw.keypair_path,
+            store: raw.store,
+        })
+    }
+}
+
+/// Parse a `--commitment` flag, named the way the RPC API names the levels.
+pub fn parse_commitment(s: &str) -> Result<CommitmentLevel, String> {
+    match s {
+        "processed" => Ok(CommitmentLevel::Processed),
+        "confirmed" => Ok(CommitmentLevel::Confirmed),
+        "finalized" => Ok(CommitmentLevel::Finalized),
+        _ => Err(format!("unknown commitment: {}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, str::FromStr};
+
+    use solana_api_types::{CommitmentLevel, Pubkey};
+
+    use super::{parse_commitment, CliConfig};
+    use crate::Cluster;
+
+    #[test]
+    fn parse_config_test() {
+        let program_id = "6Bho5aBGh5ppwxXYfLQGsNbVn2tnpCmcrvVpDbMvTtvD";
+        let config = CliConfig::from_json(&format!(
+            r#"{{
+                "cluster": "localnet",
+                "commitment": "confirmed",
+                "stake_program_id": "{}",
+                "keypair_path": "/tmp/localnet.json"
+            }}"#,
+            program_id
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config,
+            CliConfig {
+                cluster: Some(Cluster::Localnet),
+                commitment: Some(CommitmentLevel::Confirmed),
+                stake_program_id: Some(Pubkey::from_str(program_id).unwrap()),
+                locker_program_id: None,
+                keypair_path: Some(PathBuf::from("/tmp/localnet.json")),
+                store: None,
+            }
+        );
+
+        assert_eq!(CliConfig::from_json("{}").unwrap(), CliConfig::default());
+        assert!(CliConfig::from_json(r#"{ "cluster": "nowhere" }"#).is_err());
+        assert!(CliConfig::from_json(r#"{ "locker_program_id": "not a key" }"#).is_err());
+        // typos aren't silently ignored
+        assert!(CliConfig::from_json(r#"{ "clustr": "devnet" }"#).is_err());
+
+        assert_eq!(
+            parse_commitment("processed"),
+            Ok(CommitmentLevel::Processed)
+        );
+        assert!(parse_commitment("max").is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Readable explanations of failed transactions.
+//!
+//! A failed send only reports the raw transaction error, e.g. `custom program error: 0xa`. The
+//! transaction is simulated again to recover the program logs, whose last `Program log:` line is
+//! usually the reason the program gave, and custom error codes of known programs are decoded.
+
+use std::fmt;
+
+use solana_api_types::{
+    InstructionError, Pubkey, RpcSimulateTransactionResult, Transaction, TransactionError,
+};
+
+use crate::summary::KnownPrograms;
+
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// What a simulation of a failed transaction revealed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureExplanation {
+    /// `None` if the simulation succeeded, e.g. because the state changed since the send.
+    pub err: Option<TransactionError>,
+    /// Custom error of a known program, e.g. `Validation` for code 10 of the stake program.
+    pub program_error: Option<String>,
+    /// Last message logged by a program before it failed.
+    pub reason: Option<String>,
+    pub logs: Vec<String>,
+}
+
+impl FailureExplanation {
+    pub fn from_simulation(
+        transaction: &Transaction,
+        result: RpcSimulateTransactionResult,
+        programs: &KnownPrograms,
+    ) -> Self {
+        let logs = result.logs.unwrap_or_default();
+        // the entrypoint ends with `<method> failed: <error>`, the reason is logged before that
+        let reason = logs
+            .iter()
+            .rev()
+            .filter_map(|log| log.strip_prefix(PROGRAM_LOG_PREFIX))
+            .find(|message| !message.contains(" failed: "))
+            .map(str::to_string);
+        let program_error = result
+            .err
+            .as_ref()
+            .and_then(|err| describe_program_error(transaction, err, programs));
+
+        Self {
+            err: result.err,
+            program_error,
+            reason,
+            logs,
+        }
+    }
+}
+
+impl fmt::Display for FailureExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.reason, &self.program_error, &self.err) {
+            (Some(reason), Some(program_error), _) => {
+                writeln!(f, "transaction failed: {} ({})", reason, program_error)?
+            }
+            (Some(reason), None, _) => writeln!(f, "transaction failed: {}", reason)?,
+            (None, Some(program_error), _) => writeln!(f, "transaction failed: {}", program_error)?,
+            (None, None, Some(err)) => writeln!(f, "transaction failed: {}", err)?,
+            (None, None, None) => writeln!(f, "transaction succeeds when simulated again")?,
+        }
+
+        for log in &self.logs {
+            writeln!(f, "  {}", log)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Name of the custom error `err` failed with, if it was raised by a known program.
+fn describe_program_error(
+    transaction: &Transaction,
+    err: &TransactionError,
+    programs: &KnownPrograms,
+) -> Option<String> {
+    let (index, code) = match err {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            (*index, *code)
+        }
+        _ => return None,
+    };
+
+    let message = &transaction.message;
+    let instruction = message.instructions.get(index as usize)?;
+    let program: &Pubkey = message
+        .account_keys
+        .get(instruction.program_id_index as usize)?;
+
+    if Some(*program) == programs.stake {
+        x5margin_program::error::Error::from_code(code).map(|err| format!("{:?}", err))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_api_types::*;
+    use x5margin_program::instructions::{self, WithdrawAccounts};
+
+    use super::FailureExplanation;
+    use crate::summary::KnownPrograms;
+
+    #[test]
+    fn explain_locked_pool_test() {
+        let program_id = Pubkey::new_unique();
+        let staker = Pubkey::new_unique();
+        let unstake = instructions::unstake(
+            program_id,
+            &WithdrawAccounts {
+                pool: Pubkey::new_unique(),
+                ticket: Pubkey::new_unique(),
+                staker,
+                program_authority: Pubkey::new_unique(),
+                stake_vault: Pubkey::new_unique(),
+                target_wallet: Pubkey::new_unique(),
+            },
+            50.into(),
+        );
+        let transaction = Transaction::new_with_payer(&[unstake], Some(&staker));
+        let programs = KnownPrograms {
+            stake: Some(program_id),
+            locker: None,
+        };
+
+        let simulation = || RpcSimulateTransactionResult {
+            err: Some(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(10),
+            )),
+            logs: Some(vec![
+                format!("Program {} invoke [1]", program_id),
+                "Program log: pool is locked and funds can no longer be removed".to_string(),
+                "Program log: Simple::Unstake(50) failed: Validation".to_string(),
+                format!("Program {} failed: custom program error: 0xa", program_id),
+            ]),
+            accounts: None,
+            units_consumed: None,
+        };
+
+        let explanation =
+            FailureExplanation::from_simulation(&transaction, simulation(), &programs);
+        assert_eq!(explanation.program_error.as_deref(), Some("Validation"));
+        assert_eq!(
+            explanation.reason.as_deref(),
+            Some("pool is locked and funds can no longer be removed")
+        );
+        assert!(explanation.to_string().starts_with(
+            "transaction failed: pool is locked and funds can no longer be removed (Validation)\n"
+        ));
+        assert_eq!(explanation.to_string().lines().count(), 5);
+
+        // codes of other programs are left alone
+        let explanation = FailureExplanation::from_simulation(
+            &transaction,
+            simulation(),
+            &KnownPrograms::default(),
+        );
+        assert_eq!(explanation.program_error, None);
+    }
+}
@@ -1,3 +1,1295 @@
-pub async fn send_and_confirm_transaction() {
-    todo!()
-}
\ No newline at end of file
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either};
+use serde_json::json;
+
+use solana_api_types::{
+    sysvar::clock::{self, Clock},
+    Account, Client, ClientError, ClientErrorKind, CommitmentConfig, Hash, Instruction, Keypair,
+    Pubkey, RpcAccountInfoConfig, RpcError, RpcSendTransactionConfig, RpcSimulateTransactionConfig,
+    RpcSlotConfig, Signature, Signer, Slot, Transaction, UiAccountEncoding,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+};
+use solana_rpc_client::{
+    blockhash::get_recent_blockhash_with_backoff,
+    confirm::{confirm_transaction, signature_notification},
+    pubsub::{Listener, PubsubTransport, Subscriptions},
+    SolanaApiClient,
+};
+use solar::{
+    spl::{MintAccount, WalletAccount},
+    time::SolTimestamp,
+};
+use token_locker::TokenLockEntity;
+use zeroize::Zeroizing;
+
+use crate::{
+    cache::{AccountCache, AccountCacheConfig},
+    explain::FailureExplanation,
+    summary::KnownPrograms,
+};
+
+pub use solar::spl::{create_associated_wallet, find_associated_wallet};
+
+pub mod anchor;
+pub mod builder;
+pub mod cache;
+pub mod config;
+pub mod explain;
+pub mod store;
+pub mod summary;
+
+#[cfg(test)]
+mod mock;
+
+/// Maximum number of keys accepted by a single `getMultipleAccounts` request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// How often transaction statuses are polled while waiting for confirmation.
+pub const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Attempts at fetching a blockhash before an unreachable node is reported.
+pub const BLOCKHASH_ATTEMPTS: usize = 5;
+
+/// Pause after the first failed blockhash request, doubled after every further failure.
+pub const BLOCKHASH_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cluster {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Cluster {
+    pub fn url(&self) -> &'static str {
+        match self {
+            Cluster::Mainnet => "https://api.mainnet-beta.solana.com",
+            Cluster::Testnet => "https://api.testnet.solana.com",
+            Cluster::Devnet => "https://api.devnet.solana.com",
+            Cluster::Localnet => "http://127.0.0.1:8899",
+        }
+    }
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Cluster::Devnet
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" | "m" => Ok(Cluster::Mainnet),
+            "testnet" | "t" => Ok(Cluster::Testnet),
+            "devnet" | "d" => Ok(Cluster::Devnet),
+            "localnet" | "l" => Ok(Cluster::Localnet),
+            _ => Err(format!("unknown cluster: {}", s)),
+        }
+    }
+}
+
+/// Default location of the Solana CLI keypair.
+pub fn default_keypair_path() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    Path::new(&home).join(".config/solana/id.json")
+}
+
+/// Read a keypair stored in the Solana CLI format, i.e. a JSON array of 64 bytes.
+///
+/// Intermediate copies of the secret key are scrubbed from memory once the keypair is built.
+pub fn read_keypair_file(path: impl AsRef<Path>) -> anyhow::Result<Keypair> {
+    let path = path.as_ref();
+    let contents = Zeroizing::new(fs::read(path)?);
+    let bytes: Zeroizing<Vec<u8>> = Zeroizing::new(serde_json::from_slice(&contents)?);
+
+    Keypair::from_bytes(&bytes)
+        .map_err(|err| anyhow::anyhow!("invalid keypair in {}: {}", path.display(), err))
+}
+
+/// Human-readable distance from `now` to `then`, e.g. `in 2d 3h 10m` or `5h 2m ago`.
+pub fn format_relative(now: SolTimestamp, then: SolTimestamp) -> String {
+    let delta = i64::from(then) - i64::from(now);
+    let secs = delta.abs();
+
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    let span = if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", secs)
+    };
+
+    if delta >= 0 {
+        format!("in {}", span)
+    } else {
+        format!("{} ago", span)
+    }
+}
+
+/// Account types that can be loaded through [`SolanaClient::load`].
+pub trait Loadable: Sized {
+    fn load(account: Box<Account>) -> Result<Self, ClientError>;
+}
+
+impl Loadable for Box<Account> {
+    fn load(account: Box<Account>) -> Result<Self, ClientError> {
+        Ok(account)
+    }
+}
+
+impl Loadable for WalletAccount<Box<Account>> {
+    fn load(account: Box<Account>) -> Result<Self, ClientError> {
+        WalletAccount::any(account).map_err(|err| ClientErrorKind::Custom(err.to_string()).into())
+    }
+}
+
+impl Loadable for MintAccount<Box<Account>> {
+    fn load(account: Box<Account>) -> Result<Self, ClientError> {
+        MintAccount::any(account).map_err(|err| ClientErrorKind::Custom(err.to_string()).into())
+    }
+}
+
+impl Loadable for TokenLockEntity<Box<Account>> {
+    /// Lockers are validated against their own owner, callers are expected to check that
+    /// `entity.account().owner` matches the locker program they are working with.
+    fn load(account: Box<Account>) -> Result<Self, ClientError> {
+        let owner = account.owner;
+        TokenLockEntity::load(&owner, account)
+            .map_err(|err| ClientErrorKind::Custom(format!("{:?}", err)).into())
+    }
+}
+
+/// Tunables of [`SolanaClient`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolanaClientConfig {
+    /// How long a fetched blockhash is reused for new transactions.
+    pub recent_blockhash_interval: Duration,
+    /// Commitment of blockhashes, and of accounts unless a call asks for another one.
+    pub commitment: CommitmentConfig,
+    /// How long to wait for a transaction to be confirmed, `None` waits forever.
+    pub confirm_timeout: Option<Duration>,
+    /// Send transactions without the node simulating them first, for endpoints whose preflight
+    /// fails spuriously. Transactions that would fail are only caught once they land.
+    pub skip_preflight: bool,
+    /// Airdrop requests made by [`SolanaClient::request_airdrop`] before giving up.
+    pub airdrop_attempts: usize,
+    /// How long an airdrop is given to show up in the balance of its recipient.
+    pub airdrop_timeout: Duration,
+    /// Pause before the second airdrop request, doubled before every further one.
+    pub airdrop_backoff: Duration,
+    /// Reads made by [`SolanaClient::load_at_slot`] before giving up on the node catching up.
+    pub min_context_slot_attempts: usize,
+    /// Pause between the reads of [`SolanaClient::load_at_slot`].
+    pub min_context_slot_interval: Duration,
+    /// Serve repeated [`SolanaClient::load`] and [`SolanaClient::load_many`] reads of an account
+    /// from memory, `None` always fetches.
+    pub account_cache: Option<AccountCacheConfig>,
+}
+
+impl Default for SolanaClientConfig {
+    fn default() -> Self {
+        Self {
+            recent_blockhash_interval: Duration::from_secs(5),
+            commitment: CommitmentConfig::default(),
+            confirm_timeout: None,
+            skip_preflight: false,
+            airdrop_attempts: 5,
+            airdrop_timeout: Duration::from_secs(30),
+            airdrop_backoff: Duration::from_secs(1),
+            min_context_slot_attempts: 10,
+            min_context_slot_interval: Duration::from_millis(500),
+            account_cache: None,
+        }
+    }
+}
+
+/// Health of a [`SolanaClient`], for services embedding it to report, see
+/// [`SolanaClient::status`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientStatus {
+    /// As last reported with [`SolanaClient::set_ws_connected`].
+    pub ws_connected: bool,
+    /// Slot last fetched with [`SolanaClient::slot`].
+    pub last_slot: Option<Slot>,
+    pub slot_age: Option<Duration>,
+    /// Time since the blockhash new transactions are signed with was fetched.
+    pub blockhash_age: Option<Duration>,
+    /// Transactions sent by [`SolanaClient::send_and_confirm_instructions`] still waiting for
+    /// confirmation.
+    pub pending_signatures: Vec<Signature>,
+}
+
+pub struct SolanaClient<C = SolanaApiClient> {
+    inner: C,
+    dry_run: bool,
+    programs: KnownPrograms,
+    config: SolanaClientConfig,
+    recent_blockhash: Mutex<Option<(Hash, Instant)>>,
+    account_cache: Option<Mutex<AccountCache>>,
+    ws_connected: AtomicBool,
+    pubsub: Option<Subscriptions<Box<dyn PubsubTransport>>>,
+    last_slot: Mutex<Option<(Slot, Instant)>>,
+    pending_signatures: Mutex<Vec<Signature>>,
+}
+
+/// Keeps a signature listed as pending until the confirmation waiting for it ends, even if it's
+/// dropped halfway.
+struct PendingSignature<'a> {
+    pending: &'a Mutex<Vec<Signature>>,
+    signature: Signature,
+}
+
+impl<'a> PendingSignature<'a> {
+    fn register(pending: &'a Mutex<Vec<Signature>>, signature: Signature) -> Self {
+        pending.lock().unwrap().push(signature);
+        Self { pending, signature }
+    }
+}
+
+impl Drop for PendingSignature<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(position) = pending.iter().position(|other| *other == self.signature) {
+            pending.remove(position);
+        }
+    }
+}
+
+impl SolanaClient<SolanaApiClient> {
+    pub fn new(solana_api_url: &'static str) -> Self {
+        Self::from_client(SolanaApiClient::new(solana_api_url))
+    }
+
+    pub fn with_cluster(cluster: Cluster) -> Self {
+        Self::new(cluster.url())
+    }
+
+    pub fn devnet() -> Self {
+        Self::from_client(SolanaApiClient::devnet())
+    }
+}
+
+impl<C: Client> SolanaClient<C> {
+    pub fn from_client(inner: C) -> Self {
+        Self {
+            inner,
+            dry_run: false,
+            programs: KnownPrograms::default(),
+            config: SolanaClientConfig::default(),
+            recent_blockhash: Mutex::new(None),
+            account_cache: None,
+            ws_connected: AtomicBool::new(false),
+            pubsub: None,
+            last_slot: Mutex::new(None),
+            pending_signatures: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn with_config(mut self, config: SolanaClientConfig) -> Self {
+        self.config = config;
+        self.account_cache = config
+            .account_cache
+            .map(|config| Mutex::new(AccountCache::new(config)));
+        self
+    }
+
+    pub fn config(&self) -> &SolanaClientConfig {
+        &self.config
+    }
+
+    /// Simulate transactions passed to [`SolanaClient::send_instructions`] instead of sending
+    /// them.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Record whether the websocket connection of the embedding service is up, as reported by
+    /// [`SolanaClient::status`].
+    pub fn set_ws_connected(&self, connected: bool) {
+        self.ws_connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Confirm transactions from `signatureSubscribe` notifications of `subscriptions` as well as
+    /// by polling, while the websocket connection is reported up with
+    /// [`SolanaClient::set_ws_connected`].
+    ///
+    /// The embedding service keeps a clone of `subscriptions` to pass the notifications it reads
+    /// to.
+    pub fn with_pubsub(mut self, subscriptions: Subscriptions<Box<dyn PubsubTransport>>) -> Self {
+        self.pubsub = Some(subscriptions);
+        self
+    }
+
+    /// Current health of the client, from what it has fetched and sent so far.
+    pub fn status(&self) -> ClientStatus {
+        let last_slot = *self.last_slot.lock().unwrap();
+        let recent_blockhash = *self.recent_blockhash.lock().unwrap();
+
+        ClientStatus {
+            ws_connected: self.ws_connected.load(Ordering::Relaxed),
+            last_slot: last_slot.map(|(slot, _)| slot),
+            slot_age: last_slot.map(|(_, fetched_at)| fetched_at.elapsed()),
+            blockhash_age: recent_blockhash.map(|(_, fetched_at)| fetched_at.elapsed()),
+            pending_signatures: self.pending_signatures.lock().unwrap().clone(),
+        }
+    }
+
+    /// Programs whose custom errors are decoded when a transaction fails, see
+    /// [`SolanaClient::explain_failure`].
+    pub fn known_programs(mut self, programs: KnownPrograms) -> Self {
+        self.programs = programs;
+        self
+    }
+
+    fn account_config(&self, commitment: Option<CommitmentConfig>) -> RpcAccountInfoConfig {
+        RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: commitment.or(Some(self.config.commitment)),
+            min_context_slot: None,
+        }
+    }
+
+    /// Cached copy of `pubkey`, if the cache is enabled and the account was fetched recently.
+    fn cached(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.account_cache.as_ref()?.lock().unwrap().get(pubkey)
+    }
+
+    fn cache(&self, account: &Account) {
+        if let Some(cache) = &self.account_cache {
+            cache.lock().unwrap().insert(account.clone());
+        }
+    }
+
+    /// Drop `pubkey` from the account cache, so the next load fetches it again.
+    pub fn invalidate(&self, pubkey: &Pubkey) {
+        if let Some(cache) = &self.account_cache {
+            cache.lock().unwrap().invalidate(pubkey);
+        }
+    }
+
+    /// Drop every account `instructions` may write to from the account cache, as the cached
+    /// copies are stale once the transaction lands.
+    fn invalidate_written(&self, instructions: &[Instruction], payer: &Pubkey) {
+        self.invalidate(payer);
+
+        for instruction in instructions {
+            for meta in &instruction.accounts {
+                if meta.is_writable {
+                    self.invalidate(&meta.pubkey);
+                }
+            }
+        }
+    }
+
+    /// Fetch `pubkey` at `commitment`, the configured one if `None`, and interpret it as `T`.
+    ///
+    /// With the account cache enabled, a recently fetched copy is used instead. Reads at an
+    /// explicit `commitment` always go to the node, but still refresh the cache.
+    pub async fn load<T: Loadable>(
+        &self,
+        pubkey: &Pubkey,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<T, ClientError> {
+        if commitment.is_none() {
+            if let Some(account) = self.cached(pubkey) {
+                return T::load(Box::new(account));
+            }
+        }
+
+        let account = self
+            .inner
+            .get_account_info(*pubkey, Some(self.account_config(commitment)))
+            .await?;
+        self.cache(&account);
+        T::load(Box::new(account))
+    }
+
+    /// Like [`SolanaClient::load`], but from a node which has reached `min_context_slot`, e.g.
+    /// the slot a transaction that just wrote the account landed in.
+    ///
+    /// A node that is still behind is asked again, `min_context_slot_attempts` reads in total
+    /// `min_context_slot_interval` apart. The cache is bypassed, but refreshed.
+    pub async fn load_at_slot<T: Loadable>(
+        &self,
+        pubkey: &Pubkey,
+        min_context_slot: Slot,
+    ) -> Result<T, ClientError> {
+        let config = RpcAccountInfoConfig {
+            min_context_slot: Some(min_context_slot),
+            ..self.account_config(None)
+        };
+
+        let mut attempt = 1;
+        let account = loop {
+            match self
+                .inner
+                .get_account_info(*pubkey, Some(config.clone()))
+                .await
+            {
+                Err(ClientError {
+                    kind:
+                        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                            code: JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+                            ..
+                        }),
+                    ..
+                }) if attempt < self.config.min_context_slot_attempts => {
+                    attempt += 1;
+                    tokio::time::sleep(self.config.min_context_slot_interval).await;
+                }
+                result => break result?,
+            }
+        };
+
+        self.cache(&account);
+        T::load(Box::new(account))
+    }
+
+    /// Fetch all of `pubkeys` with as few `getMultipleAccounts` requests as possible and
+    /// interpret each of them as `T`.
+    ///
+    /// The outer error is returned if any of the requests fail, the inner results line up with
+    /// `pubkeys` and report missing accounts and accounts which failed to parse.
+    ///
+    /// With the account cache enabled, only accounts missing from it are fetched. Reads at an
+    /// explicit `commitment` always go to the node, but still refresh the cache.
+    pub async fn load_many<T: Loadable>(
+        &self,
+        pubkeys: &[Pubkey],
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<Result<T, ClientError>>, ClientError> {
+        let mut accounts: Vec<Option<Account>> = pubkeys
+            .iter()
+            .map(|pubkey| commitment.is_none().then(|| self.cached(pubkey)).flatten())
+            .collect();
+
+        let missing: Vec<Pubkey> = pubkeys
+            .iter()
+            .zip(&accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(pubkey, _)| *pubkey)
+            .collect();
+
+        for chunk in missing.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let fetched = self
+                .inner
+                .get_multiple_accounts(chunk, Some(self.account_config(commitment)))
+                .await?;
+
+            // missing accounts are skipped by the rpc client, so match the rest up by key
+            for account in fetched {
+                self.cache(&account);
+
+                for (pubkey, slot) in pubkeys.iter().zip(&mut accounts) {
+                    if slot.is_none() && *pubkey == account.pubkey {
+                        *slot = Some(account.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(pubkeys
+            .iter()
+            .zip(accounts)
+            .map(|(key, account)| match account {
+                Some(account) => T::load(Box::new(account)),
+                None => Err(ClientErrorKind::Custom(format!("account {} not found", key)).into()),
+            })
+            .collect())
+    }
+
+    /// Fail with "account already exists" if any of `pubkeys` is already on-chain.
+    ///
+    /// Meant to run before sending a transaction which creates `pubkeys`, so a reused keypair is
+    /// caught before paying for a transaction bound to fail. The cache is bypassed.
+    pub async fn ensure_accounts_missing(&self, pubkeys: &[Pubkey]) -> Result<(), ClientError> {
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let existing = self
+                .inner
+                .get_multiple_accounts(chunk, Some(self.account_config(None)))
+                .await?;
+
+            if let Some(account) = existing.first() {
+                return Err(ClientErrorKind::Custom(format!(
+                    "account {} already exists",
+                    account.pubkey
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether `pubkey` is on-chain right now, e.g. to tell whether a transaction closed it. The
+    /// cache is bypassed.
+    pub async fn account_exists(&self, pubkey: &Pubkey) -> Result<bool, ClientError> {
+        let existing = self
+            .inner
+            .get_multiple_accounts(&[*pubkey], Some(self.account_config(None)))
+            .await?;
+
+        Ok(!existing.is_empty())
+    }
+
+    /// Whether `wallet` is already a token wallet of `mint` held by `authority`, `false` if the
+    /// account doesn't exist yet.
+    ///
+    /// Lets a command creating a vault be run again with the same keys once an earlier run
+    /// created the vault but failed afterwards. An existing account which is anything else is an
+    /// error, as it can't be created over. The cache is bypassed.
+    pub async fn wallet_exists(
+        &self,
+        wallet: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<bool, ClientError> {
+        let account = match self
+            .inner
+            .get_multiple_accounts(&[*wallet], Some(self.account_config(None)))
+            .await?
+            .pop()
+        {
+            Some(account) => account,
+            None => return Ok(false),
+        };
+
+        let existing = WalletAccount::any(Box::new(account)).map_err(|err| {
+            ClientErrorKind::Custom(format!("account {} is not a token wallet: {}", wallet, err))
+        })?;
+
+        if existing.mint() != mint || existing.authority() != authority {
+            return Err(ClientErrorKind::Custom(format!(
+                "wallet {} holds {} for {}, expected {} for {}",
+                wallet,
+                existing.mint(),
+                existing.authority(),
+                mint,
+                authority
+            ))
+            .into());
+        }
+
+        Ok(true)
+    }
+
+    /// Balance of `pubkey` in lamports, zero if the account doesn't exist. The cache is bypassed.
+    pub async fn balance(&self, pubkey: &Pubkey) -> Result<u64, ClientError> {
+        let accounts = self
+            .inner
+            .get_multiple_accounts(&[*pubkey], Some(self.account_config(None)))
+            .await?;
+
+        Ok(accounts.first().map_or(0, |account| account.lamports))
+    }
+
+    /// Airdrop `lamports` to `pubkey` and wait until they show up in its balance.
+    ///
+    /// Faucets rate limit and sometimes drop requests without reporting an error, so a request
+    /// which fails or whose lamports don't arrive within `airdrop_timeout` is retried, up to
+    /// `airdrop_attempts` times with a growing pause in between. Once all attempts are used up,
+    /// the error of the last one is returned.
+    pub async fn request_airdrop(
+        &self,
+        pubkey: &Pubkey,
+        lamports: u64,
+    ) -> Result<Signature, ClientError> {
+        let target = self.balance(pubkey).await?.saturating_add(lamports);
+        let mut backoff = self.config.airdrop_backoff;
+        let mut last_error = None;
+
+        for attempt in 0..self.config.airdrop_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+
+            let signature = match self
+                .inner
+                .request_airdrop(pubkey, lamports, Some(self.config.commitment))
+                .await
+            {
+                Ok(signature) => signature,
+                Err(err) => {
+                    last_error = Some(err.to_string());
+                    continue;
+                }
+            };
+
+            let arrival = self.wait_for_balance(pubkey, target);
+            match tokio::time::timeout(self.config.airdrop_timeout, arrival).await {
+                Ok(Ok(())) => {
+                    self.invalidate(pubkey);
+                    return Ok(signature);
+                }
+                Ok(Err(err)) => last_error = Some(err.to_string()),
+                Err(_) => {
+                    last_error = Some(format!(
+                        "airdrop {} did not arrive within {:?}",
+                        signature, self.config.airdrop_timeout
+                    ))
+                }
+            }
+        }
+
+        Err(ClientErrorKind::Custom(format!(
+            "airdrop of {} lamports to {} failed after {} attempts: {}",
+            lamports,
+            pubkey,
+            self.config.airdrop_attempts,
+            last_error.as_deref().unwrap_or("no airdrop requested")
+        ))
+        .into())
+    }
+
+    async fn wait_for_balance(&self, pubkey: &Pubkey, target: u64) -> Result<(), ClientError> {
+        while self.balance(pubkey).await? < target {
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+
+        Ok(())
+    }
+
+    /// Blockhash for new transactions, fetched again once the last one is older than
+    /// `recent_blockhash_interval`.
+    pub async fn recent_blockhash(&self) -> Result<Hash, ClientError> {
+        if let Some((blockhash, fetched_at)) = *self.recent_blockhash.lock().unwrap() {
+            if fetched_at.elapsed() < self.config.recent_blockhash_interval {
+                return Ok(blockhash);
+            }
+        }
+
+        let blockhash = get_recent_blockhash_with_backoff(
+            &self.inner,
+            Some(self.config.commitment),
+            BLOCKHASH_ATTEMPTS,
+            BLOCKHASH_BACKOFF,
+        )
+        .await?
+        .blockhash;
+        *self.recent_blockhash.lock().unwrap() = Some((blockhash, Instant::now()));
+
+        Ok(blockhash)
+    }
+
+    /// Sign `instructions` with a recent blockhash and submit them as a single transaction.
+    ///
+    /// `signers` must include the fee payer. In dry-run mode the transaction is only simulated,
+    /// the simulation is printed and the signature it would have landed with is returned.
+    pub async fn send_instructions(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&dyn Signer],
+    ) -> Result<Signature, ClientError> {
+        let blockhash = self.recent_blockhash().await?;
+
+        self.invalidate_written(instructions, payer);
+
+        let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+        transaction
+            .try_sign(&signers.to_vec(), blockhash)
+            .map_err(ClientErrorKind::from)?;
+
+        if self.dry_run {
+            return self.simulate(&transaction).await;
+        }
+
+        let config = RpcSendTransactionConfig {
+            skip_preflight: self.config.skip_preflight,
+            ..Default::default()
+        };
+        let result = self.inner.send_transaction(&transaction, config).await;
+
+        if result.is_err() {
+            // the send error stays the one reported, a failed simulation just explains less
+            if let Ok(explanation) = self.explain_failure(&transaction).await {
+                eprint!("{}", explanation);
+            }
+        }
+
+        result
+    }
+
+    /// Simulate `transaction` again to find out why it failed, with the program logs and the
+    /// custom error decoded for programs set with [`SolanaClient::known_programs`].
+    pub async fn explain_failure(
+        &self,
+        transaction: &Transaction,
+    ) -> Result<FailureExplanation, ClientError> {
+        let result = self
+            .inner
+            .simulate_transaction(transaction, RpcSimulateTransactionConfig::default())
+            .await?;
+
+        Ok(FailureExplanation::from_simulation(
+            transaction,
+            result,
+            &self.programs,
+        ))
+    }
+
+    /// Like [`SolanaClient::send_instructions`], but also waits for the transaction to be
+    /// confirmed, polling its status every `CONFIRMATION_POLL_INTERVAL` for up to
+    /// `confirm_timeout`.
+    pub async fn send_and_confirm_instructions(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&dyn Signer],
+        commitment: CommitmentConfig,
+    ) -> Result<Signature, ClientError> {
+        let signature = self.send_instructions(instructions, payer, signers).await?;
+
+        if !self.dry_run {
+            let _pending = PendingSignature::register(&self.pending_signatures, signature);
+            let confirmation = async {
+                let notification = match self.subscribe_signature(&signature, commitment).await {
+                    Some(listener) => Either::Left(signature_notification(listener)),
+                    None => Either::Right(future::pending()),
+                };
+
+                confirm_transaction(
+                    &self.inner,
+                    &signature,
+                    commitment,
+                    CONFIRMATION_POLL_INTERVAL,
+                    notification,
+                )
+                .await
+            };
+
+            match self.config.confirm_timeout {
+                Some(timeout) => {
+                    tokio::time::timeout(timeout, confirmation)
+                        .await
+                        .map_err(|_| {
+                            ClientErrorKind::Custom(format!(
+                                "transaction {} not confirmed after {:?}",
+                                signature, timeout
+                            ))
+                        })??
+                }
+                None => confirmation.await?,
+            }
+
+            // reads made while the transaction was in flight may have cached the old state
+            self.invalidate_written(instructions, payer);
+        }
+
+        Ok(signature)
+    }
+
+    /// `signatureSubscribe` notifications of `signature`, `None` without a websocket connection
+    /// or if subscribing fails, which leaves confirmation to polling.
+    async fn subscribe_signature(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> Option<Listener<Box<dyn PubsubTransport>>> {
+        let subscriptions = self.pubsub.as_ref()?;
+        if !self.ws_connected.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        subscriptions
+            .subscribe(
+                "signatureSubscribe",
+                json!([signature.to_string(), commitment]),
+            )
+            .await
+            .ok()
+    }
+
+    async fn simulate(&self, transaction: &Transaction) -> Result<Signature, ClientError> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: true,
+            ..Default::default()
+        };
+        let result = self.inner.simulate_transaction(transaction, config).await?;
+
+        println!("dry run, transaction not sent");
+        for log in result.logs.iter().flatten() {
+            println!("  {}", log);
+        }
+
+        let units_consumed = result
+            .units_consumed
+            .or_else(|| result.logs.as_deref().and_then(units_consumed_from_logs));
+        if let Some(units) = units_consumed {
+            println!("compute units consumed: {}", units);
+        }
+
+        match result.err {
+            Some(err) => Err(ClientErrorKind::TransactionError(err).into()),
+            None => Ok(transaction.signatures[0]),
+        }
+    }
+
+    /// Slot the node has reached at the configured commitment.
+    pub async fn slot(&self) -> Result<Slot, ClientError> {
+        let slot = self
+            .inner
+            .get_slot(Some(RpcSlotConfig {
+                commitment: Some(self.config.commitment),
+            }))
+            .await?;
+        *self.last_slot.lock().unwrap() = Some((slot, Instant::now()));
+
+        Ok(slot)
+    }
+
+    /// Current cluster time, as seen by the `Clock` sysvar.
+    pub async fn clock(&self) -> Result<Clock, ClientError> {
+        let account = self.inner.get_account_info(*clock::ID, None).await?;
+        bincode::deserialize(&account.data).map_err(|err| {
+            ClientErrorKind::Custom(format!("failed to decode clock sysvar: {}", err)).into()
+        })
+    }
+}
+
+/// Total compute units of the top level instructions, as reported by the
+/// `Program <id> consumed <n> of <m> compute units` log lines.
+///
+/// Inner instructions are already included in their caller's count, so they're skipped.
+pub(crate) fn units_consumed_from_logs(logs: &[String]) -> Option<u64> {
+    let mut depth = 0;
+    let mut total = None;
+
+    for log in logs {
+        if log.contains(" invoke [") {
+            depth += 1;
+        } else if log.ends_with(" success") || log.contains(" failed: ") {
+            depth -= 1;
+        } else if depth == 1 {
+            let units = log
+                .split_once(" consumed ")
+                .and_then(|(_, rest)| rest.split(' ').next()?.parse::<u64>().ok());
+
+            if let Some(units) = units {
+                total = Some(total.unwrap_or(0) + units);
+            }
+        }
+    }
+
+    total
+}
+
+impl<C> Deref for SolanaClient<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use solana_api_types::*;
+    use solana_rpc_client::pubsub::{PubsubTransport, Subscriptions};
+
+    use x5margin_program::simple_stake::InitializeArgs;
+
+    use super::{
+        builder::PoolTransactionBuilder,
+        cache::AccountCacheConfig,
+        mock::{CountingClient, CountingTransport},
+        Cluster, SolanaClient, SolanaClientConfig,
+    };
+
+    #[tokio::test]
+    async fn account_cache_test() {
+        let keys: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let accounts = keys
+            .iter()
+            .map(|pubkey| Account {
+                pubkey: *pubkey,
+                ..Default::default()
+            })
+            .collect();
+
+        let config = SolanaClientConfig {
+            account_cache: Some(AccountCacheConfig {
+                capacity: 8,
+                ttl: Duration::from_secs(60),
+            }),
+            ..Default::default()
+        };
+        let client = SolanaClient::from_client(CountingClient::new(accounts)).with_config(config);
+
+        let account: Box<Account> = client.load(&keys[0], None).await.unwrap();
+        assert_eq!(account.pubkey, keys[0]);
+        let _: Box<Account> = client.load(&keys[0], None).await.unwrap();
+        assert_eq!(client.requests.get(), 1);
+
+        // only the accounts that aren't cached yet are fetched, in a single request
+        let loaded = client.load_many::<Box<Account>>(&keys, None).await.unwrap();
+        assert!(loaded.iter().all(Result::is_ok));
+        assert_eq!(client.requests.get(), 2);
+        client.load_many::<Box<Account>>(&keys, None).await.unwrap();
+        assert_eq!(client.requests.get(), 2);
+
+        client.invalidate(&keys[1]);
+        let _: Box<Account> = client.load(&keys[1], None).await.unwrap();
+        assert_eq!(client.requests.get(), 3);
+
+        // without the cache every load goes to the node
+        let client = SolanaClient::from_client(CountingClient::new(vec![]));
+        assert!(client.load::<Box<Account>>(&keys[0], None).await.is_err());
+        assert!(client.load::<Box<Account>>(&keys[0], None).await.is_err());
+        assert_eq!(client.requests.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn load_at_slot_test() {
+        let key = Pubkey::new_unique();
+        let config = SolanaClientConfig {
+            account_cache: Some(AccountCacheConfig {
+                capacity: 8,
+                ttl: Duration::from_secs(60),
+            }),
+            min_context_slot_attempts: 5,
+            min_context_slot_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let client = SolanaClient::from_client(CountingClient::new(vec![Account {
+            pubkey: key,
+            lamports: 10,
+            ..Default::default()
+        }]))
+        .with_config(config);
+
+        // cached at slot 0
+        let _: Box<Account> = client.load(&key, None).await.unwrap();
+
+        // the stale reads at slots 1 and 2 are retried, the cached copy isn't used
+        let account: Box<Account> = client.load_at_slot(&key, 3).await.unwrap();
+        assert_eq!(account.lamports, 10);
+        assert_eq!(client.requests.get(), 4);
+
+        // a node that doesn't catch up in time is reported
+        let err = client
+            .load_at_slot::<Box<Account>>(&key, 100)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                code: JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+                ..
+            })
+        ));
+        assert_eq!(client.requests.get(), 9);
+
+        // an explicit commitment skips the cache as well
+        let _: Box<Account> = client
+            .load(&key, Some(CommitmentConfig::default()))
+            .await
+            .unwrap();
+        assert_eq!(client.requests.get(), 10);
+    }
+
+    #[tokio::test]
+    async fn ensure_accounts_missing_test() {
+        let existing = Pubkey::new_unique();
+        let client = SolanaClient::from_client(CountingClient::new(vec![Account {
+            pubkey: existing,
+            ..Default::default()
+        }]));
+
+        let fresh: Vec<_> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        client.ensure_accounts_missing(&fresh).await.unwrap();
+
+        let err = client
+            .ensure_accounts_missing(&[fresh[0], existing])
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("account {} already exists", existing)));
+    }
+
+    #[tokio::test]
+    async fn account_exists_test() {
+        let ticket = Pubkey::new_unique();
+        let client = SolanaClient::from_client(CountingClient::new(vec![Account {
+            pubkey: ticket,
+            ..Default::default()
+        }]));
+
+        assert!(client.account_exists(&ticket).await.unwrap());
+        assert!(!client.account_exists(&Pubkey::new_unique()).await.unwrap());
+
+        // closed by a later transaction
+        client.accounts.borrow_mut().clear();
+        assert!(!client.account_exists(&ticket).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn wallet_exists_test() {
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let wallet = |pubkey: Pubkey, mint: Pubkey| {
+            let mut data = vec![0; 165];
+            data[..32].copy_from_slice(mint.as_ref());
+            data[32..64].copy_from_slice(authority.as_ref());
+            data[108] = 1;
+
+            Account {
+                data,
+                owner: *solar::spl::ID,
+                pubkey,
+                ..Default::default()
+            }
+        };
+
+        let vault = Pubkey::new_unique();
+        let other_mint_vault = Pubkey::new_unique();
+        let not_a_wallet = Pubkey::new_unique();
+        let client = SolanaClient::from_client(CountingClient::new(vec![
+            wallet(vault, mint),
+            wallet(other_mint_vault, Pubkey::new_unique()),
+            Account {
+                pubkey: not_a_wallet,
+                ..Default::default()
+            },
+        ]));
+
+        assert!(client
+            .wallet_exists(&vault, &mint, &authority)
+            .await
+            .unwrap());
+        assert!(!client
+            .wallet_exists(&Pubkey::new_unique(), &mint, &authority)
+            .await
+            .unwrap());
+        assert!(client
+            .wallet_exists(&vault, &mint, &Pubkey::new_unique())
+            .await
+            .is_err());
+        assert!(client
+            .wallet_exists(&other_mint_vault, &mint, &authority)
+            .await
+            .is_err());
+        assert!(client
+            .wallet_exists(&not_a_wallet, &mint, &authority)
+            .await
+            .is_err());
+
+        // a pre-existing vault is used without being created again
+        let program_id = Pubkey::new_unique();
+        let pool = Keypair::new();
+        let transaction = PoolTransactionBuilder::new(
+            program_id,
+            Pubkey::new_unique(),
+            mint,
+            InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            },
+        )
+        .pool_key(pool.clone())
+        .existing_stake_vault(vault)
+        .build();
+
+        assert_eq!(transaction.stake_vault, vault);
+        assert_eq!(transaction.instructions.len(), 2);
+        assert!(transaction
+            .instructions
+            .iter()
+            .all(|instruction| instruction.program_id != *solar::spl::ID));
+        let signers: Vec<_> = transaction.signers.iter().map(Signer::pubkey).collect();
+        assert_eq!(signers, vec![pool.pubkey()]);
+    }
+
+    #[tokio::test]
+    async fn request_airdrop_test() {
+        let config = SolanaClientConfig {
+            airdrop_attempts: 3,
+            airdrop_timeout: Duration::from_millis(50),
+            airdrop_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let pubkey = Pubkey::new_unique();
+
+        // rate limited, then dropped, then credited
+        let client = SolanaClient::from_client(CountingClient::new(vec![])).with_config(config);
+        client.request_airdrop(&pubkey, 1_000).await.unwrap();
+        assert_eq!(client.airdrops.get(), 3);
+        assert_eq!(client.balance(&pubkey).await.unwrap(), 1_000);
+
+        let client = SolanaClient::from_client(CountingClient::new(vec![])).with_config(
+            SolanaClientConfig {
+                airdrop_attempts: 2,
+                ..config
+            },
+        );
+        let err = client.request_airdrop(&pubkey, 1_000).await.unwrap_err();
+        assert_eq!(client.airdrops.get(), 2);
+        assert!(err.to_string().contains("failed after 2 attempts"));
+        assert!(err.to_string().contains("did not arrive"));
+    }
+
+    #[tokio::test]
+    async fn status_test() {
+        let client = SolanaClient::from_client(CountingClient::new(vec![])).with_config(
+            SolanaClientConfig {
+                confirm_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            },
+        );
+
+        let status = client.status();
+        assert!(!status.ws_connected);
+        assert_eq!(status.last_slot, None);
+        assert_eq!(status.blockhash_age, None);
+        assert!(status.pending_signatures.is_empty());
+
+        client.slot.set(42);
+        assert_eq!(client.slot().await.unwrap(), 42);
+        client.set_ws_connected(true);
+
+        // the mock never confirms, so the transaction stays pending until the timeout
+        let payer = Keypair::new();
+        let sending = client.send_and_confirm_instructions(
+            &[],
+            &payer.pubkey(),
+            &[&payer],
+            CommitmentConfig::default(),
+        );
+        let watching = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            client.status()
+        };
+        let (result, status) = tokio::join!(sending, watching);
+
+        assert!(result.unwrap_err().to_string().contains("not confirmed"));
+        assert!(status.ws_connected);
+        assert_eq!(status.last_slot, Some(42));
+        assert!(status.slot_age.is_some());
+        assert!(status.blockhash_age.is_some());
+        assert_eq!(status.pending_signatures.len(), 1);
+
+        assert!(client.status().pending_signatures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn signature_notification_test() {
+        let subscriptions: Subscriptions<Box<dyn PubsubTransport>> =
+            Subscriptions::new(Box::new(CountingTransport::default()));
+        let client = SolanaClient::from_client(CountingClient::new(vec![]))
+            .with_config(SolanaClientConfig {
+                confirm_timeout: Some(Duration::from_millis(50)),
+                ..Default::default()
+            })
+            .with_pubsub(subscriptions.clone());
+
+        // the mock never confirms a poll, so only a notification can settle the transaction
+        let payer = Keypair::new();
+        let send = || {
+            client.send_and_confirm_instructions(
+                &[],
+                &payer.pubkey(),
+                &[&payer],
+                CommitmentConfig::default(),
+            )
+        };
+        let notify = |subscription_id: u64, err: serde_json::Value| {
+            let subscriptions = subscriptions.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                subscriptions.notify(
+                    subscription_id,
+                    json!({ "context": { "slot": 1 }, "value": { "err": err } }),
+                )
+            }
+        };
+
+        // without a websocket connection nothing is subscribed
+        let (result, notified) = tokio::join!(send(), notify(1, json!(null)));
+        assert!(result.unwrap_err().to_string().contains("not confirmed"));
+        assert!(!notified);
+
+        client.set_ws_connected(true);
+        let (result, notified) = tokio::join!(send(), notify(1, json!(null)));
+        assert!(notified);
+        result.unwrap();
+        assert_eq!(subscriptions.subscription_count(), 0);
+
+        let (result, notified) = tokio::join!(send(), notify(2, json!("AccountInUse")));
+        assert!(notified);
+        assert!(matches!(
+            result.unwrap_err().kind,
+            ClientErrorKind::TransactionError(TransactionError::AccountInUse)
+        ));
+    }
+
+    #[tokio::test]
+    async fn skip_preflight_test() {
+        let payer = Keypair::new();
+
+        for skip_preflight in [false, true] {
+            let client = SolanaClient::from_client(CountingClient::new(vec![])).with_config(
+                SolanaClientConfig {
+                    skip_preflight,
+                    ..Default::default()
+                },
+            );
+            client
+                .send_instructions(&[], &payer.pubkey(), &[&payer])
+                .await
+                .unwrap();
+
+            let sent = client.sent.borrow();
+            assert_eq!(sent.len(), 1);
+            assert_eq!(sent[0].skip_preflight, skip_preflight);
+        }
+    }
+
+    #[test]
+    fn custom_config_test() {
+        let client = SolanaClient::with_cluster(Cluster::Localnet);
+        assert_eq!(client.config(), &SolanaClientConfig::default());
+
+        let config = SolanaClientConfig {
+            recent_blockhash_interval: Duration::from_secs(30),
+            confirm_timeout: Some(Duration::from_secs(90)),
+            ..Default::default()
+        };
+        let client = SolanaClient::with_cluster(Cluster::Localnet).with_config(config);
+        assert_eq!(client.config(), &config);
+    }
+}
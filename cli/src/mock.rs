@@ -0,0 +1,221 @@
+use std::cell::{Cell, RefCell};
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use solana_api_types::*;
+use solana_rpc_client::pubsub::PubsubTransport;
+
+/// Serves a set of accounts and counts the account requests it answers.
+///
+/// Airdrops behave like a flaky faucet: the first request is rate limited, the second one
+/// is accepted but never lands and the rest are credited right away. Simulations succeed and
+/// report `units_consumed`. The node is at `slot`, which advances with every `getAccountInfo`.
+/// Sent transactions are accepted but never confirmed.
+pub(crate) struct CountingClient {
+    pub accounts: RefCell<Vec<Account>>,
+    pub requests: Cell<usize>,
+    pub airdrops: Cell<usize>,
+    pub units_consumed: Option<u64>,
+    /// Transactions passed to `simulateTransaction`.
+    pub simulated: RefCell<Vec<Transaction>>,
+    /// Configs passed to `sendTransaction`.
+    pub sent: RefCell<Vec<RpcSendTransactionConfig>>,
+    pub slot: Cell<Slot>,
+}
+
+impl CountingClient {
+    pub fn new(accounts: Vec<Account>) -> Self {
+        Self {
+            accounts: RefCell::new(accounts),
+            requests: Cell::new(0),
+            airdrops: Cell::new(0),
+            units_consumed: None,
+            simulated: RefCell::new(vec![]),
+            sent: RefCell::new(vec![]),
+            slot: Cell::new(0),
+        }
+    }
+
+    pub fn with_units_consumed(units_consumed: u64) -> Self {
+        Self {
+            units_consumed: Some(units_consumed),
+            ..Self::new(vec![])
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for CountingClient {
+    async fn get_account_info(
+        &self,
+        account: Pubkey,
+        cfg: Option<RpcAccountInfoConfig>,
+    ) -> Result<Account, ClientError> {
+        self.requests.set(self.requests.get() + 1);
+
+        let slot = self.slot.get();
+        self.slot.set(slot + 1);
+        if let Some(min_context_slot) = cfg.and_then(|cfg| cfg.min_context_slot) {
+            if slot < min_context_slot {
+                return Err(RpcError::RpcResponseError {
+                    code: JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+                    message: "Minimum context slot has not been reached".to_string(),
+                    data: RpcResponseErrorData::Empty,
+                }
+                .into());
+            }
+        }
+
+        self.accounts
+            .borrow()
+            .iter()
+            .find(|candidate| candidate.pubkey == account)
+            .cloned()
+            .ok_or_else(|| ClientErrorKind::Custom("account not found".to_string()).into())
+    }
+
+    async fn get_program_accounts(
+        &self,
+        _: Pubkey,
+        _: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        accounts: &[Pubkey],
+        _: Option<RpcAccountInfoConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        self.requests.set(self.requests.get() + 1);
+        Ok(self
+            .accounts
+            .borrow()
+            .iter()
+            .filter(|account| accounts.contains(&account.pubkey))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+        _: Option<RpcSignatureStatusConfig>,
+    ) -> Result<Vec<Option<TransactionStatus>>, ClientError> {
+        Ok(vec![None; signatures.len()])
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        _: &Pubkey,
+        _: Option<RpcSignaturesForAddressConfig>,
+    ) -> Result<Vec<SignatureInfo>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_slot(&self, _: Option<RpcSlotConfig>) -> Result<Slot, ClientError> {
+        Ok(self.slot.get())
+    }
+
+    async fn get_transaction(
+        &self,
+        _: Signature,
+        _: Option<RpcTransactionConfig>,
+    ) -> Result<Option<EncodedConfirmedTransaction>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_block(
+        &self,
+        _: Slot,
+        _: Option<RpcBlockConfig>,
+    ) -> Result<Option<ConfirmedBlock>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn request_airdrop(
+        &self,
+        pubkey: &Pubkey,
+        lamports: u64,
+        _: Option<CommitmentConfig>,
+    ) -> Result<Signature, ClientError> {
+        let airdrop = self.airdrops.get();
+        self.airdrops.set(airdrop + 1);
+
+        match airdrop {
+            0 => Err(RpcError::RpcRequestError("rate limited".to_string()).into()),
+            1 => Ok(Signature::default()),
+            _ => {
+                let mut accounts = self.accounts.borrow_mut();
+                match accounts
+                    .iter_mut()
+                    .find(|account| account.pubkey == *pubkey)
+                {
+                    Some(account) => account.lamports += lamports,
+                    None => accounts.push(Account {
+                        pubkey: *pubkey,
+                        lamports,
+                        ..Default::default()
+                    }),
+                }
+                Ok(Signature::default())
+            }
+        }
+    }
+
+    async fn send_transaction(
+        &self,
+        transaction: &Transaction,
+        cfg: RpcSendTransactionConfig,
+    ) -> Result<Signature, ClientError> {
+        self.sent.borrow_mut().push(cfg);
+        Ok(transaction.signatures[0])
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+        _: RpcSimulateTransactionConfig,
+    ) -> Result<RpcSimulateTransactionResult, ClientError> {
+        self.simulated.borrow_mut().push(transaction.clone());
+        Ok(RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(vec![]),
+            accounts: None,
+            units_consumed: self.units_consumed,
+        })
+    }
+
+    async fn get_recent_blockhash(
+        &self,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcRecentBlockhash, ClientError> {
+        Ok(RpcRecentBlockhash {
+            blockhash: Hash::default(),
+            fee_calculator: FeeCalculator {
+                lamports_per_signature: 5000,
+            },
+        })
+    }
+
+    async fn get_health(&self) -> Result<(), ClientError> {
+        unimplemented!()
+    }
+}
+
+/// Accepts every subscription, numbering them from 1 as the server would.
+#[derive(Default)]
+pub(crate) struct CountingTransport {
+    pub subscribed: Cell<u64>,
+}
+
+#[async_trait(?Send)]
+impl PubsubTransport for CountingTransport {
+    async fn subscribe(&self, _: &str, _: &Value) -> Result<u64, ClientError> {
+        self.subscribed.set(self.subscribed.get() + 1);
+        Ok(self.subscribed.get())
+    }
+
+    fn unsubscribe(&self, _: &str, _: u64) {}
+}
@@ -0,0 +1,210 @@
+use std::{fs, io, path::Path};
+
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use hmac::Hmac;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_api_types::Pubkey;
+use zeroize::Zeroizing;
+
+pub const DEFAULT_STORE_PATH: &str = "store.json";
+
+/// PBKDF2 rounds used to derive the store key from a passphrase.
+const KDF_ROUNDS: u32 = 100_000;
+
+/// Local state shared between CLI invocations, persisted as JSON.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ClientStore {
+    #[serde(default)]
+    pub lockers: Vec<Pubkey>,
+    #[serde(default)]
+    pub pools: Vec<Pubkey>,
+    #[serde(default)]
+    pub tickets: Vec<TicketRecord>,
+}
+
+/// On-disk format of a passphrase protected store, the ciphertext is the AES-256-GCM encrypted
+/// plaintext JSON.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedStore {
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0; 32]);
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut *key);
+    key
+}
+
+/// Staker ticket created for `staker` in `pool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketRecord {
+    pub pool: Pubkey,
+    pub staker: Pubkey,
+    pub ticket: Pubkey,
+}
+
+impl ClientStore {
+    /// Read the plaintext store at `path`, starting with an empty one if the file doesn't exist
+    /// yet.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::load_with(path, None)
+    }
+
+    /// Read the store at `path`, decrypting it with `passphrase` if it was saved encrypted.
+    ///
+    /// Plaintext stores are read as is, even if a passphrase is given, so they can be encrypted
+    /// by saving them again.
+    pub fn load_with(path: impl AsRef<Path>, passphrase: Option<&str>) -> anyhow::Result<Self> {
+        let data = match fs::read(path) {
+            Ok(data) => Zeroizing::new(data),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let encrypted = match serde_json::from_slice::<EncryptedStore>(&data) {
+            Ok(encrypted) => encrypted,
+            Err(_) => return Ok(serde_json::from_slice(&data)?),
+        };
+
+        let passphrase = passphrase
+            .ok_or_else(|| anyhow::anyhow!("the store is encrypted, a passphrase is required"))?;
+        let key = derive_key(passphrase, &encrypted.salt);
+        let plaintext = Aes256Gcm::new(Key::from_slice(&*key))
+            .decrypt(
+                Nonce::from_slice(&encrypted.nonce),
+                encrypted.ciphertext.as_ref(),
+            )
+            .map_err(|_| anyhow::anyhow!("failed to decrypt the store, wrong passphrase?"))?;
+
+        Ok(serde_json::from_slice(&Zeroizing::new(plaintext))?)
+    }
+
+    pub fn find_ticket(&self, pool: &Pubkey, staker: &Pubkey) -> Option<&TicketRecord> {
+        self.tickets
+            .iter()
+            .find(|record| record.pool == *pool && record.staker == *staker)
+    }
+
+    /// Write the store to `path` as plaintext JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.save_with(path, None)
+    }
+
+    /// Write the store to `path`, encrypted with `passphrase` if one is given.
+    pub fn save_with(
+        &self,
+        path: impl AsRef<Path>,
+        passphrase: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let plaintext = Zeroizing::new(serde_json::to_vec_pretty(self)?);
+
+        let passphrase = match passphrase {
+            Some(passphrase) => passphrase,
+            None => {
+                fs::write(path, &*plaintext)?;
+                return Ok(());
+            }
+        };
+
+        let mut salt = [0; 16];
+        let mut nonce = [0; 12];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt);
+        let ciphertext = Aes256Gcm::new(Key::from_slice(&*key))
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|_| anyhow::anyhow!("failed to encrypt the store"))?;
+
+        let encrypted = EncryptedStore {
+            salt,
+            nonce,
+            ciphertext,
+        };
+        fs::write(path, serde_json::to_vec_pretty(&encrypted)?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use solana_api_types::Pubkey;
+
+    use super::{ClientStore, TicketRecord};
+
+    /// A path in the temp dir unique to `name`, removed again when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("x5-store-{}-{}.json", name, std::process::id()));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn store() -> ClientStore {
+        ClientStore {
+            lockers: vec![Pubkey::new([1; 32])],
+            pools: vec![Pubkey::new([2; 32])],
+            tickets: vec![TicketRecord {
+                pool: Pubkey::new([2; 32]),
+                staker: Pubkey::new([3; 32]),
+                ticket: Pubkey::new([4; 32]),
+            }],
+        }
+    }
+
+    fn assert_same(loaded: &ClientStore, expected: &ClientStore) {
+        assert_eq!(loaded.lockers, expected.lockers);
+        assert_eq!(loaded.pools, expected.pools);
+        assert_eq!(loaded.tickets, expected.tickets);
+    }
+
+    #[test]
+    fn encrypted_store_test() {
+        let path = TempPath::new("encrypted");
+        store().save_with(&path.0, Some("correct horse")).unwrap();
+
+        let loaded = ClientStore::load_with(&path.0, Some("correct horse")).unwrap();
+        assert_same(&loaded, &store());
+
+        assert!(ClientStore::load_with(&path.0, Some("battery staple")).is_err());
+        assert!(ClientStore::load_with(&path.0, None).is_err());
+        assert!(ClientStore::load(&path.0).is_err());
+    }
+
+    #[test]
+    fn plaintext_store_test() {
+        let path = TempPath::new("plaintext");
+        store().save(&path.0).unwrap();
+
+        assert_same(&ClientStore::load(&path.0).unwrap(), &store());
+        // a passphrase doesn't get in the way of reading a store saved before encryption
+        assert_same(
+            &ClientStore::load_with(&path.0, Some("correct horse")).unwrap(),
+            &store(),
+        );
+
+        // a missing store starts out empty
+        let missing = TempPath::new("missing");
+        assert!(ClientStore::load(&missing.0).unwrap().pools.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+use solana_api_types::{
+    system::{self, SystemInstruction},
+    ClientErrorKind, CompiledInstruction, EncodedConfirmedTransaction, Pubkey,
+};
+use solar::spl;
+use x5margin_program::simple_stake;
+
+/// Program ids of the deployments a summary should recognize.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KnownPrograms {
+    pub stake: Option<Pubkey>,
+    pub locker: Option<Pubkey>,
+}
+
+/// Describe every instruction of `transaction` in a human-readable way.
+pub fn summarize_transaction(
+    transaction: &EncodedConfirmedTransaction,
+    programs: &KnownPrograms,
+) -> Result<Vec<String>, ClientErrorKind> {
+    let decoded = transaction
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| ClientErrorKind::Custom("failed to decode transaction".to_string()))?;
+    let keys = &decoded.message.account_keys;
+
+    Ok(decoded
+        .message
+        .instructions
+        .iter()
+        .map(|instruction| summarize_instruction(keys, instruction, programs))
+        .collect())
+}
+
+fn summarize_instruction(
+    keys: &[Pubkey],
+    instruction: &CompiledInstruction,
+    programs: &KnownPrograms,
+) -> String {
+    let program = match keys.get(instruction.program_id_index as usize) {
+        Some(program) => program,
+        None => {
+            return format!(
+                "call <invalid program index {}>",
+                instruction.program_id_index
+            )
+        }
+    };
+
+    let accounts: Vec<Pubkey> = instruction
+        .accounts
+        .iter()
+        .filter_map(|index| keys.get(*index as usize).copied())
+        .collect();
+    let data = &instruction.data;
+
+    let summary = if Some(*program) == programs.stake {
+        summarize_stake(&accounts, data)
+    } else if Some(*program) == programs.locker {
+        summarize_locker(&accounts, data)
+    } else if program == spl::ID {
+        summarize_token(&accounts, data)
+    } else if program == system::ID {
+        summarize_system(&accounts, data)
+    } else {
+        None
+    };
+
+    summary.unwrap_or_else(|| format!("call {} ({} bytes)", program, data.len()))
+}
+
+fn summarize_stake(accounts: &[Pubkey], data: &[u8]) -> Option<String> {
+    let x5margin_program::Method::Simple(method) = x5margin_program::Method::decode(data).ok()?;
+
+    let summary = match method {
+        simple_stake::Method::CreatePool(args) => format!(
+            "Create pool {} (target {}, reward {}, lockup {}s, topup {}s)",
+            accounts.get(2)?,
+            args.target_amount.value(),
+            args.reward_amount.value(),
+            args.lockup_duration.value(),
+            args.topup_duration.value()
+        ),
+        simple_stake::Method::Stake { amount } => {
+            format!("Stake {} to pool {}", amount.value(), accounts.get(1)?)
+        }
+        simple_stake::Method::StakeBatch { amounts } => format!(
+            "Stake {} on {} tickets of pool {}",
+            amounts.iter().map(|amount| amount.value()).sum::<u64>(),
+            amounts.len(),
+            accounts.get(1)?
+        ),
+        simple_stake::Method::Unstake { amount } => {
+            format!("Unstake {} from pool {}", amount.value(), accounts.get(1)?)
+        }
+        simple_stake::Method::ClaimReward => format!("Claim reward from pool {}", accounts.get(1)?),
+        simple_stake::Method::AddReward { amount } => {
+            format!("Add {} reward to pool {}", amount.value(), accounts.get(1)?)
+        }
+        simple_stake::Method::UnstakePercent { bps } => format!(
+            "Unstake {}.{:02}% from pool {}",
+            bps / 100,
+            bps % 100,
+            accounts.get(1)?
+        ),
+        simple_stake::Method::SetAdministrator => format!(
+            "Hand pool {} over to {}",
+            accounts.get(0)?,
+            accounts.get(2)?
+        ),
+    };
+
+    Some(summary)
+}
+
+fn summarize_locker(accounts: &[Pubkey], data: &[u8]) -> Option<String> {
+    let method = token_locker::Method::decode(data).ok()?;
+
+    // the locker is the first account, except for methods that start with the token program
+    let locker = match method {
+        token_locker::Method::Withdraw { .. }
+        | token_locker::Method::Increment { .. }
+        | token_locker::Method::Split { .. } => accounts.get(1)?,
+        _ => accounts.get(0)?,
+    };
+
+    Some(format!("{} on locker {}", method, locker))
+}
+
+fn summarize_token(accounts: &[Pubkey], data: &[u8]) -> Option<String> {
+    use spl::TokenInstruction;
+
+    let summary = match TokenInstruction::unpack(data)? {
+        TokenInstruction::InitializeMint { decimals, .. } => format!(
+            "Initialize mint {} ({} decimals)",
+            accounts.get(0)?,
+            decimals
+        ),
+        TokenInstruction::InitializeAccount => format!(
+            "Initialize token wallet {} for mint {}",
+            accounts.get(0)?,
+            accounts.get(1)?
+        ),
+        TokenInstruction::Transfer { amount } => format!(
+            "Transfer {} tokens from {} to {}",
+            amount,
+            accounts.get(0)?,
+            accounts.get(1)?
+        ),
+        // the checked variant passes the mint between the wallets
+        TokenInstruction::TransferChecked { amount, .. } => format!(
+            "Transfer {} tokens from {} to {}",
+            amount,
+            accounts.get(0)?,
+            accounts.get(2)?
+        ),
+        TokenInstruction::MintTo { amount } | TokenInstruction::MintToChecked { amount, .. } => {
+            format!(
+                "Mint {} tokens of {} to {}",
+                amount,
+                accounts.get(0)?,
+                accounts.get(1)?
+            )
+        }
+        TokenInstruction::Burn { amount } | TokenInstruction::BurnChecked { amount, .. } => {
+            format!("Burn {} tokens from {}", amount, accounts.get(0)?)
+        }
+        TokenInstruction::CloseAccount => format!(
+            "Close token wallet {}, rent to {}",
+            accounts.get(0)?,
+            accounts.get(1)?
+        ),
+        instruction => format!("{} on {}", instruction, accounts.get(0)?),
+    };
+
+    Some(summary)
+}
+
+fn summarize_system(accounts: &[Pubkey], data: &[u8]) -> Option<String> {
+    let summary = match bincode::deserialize::<SystemInstruction>(data).ok()? {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => format!(
+            "Create account {} ({} bytes, owner {}) with {} lamports from {}",
+            accounts.get(1)?,
+            space,
+            owner,
+            lamports,
+            accounts.get(0)?
+        ),
+        SystemInstruction::Transfer { lamports } => format!(
+            "Transfer {} lamports from {} to {}",
+            lamports,
+            accounts.get(0)?,
+            accounts.get(1)?
+        ),
+        _ => return None,
+    };
+
+    Some(summary)
+}
@@ -0,0 +1,381 @@
+//! Disassembly and size analysis of BPF programs.
+//!
+//! Sizes are counted in instructions, a 64-bit immediate load (`lddw`) spanning two slots counts
+//! once. CI can assert the total of [`size_report_from_file`] stays under a threshold to catch a
+//! program outgrowing its deploy budget.
+
+use std::{
+    cmp::Reverse,
+    fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+use solana_rbpf::{
+    ebpf,
+    elf::{EBpfElf, ElfError},
+    static_analysis::Analysis,
+    user_error::UserError,
+    vm::{Config, DefaultInstructionMeter, InstructionMeter},
+};
+
+pub type Executable = EBpfElf<UserError, DefaultInstructionMeter>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Elf(ElfError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read program: {}", err),
+            Error::Elf(err) => write!(f, "failed to load program: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Elf(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<ElfError> for Error {
+    fn from(err: ElfError) -> Self {
+        Error::Elf(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSize {
+    pub label: String,
+    pub instructions: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Largest first. Instructions ahead of the first known function are labeled `<N/A>`.
+    pub functions: Vec<FunctionSize>,
+    pub total: usize,
+}
+
+pub fn load(bytes: &[u8]) -> Result<Executable, ElfError> {
+    EBpfElf::load(Config::default(), bytes)
+}
+
+pub fn size_report_from_bytes(bytes: &[u8]) -> Result<SizeReport, ElfError> {
+    let executable = load(bytes)?;
+    Ok(size_report(&Analysis::from_executable(&executable)))
+}
+
+pub fn size_report_from_file(path: impl AsRef<Path>) -> Result<SizeReport, Error> {
+    let bytes = std::fs::read(path)?;
+    Ok(size_report_from_bytes(&bytes)?)
+}
+
+/// Instructions of every function of the analyzed program.
+pub fn size_report<E, I>(analysis: &Analysis<E, I>) -> SizeReport
+where
+    E: solana_rbpf::error::UserDefinedError,
+    I: InstructionMeter,
+{
+    let mut functions = vec![];
+    let mut current = FunctionSize {
+        label: "<N/A>".to_string(),
+        instructions: 0,
+    };
+
+    for insn in &analysis.instructions {
+        if let Some(cfg_node) = analysis.cfg_nodes.get(&insn.ptr) {
+            if analysis.functions.contains_key(&insn.ptr) {
+                let next = FunctionSize {
+                    label: cfg_node.label.clone(),
+                    instructions: 0,
+                };
+                let previous = std::mem::replace(&mut current, next);
+                if previous.instructions > 0 {
+                    functions.push(previous);
+                }
+            }
+        }
+
+        current.instructions += 1;
+    }
+
+    if current.instructions > 0 {
+        functions.push(current);
+    }
+
+    functions.sort_by_key(|function| Reverse(function.instructions));
+
+    SizeReport {
+        total: analysis.instructions.len(),
+        functions,
+    }
+}
+
+fn csize(opc: u8) -> u32 {
+    match opc {
+        ebpf::BPF_W => 32,
+        ebpf::BPF_H => 16,
+        ebpf::BPF_DW => 64,
+        ebpf::BPF_B => 8,
+        _ => 0,
+    }
+}
+
+fn alu_op(op: u8) -> &'static str {
+    match op {
+        ebpf::BPF_ADD => "+=",
+        ebpf::BPF_SUB => "-=",
+        ebpf::BPF_MUL => "*=",
+        ebpf::BPF_DIV => "/=",
+        ebpf::BPF_OR => "|=",
+        ebpf::BPF_AND => "&=",
+        ebpf::BPF_LSH => "<<=",
+        ebpf::BPF_RSH => ">>=",
+        ebpf::BPF_MOD => "%=",
+        ebpf::BPF_XOR => "^=",
+        ebpf::BPF_MOV => "=",
+        ebpf::BPF_ARSH => ">>=",
+        _ => "?=?",
+    }
+}
+
+fn jmp_op(op: u8) -> &'static str {
+    match op {
+        ebpf::BPF_JEQ => "==",
+        ebpf::BPF_JSET => "&",
+        ebpf::BPF_JNE => "!=",
+
+        ebpf::BPF_JGT => ">",
+        ebpf::BPF_JGE => ">=",
+        ebpf::BPF_JSGT => ">",
+        ebpf::BPF_JSGE => ">=",
+
+        ebpf::BPF_JLT => "<",
+        ebpf::BPF_JLE => "<=",
+        ebpf::BPF_JSLT => "<",
+        ebpf::BPF_JSLE => "<=",
+
+        _ => "???",
+    }
+}
+
+/// Write the instructions of the analyzed program to `out`, one function after another.
+pub fn disassemble<E, I, W>(analysis: &Analysis<E, I>, out: &mut W) -> io::Result<()>
+where
+    E: solana_rbpf::error::UserDefinedError,
+    I: InstructionMeter,
+    W: Write,
+{
+    for insn in &analysis.instructions {
+        let pc = insn.ptr;
+        if let Some(cfg_node) = analysis.cfg_nodes.get(&pc) {
+            let is_function = analysis.functions.contains_key(&pc);
+            if is_function {
+                writeln!(out)?;
+            }
+
+            if !cfg_node.sources.is_empty() || is_function {
+                writeln!(out, "{}:", cfg_node.label)?;
+            }
+        }
+
+        let class = insn.opc & 0b0000_0111;
+        write!(out, "    ")?;
+
+        if class == ebpf::BPF_LD {
+            let size = insn.opc & 0b0001_1000;
+            let mode = insn.opc & 0b1110_0000;
+
+            if mode == ebpf::BPF_ABS {
+                write!(out, "[{:2}] r0 = *({:#x})", csize(size), insn.imm)?;
+            } else if mode == ebpf::BPF_IND {
+                write!(out, "[{:2}] r0 = *r{}", csize(size), insn.src)?;
+            } else if mode == ebpf::BPF_IMM {
+                write!(out, "[64] r{} = {:#x}", insn.dst, insn.imm)?;
+            } else if mode == ebpf::BPF_MEM {
+                panic!("unknown opcode");
+            }
+
+            writeln!(out)?;
+        } else if class == ebpf::BPF_LDX {
+            let size = insn.opc & 0b0001_1000;
+
+            if insn.off != 0 {
+                writeln!(
+                    out,
+                    "[{:2}] r{} = *(r{} + {:#x})",
+                    csize(size),
+                    insn.dst,
+                    insn.src,
+                    insn.off,
+                )?;
+            } else {
+                writeln!(out, "[{:2}] r{} = *r{}", csize(size), insn.dst, insn.src)?;
+            }
+        } else if class == ebpf::BPF_ST {
+            let size = insn.opc & 0b0001_1000;
+
+            if insn.off != 0 {
+                writeln!(
+                    out,
+                    "[{:2}] *(r{} + {:#x}) = {}",
+                    csize(size),
+                    insn.dst,
+                    insn.off,
+                    insn.imm
+                )?;
+            } else {
+                writeln!(out, "[{:2}] *r{}: = {}", csize(size), insn.dst, insn.imm)?;
+            }
+        } else if class == ebpf::BPF_STX {
+            let size = insn.opc & 0b0001_1000;
+
+            if insn.off != 0 {
+                writeln!(
+                    out,
+                    "[{:2}] *(r{} + {:#x}) = r{}",
+                    csize(size),
+                    insn.dst,
+                    insn.off,
+                    insn.src
+                )?;
+            } else {
+                writeln!(out, "[{:2}] *r{} = r{}", csize(size), insn.dst, insn.src)?;
+            }
+        } else if class == ebpf::BPF_ALU || class == ebpf::BPF_ALU64 {
+            let is_immediate = insn.opc & 0b1000 == 0;
+            let width = if class == ebpf::BPF_ALU64 { 64 } else { 32 };
+            let op = insn.opc & 0b1111_0000;
+
+            write!(out, "[{:2}] ", width)?;
+
+            let arg = if is_immediate {
+                format!("{:#x}", insn.imm)
+            } else {
+                format!("r{}", insn.src)
+            };
+
+            if op == ebpf::BPF_NEG {
+                write!(out, "r{} = {}", insn.dst, arg)?;
+            } else if op == ebpf::BPF_END {
+                if is_immediate {
+                    write!(out, "r{} = to_le<{}>(r{})", insn.dst, insn.imm, insn.dst)?;
+                } else {
+                    write!(out, "r{} = to_be<{}>(r{})", insn.dst, insn.imm, insn.dst)?;
+                }
+            } else {
+                write!(out, "r{} {} {}", insn.dst, alu_op(op), arg)?;
+            }
+
+            writeln!(out)?;
+        } else if class == ebpf::BPF_JMP {
+            let is_immediate = insn.opc & 0b1000 == 0;
+            let op = insn.opc & 0b1111_0000;
+
+            let arg = if is_immediate {
+                format!("{:#x}", insn.imm)
+            } else {
+                format!("r{}", insn.src)
+            };
+
+            if op == ebpf::BPF_CALL {
+                if insn.opc == ebpf::CALL_IMM {
+                    if let Some(syscall) = analysis.syscalls.get(&(insn.imm as u32)) {
+                        if syscall == "abort" {
+                            writeln!(out, "abort")?;
+                        } else {
+                            writeln!(out, "syscall r0 = {}(r1, r2, r3, r4, r5)", syscall)?;
+                        }
+                    } else {
+                        let label = analysis
+                            .executable
+                            .lookup_bpf_function(insn.imm as u32)
+                            .and_then(|pc| analysis.cfg_nodes.get(&pc))
+                            .map(|node| node.label.as_str())
+                            .unwrap_or("[unknown]");
+
+                        writeln!(out, "call {}", label)?;
+                    }
+                } else if insn.opc == ebpf::CALL_REG {
+                    writeln!(out, "callx {:#x}", insn.imm)?;
+                }
+            } else if op == ebpf::BPF_EXIT {
+                writeln!(out, "exit")?;
+            } else if op == ebpf::BPF_JA {
+                let target = analysis
+                    .cfg_nodes
+                    .get(&((pc as isize + insn.off as isize + 1) as usize))
+                    .expect("invalid jump destination");
+
+                writeln!(out, "goto {}", target.label)?;
+            } else {
+                let target = analysis
+                    .cfg_nodes
+                    .get(&((pc as isize + insn.off as isize + 1) as usize))
+                    .expect("invalid jump destination");
+
+                writeln!(
+                    out,
+                    "if r{} {} {} {{ goto {} }}",
+                    insn.dst,
+                    jmp_op(op),
+                    arg,
+                    target.label
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{size_report_from_bytes, size_report_from_file};
+
+    /// `relative_call.c` built with the BPF toolchain, as shipped with the `solana_rbpf` tests.
+    const RELATIVE_CALL: &[u8] = include_bytes!("../tests/elfs/relative_call.so");
+
+    #[test]
+    fn size_report_test() {
+        let report = size_report_from_bytes(RELATIVE_CALL).unwrap();
+
+        // 16 slots, two of which hold the second half of a `lddw`
+        assert_eq!(report.total, 14);
+        assert_eq!(
+            report
+                .functions
+                .iter()
+                .map(|function| function.instructions)
+                .sum::<usize>(),
+            report.total
+        );
+
+        let entrypoint = report
+            .functions
+            .iter()
+            .find(|function| function.label == "entrypoint")
+            .unwrap();
+        assert_eq!(entrypoint.instructions, 7);
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/elfs/relative_call.so");
+        assert_eq!(size_report_from_file(path).unwrap(), report);
+
+        // a truncated program fails to load
+        assert!(size_report_from_bytes(&RELATIVE_CALL[..64]).is_err());
+    }
+}
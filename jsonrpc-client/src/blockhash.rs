@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use solana_api_types::*;
+
+use crate::failover::is_transport_error;
+
+/// Fetch a recent blockhash, retrying up to `attempts` times while the endpoint is unreachable.
+///
+/// Failed attempts are followed by a pause of `backoff`, doubled after every failure, so a
+/// node that is briefly down isn't flooded with requests. RPC errors are returned right away.
+pub async fn get_recent_blockhash_with_backoff<C: Client>(
+    client: &C,
+    commitment: Option<CommitmentConfig>,
+    attempts: usize,
+    mut backoff: Duration,
+) -> Result<RpcRecentBlockhash, ClientError> {
+    let mut attempt = 1;
+
+    loop {
+        match client.get_recent_blockhash(commitment).await {
+            Err(err) if is_transport_error(&err) && attempt < attempts => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::Ordering,
+        time::{Duration, Instant},
+    };
+
+    use solana_api_types::*;
+
+    use super::get_recent_blockhash_with_backoff;
+    use crate::mock::MockClient;
+
+    #[tokio::test]
+    async fn flaky_endpoint_test() {
+        let client = MockClient::new(0, false);
+        client.failures.store(3, Ordering::SeqCst);
+
+        let started = Instant::now();
+        let blockhash =
+            get_recent_blockhash_with_backoff(&client, None, 5, Duration::from_millis(10))
+                .await
+                .unwrap();
+
+        assert_eq!(blockhash.blockhash, Hash::default());
+        assert_eq!(client.calls.load(Ordering::SeqCst), 4);
+        // 10 + 20 + 40 ms of backoff between the four calls
+        assert!(started.elapsed() >= Duration::from_millis(70));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_attempts_test() {
+        let client = MockClient::new(0, true);
+
+        let err = get_recent_blockhash_with_backoff(&client, None, 3, Duration::from_millis(1))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err.kind, ClientErrorKind::Io(_)));
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{future::Future, time::Duration};
+
+use futures::{
+    future::{self, Either},
+    stream, Stream, StreamExt, TryStreamExt,
+};
+use serde_json::Value;
+
+use solana_api_types::*;
+
+/// Wait for `signature` to reach `commitment`.
+///
+/// `notification` is a push based signal, e.g. [`signature_notification`], which resolves
+/// once the transaction is confirmed, with the error it failed with if any. Notifications can
+/// get lost when a subscription reconnects, so `getSignatureStatuses` is polled every
+/// `interval` at the same time and whichever confirms first wins. If the notification fails,
+/// polling carries on alone, pass [`future::pending`] to rely on polling only.
+///
+/// A transaction which failed on-chain is reported as a `TransactionError`.
+///
+/// This never gives up by itself, wrap it in `tokio::time::timeout` to bound the wait.
+pub async fn confirm_transaction<C, N>(
+    client: &C,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    interval: Duration,
+    notification: N,
+) -> Result<(), ClientError>
+where
+    C: Client,
+    N: Future<Output = Result<Option<TransactionError>, ClientError>>,
+{
+    let poll = poll_confirmation(client, signature, commitment, interval);
+    futures::pin_mut!(poll, notification);
+
+    match future::select(notification, poll).await {
+        Either::Left((Ok(None), _)) => Ok(()),
+        Either::Left((Ok(Some(err)), _)) => Err(ClientErrorKind::TransactionError(err).into()),
+        Either::Left((Err(_), poll)) => poll.await,
+        Either::Right((result, _)) => result,
+    }
+}
+
+/// Outcome of a transaction from its `signatureSubscribe` notifications, e.g. a
+/// [`Listener`](crate::pubsub::Listener), `Some` error if it failed on-chain.
+///
+/// `receivedSignature` notifications are skipped, the stream ending before the transaction is
+/// processed is an error.
+pub async fn signature_notification<S>(
+    notifications: S,
+) -> Result<Option<TransactionError>, ClientError>
+where
+    S: Stream<Item = Value>,
+{
+    futures::pin_mut!(notifications);
+
+    while let Some(notification) = notifications.next().await {
+        let err = match notification.get("value").and_then(|value| value.get("err")) {
+            Some(err) => err,
+            // `receivedSignature`
+            None => continue,
+        };
+
+        return serde_json::from_value(err.clone()).map_err(|err| {
+            ClientErrorKind::Custom(format!("invalid signature notification: {}", err)).into()
+        });
+    }
+
+    Err(ClientErrorKind::Custom("signature subscription closed".to_string()).into())
+}
+
+/// Poll `getSignatureStatuses` until `signature` reaches `commitment` or turns out to have
+/// failed.
+pub async fn poll_confirmation<C: Client>(
+    client: &C,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    interval: Duration,
+) -> Result<(), ClientError> {
+    loop {
+        let statuses = client.get_signature_statuses(&[*signature], None).await?;
+
+        if let Some(Some(status)) = statuses.first() {
+            if let Some(err) = &status.err {
+                return Err(ClientErrorKind::TransactionError(err.clone()).into());
+            }
+
+            if status.is_confirmed_at(commitment) {
+                return Ok(());
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Most signatures `getSignatureStatuses` accepts in a single request.
+pub const MAX_SIGNATURE_STATUSES: usize = 256;
+
+/// `getSignatureStatuses` for any number of signatures, split into requests of at most
+/// [`MAX_SIGNATURE_STATUSES`] signatures.
+///
+/// Statuses are returned in the order of `signatures`. At most `concurrency` requests are in
+/// flight at once, all of them if `None`. The first failed request fails the whole call.
+pub async fn get_signature_statuses_chunked<C: Client>(
+    client: &C,
+    signatures: &[Signature],
+    cfg: Option<RpcSignatureStatusConfig>,
+    concurrency: Option<usize>,
+) -> Result<Vec<Option<TransactionStatus>>, ClientError> {
+    let chunks = signatures.chunks(MAX_SIGNATURE_STATUSES);
+    let concurrency = concurrency.unwrap_or_else(|| chunks.len()).max(1);
+
+    let statuses: Vec<_> = stream::iter(chunks)
+        .map(|chunk| client.get_signature_statuses(chunk, cfg.clone()))
+        .buffered(concurrency)
+        .try_collect()
+        .await?;
+
+    Ok(statuses.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::atomic::Ordering, time::Duration};
+
+    use futures::{future, stream};
+    use serde_json::json;
+
+    use solana_api_types::*;
+
+    use super::{confirm_transaction, get_signature_statuses_chunked, signature_notification};
+    use crate::mock::MockClient;
+
+    fn status(err: Option<TransactionError>) -> TransactionStatus {
+        TransactionStatus {
+            slot: 1,
+            confirmations: Some(10),
+            status: Ok(()),
+            err,
+            confirmation_status: Some(TransactionConfirmationStatus::Confirmed),
+        }
+    }
+
+    fn confirmed() -> CommitmentConfig {
+        CommitmentConfig {
+            commitment: CommitmentLevel::Confirmed,
+        }
+    }
+
+    #[tokio::test]
+    async fn poll_confirms_without_notification_test() {
+        let client = MockClient::with_status(2, status(None));
+
+        confirm_transaction(
+            &client,
+            &Signature::default(),
+            confirmed(),
+            Duration::from_millis(1),
+            future::pending(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn poll_continues_after_notification_fails_test() {
+        let client = MockClient::with_status(1, status(None));
+        let notification = async {
+            Err::<Option<TransactionError>, ClientError>(
+                io::Error::from(io::ErrorKind::ConnectionReset).into(),
+            )
+        };
+
+        confirm_transaction(
+            &client,
+            &Signature::default(),
+            confirmed(),
+            Duration::from_millis(1),
+            notification,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_transaction_test() {
+        let client = MockClient::with_status(0, status(Some(TransactionError::AccountInUse)));
+
+        let err = confirm_transaction(
+            &client,
+            &Signature::default(),
+            confirmed(),
+            Duration::from_millis(1),
+            future::pending(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            err.kind,
+            ClientErrorKind::TransactionError(TransactionError::AccountInUse)
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_notification_test() {
+        // the node never reports a status, so only the notification can settle it
+        let client = MockClient::with_status(usize::MAX, status(None));
+        let notifications = stream::iter(vec![
+            json!({ "context": { "slot": 5 }, "value": "receivedSignature" }),
+            json!({ "context": { "slot": 6 }, "value": { "err": "AccountInUse" } }),
+        ]);
+
+        let err = confirm_transaction(
+            &client,
+            &Signature::default(),
+            confirmed(),
+            Duration::from_millis(1),
+            signature_notification(notifications),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            err.kind,
+            ClientErrorKind::TransactionError(TransactionError::AccountInUse)
+        ));
+
+        let notifications = stream::iter(vec![
+            json!({ "context": { "slot": 6 }, "value": { "err": null } }),
+        ]);
+        assert_eq!(signature_notification(notifications).await.unwrap(), None);
+        assert!(signature_notification(stream::empty()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn chunked_statuses_test() {
+        let signatures: Vec<_> = (0..500u32)
+            .map(|i| {
+                let mut bytes = [0; 64];
+                bytes[..4].copy_from_slice(&i.to_le_bytes());
+                Signature::new(bytes)
+            })
+            .collect();
+
+        for &concurrency in &[None, Some(1)] {
+            // every third transaction landed
+            let known = signatures.iter().step_by(3).copied().collect();
+            let client = MockClient::with_known_signatures(status(None), known);
+
+            let statuses = get_signature_statuses_chunked(&client, &signatures, None, concurrency)
+                .await
+                .unwrap();
+
+            assert_eq!(statuses.len(), signatures.len());
+            for (i, status) in statuses.iter().enumerate() {
+                assert_eq!(status.is_some(), i % 3 == 0, "status {}", i);
+            }
+            assert_eq!(client.calls.load(Ordering::SeqCst), 2);
+        }
+
+        let client = MockClient::with_status(0, status(None));
+        assert!(get_signature_statuses_chunked(&client, &[], None, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
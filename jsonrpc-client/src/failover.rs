@@ -0,0 +1,395 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use futures::future;
+use tokio::sync::watch;
+
+use solana_api_types::*;
+
+use crate::SolanaApiClient;
+
+/// A [`Client`] spread over several RPC endpoints.
+///
+/// Every call goes to the current endpoint first. Transport errors (which include HTTP 429 and
+/// 5xx responses) mark the endpoint as unhealthy and the call is retried on the next one.
+/// Unhealthy endpoints are skipped until [`FailoverClient::check_health`] sees them recover.
+pub struct FailoverClient<C = SolanaApiClient> {
+    clients: Vec<C>,
+    healthy: Vec<AtomicBool>,
+    current: AtomicUsize,
+    fan_out_transactions: bool,
+    shutdown: watch::Sender<bool>,
+    shutdown_signal: watch::Receiver<bool>,
+}
+
+impl FailoverClient<SolanaApiClient> {
+    pub fn from_urls(urls: &[&'static str]) -> Self {
+        Self::new(urls.iter().map(|url| SolanaApiClient::new(url)).collect())
+    }
+}
+
+impl<C: Client> FailoverClient<C> {
+    /// `clients` are tried in order, so the preferred endpoint goes first.
+    pub fn new(clients: Vec<C>) -> Self {
+        assert!(
+            !clients.is_empty(),
+            "failover client needs at least one endpoint"
+        );
+
+        let (shutdown, shutdown_signal) = watch::channel(false);
+
+        Self {
+            healthy: clients.iter().map(|_| AtomicBool::new(true)).collect(),
+            clients,
+            current: AtomicUsize::new(0),
+            fan_out_transactions: false,
+            shutdown,
+            shutdown_signal,
+        }
+    }
+
+    /// Submit transactions to every endpoint instead of only the current one.
+    pub fn fan_out_transactions(mut self, enabled: bool) -> Self {
+        self.fan_out_transactions = enabled;
+        self
+    }
+
+    /// Index of the endpoint calls are currently routed to.
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::SeqCst)
+    }
+
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.healthy[index].load(Ordering::SeqCst)
+    }
+
+    /// Query `getHealth` on every endpoint and update their status.
+    ///
+    /// If the current endpoint turned out unhealthy, calls are moved to the first healthy one.
+    pub async fn check_health(&self) {
+        for (client, healthy) in self.clients.iter().zip(&self.healthy) {
+            healthy.store(client.get_health().await.is_ok(), Ordering::SeqCst);
+        }
+
+        if !self.is_healthy(self.current()) {
+            if let Some(index) = (0..self.clients.len()).find(|index| self.is_healthy(*index)) {
+                self.current.store(index, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Run [`FailoverClient::check_health`] every `interval` until [`FailoverClient::shutdown`]
+    /// is called.
+    pub async fn monitor_health(&self, interval: Duration) {
+        let mut shutdown = self.shutdown_signal.clone();
+
+        while !*shutdown.borrow() {
+            self.check_health().await;
+
+            let sleep = tokio::time::sleep(interval);
+            let changed = shutdown.changed();
+            futures::pin_mut!(sleep, changed);
+            future::select(sleep, changed).await;
+        }
+    }
+
+    /// Stop every [`FailoverClient::monitor_health`] loop of this client, including ones
+    /// started afterwards.
+    pub fn shutdown(&self) {
+        // the receiver is owned by `self`, so sending can't fail
+        let _ = self.shutdown.send(true);
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        *self.shutdown_signal.borrow()
+    }
+
+    /// Endpoints in the order they should be tried: the current one, then the healthy ones,
+    /// then the rest as a last resort.
+    fn candidates(&self) -> Vec<usize> {
+        let count = self.clients.len();
+        let current = self.current();
+        let rotated = (0..count).map(|offset| (current + offset) % count);
+
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) =
+            rotated.partition(|index| self.is_healthy(*index));
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    async fn route<'a, T, F, Fut>(&'a self, call: F) -> Result<T, ClientError>
+    where
+        F: Fn(&'a C) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut last_error = None;
+
+        for index in self.candidates() {
+            match call(&self.clients[index]).await {
+                Err(err) if is_transport_error(&err) => {
+                    self.healthy[index].store(false, Ordering::SeqCst);
+                    last_error = Some(err);
+                }
+                result => {
+                    self.healthy[index].store(true, Ordering::SeqCst);
+                    self.current.store(index, Ordering::SeqCst);
+                    return result;
+                }
+            }
+        }
+
+        Err(last_error.expect("failover client has at least one endpoint"))
+    }
+}
+
+/// Errors worth retrying on another endpoint, RPC errors would be returned by any node.
+pub(crate) fn is_transport_error(err: &ClientError) -> bool {
+    matches!(
+        err.kind,
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_)
+    )
+}
+
+#[async_trait(?Send)]
+impl<C: Client> Client for FailoverClient<C> {
+    async fn get_account_info(
+        &self,
+        account: Pubkey,
+        cfg: Option<RpcAccountInfoConfig>,
+    ) -> Result<Account, ClientError> {
+        self.route(|client| client.get_account_info(account, cfg.clone()))
+            .await
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program: Pubkey,
+        cfg: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        self.route(|client| client.get_program_accounts(program, cfg.clone()))
+            .await
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        accounts: &[Pubkey],
+        cfg: Option<RpcAccountInfoConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        self.route(|client| client.get_multiple_accounts(accounts, cfg.clone()))
+            .await
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+        cfg: Option<RpcSignatureStatusConfig>,
+    ) -> Result<Vec<Option<TransactionStatus>>, ClientError> {
+        self.route(|client| client.get_signature_statuses(signatures, cfg.clone()))
+            .await
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        cfg: Option<RpcSignaturesForAddressConfig>,
+    ) -> Result<Vec<SignatureInfo>, ClientError> {
+        self.route(|client| client.get_signatures_for_address(address, cfg.clone()))
+            .await
+    }
+
+    async fn get_slot(&self, cfg: Option<RpcSlotConfig>) -> Result<Slot, ClientError> {
+        self.route(|client| client.get_slot(cfg.clone())).await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: Signature,
+        cfg: Option<RpcTransactionConfig>,
+    ) -> Result<Option<EncodedConfirmedTransaction>, ClientError> {
+        self.route(|client| client.get_transaction(signature, cfg.clone()))
+            .await
+    }
+
+    async fn get_block(
+        &self,
+        slot: Slot,
+        cfg: Option<RpcBlockConfig>,
+    ) -> Result<Option<ConfirmedBlock>, ClientError> {
+        self.route(|client| client.get_block(slot, cfg)).await
+    }
+
+    async fn request_airdrop(
+        &self,
+        pubkey: &Pubkey,
+        lamports: u64,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Signature, ClientError> {
+        self.route(|client| client.request_airdrop(pubkey, lamports, commitment))
+            .await
+    }
+
+    /// With fan-out enabled the transaction is sent to every endpoint at once. All of them
+    /// report the same signature, so the first successful one is returned. Unless the caller
+    /// asks otherwise the endpoints don't rebroadcast it (`maxRetries: 0`), resending is left
+    /// to the caller rather than multiplied by the number of endpoints.
+    async fn send_transaction(
+        &self,
+        transaction: &Transaction,
+        cfg: RpcSendTransactionConfig,
+    ) -> Result<Signature, ClientError> {
+        if !self.fan_out_transactions {
+            return self
+                .route(|client| client.send_transaction(transaction, cfg))
+                .await;
+        }
+
+        let cfg = RpcSendTransactionConfig {
+            max_retries: cfg.max_retries.or(Some(0)),
+            ..cfg
+        };
+
+        let results = futures::future::join_all(
+            self.clients
+                .iter()
+                .map(|client| client.send_transaction(transaction, cfg)),
+        )
+        .await;
+
+        let mut last_error = None;
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(signature) => return Ok(signature),
+                Err(err) => {
+                    if is_transport_error(&err) {
+                        self.healthy[index].store(false, Ordering::SeqCst);
+                    }
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.expect("failover client has at least one endpoint"))
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+        cfg: RpcSimulateTransactionConfig,
+    ) -> Result<RpcSimulateTransactionResult, ClientError> {
+        self.route(|client| client.simulate_transaction(transaction, cfg.clone()))
+            .await
+    }
+
+    async fn get_recent_blockhash(
+        &self,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcRecentBlockhash, ClientError> {
+        self.route(|client| client.get_recent_blockhash(commitment))
+            .await
+    }
+
+    async fn get_health(&self) -> Result<(), ClientError> {
+        self.route(|client| client.get_health()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, sync::atomic::Ordering, time::Duration};
+
+    use solana_api_types::*;
+
+    use super::FailoverClient;
+    use crate::mock::MockClient;
+
+    #[tokio::test]
+    async fn secondary_takes_over_test() {
+        let client = FailoverClient::new(vec![MockClient::new(1, true), MockClient::new(2, false)]);
+
+        assert_eq!(client.get_slot(None).await.unwrap(), 2);
+        assert_eq!(client.current(), 1);
+        assert!(!client.is_healthy(0));
+
+        // the failed primary is not retried until a health check sees it recover
+        assert_eq!(client.get_slot(None).await.unwrap(), 2);
+        assert_eq!(client.clients[0].calls.load(Ordering::SeqCst), 1);
+
+        client.clients[0].down.store(false, Ordering::SeqCst);
+        client.check_health().await;
+        assert!(client.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn all_endpoints_down_test() {
+        let client = FailoverClient::new(vec![MockClient::new(1, true), MockClient::new(2, true)]);
+
+        let err = client.get_slot(None).await.unwrap_err();
+        assert!(matches!(err.kind, ClientErrorKind::Io(_)));
+    }
+
+    #[tokio::test]
+    async fn fan_out_send_transaction_test() {
+        let client = FailoverClient::new(vec![MockClient::new(1, true), MockClient::new(2, false)])
+            .fan_out_transactions(true);
+
+        let transaction = Transaction {
+            signatures: vec![Signature::default()],
+            message: Message::default(),
+        };
+
+        let signature = client
+            .send_transaction(&transaction, RpcSendTransactionConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(signature, Signature::default());
+        assert_eq!(client.clients[0].calls.load(Ordering::SeqCst), 1);
+        assert_eq!(client.clients[1].calls.load(Ordering::SeqCst), 1);
+        assert!(!client.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_health_monitor_test() {
+        let local = tokio::task::LocalSet::new();
+
+        local
+            .run_until(async {
+                let clients: Vec<_> = (0..64)
+                    .map(|slot| Rc::new(FailoverClient::new(vec![MockClient::new(slot, false)])))
+                    .collect();
+
+                let monitors: Vec<_> = clients
+                    .iter()
+                    .map(|client| {
+                        let client = client.clone();
+                        tokio::task::spawn_local(async move {
+                            client.monitor_health(Duration::from_secs(3600)).await
+                        })
+                    })
+                    .collect();
+
+                tokio::task::yield_now().await;
+                for client in &clients {
+                    client.shutdown();
+                    assert!(client.is_shut_down());
+                }
+
+                for monitor in monitors {
+                    tokio::time::timeout(Duration::from_secs(5), monitor)
+                        .await
+                        .expect("monitor did not stop")
+                        .unwrap();
+                }
+
+                // every task let go of its client
+                for client in clients {
+                    assert_eq!(Rc::strong_count(&client), 1);
+                }
+            })
+            .await;
+    }
+}
@@ -8,10 +8,24 @@ use serde::{de::DeserializeOwned, Deserialize};
 
 use solana_api_types::*;
 
-struct SolanaApiClient {
+pub mod blockhash;
+pub mod confirm;
+pub mod failover;
+pub mod pagination;
+pub mod pubsub;
+
+#[cfg(test)]
+mod mock;
+
+/// Largest response body buffered by a [`SolanaApiClient`] unless told otherwise, see
+/// [`SolanaApiClient::max_response_size`].
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 128 * 1024 * 1024;
+
+pub struct SolanaApiClient {
     client: reqwest::Client,
     current_id: AtomicUsize,
     solana_api_url: &'static str,
+    max_response_size: Option<usize>,
 }
 
 struct Request {
@@ -26,7 +40,36 @@ struct JsonRpcResponse<T> {
     result: T,
 }
 
+#[derive(Deserialize, Debug)]
+struct JsonRpcErrorObject {
+    code: i64,
+    message: String,
+}
+
 impl SolanaApiClient {
+    pub fn new(solana_api_url: &'static str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            current_id: AtomicUsize::new(0),
+            solana_api_url,
+            max_response_size: Some(DEFAULT_MAX_RESPONSE_SIZE),
+        }
+    }
+
+    pub fn devnet() -> Self {
+        Self::new("https://api.devnet.solana.com")
+    }
+
+    /// Fail requests whose response body exceeds `max_response_size` bytes rather than buffer
+    /// it, `None` buffers responses of any size.
+    ///
+    /// The body is read chunk by chunk and dropped as soon as it crosses the limit, so e.g.
+    /// `getProgramAccounts` of a program with millions of accounts can't exhaust memory.
+    pub fn max_response_size(mut self, max_response_size: Option<usize>) -> Self {
+        self.max_response_size = max_response_size;
+        self
+    }
+
     async fn mk_request<T: DeserializeOwned>(&self, r: Request) -> Result<T, ClientError> {
         let id = self.current_id.fetch_add(1, Ordering::SeqCst);
 
@@ -45,24 +88,50 @@ impl SolanaApiClient {
             .header("Content-Type", "application/json")
             .body(request)
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
-        let body = r.bytes().await?;
+        let body = self.read_body(r).await?;
         let body: serde_json::Value = serde_json::from_slice(&body)?;
-        println!("{}", body);
-        let body: JsonRpcResponse<T> = serde_json::from_value(body)?;
 
-        Ok(body.result)
+        parse_response(body)
     }
-}
 
-#[async_trait(?Send)]
-impl Client for SolanaApiClient {
-    async fn get_account_info(
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>, ClientError> {
+        let limit = match self.max_response_size {
+            Some(limit) => limit,
+            None => return Ok(response.bytes().await?.to_vec()),
+        };
+        let too_large = || -> ClientError {
+            ClientErrorKind::Custom(format!(
+                "response body exceeds the limit of {} bytes",
+                limit
+            ))
+            .into()
+        };
+
+        let content_length = response.content_length().unwrap_or(0);
+        if content_length > limit as u64 {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::with_capacity(content_length as usize);
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > limit {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body)
+    }
+
+    /// Like [`Client::get_account_info`], but also returns the slot the account was read at.
+    pub async fn get_account_info_with_context(
         &self,
-        account: solana_api_types::Pubkey,
-        cfg: Option<solana_api_types::RpcAccountInfoConfig>,
-    ) -> Result<solana_api_types::Account, solana_api_types::ClientError> {
+        account: Pubkey,
+        cfg: Option<RpcAccountInfoConfig>,
+    ) -> Result<RpcResponse<Account>, ClientError> {
         let r: RpcResponse<UiAccount> = self
             .mk_request(Request {
                 method: "getAccountInfo",
@@ -70,12 +139,142 @@ impl Client for SolanaApiClient {
             })
             .await?;
 
-        let account = r
-            .value
-            .decode(account)
-            .ok_or_else(|| RpcError::ParseError("failed to decode account".to_string()))?;
+        decode_account(r, account)
+    }
+
+    /// Like [`Client::get_multiple_accounts`], but also returns the slot the accounts were read
+    /// at.
+    pub async fn get_multiple_accounts_with_context(
+        &self,
+        accounts: &[Pubkey],
+        cfg: Option<RpcAccountInfoConfig>,
+    ) -> Result<RpcResponse<Vec<Account>>, ClientError> {
+        let accounts_as_str: Vec<String> = accounts.iter().map(|a| a.to_string()).collect();
 
-        Ok(account)
+        let r: RpcResponse<Vec<Option<UiAccount>>> = self
+            .mk_request(Request {
+                method: "getMultipleAccounts",
+                params: serde_json::json!([accounts_as_str, serde_json::to_value(&cfg)?,]),
+            })
+            .await?;
+
+        Ok(decode_multiple_accounts(r, accounts))
+    }
+
+    /// Like [`Client::get_program_accounts`], but also returns the slot the accounts were read
+    /// at. `with_context` is always requested, whatever `cfg` says.
+    pub async fn get_program_accounts_with_context(
+        &self,
+        program: Pubkey,
+        cfg: Option<RpcProgramAccountsConfig>,
+    ) -> Result<RpcResponse<Vec<Account>>, ClientError> {
+        let cfg = RpcProgramAccountsConfig {
+            with_context: Some(true),
+            ..cfg.unwrap_or_default()
+        };
+
+        let r: RpcResponse<Vec<RpcKeyedAccount>> = self
+            .mk_request(Request {
+                method: "getProgramAccounts",
+                params: serde_json::json!([program.to_string(), serde_json::to_value(&cfg)?,]),
+            })
+            .await?;
+
+        Ok(RpcResponse {
+            context: r.context,
+            value: decode_keyed_accounts(r.value),
+        })
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(mut body: serde_json::Value) -> Result<T, ClientError> {
+    if let Some(error) = body.get_mut("error") {
+        let error: JsonRpcErrorObject = serde_json::from_value(error.take())?;
+        return Err(RpcError::RpcResponseError {
+            code: error.code,
+            message: error.message,
+            data: RpcResponseErrorData::Empty,
+        }
+        .into());
+    }
+
+    let body: JsonRpcResponse<T> = serde_json::from_value(body)?;
+    Ok(body.result)
+}
+
+fn decode_account(
+    r: RpcResponse<UiAccount>,
+    account: Pubkey,
+) -> Result<RpcResponse<Account>, ClientError> {
+    let value = r
+        .value
+        .decode(account)
+        .ok_or_else(|| RpcError::ParseError("failed to decode account".to_string()))?;
+
+    Ok(RpcResponse {
+        context: r.context,
+        value,
+    })
+}
+
+fn decode_multiple_accounts(
+    r: RpcResponse<Vec<Option<UiAccount>>>,
+    accounts: &[Pubkey],
+) -> RpcResponse<Vec<Account>> {
+    let value = r
+        .value
+        .into_iter()
+        .zip(accounts)
+        .filter_map(|(acc, key)| acc?.decode(*key))
+        .collect();
+
+    RpcResponse {
+        context: r.context,
+        value,
+    }
+}
+
+fn decode_keyed_accounts(accounts: Vec<RpcKeyedAccount>) -> Vec<Account> {
+    accounts
+        .into_iter()
+        .filter_map(|a| {
+            let pubkey = Pubkey::from_str(a.pubkey.as_str()).ok()?;
+            a.account.decode(pubkey)
+        })
+        .collect()
+}
+
+/// `sendTransaction` params, with the encoding and preflight commitment filled in.
+fn send_transaction_params(
+    transaction: &Transaction,
+    cfg: RpcSendTransactionConfig,
+) -> Result<serde_json::Value, ClientError> {
+    let encoding = cfg.encoding.unwrap_or_default();
+    let transaction = transaction.encode(encoding)?;
+    let preflight_commitment = cfg.preflight_commitment.unwrap_or_default();
+
+    let cfg = RpcSendTransactionConfig {
+        preflight_commitment: Some(preflight_commitment),
+        encoding: Some(encoding),
+        ..cfg
+    };
+
+    Ok(serde_json::json!([
+        transaction,
+        serde_json::to_value(&cfg)?
+    ]))
+}
+
+#[async_trait(?Send)]
+impl Client for SolanaApiClient {
+    async fn get_account_info(
+        &self,
+        account: solana_api_types::Pubkey,
+        cfg: Option<solana_api_types::RpcAccountInfoConfig>,
+    ) -> Result<solana_api_types::Account, solana_api_types::ClientError> {
+        let r = self.get_account_info_with_context(account, cfg).await?;
+
+        Ok(r.value)
     }
 
     async fn get_program_accounts(
@@ -90,15 +289,7 @@ impl Client for SolanaApiClient {
             })
             .await?;
 
-        let r = r
-            .into_iter()
-            .filter_map(|a| {
-                let pubkey = Pubkey::from_str(a.pubkey.as_str()).ok()?;
-                a.account.decode(pubkey)
-            })
-            .collect();
-
-        Ok(r)
+        Ok(decode_keyed_accounts(r))
     }
 
     async fn get_multiple_accounts(
@@ -106,23 +297,11 @@ impl Client for SolanaApiClient {
         accounts: &[solana_api_types::Pubkey],
         cfg: Option<solana_api_types::RpcAccountInfoConfig>,
     ) -> Result<Vec<solana_api_types::Account>, solana_api_types::ClientError> {
-        let accounts_as_str: Vec<String> = accounts.iter().map(|a| a.to_string()).collect();
-
-        let r: RpcResponse<Vec<Option<UiAccount>>> = self
-            .mk_request(Request {
-                method: "getMultipleAccounts",
-                params: serde_json::json!([accounts_as_str, serde_json::to_value(&cfg)?,]),
-            })
+        let r = self
+            .get_multiple_accounts_with_context(accounts, cfg)
             .await?;
 
-        let r = r
-            .value
-            .into_iter()
-            .zip(accounts)
-            .filter_map(|(acc, key)| acc?.decode(*key))
-            .collect();
-
-        Ok(r)
+        Ok(r.value)
     }
 
     async fn get_signature_statuses(
@@ -187,6 +366,20 @@ impl Client for SolanaApiClient {
         Ok(r)
     }
 
+    async fn get_block(
+        &self,
+        slot: Slot,
+        cfg: Option<RpcBlockConfig>,
+    ) -> Result<Option<ConfirmedBlock>, ClientError> {
+        let cfg = RpcBlockConfig::with_binary_encoding(cfg);
+
+        self.mk_request(Request {
+            method: "getBlock",
+            params: serde_json::json!([slot, serde_json::to_value(&cfg)?]),
+        })
+        .await
+    }
+
     async fn request_airdrop(
         &self,
         pubkey: &solana_api_types::Pubkey,
@@ -210,20 +403,10 @@ impl Client for SolanaApiClient {
         transaction: &solana_api_types::Transaction,
         cfg: solana_api_types::RpcSendTransactionConfig,
     ) -> Result<Signature, solana_api_types::ClientError> {
-        let encoding = cfg.encoding.unwrap_or_default();
-        let transaction = transaction.encode(encoding)?;
-        let preflight_commitment = cfg.preflight_commitment.unwrap_or_default();
-
-        let cfg = RpcSendTransactionConfig {
-            preflight_commitment: Some(preflight_commitment),
-            encoding: Some(encoding),
-            ..cfg
-        };
-
         let r: String = self
             .mk_request(Request {
                 method: "sendTransaction",
-                params: serde_json::json!([transaction, serde_json::to_value(&cfg)?,]),
+                params: send_transaction_params(transaction, cfg)?,
             })
             .await?;
 
@@ -255,13 +438,42 @@ impl Client for SolanaApiClient {
 
         Ok(r.value)
     }
+
+    async fn get_recent_blockhash(
+        &self,
+        commitment: Option<solana_api_types::CommitmentConfig>,
+    ) -> Result<solana_api_types::RpcRecentBlockhash, solana_api_types::ClientError> {
+        let r: RpcResponse<RpcRecentBlockhash> = self
+            .mk_request(Request {
+                method: "getRecentBlockhash",
+                params: serde_json::json!([serde_json::to_value(&commitment)?]),
+            })
+            .await?;
+
+        Ok(r.value)
+    }
+
+    async fn get_health(&self) -> Result<(), solana_api_types::ClientError> {
+        let r: String = self
+            .mk_request(Request {
+                method: "getHealth",
+                params: serde_json::json!([]),
+            })
+            .await?;
+
+        if r == "ok" {
+            Ok(())
+        } else {
+            Err(RpcError::ForUser(format!("node is unhealthy: {}", r)).into())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{convert::TryFrom, str::FromStr, sync::atomic::AtomicUsize};
 
-    use super::{Client, SolanaApiClient};
+    use super::{send_transaction_params, Client, SolanaApiClient};
 
     use solana_api_types::*;
 
@@ -272,12 +484,84 @@ mod tests {
         }
     }
 
+    fn account_json(lamports: u64) -> serde_json::Value {
+        serde_json::json!({
+            "data": ["AQID", "base64"],
+            "executable": false,
+            "lamports": lamports,
+            "owner": "11111111111111111111111111111111",
+            "rentEpoch": 2,
+        })
+    }
+
+    #[test]
+    fn context_slot_test() {
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": {"context": {"slot": 83_000_001}, "value": account_json(10)},
+        });
+        let r: RpcResponse<UiAccount> = super::parse_response(body).unwrap();
+        let r = super::decode_account(r, keys[0]).unwrap();
+        assert_eq!(r.context.slot, 83_000_001);
+        assert_eq!(r.value.lamports, 10);
+        assert_eq!(r.value.data, vec![1, 2, 3]);
+
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {"context": {"slot": 83_000_002}, "value": [null, account_json(20)]},
+        });
+        let r: RpcResponse<Vec<Option<UiAccount>>> = super::parse_response(body).unwrap();
+        let r = super::decode_multiple_accounts(r, &keys);
+        assert_eq!(r.context.slot, 83_000_002);
+        assert_eq!(r.value.len(), 1);
+        assert_eq!(r.value[0].pubkey, keys[1]);
+
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "result": {
+                "context": {"slot": 83_000_003},
+                "value": [{"pubkey": keys[0].to_string(), "account": account_json(30)}],
+            },
+        });
+        let r: RpcResponse<Vec<RpcKeyedAccount>> = super::parse_response(body).unwrap();
+        assert_eq!(r.context.slot, 83_000_003);
+        let accounts = super::decode_keyed_accounts(r.value);
+        assert_eq!(accounts[0].pubkey, keys[0]);
+        assert_eq!(accounts[0].lamports, 30);
+    }
+
+    #[test]
+    fn error_response_test() {
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "error": {
+                "code": JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+                "message": "Minimum context slot has not been reached",
+            },
+        });
+
+        match super::parse_response::<UiAccount>(body).unwrap_err().kind {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. }) => {
+                assert_eq!(code, JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED);
+                assert_eq!(message, "Minimum context slot has not been reached");
+            }
+            kind => panic!("unexpected error: {:?}", kind),
+        }
+    }
+
     #[tokio::test]
     async fn get_account_info_test() {
         let client = SolanaApiClient {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let pubkey =
@@ -298,6 +582,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let pubkey =
@@ -307,6 +592,7 @@ mod tests {
             encoding: Some(solana_api_types::UiAccountEncoding::Base64),
             data_slice: None,
             commitment: None,
+            min_context_slot: None,
         };
         let cfg = solana_api_types::RpcProgramAccountsConfig {
             filters: None,
@@ -328,6 +614,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let accounts = &[
@@ -343,6 +630,7 @@ mod tests {
                     encoding: Some(solana_api_types::UiAccountEncoding::Base64),
                     data_slice: None,
                     commitment: None,
+                    min_context_slot: None,
                 }),
             )
             .await
@@ -357,6 +645,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let signatures = &[
@@ -383,6 +672,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let pubkey =
@@ -410,6 +700,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let r = client.get_slot(None).await.map_err(|err| err.to_string());
@@ -423,6 +714,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let pubkey =
@@ -443,6 +735,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let signature = solana_api_types::Signature::from_str("44pGayfTYPSMT31zdzsdRWovCzRv3AeMEJZ4Z83XzNbDmHyzVGN2LV6SGkqbkPQbgNWQmV9fVEtVV6nZCEgpa7E6").unwrap();
@@ -461,6 +754,7 @@ mod tests {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let transaction = create_sample_transaction();
@@ -479,12 +773,47 @@ mod tests {
         println!("{:?}", r);
     }
 
+    #[test]
+    fn send_transaction_params_test() {
+        let transaction = create_sample_transaction();
+
+        let params = send_transaction_params(
+            &transaction,
+            RpcSendTransactionConfig {
+                max_retries: Some(0),
+                min_context_slot: Some(42),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(params[1]["maxRetries"], 0);
+        assert_eq!(params[1]["minContextSlot"], 42);
+        assert_eq!(params[1]["skipPreflight"], false);
+
+        let params = send_transaction_params(
+            &transaction,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(params[1]["skipPreflight"], true);
+
+        // only sent when set
+        let params =
+            send_transaction_params(&transaction, RpcSendTransactionConfig::default()).unwrap();
+        assert!(params[1].get("maxRetries").is_none());
+        assert!(params[1].get("minContextSlot").is_none());
+    }
+
     #[tokio::test]
     async fn simulate_transaction_test() {
         let client = SolanaApiClient {
             client: reqwest::Client::new(),
             current_id: AtomicUsize::new(0),
             solana_api_url: "https://api.devnet.solana.com",
+            max_response_size: None,
         };
 
         let transaction = create_sample_transaction();
@@ -502,4 +831,59 @@ mod tests {
 
         println!("{:?}", r);
     }
+
+    /// Serve `getSlot` responses padded to `body_size` bytes on a local port, announcing their
+    /// length only if `content_length` is set.
+    async fn serve_large_responses(body_size: usize, content_length: bool) -> &'static str {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+
+                // the request fits a single read
+                let mut request = vec![0; 4096];
+                let _ = socket.read(&mut request).await;
+
+                let mut body = br#"{"jsonrpc":"2.0","id":0,"result":42}"#.to_vec();
+                body.resize(body_size, b' ');
+
+                let mut response =
+                    b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n".to_vec();
+                if content_length {
+                    response.extend(format!("Content-Length: {}\r\n", body.len()).bytes());
+                }
+                response.extend(b"Connection: close\r\n\r\n");
+                response.extend(body);
+
+                let _ = socket.write_all(&response).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        Box::leak(url.into_boxed_str())
+    }
+
+    #[tokio::test]
+    async fn max_response_size_test() {
+        for &content_length in &[true, false] {
+            let url = serve_large_responses(1024 * 1024, content_length).await;
+
+            let client = SolanaApiClient::new(url).max_response_size(Some(64 * 1024));
+            let err = client.get_slot(None).await.unwrap_err();
+            assert!(err.to_string().contains("exceeds the limit of 65536 bytes"));
+
+            let client = SolanaApiClient::new(url).max_response_size(Some(2 * 1024 * 1024));
+            assert_eq!(client.get_slot(None).await.unwrap(), 42);
+
+            let client = SolanaApiClient::new(url).max_response_size(None);
+            assert_eq!(client.get_slot(None).await.unwrap(), 42);
+        }
+    }
 }
@@ -0,0 +1,227 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use async_trait::async_trait;
+
+use solana_api_types::*;
+
+use crate::confirm::MAX_SIGNATURE_STATUSES;
+
+/// In-memory endpoint for tests, answers with a fixed slot and a fixed set of program accounts,
+/// or refuses connections while `down` is set and for the next `failures` calls.
+pub(crate) struct MockClient {
+    pub slot: Slot,
+    pub accounts: Vec<Account>,
+    /// Reported by `getSignatureStatuses` once it was asked `pending_polls` times.
+    pub status: Option<TransactionStatus>,
+    pub pending_polls: usize,
+    /// Only these signatures get `status` if set, the others have none.
+    pub known_signatures: Option<Vec<Signature>>,
+    pub down: AtomicBool,
+    pub failures: AtomicUsize,
+    pub calls: AtomicUsize,
+}
+
+impl MockClient {
+    pub fn new(slot: Slot, down: bool) -> Self {
+        Self {
+            slot,
+            accounts: vec![],
+            status: None,
+            pending_polls: 0,
+            known_signatures: None,
+            down: AtomicBool::new(down),
+            failures: AtomicUsize::new(0),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_accounts(accounts: Vec<Account>) -> Self {
+        Self {
+            accounts,
+            ..Self::new(0, false)
+        }
+    }
+
+    pub fn with_status(pending_polls: usize, status: TransactionStatus) -> Self {
+        Self {
+            status: Some(status),
+            pending_polls,
+            ..Self::new(0, false)
+        }
+    }
+
+    pub fn with_known_signatures(status: TransactionStatus, signatures: Vec<Signature>) -> Self {
+        Self {
+            status: Some(status),
+            known_signatures: Some(signatures),
+            ..Self::new(0, false)
+        }
+    }
+
+    fn respond<T>(&self, value: T) -> Result<T, ClientError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let flaky = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+
+        if self.down.load(Ordering::SeqCst) || flaky {
+            Err(io::Error::from(io::ErrorKind::ConnectionRefused).into())
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for MockClient {
+    async fn get_account_info(
+        &self,
+        _: Pubkey,
+        _: Option<RpcAccountInfoConfig>,
+    ) -> Result<Account, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_program_accounts(
+        &self,
+        program: Pubkey,
+        cfg: Option<RpcProgramAccountsConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        let filters = cfg.and_then(|cfg| cfg.filters).unwrap_or_default();
+        let matches = |account: &Account| {
+            filters.iter().all(|filter| match filter {
+                RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
+                RpcFilterType::Memcmp(Memcmp {
+                    offset,
+                    bytes: MemcmpEncodedBytes::Binary(bytes),
+                    ..
+                }) => {
+                    let bytes = bs58::decode(bytes).into_vec().unwrap();
+                    account.data.get(*offset..*offset + bytes.len()) == Some(&bytes[..])
+                }
+            })
+        };
+
+        let accounts = self
+            .accounts
+            .iter()
+            .filter(|account| account.owner == program && matches(account))
+            .cloned()
+            .collect();
+
+        self.respond(accounts)
+    }
+
+    async fn get_multiple_accounts(
+        &self,
+        _: &[Pubkey],
+        _: Option<RpcAccountInfoConfig>,
+    ) -> Result<Vec<Account>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+        _: Option<RpcSignatureStatusConfig>,
+    ) -> Result<Vec<Option<TransactionStatus>>, ClientError> {
+        if signatures.len() > MAX_SIGNATURE_STATUSES {
+            return Err(RpcError::RpcRequestError("too many signatures".to_string()).into());
+        }
+
+        if let Some(known) = &self.known_signatures {
+            let statuses = signatures
+                .iter()
+                .map(|signature| {
+                    known
+                        .contains(signature)
+                        .then(|| self.status.clone())
+                        .flatten()
+                })
+                .collect();
+            return self.respond(statuses);
+        }
+
+        let status = if self.calls.load(Ordering::SeqCst) < self.pending_polls {
+            None
+        } else {
+            self.status.clone()
+        };
+
+        self.respond(vec![status; signatures.len()])
+    }
+
+    async fn get_signatures_for_address(
+        &self,
+        _: &Pubkey,
+        _: Option<RpcSignaturesForAddressConfig>,
+    ) -> Result<Vec<SignatureInfo>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_slot(&self, _: Option<RpcSlotConfig>) -> Result<Slot, ClientError> {
+        self.respond(self.slot)
+    }
+
+    async fn get_transaction(
+        &self,
+        _: Signature,
+        _: Option<RpcTransactionConfig>,
+    ) -> Result<Option<EncodedConfirmedTransaction>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_block(
+        &self,
+        _: Slot,
+        _: Option<RpcBlockConfig>,
+    ) -> Result<Option<ConfirmedBlock>, ClientError> {
+        unimplemented!()
+    }
+
+    async fn request_airdrop(
+        &self,
+        _: &Pubkey,
+        _: u64,
+        _: Option<CommitmentConfig>,
+    ) -> Result<Signature, ClientError> {
+        unimplemented!()
+    }
+
+    async fn send_transaction(
+        &self,
+        transaction: &Transaction,
+        _: RpcSendTransactionConfig,
+    ) -> Result<Signature, ClientError> {
+        self.respond(transaction.signatures[0])
+    }
+
+    async fn simulate_transaction(
+        &self,
+        _: &Transaction,
+        _: RpcSimulateTransactionConfig,
+    ) -> Result<RpcSimulateTransactionResult, ClientError> {
+        unimplemented!()
+    }
+
+    async fn get_recent_blockhash(
+        &self,
+        _: Option<CommitmentConfig>,
+    ) -> Result<RpcRecentBlockhash, ClientError> {
+        self.respond(RpcRecentBlockhash {
+            blockhash: Hash::default(),
+            fee_calculator: FeeCalculator {
+                lamports_per_signature: 5000,
+            },
+        })
+    }
+
+    async fn get_health(&self) -> Result<(), ClientError> {
+        self.respond(())
+    }
+}
@@ -0,0 +1,155 @@
+use solana_api_types::*;
+
+/// Walks the accounts of a program in chunks instead of a single `getProgramAccounts` call.
+///
+/// The RPC has no native pagination, so every page is a separate query for accounts of
+/// `data_size` bytes whose byte at `prefix_offset` equals the current prefix, going from `0x00`
+/// to `0xff`. Within a page accounts are sorted by their own address.
+///
+/// This assumes the byte at `prefix_offset` is evenly distributed, e.g. the first byte of a
+/// pubkey stored in the account, otherwise pages will be uneven. Accounts created or modified
+/// while the walk is in progress can be missed or seen twice, just as with any other paginated
+/// view over live data.
+pub struct ProgramAccountsCursor<'a, C> {
+    client: &'a C,
+    program: Pubkey,
+    data_size: u64,
+    prefix_offset: usize,
+    next_prefix: Option<u8>,
+    config: RpcAccountInfoConfig,
+}
+
+impl<'a, C: Client> ProgramAccountsCursor<'a, C> {
+    pub fn new(client: &'a C, program: Pubkey, data_size: u64, prefix_offset: usize) -> Self {
+        Self {
+            client,
+            program,
+            data_size,
+            prefix_offset,
+            next_prefix: Some(0),
+            config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn with_config(mut self, config: RpcAccountInfoConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Continue a previous walk from `prefix`, as returned by [`ProgramAccountsCursor::position`].
+    pub fn resume_from(mut self, prefix: Option<u8>) -> Self {
+        self.next_prefix = prefix;
+        self
+    }
+
+    /// Prefix of the next page, `None` once every page was fetched.
+    pub fn position(&self) -> Option<u8> {
+        self.next_prefix
+    }
+
+    /// Fetch the next page, `None` once the walk is complete.
+    ///
+    /// The cursor only advances on success, so a failed page can be retried.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<Account>>, ClientError> {
+        let prefix = match self.next_prefix {
+            Some(prefix) => prefix,
+            None => return Ok(None),
+        };
+
+        let filters = vec![
+            RpcFilterType::DataSize(self.data_size),
+            RpcFilterType::Memcmp(Memcmp {
+                offset: self.prefix_offset,
+                bytes: MemcmpEncodedBytes::Binary(bs58::encode([prefix]).into_string()),
+                encoding: None,
+            }),
+        ];
+
+        let mut accounts = self
+            .client
+            .get_program_accounts(
+                self.program,
+                Some(RpcProgramAccountsConfig {
+                    filters: Some(filters),
+                    account_config: self.config.clone(),
+                    with_context: None,
+                }),
+            )
+            .await?;
+        accounts.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+
+        self.next_prefix = prefix.checked_add(1);
+
+        Ok(Some(accounts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_api_types::*;
+
+    use super::ProgramAccountsCursor;
+    use crate::mock::MockClient;
+
+    fn fixture(program: Pubkey, prefix: u8, size: usize) -> Account {
+        let mut data = vec![0; size];
+        data[8] = prefix;
+
+        Account {
+            data,
+            owner: program,
+            pubkey: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn walks_all_accounts_in_order_test() {
+        let program = Pubkey::new_unique();
+        let accounts = vec![
+            fixture(program, 0xff, 40),
+            fixture(program, 0x10, 40),
+            fixture(program, 0x00, 40),
+            fixture(program, 0x10, 40),
+            // filtered out by size
+            fixture(program, 0x10, 48),
+            // owned by another program
+            fixture(Pubkey::new_unique(), 0x10, 40),
+        ];
+        let client = MockClient::with_accounts(accounts.clone());
+
+        let mut cursor = ProgramAccountsCursor::new(&client, program, 40, 8);
+        let mut pages = vec![];
+        while let Some(page) = cursor.next_page().await.unwrap() {
+            pages.push(page);
+        }
+
+        assert_eq!(pages.len(), 256);
+        assert_eq!(cursor.position(), None);
+        assert_eq!(pages[0], vec![accounts[2].clone()]);
+        assert_eq!(pages[0xff], vec![accounts[0].clone()]);
+
+        let mut expected = vec![accounts[1].clone(), accounts[3].clone()];
+        expected.sort_by(|a, b| a.pubkey.cmp(&b.pubkey));
+        assert_eq!(pages[0x10], expected);
+
+        let total: usize = pages.iter().map(Vec::len).sum();
+        assert_eq!(total, 4);
+    }
+
+    #[tokio::test]
+    async fn resume_from_position_test() {
+        let program = Pubkey::new_unique();
+        let client =
+            MockClient::with_accounts(vec![fixture(program, 0x01, 40), fixture(program, 0xfe, 40)]);
+
+        let mut cursor =
+            ProgramAccountsCursor::new(&client, program, 40, 8).resume_from(Some(0xfe));
+        assert_eq!(cursor.next_page().await.unwrap().unwrap().len(), 1);
+        assert_eq!(cursor.next_page().await.unwrap().unwrap().len(), 0);
+        assert_eq!(cursor.next_page().await.unwrap(), None);
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use async_trait::async_trait;
+use futures::{channel::mpsc, Stream, StreamExt};
+use serde_json::Value;
+
+use solana_api_types::*;
+
+/// Upstream side of pubsub subscriptions, e.g. a websocket connection to an RPC node.
+#[async_trait(?Send)]
+pub trait PubsubTransport {
+    /// Send a subscription request such as `signatureSubscribe` and return the id the server
+    /// assigned to the subscription.
+    async fn subscribe(&self, method: &str, params: &Value) -> Result<u64, ClientError>;
+
+    /// Send an unsubscribe request such as `signatureUnsubscribe` for `subscription_id`.
+    ///
+    /// This is called when a listener is dropped, so it can't wait for the response and should
+    /// only queue the request, e.g. on the channel feeding the websocket writer.
+    fn unsubscribe(&self, method: &str, subscription_id: u64);
+}
+
+#[async_trait(?Send)]
+impl<T: PubsubTransport + ?Sized> PubsubTransport for Box<T> {
+    async fn subscribe(&self, method: &str, params: &Value) -> Result<u64, ClientError> {
+        (**self).subscribe(method, params).await
+    }
+
+    fn unsubscribe(&self, method: &str, subscription_id: u64) {
+        (**self).unsubscribe(method, subscription_id)
+    }
+}
+
+/// Unsubscribe method matching a subscribe method, e.g. `accountSubscribe` to
+/// `accountUnsubscribe`.
+pub fn unsubscribe_method(method: &str) -> String {
+    match method.strip_suffix("Subscribe") {
+        Some(prefix) => format!("{}Unsubscribe", prefix),
+        None => format!("{}Unsubscribe", method),
+    }
+}
+
+type SubscriptionKey = (&'static str, String);
+
+struct Subscription {
+    /// Assigned by the server, `None` while the subscription request is in flight.
+    id: Option<u64>,
+    listeners: Vec<(u64, mpsc::UnboundedSender<Value>)>,
+}
+
+struct Shared<T> {
+    transport: T,
+    subscriptions: RefCell<HashMap<SubscriptionKey, Subscription>>,
+    next_listener: Cell<u64>,
+}
+
+/// Pubsub subscriptions shared between listeners.
+///
+/// Listeners of the same method and params share a single upstream subscription, which is
+/// opened for the first of them and unsubscribed once the last one is dropped. Notifications
+/// read from the transport are passed to [`Subscriptions::notify`], which fans them out to every
+/// listener of the subscription.
+///
+/// Listeners joining while the upstream request is in flight wait for it, so the first
+/// [`Subscriptions::subscribe`] call of a subscription has to be polled to completion. If it is
+/// cancelled instead, the server id is never known and the subscription can't be unsubscribed.
+pub struct Subscriptions<T> {
+    shared: Rc<Shared<T>>,
+}
+
+/// Another handle to the same subscriptions, e.g. for the task reading the transport to pass
+/// notifications to.
+impl<T> Clone for Subscriptions<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: PubsubTransport> Subscriptions<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            shared: Rc::new(Shared {
+                transport,
+                subscriptions: RefCell::new(HashMap::new()),
+                next_listener: Cell::new(0),
+            }),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.shared.transport
+    }
+
+    /// Listen to `method` notifications for `params`, opening the upstream subscription only if
+    /// nobody listens to it yet.
+    pub async fn subscribe(
+        &self,
+        method: &'static str,
+        params: Value,
+    ) -> Result<Listener<T>, ClientError> {
+        let key = (method, params.to_string());
+        let id = self.shared.next_listener.get();
+        self.shared.next_listener.set(id + 1);
+
+        let (sender, receiver) = mpsc::unbounded();
+        let listener = Listener {
+            shared: self.shared.clone(),
+            key: key.clone(),
+            id,
+            receiver,
+        };
+
+        {
+            let mut subscriptions = self.shared.subscriptions.borrow_mut();
+            if let Some(subscription) = subscriptions.get_mut(&key) {
+                subscription.listeners.push((id, sender));
+                return Ok(listener);
+            }
+
+            subscriptions.insert(
+                key.clone(),
+                Subscription {
+                    id: None,
+                    listeners: vec![(id, sender)],
+                },
+            );
+        }
+
+        match self.shared.transport.subscribe(method, &params).await {
+            Ok(subscription_id) => {
+                if let Some(subscription) = self.shared.subscriptions.borrow_mut().get_mut(&key) {
+                    subscription.id = Some(subscription_id);
+                }
+                Ok(listener)
+            }
+            Err(err) => {
+                // the streams of listeners that joined in the meantime end
+                self.shared.subscriptions.borrow_mut().remove(&key);
+                Err(err)
+            }
+        }
+    }
+
+    /// Pass a notification of the server subscription `subscription_id` to its listeners,
+    /// `false` if there is no such subscription, e.g. because it was just released.
+    pub fn notify(&self, subscription_id: u64, value: Value) -> bool {
+        let subscriptions = self.shared.subscriptions.borrow();
+        let subscription = subscriptions
+            .values()
+            .find(|subscription| subscription.id == Some(subscription_id));
+
+        match subscription {
+            Some(subscription) => {
+                for (_, sender) in &subscription.listeners {
+                    // a listener being dropped right now is not an error
+                    let _ = sender.unbounded_send(value.clone());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of upstream subscriptions, including the ones still being opened.
+    pub fn subscription_count(&self) -> usize {
+        self.shared.subscriptions.borrow().len()
+    }
+}
+
+/// Notifications of a subscription, as returned by [`Subscriptions::subscribe`].
+///
+/// Dropping the last listener of a subscription unsubscribes it.
+pub struct Listener<T: PubsubTransport> {
+    shared: Rc<Shared<T>>,
+    key: SubscriptionKey,
+    id: u64,
+    receiver: mpsc::UnboundedReceiver<Value>,
+}
+
+impl<T: PubsubTransport> Stream for Listener<T> {
+    type Item = Value;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+impl<T: PubsubTransport> Listener<T> {
+    /// Method the listener subscribed with.
+    pub fn method(&self) -> &'static str {
+        self.key.0
+    }
+}
+
+impl<T: PubsubTransport> Drop for Listener<T> {
+    fn drop(&mut self) {
+        let released = {
+            let mut subscriptions = self.shared.subscriptions.borrow_mut();
+
+            match subscriptions.get_mut(&self.key) {
+                Some(subscription) => {
+                    subscription.listeners.retain(|(id, _)| *id != self.id);
+
+                    if subscription.listeners.is_empty() {
+                        subscriptions.remove(&self.key)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+
+        if let Some(Subscription {
+            id: Some(subscription_id),
+            ..
+        }) = released
+        {
+            self.shared
+                .transport
+                .unsubscribe(&unsubscribe_method(self.method()), subscription_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use async_trait::async_trait;
+    use futures::StreamExt;
+    use serde_json::{json, Value};
+
+    use solana_api_types::*;
+
+    use super::{unsubscribe_method, PubsubTransport, Subscriptions};
+
+    /// Records subscription requests and numbers them as the server would.
+    #[derive(Default)]
+    struct MockTransport {
+        requests: RefCell<Vec<(String, Value)>>,
+        unsubscribed: RefCell<Vec<(String, u64)>>,
+    }
+
+    #[async_trait(?Send)]
+    impl PubsubTransport for MockTransport {
+        async fn subscribe(&self, method: &str, params: &Value) -> Result<u64, ClientError> {
+            let mut requests = self.requests.borrow_mut();
+            requests.push((method.to_string(), params.clone()));
+            Ok(requests.len() as u64)
+        }
+
+        fn unsubscribe(&self, method: &str, subscription_id: u64) {
+            self.unsubscribed
+                .borrow_mut()
+                .push((method.to_string(), subscription_id));
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_subscription_test() {
+        let subscriptions = Subscriptions::new(MockTransport::default());
+        let pool = json!([Pubkey::new_unique().to_string(), { "encoding": "base64" }]);
+
+        let mut first = subscriptions
+            .subscribe("accountSubscribe", pool.clone())
+            .await
+            .unwrap();
+        let mut second = subscriptions
+            .subscribe("accountSubscribe", pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(subscriptions.transport().requests.borrow().len(), 1);
+        assert_eq!(subscriptions.subscription_count(), 1);
+
+        assert!(subscriptions.notify(1, json!({ "slot": 7 })));
+        assert_eq!(first.next().await, Some(json!({ "slot": 7 })));
+        assert_eq!(second.next().await, Some(json!({ "slot": 7 })));
+
+        // another target gets its own subscription
+        let other = subscriptions
+            .subscribe("signatureSubscribe", pool)
+            .await
+            .unwrap();
+        assert_eq!(subscriptions.transport().requests.borrow().len(), 2);
+
+        drop(first);
+        assert!(subscriptions.notify(1, json!({ "slot": 8 })));
+        assert_eq!(second.next().await, Some(json!({ "slot": 8 })));
+
+        drop(second);
+        assert!(!subscriptions.notify(1, json!({ "slot": 9 })));
+        assert_eq!(subscriptions.subscription_count(), 1);
+
+        drop(other);
+        assert_eq!(subscriptions.subscription_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_on_drop_test() {
+        let subscriptions = Subscriptions::new(MockTransport::default());
+        let signature = json!([Signature::default().to_string()]);
+
+        let first = subscriptions
+            .subscribe("signatureSubscribe", signature.clone())
+            .await
+            .unwrap();
+        let second = subscriptions
+            .subscribe("signatureSubscribe", signature.clone())
+            .await
+            .unwrap();
+
+        drop(first);
+        assert!(subscriptions.transport().unsubscribed.borrow().is_empty());
+
+        drop(second);
+        assert_eq!(
+            *subscriptions.transport().unsubscribed.borrow(),
+            vec![("signatureUnsubscribe".to_string(), 1)]
+        );
+
+        // subscribing again opens a new subscription, which is unsubscribed in turn
+        drop(
+            subscriptions
+                .subscribe("signatureSubscribe", signature)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(
+            subscriptions.transport().unsubscribed.borrow()[1],
+            ("signatureUnsubscribe".to_string(), 2)
+        );
+
+        assert_eq!(unsubscribe_method("slotSubscribe"), "slotUnsubscribe");
+        assert_eq!(unsubscribe_method("programSubscribe"), "programUnsubscribe");
+    }
+}
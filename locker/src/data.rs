@@ -4,7 +4,7 @@ use solana_api_types::{sysvar::rent::Rent, sysvar::Sysvar, Pubkey};
 use solar::{
     account::{AccountBackend, AccountFields, AccountFieldsMut, Environment},
     reinterpret::{reinterpret_mut_unchecked, reinterpret_unchecked},
-    util::{is_rent_exempt_fixed_arithmetic, minimum_balance, ResultExt},
+    util::{is_rent_exempt_fixed_arithmetic, minimum_balance, pubkey_eq, ResultExt},
 };
 
 use crate::error::Error;
@@ -117,7 +117,7 @@ where
             _phantom: Default::default(),
         };
 
-        if entity.account.owner() != program_id {
+        if !pubkey_eq(entity.account.owner(), program_id) {
             return Err(Error::InvalidOwner);
         }
 
@@ -13,6 +13,13 @@ pub enum Error {
     InvalidAccount,
     NotRentExempt,
     Validation,
+    AccountAlreadyInitialized,
+    ZeroAmount,
+    UnlockDateInPast,
+    /// The instruction was given fewer accounts than it expects.
+    NotEnoughAccountKeys,
+    /// A token transfer moved a different amount than requested.
+    InvalidAmountTransferred,
     SplReadError(SplReadError),
     TokenError(TokenError),
 }
@@ -32,6 +39,11 @@ impl Error {
             Error::Validation => 10,
             Error::SplReadError(_) => 11,
             Error::TokenError(_) => 12,
+            Error::AccountAlreadyInitialized => 13,
+            Error::ZeroAmount => 14,
+            Error::UnlockDateInPast => 15,
+            Error::NotEnoughAccountKeys => 16,
+            Error::InvalidAmountTransferred => 17,
         }
     }
 }
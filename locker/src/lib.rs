@@ -10,7 +10,7 @@ use solana_api_types::{program::ProgramError, Instruction, Pubkey};
 use az::CheckedAs;
 
 #[cfg(feature = "onchain")]
-use solar::input::{account::onchain::Account, BpfProgramInput};
+use solar::input::{account::onchain::Account, decode_instruction, BpfProgramInput, Entrypoint};
 
 use solar::{
     account::{AccountFields, AccountFieldsMut},
@@ -20,7 +20,7 @@ use solar::{
     qlog,
     spl::{TokenProgram, WalletAccount},
     time::SolTimestamp,
-    util::{pubkey_eq, timestamp_now, ResultExt},
+    util::{close_account, is_zeroed, minimum_balance, pubkey_eq, timestamp_now, ResultExt},
 };
 
 pub mod data;
@@ -65,6 +65,36 @@ pub enum Method {
     },
 }
 
+impl Method {
+    /// Decode instruction data, rejecting trailing bytes.
+    pub fn decode(data: &[u8]) -> Result<Self, parity_scale_codec::Error> {
+        parity_scale_codec::DecodeAll::decode_all(data)
+    }
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Method::CreateLock {
+                unlock_date,
+                amount,
+            } => write!(
+                f,
+                "CreateLock(unlock_date: {}, amount: {})",
+                i64::from(*unlock_date),
+                amount.value()
+            ),
+            Method::ReLock { unlock_date } => {
+                write!(f, "ReLock(unlock_date: {})", i64::from(*unlock_date))
+            }
+            Method::Withdraw { amount } => write!(f, "Withdraw(amount: {})", amount.value()),
+            Method::Increment { amount } => write!(f, "Increment(amount: {})", amount.value()),
+            Method::Split { amount } => write!(f, "Split(amount: {})", amount.value()),
+            Method::ChangeOwner { amount } => write!(f, "ChangeOwner(amount: {})", amount.value()),
+        }
+    }
+}
+
 #[repr(C)]
 pub struct TokenLockState {
     pub owner: Pubkey,
@@ -108,7 +138,7 @@ pub struct CreateArgsAccounts<B: AccountBackend> {
 impl<B: AccountBackend> CreateArgsAccounts<B> {
     #[inline]
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
-        parse_accounts! {
+        try_parse_accounts! {
             &mut locker,
             &source_spl_token_wallet,
             &source_authority,
@@ -139,7 +169,7 @@ impl<B: AccountBackend> ReLockArgsAccounts<B> {
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
 
-        parse_accounts! {
+        try_parse_accounts! {
             &mut locker = <Entity<B, TokenLock>>::raw_any(&program_id, this)?,
             &owner_authority,
         }
@@ -167,14 +197,14 @@ impl<B: AccountBackend> WithdrawArgsAccounts<B> {
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
 
-        parse_accounts! {
+        try_parse_accounts! {
             &token_program = TokenProgram::load(this)?,
 
             &mut locker = <Entity<B, TokenLock>>::raw_any(&program_id, this)?,
             &spl_token_wallet_vault,
             &destination_spl_token_wallet,
             &program_authority,
-            &owner_authority,
+            &mut owner_authority,
         }
 
         Ok(Self {
@@ -195,7 +225,7 @@ pub struct IncrementArgsAccounts<B: AccountBackend> {
 
     pub locker: Entity<B, TokenLock>,
     pub spl_token_wallet_vault: WalletAccount<B>,
-    pub source_spl_token_wallet: B,
+    pub source_spl_token_wallet: WalletAccount<B>,
     pub source_authority: B,
 }
 
@@ -204,7 +234,7 @@ impl<B: AccountBackend> IncrementArgsAccounts<B> {
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
 
-        parse_accounts! {
+        try_parse_accounts! {
             &token_program = TokenProgram::load(this)?,
 
             &locker = <Entity<B, TokenLock>>::raw_any(&program_id, this)?,
@@ -217,7 +247,7 @@ impl<B: AccountBackend> IncrementArgsAccounts<B> {
             token_program,
             locker,
             spl_token_wallet_vault: WalletAccount::any(spl_token_wallet_vault)?,
-            source_spl_token_wallet,
+            source_spl_token_wallet: WalletAccount::any(source_spl_token_wallet)?,
             source_authority,
         })
     }
@@ -229,6 +259,7 @@ pub struct SplitArgsAccounts<B: AccountBackend> {
 
     pub source_locker: Entity<B, TokenLock>,
     pub new_locker: B, //(empty, uninitialized)
+    pub program_authority: B,
     pub source_spl_token_wallet_vault: WalletAccount<B>,
     pub new_spl_token_wallet_vault: WalletAccount<B>,
 }
@@ -237,11 +268,12 @@ impl<B: AccountBackend> SplitArgsAccounts<B> {
     #[inline]
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
-        parse_accounts! {
+        try_parse_accounts! {
             &token_program = TokenProgram::load(this)?,
 
             &source_locker = <Entity<B, TokenLock>>::raw_any(&program_id, this)?,
             &mut new_locker,
+            &program_authority,
             &source_spl_token_wallet_vault,
             &new_spl_token_wallet_vault,
         }
@@ -251,6 +283,7 @@ impl<B: AccountBackend> SplitArgsAccounts<B> {
 
             source_locker,
             new_locker,
+            program_authority,
             source_spl_token_wallet_vault: WalletAccount::any(source_spl_token_wallet_vault)?,
             new_spl_token_wallet_vault: WalletAccount::any(new_spl_token_wallet_vault)?,
         })
@@ -268,7 +301,7 @@ impl<B: AccountBackend> ChangeOwnerArgsAccounts<B> {
     #[inline]
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
-        parse_accounts! {
+        try_parse_accounts! {
             &mut locker = <Entity<B, TokenLock>>::raw_any(&program_id, this)?,
             &source_owner_authority,
             &new_owner_authority,
@@ -282,10 +315,45 @@ impl<B: AccountBackend> ChangeOwnerArgsAccounts<B> {
     }
 }
 
+/// Program authority owning the vault of `locker`.
+///
+/// Lockers derive it without a nonce, so this is `None` for roughly half of the possible locker
+/// addresses and clients have to pick another locker keypair in that case.
+pub fn derive_locker_authority(
+    program_id: &Pubkey,
+    locker: &Pubkey,
+    owner: &Pubkey,
+) -> Option<Pubkey> {
+    Pubkey::create_program_address(&[locker.as_ref(), owner.as_ref()], program_id)
+}
+
+/// Check the terms of a new lock: it has to hold something and unlock after `now`.
+pub fn validate_lock(
+    unlock_date: SolTimestamp,
+    amount: TokenAmount,
+    now: SolTimestamp,
+) -> Result<(), Error> {
+    if amount == 0.into() {
+        qlog!("can't create a locker without tokens");
+        return Err(Error::ZeroAmount);
+    }
+
+    if !unlock_date.is_after(now) {
+        qlog!("can't create a locker with an unlock date in the past");
+        return Err(Error::UnlockDateInPast);
+    }
+
+    Ok(())
+}
+
 impl<B> Entity<B, TokenLock>
 where
     B: AccountBackend,
 {
+    pub fn load(program_id: &Pubkey, account: B) -> Result<Self, Error> {
+        Self::raw_initialized(program_id, account)
+    }
+
     /// Create a new locker.
     ///
     /// Account inputs:
@@ -313,8 +381,22 @@ where
             owner_authority,
         } = CreateArgsAccounts::from_program_input(&mut input)?;
 
+        let now = timestamp_now();
+        validate_lock(unlock_date, amount, now.value().into())?;
+
         let mut entity = Self::raw_any(input.program_id(), locker)?;
 
+        if entity.header().kind != EntityKind::None || !is_zeroed(entity.account().data()) {
+            qlog!("locker is already initialized");
+            return Err(Error::AccountAlreadyInitialized.into());
+        }
+
+        let minimum_balance = minimum_balance(entity.account().data().len() as u64);
+        if entity.account().lamports() < minimum_balance {
+            qlog!("locker account is not rent-exempt");
+            return Err(Error::NotRentExempt.into());
+        }
+
         // entity.owner = *owner_authority.key();
         // entity.mint = source_spl_token_wallet.mint();
         // entity.vault = *spl_token_wallet_vault;
@@ -329,12 +411,10 @@ where
         // header.parent_id = id;
         // header.root = entity_key;
 
-        let expected_program_authority = Pubkey::create_program_address(
-            &[
-                entity.account().key().as_ref(),
-                owner_authority.key().as_ref(),
-            ],
+        let expected_program_authority = derive_locker_authority(
             input.program_id(),
+            entity.account().key(),
+            owner_authority.key(),
         )
         .bpf_expect("couldn't derive program authority");
 
@@ -351,13 +431,6 @@ where
             return Err(Error::InvalidAuthority.into());
         }
 
-        let now = timestamp_now();
-
-        // if entity.release_date <= now {
-        //     qlog!("can`t initialize new locker with invalid unlock date");
-        //     return Err(Error::InvalidData.into());
-        // }
-
         Ok(())
     }
 
@@ -413,26 +486,42 @@ where
         SolTimestamp::from(now.value()) > self.release_date
     }
 
+    /// Move all lamports of the locker to `beneficiary` and zero its data, leaving it to be purged
+    /// by the runtime.
+    pub fn close(&mut self, beneficiary: &mut B)
+    where
+        B: AccountFieldsMut,
+    {
+        close_account(self.account_mut(), beneficiary);
+    }
+
     /// Withdraw funds from locker.
+    ///
+    /// Once the vault is empty, it is closed and the rent of both the vault and the locker is
+    /// returned to the owner.
+    ///
     /// Input accounts:
     /// Locker
     /// SPL Token Wallet vault
     /// SPL Token Wallet destination
     /// Program Authority
-    /// Owner (signed)
+    /// Owner (signed, writable)
     #[cfg(feature = "onchain")]
     pub fn withdraw<S: AccountSource<B>>(
         mut input: S,
         amount: TokenAmount,
-    ) -> Result<(), ProgramError> {
+    ) -> Result<(), ProgramError>
+    where
+        B: AccountBackend<Impl = Account> + AccountFieldsMut,
+    {
         let WithdrawArgsAccounts {
             token_program,
 
-            locker,
-            spl_token_wallet_vault,
-            destination_spl_token_wallet,
+            mut locker,
+            mut spl_token_wallet_vault,
+            mut destination_spl_token_wallet,
             program_authority,
-            owner_authority,
+            mut owner_authority,
         } = WithdrawArgsAccounts::from_program_input(&mut input)?;
 
         let now = timestamp_now();
@@ -452,20 +541,47 @@ where
             return Err(Error::Validation.into());
         }
 
+        // the vault is owned by the locker authority, which signs both the transfer and the close
+        let locker_key = *locker.account().key();
+        let expected_program_authority =
+            derive_locker_authority(input.program_id(), &locker_key, &locker.owner)
+                .bpf_expect("couldn't derive program authority");
+        if !pubkey_eq(program_authority.key(), &expected_program_authority) {
+            qlog!("provided program authority does not match expected authority");
+            return Err(Error::InvalidAuthority.into());
+        }
+        let seeds: [&[u8]; 2] = [locker_key.as_ref(), locker.owner.as_ref()];
+
         let amount_before = spl_token_wallet_vault.amount();
         token_program
             .transfer(
                 &mut spl_token_wallet_vault,
                 &mut destination_spl_token_wallet,
                 amount.value(),
-                &owner_authority,
-                &[],
+                &program_authority,
+                &[&seeds],
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
         let amount_after = spl_token_wallet_vault.amount();
 
-        assert!(amount_after - amount_before == amount);
+        if amount_before.value().checked_sub(amount_after.value()) != Some(amount.value()) {
+            qlog!("vault balance changed by a different amount than withdrawn");
+            return Err(Error::InvalidAmountTransferred.into());
+        }
+
+        if amount_after == 0.into() {
+            token_program
+                .close_account(
+                    &mut spl_token_wallet_vault,
+                    &mut owner_authority,
+                    &program_authority,
+                    &[&seeds],
+                )
+                .bpf_expect("call failed")
+                .bpf_expect("close failed");
+            locker.close(&mut owner_authority);
+        }
 
         Ok(())
     }
@@ -481,13 +597,16 @@ where
     pub fn increment<S: AccountSource<B>>(
         mut input: S,
         amount: TokenAmount,
-    ) -> Result<(), ProgramError> {
+    ) -> Result<(), ProgramError>
+    where
+        B: AccountBackend<Impl = Account>,
+    {
         let IncrementArgsAccounts {
             token_program,
 
             locker,
-            spl_token_wallet_vault,
-            source_spl_token_wallet,
+            mut spl_token_wallet_vault,
+            mut source_spl_token_wallet,
             source_authority,
         } = IncrementArgsAccounts::from_program_input(&mut input)?;
 
@@ -516,7 +635,10 @@ where
             .bpf_expect("transfer failed");
         let amount_after = source_spl_token_wallet.amount();
 
-        assert!(amount_after - amount_before == amount);
+        if amount_before.value().checked_sub(amount_after.value()) != Some(amount.value()) {
+            qlog!("wallet balance changed by a different amount than deposited");
+            return Err(Error::InvalidAmountTransferred.into());
+        }
 
         Ok(())
     }
@@ -530,17 +652,18 @@ where
     /// SPL Token Vault (Source Locker)
     /// SPL Token Vault (New Locker)
     #[cfg(feature = "onchain")]
-    pub fn split<S: AccountSource<B>>(
-        mut input: S,
-        amount: TokenAmount,
-    ) -> Result<(), ProgramError> {
+    pub fn split<S: AccountSource<B>>(mut input: S, amount: TokenAmount) -> Result<(), ProgramError>
+    where
+        B: AccountBackend<Impl = Account>,
+    {
         let SplitArgsAccounts {
             token_program,
 
             source_locker,
             new_locker,
-            source_spl_token_wallet_vault,
-            new_spl_token_wallet_vault,
+            program_authority,
+            mut source_spl_token_wallet_vault,
+            mut new_spl_token_wallet_vault,
         } = SplitArgsAccounts::from_program_input(&mut input)?;
 
         let now = timestamp_now();
@@ -550,8 +673,25 @@ where
             return Err(Error::Validation.into());
         }
 
-        if !pubkey_eq(source_locker.owner, new_spl_token_wallet_vault.owner()) {
-            qlog!("locker owner authority does not match destination wallet vault owner ");
+        // the source vault is signed for by the source locker authority, while the new vault has
+        // to be held by the authority of the new locker
+        let source_locker_key = *source_locker.account().key();
+        let expected_program_authority =
+            derive_locker_authority(input.program_id(), &source_locker_key, &source_locker.owner)
+                .bpf_expect("couldn't derive program authority");
+        if !pubkey_eq(program_authority.key(), &expected_program_authority) {
+            qlog!("provided program authority does not match expected authority");
+            return Err(Error::InvalidAuthority.into());
+        }
+
+        let new_program_authority =
+            derive_locker_authority(input.program_id(), new_locker.key(), &source_locker.owner)
+                .bpf_expect("couldn't derive new program authority");
+        if !pubkey_eq(
+            new_spl_token_wallet_vault.authority(),
+            &new_program_authority,
+        ) {
+            qlog!("new wallet vault authority does not match new locker authority");
             return Err(Error::InvalidAuthority.into());
         }
 
@@ -559,30 +699,34 @@ where
 
         entity.owner = source_locker.owner;
         entity.mint = source_locker.mint;
-        entity.vault = source_locker.vault;
-        entity.program_authority = source_locker.program_authority;
+        entity.vault = *new_spl_token_wallet_vault.key();
+        entity.program_authority = new_program_authority;
         entity.release_date = source_locker.release_date;
 
         let id = entity.allocator.allocate_id();
-        let entity_key = *entity.account().key();
         let header = entity.header_mut();
         header.id = id;
         header.kind = EntityKind::Locker;
 
+        let seeds: [&[u8]; 2] = [source_locker_key.as_ref(), source_locker.owner.as_ref()];
+
         let amount_before = source_spl_token_wallet_vault.amount();
         token_program
             .transfer(
                 &mut source_spl_token_wallet_vault,
                 &mut new_spl_token_wallet_vault,
                 amount.value(),
-                &source_locker.owner,
-                &[],
+                &program_authority,
+                &[&seeds],
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
         let amount_after = source_spl_token_wallet_vault.amount();
 
-        assert!(amount_after - amount_before == amount);
+        if amount_before.value().checked_sub(amount_after.value()) != Some(amount.value()) {
+            qlog!("vault balance changed by a different amount than split off");
+            return Err(Error::InvalidAmountTransferred.into());
+        }
 
         Ok(())
     }
@@ -623,29 +767,351 @@ where
 
 #[cfg(feature = "onchain")]
 pub fn main(mut input: BpfProgramInput) -> Result<(), ProgramError> {
-    let mut data = input.data();
-    let method = Method::decode(&mut data)
-        .ok()
-        .bpf_expect("couldn't parse method");
+    let method: Method = decode_instruction(input.data())?;
 
     match method {
         Method::CreateLock {
             unlock_date,
             amount,
-        } => TokenLock::create(input, unlock_date, amount).bpf_unwrap(),
-        Method::ReLock { unlock_date } => TokenLock::relock(input, unlock_date).bpf_unwrap(),
-        Method::Withdraw { amount } => withdraw(input, amount).bpf_unwrap(),
-        Method::Increment { amount } => increment(input, amount).bpf_unwrap(),
-        Method::Split { amount } => split(input, amount).bpf_unwrap(),
-        Method::ChangeOwner { amount } => change_owner(input, amount).bpf_unwrap(),
+        } => TokenLockEntity::create(input, unlock_date, amount)?,
+        Method::ReLock { unlock_date } => TokenLockEntity::relock(input, unlock_date)?,
+        Method::Withdraw { amount } => TokenLockEntity::withdraw(input, amount)?,
+        Method::Increment { amount } => TokenLockEntity::increment(input, amount)?,
+        Method::Split { amount } => TokenLockEntity::split(input, amount)?,
+        Method::ChangeOwner { amount } => TokenLockEntity::change_owner(input, amount)?,
     }
 
     Ok(())
 }
 
+pub struct Program;
+
+#[cfg(feature = "onchain")]
+impl Entrypoint for Program {
+    fn call(input: BpfProgramInput) -> Result<(), ProgramError> {
+        main(input)
+    }
+}
+
+#[cfg(test)]
+mod method_test {
+    use parity_scale_codec::Encode;
+
+    use super::Method;
+
+    #[test]
+    fn method_roundtrip_test() {
+        let methods = vec![
+            Method::CreateLock {
+                unlock_date: 1_700_000_000.into(),
+                amount: 100.into(),
+            },
+            Method::ReLock {
+                unlock_date: 1_800_000_000.into(),
+            },
+            Method::Withdraw { amount: 10.into() },
+            Method::Increment { amount: 20.into() },
+            Method::Split { amount: 30.into() },
+            Method::ChangeOwner { amount: 40.into() },
+        ];
+
+        for method in methods {
+            let data = method.encode();
+            assert_eq!(Method::decode(&data).unwrap(), method);
+
+            let mut trailing = data.clone();
+            trailing.push(0);
+            assert!(Method::decode(&trailing).is_err());
+        }
+
+        assert_eq!(
+            Method::Withdraw { amount: 10.into() }.to_string(),
+            "Withdraw(amount: 10)"
+        );
+    }
+}
+
+#[cfg(test)]
+mod unlock_date_test {
+    use solar::time::{SolDuration, SolTimestamp};
+
+    #[test]
+    fn arithmetic_test() {
+        let date = SolTimestamp::from(1_700_000_000);
+        let day = SolDuration::from(86400);
+
+        let later = date.checked_add(day).unwrap();
+        assert_eq!(later.unix_timestamp(), 1_700_086_400);
+        assert_eq!(later.checked_sub(day), Some(date));
+        assert_eq!(later.duration_since(date), Some(day));
+        assert_eq!(date.duration_since(later), Some(SolDuration::from(-86400)));
+
+        assert!(date.is_before(later));
+        assert!(later.is_after(date));
+        assert!(date.has_passed(date));
+        assert!(!later.has_passed(date));
+
+        assert_eq!(SolTimestamp::from(i64::MAX).checked_add(day), None);
+    }
+
+    #[test]
+    fn display_test() {
+        let cases = [
+            (0, "1970-01-01T00:00:00Z"),
+            (-1, "1969-12-31T23:59:59Z"),
+            (951_782_400, "2000-02-29T00:00:00Z"),
+            (1_700_000_000, "2023-11-14T22:13:20Z"),
+            (4_102_444_799, "2099-12-31T23:59:59Z"),
+        ];
+
+        for (ts, expected) in cases.iter() {
+            assert_eq!(SolTimestamp::from(*ts).to_string(), *expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod validate_lock_test {
+    use solar::time::SolTimestamp;
+
+    use super::validate_lock;
+    use crate::error::Error;
+
+    #[test]
+    fn rejects_invalid_terms_test() {
+        let now = SolTimestamp::from(1_700_000_000);
+
+        assert!(validate_lock(1_700_000_001.into(), 1.into(), now).is_ok());
+        assert!(matches!(
+            validate_lock(1_700_000_001.into(), 0.into(), now),
+            Err(Error::ZeroAmount)
+        ));
+        assert!(matches!(
+            validate_lock(1_600_000_000.into(), 100.into(), now),
+            Err(Error::UnlockDateInPast)
+        ));
+        assert!(matches!(
+            validate_lock(now, 100.into(), now),
+            Err(Error::UnlockDateInPast)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod close_test {
+    use solana_api_types::{Account, Pubkey};
+    use solar::{account::AccountFields, util::is_zeroed};
+
+    use super::{TokenLock, TokenLockEntity};
+    use crate::data::{AccountType, HEADER_RESERVED};
+
+    #[test]
+    fn derive_locker_authority_test() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let derived: Vec<_> = (0..32)
+            .map(|_| {
+                let locker = Pubkey::new_unique();
+                let authority = super::derive_locker_authority(&program_id, &locker, &owner);
+                assert_eq!(
+                    authority,
+                    Pubkey::create_program_address(&[locker.as_ref(), owner.as_ref()], &program_id)
+                );
+                authority
+            })
+            .collect();
+
+        assert!(derived.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn close_returns_lamports_test() {
+        let program_id = Pubkey::new_unique();
+        let mut data = vec![0; TokenLock::default_size()];
+        data[HEADER_RESERVED..]
+            .iter_mut()
+            .for_each(|byte| *byte = 7);
+        let locker = Account {
+            pubkey: Pubkey::new_unique(),
+            owner: program_id,
+            lamports: 2_000_000,
+            data,
+            ..Default::default()
+        };
+        let mut owner = Box::new(Account {
+            pubkey: Pubkey::new_unique(),
+            lamports: 500,
+            ..Default::default()
+        });
+
+        let mut locker = TokenLockEntity::raw_any(&program_id, Box::new(locker)).unwrap();
+        locker.close(&mut owner);
+
+        assert_eq!(locker.account().lamports(), 0);
+        assert_eq!(owner.lamports(), 2_000_500);
+        // funding the address again within the transaction doesn't revive the locker
+        assert!(is_zeroed(locker.account().data()));
+    }
+}
+
 #[cfg(feature = "onchain")]
 #[cfg(test)]
 mod test {
+    use parity_scale_codec::Encode;
+    use solana_api_types::{
+        program_test::ProgramTest, Account, AccountMeta, Instruction, Keypair, Pubkey, Signer,
+        Transaction,
+    };
+    use solana_program_test::builtin_process_instruction;
+    use solar::{
+        input::wrapped_entrypoint,
+        spl::{self, create_mint, create_wallet, mint_to, WalletAccount},
+        util::minimum_balance,
+    };
+
+    use super::{derive_locker_authority, Method, TokenLock, TokenLockEntity};
+    use crate::data::{AccountType, EntityKind};
+
+    #[tokio::test]
+    async fn withdraw_closes_locker_test() -> anyhow::Result<()> {
+        let mut program_test = ProgramTest::default();
+        let program_id = Pubkey::new_unique();
+
+        program_test.add_program(
+            "locker",
+            program_id,
+            Some(|a, b, c| {
+                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+            }),
+        );
+
+        let owner_key = Keypair::new();
+        let mint_key = Keypair::new();
+        let vault_key = Keypair::new();
+        let destination_key = Keypair::new();
+
+        // lockers derive their authority without a nonce, so not every address has one
+        let (locker_key, program_authority) = std::iter::repeat_with(Pubkey::new_unique)
+            .find_map(|locker| {
+                derive_locker_authority(&program_id, &locker, &owner_key.pubkey())
+                    .map(|authority| (locker, authority))
+            })
+            .unwrap();
+
+        let size = TokenLock::default_size();
+        let mut locker = TokenLockEntity::raw_any(
+            &program_id,
+            Box::new(Account {
+                pubkey: locker_key,
+                owner: program_id,
+                lamports: minimum_balance(size as u64),
+                data: vec![0; size],
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        locker.header_mut().kind = EntityKind::Locker;
+        locker.owner = owner_key.pubkey();
+        locker.mint = mint_key.pubkey();
+        locker.vault = vault_key.pubkey();
+        locker.program_authority = program_authority;
+        locker.release_date = 0.into();
+        let locker_lamports = locker.account().lamports;
+        program_test.add_account(locker.account());
+
+        let (mut client, payer, hash) = program_test.start().await;
+
+        let mut instrs = vec![];
+        instrs.extend(create_mint(
+            &payer.pubkey(),
+            &mint_key.pubkey(),
+            &payer.pubkey(),
+            None,
+            6,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &vault_key.pubkey(),
+            &mint_key.pubkey(),
+            &program_authority,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &destination_key.pubkey(),
+            &mint_key.pubkey(),
+            &owner_key.pubkey(),
+        ));
+        instrs.push(mint_to(
+            &mint_key.pubkey(),
+            &vault_key.pubkey(),
+            &payer.pubkey(),
+            1000,
+        ));
+        let trx = Transaction::new_signed_with_payer(
+            &instrs,
+            Some(&payer.pubkey()),
+            &vec![
+                &payer as &dyn Signer,
+                &mint_key,
+                &vault_key,
+                &destination_key,
+            ],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let vault_lamports = client
+            .get_account(&vault_key.pubkey())
+            .await?
+            .unwrap()
+            .lamports;
+
+        let withdraw = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(*spl::ID, false),
+                AccountMeta::new(locker_key, false),
+                AccountMeta::new(vault_key.pubkey(), false),
+                AccountMeta::new(destination_key.pubkey(), false),
+                AccountMeta::new_readonly(program_authority, false),
+                AccountMeta::new(owner_key.pubkey(), true),
+            ],
+            data: Method::Withdraw {
+                amount: 1000.into(),
+            }
+            .encode(),
+        };
+        let trx = Transaction::new_signed_with_payer(
+            &[withdraw],
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &owner_key],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let destination = client
+            .get_account(&destination_key.pubkey())
+            .await?
+            .unwrap();
+        assert_eq!(
+            WalletAccount::any(Box::new(destination)).unwrap().amount(),
+            1000.into()
+        );
+        // both the vault and the locker are closed, their rent goes to the owner
+        assert!(client.get_account(&vault_key.pubkey()).await?.is_none());
+        assert!(client.get_account(&locker_key).await?.is_none());
+        assert_eq!(
+            client
+                .get_account(&owner_key.pubkey())
+                .await?
+                .unwrap()
+                .lamports,
+            vault_lamports + locker_lamports
+        );
+
+        Ok(())
+    }
 
     #[tokio::test]
     async fn init_test() -> anyhow::Result<()> {
@@ -663,23 +1129,11 @@ mod test {
         let locker_key = Keypair::new();
         let locker_owner_key = Keypair::new();
 
-        let mut salt: u64 = 0;
-        let locker_program_authority = loop {
-            let locker_program_authority = Pubkey::create_program_address(
-                &[
-                    locker_key.pubkey().as_ref(),
-                    locker_owner_key.pubkey().as_ref(),
-                ],
-                &program_id,
-            );
-
-            match locker_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
-            }
-        };
+        let locker_program_authority = super::derive_locker_authority(
+            &program_id,
+            &locker_key.pubkey(),
+            &locker_owner_key.pubkey(),
+        );
 
         let (mut client, payer, hash) = program_test.start().await;
 
@@ -9,6 +9,7 @@ use solar::{
     prelude::AccountBackend,
     reinterpret::{reinterpret_mut_unchecked, reinterpret_unchecked},
     spl::{MintAccount, WalletAccount},
+    util::pubkey_eq,
 };
 
 use crate::{
@@ -95,7 +96,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
 
         let header = farm.header();
 
-        if &header.root != farm.account().key()
+        if !pubkey_eq(&header.root, farm.account().key())
             || header.parent_id != EntityId::new(0)
             || header.id != EntityId::new(0)
         {
@@ -107,7 +108,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
 
     fn wallet<T: AccountBackend>(&self, account: T) -> Result<WalletAccount<T>, Error> {
         let wallet = WalletAccount::any(account)?;
-        if wallet.authority() != &self.program_authority {
+        if !pubkey_eq(wallet.authority(), &self.program_authority) {
             return Err(Error::Validation);
         }
 
@@ -119,7 +120,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
         account: T,
     ) -> Result<WalletAccount<T>, Error> {
         let wallet = self.wallet(account)?;
-        if wallet.mint() != &self.stake_mint {
+        if !pubkey_eq(wallet.mint(), &self.stake_mint) {
             return Err(Error::Validation);
         }
 
@@ -131,7 +132,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
         account: T,
     ) -> Result<WalletAccount<T>, Error> {
         let wallet = self.wallet(account)?;
-        if wallet.mint() != &self.reward_mint {
+        if !pubkey_eq(wallet.mint(), &self.reward_mint) {
             return Err(Error::Validation);
         }
 
@@ -140,7 +141,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
 
     pub fn load_stake_mint<T: AccountBackend>(&self, account: T) -> Result<MintAccount<T>, Error> {
         let mint = MintAccount::any(account)?;
-        if mint.key() != &self.stake_mint {
+        if !pubkey_eq(mint.key(), &self.stake_mint) {
             return Err(Error::Validation);
         }
 
@@ -149,7 +150,7 @@ impl<B: AccountBackend> Entity<B, Farm> {
 
     pub fn load_reward_mint<T: AccountBackend>(&self, account: T) -> Result<MintAccount<T>, Error> {
         let mint = MintAccount::any(account)?;
-        if mint.key() != &self.reward_mint {
+        if !pubkey_eq(mint.key(), &self.reward_mint) {
             return Err(Error::Validation);
         }
 
@@ -4,7 +4,7 @@ use solana_api_types::{sysvar::rent::Rent, sysvar::Sysvar, Pubkey};
 use solar::{
     account::{AccountBackend, AccountFields, AccountFieldsMut, Environment},
     reinterpret::{reinterpret_mut_unchecked, reinterpret_unchecked},
-    util::{is_rent_exempt_fixed_arithmetic, minimum_balance, ResultExt},
+    util::{is_rent_exempt_fixed_arithmetic, minimum_balance, pubkey_eq, ResultExt},
 };
 
 use crate::error::Error;
@@ -85,6 +85,9 @@ pub struct EntityHeader {
     pub id: EntityId,
     pub parent_id: EntityId,
     pub kind: EntityKind,
+    /// Non-zero while an instruction acting on the entity is calling out of the program, see
+    /// [`Entity::enter`].
+    pub in_flight: u8,
 }
 
 #[derive(Debug)]
@@ -120,7 +123,7 @@ where
             _phantom: Default::default(),
         };
 
-        if entity.account.owner() != program_id {
+        if !pubkey_eq(entity.account.owner(), program_id) {
             return Err(Error::InvalidOwner);
         }
 
@@ -191,6 +194,36 @@ where
         self.header().id
     }
 
+    /// Mark the entity as in flight ahead of a CPI, failing with
+    /// [`Error::ReentrancyNotAllowed`] if it already is.
+    ///
+    /// An instruction re-entering the program during the CPI then can't act on state the outer
+    /// one is about to update. Cleared again with [`Entity::exit`] once the CPI returns, a failed
+    /// instruction leaves nothing behind as its changes are discarded.
+    pub fn enter(&mut self) -> Result<(), Error>
+    where
+        B::Impl: AccountFieldsMut,
+    {
+        let header = self.header_mut();
+        if header.in_flight != 0 {
+            return Err(Error::ReentrancyNotAllowed);
+        }
+
+        header.in_flight = 1;
+        Ok(())
+    }
+
+    pub fn exit(&mut self)
+    where
+        B::Impl: AccountFieldsMut,
+    {
+        self.header_mut().in_flight = 0;
+    }
+
+    pub fn is_in_flight(&self) -> bool {
+        self.header().in_flight != 0
+    }
+
     pub fn root(&self) -> &Pubkey {
         &self.header().root
     }
@@ -200,11 +233,21 @@ where
     }
 
     pub fn is_parent<U: AccountType>(&self, other: &Entity<B, U>) -> bool {
-        self.root() == other.root() && self.id() == other.parent_id()
+        pubkey_eq(self.root(), other.root()) && self.id() == other.parent_id()
     }
 
     pub fn is_child<U: AccountType>(&self, other: &Entity<B, U>) -> bool {
-        self.root() == other.root() && self.parent_id() == other.id()
+        pubkey_eq(self.root(), other.root()) && self.parent_id() == other.id()
+    }
+
+    /// Check that `self` belongs to `parent`: it shares the root and owner of `parent` and its
+    /// `parent_id` is the id of `parent`.
+    pub fn verify_child_of<U: AccountType>(&self, parent: &Entity<B, U>) -> Result<(), Error> {
+        if !pubkey_eq(self.account.owner(), parent.account.owner()) || !self.is_child(parent) {
+            return Err(Error::InvalidParent);
+        }
+
+        Ok(())
     }
 
     #[inline(never)]
@@ -217,18 +260,54 @@ where
     }
 }
 
+/// Number of released ids an [`EntityAllocator`] keeps for reuse.
+pub const FREE_ID_CAPACITY: usize = 8;
+
 #[derive(Default)]
 #[repr(C)]
 pub struct EntityAllocator {
     counter: u64,
+    free_count: u64,
+    free: [EntityId; FREE_ID_CAPACITY],
 }
 
 impl EntityAllocator {
+    /// Hand out the most recently released id, or a fresh one if none is left.
     pub fn allocate_id(&mut self) -> EntityId {
+        if self.free_count > 0 {
+            self.free_count -= 1;
+            return self.free[self.free_count as usize];
+        }
+
         let id = self.counter;
         self.counter += 1;
         EntityId { id }
     }
+
+    /// Return the id of a closed entity so it can be allocated again, `true` if it will be.
+    ///
+    /// Ids that were never allocated are ignored and `false` is returned for them. Once the free
+    /// list is full further ids are dropped and never reused, `false` is returned in that case
+    /// too. Releasing an id that is already free does nothing.
+    pub fn release_id(&mut self, id: EntityId) -> bool {
+        let count = self.free_count as usize;
+
+        if id.id >= self.counter {
+            return false;
+        }
+
+        if self.free[..count].contains(&id) {
+            return true;
+        }
+
+        if count == FREE_ID_CAPACITY {
+            return false;
+        }
+
+        self.free[count] = id;
+        self.free_count += 1;
+        true
+    }
 }
 
 pub enum RelationshipKind {
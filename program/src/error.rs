@@ -1,7 +1,11 @@
 use solana_api_types::program::ProgramError;
-use solar::spl::{SplReadError, TokenError};
+use solar::{
+    account::AccountCheckError,
+    log::{Loggable, Logger},
+    spl::{SplReadError, TokenError},
+};
 
-#[derive(IntoStaticStr, Debug, Display)]
+#[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     InvalidData,
     InvalidAlignment,
@@ -13,12 +17,30 @@ pub enum Error {
     InvalidAccount,
     NotRentExempt,
     Validation,
+    AccountAlreadyInitialized,
     SplReadError(SplReadError),
     TokenError(TokenError),
+    /// The vault balance changed by a different amount than was transferred, e.g. because the
+    /// mint takes a fee on transfer.
+    InvalidAmountTransferred,
+    /// The instruction was given fewer accounts than it expects.
+    NotEnoughAccountKeys,
+    AccountCheck(AccountCheckError),
+    /// An instruction re-entered the program while another one acting on the same pool was
+    /// calling out of it.
+    ReentrancyNotAllowed,
 }
 
 impl Error {
-    fn code(&self) -> u32 {
+    /// Custom error code the program fails with, as reported in `ProgramError::Custom`.
+    ///
+    /// The low byte identifies the variant and never changes, wrapped SPL errors keep their own
+    /// code in the next byte.
+    ///
+    /// This breaks with earlier builds, which failed with a bare 11 for every `SplReadError` and
+    /// 12 for every `TokenError`. Clients matching on those codes should match on the low byte,
+    /// or decode with [`Error::from_code`].
+    pub fn code(&self) -> u32 {
         match self {
             Error::InvalidData => 1,
             Error::InvalidAlignment => 2,
@@ -30,8 +52,110 @@ impl Error {
             Error::InvalidAccount => 8,
             Error::NotRentExempt => 9,
             Error::Validation => 10,
-            Error::SplReadError(_) => 11,
-            Error::TokenError(_) => 12,
+            Error::SplReadError(err) => 11 | (spl_read_error_code(*err) << 8),
+            Error::TokenError(err) => 12 | ((*err as u32) << 8),
+            Error::AccountAlreadyInitialized => 13,
+            Error::InvalidAmountTransferred => 14,
+            Error::NotEnoughAccountKeys => 15,
+            Error::AccountCheck(err) => 16 | (account_check_error_code(*err) << 8),
+            Error::ReentrancyNotAllowed => 17,
+        }
+    }
+
+    /// Decode the code of a failed transaction back into the error, `None` if the code was not
+    /// produced by [`Error::code`].
+    pub fn from_code(code: u32) -> Option<Self> {
+        let payload = code >> 8;
+
+        let error = match code & 0xff {
+            1 => Error::InvalidData,
+            2 => Error::InvalidAlignment,
+            3 => Error::InvalidOwner,
+            4 => Error::InvalidParent,
+            5 => Error::InvalidKind,
+            6 => Error::InvalidAuthority,
+            7 => Error::InvalidMint,
+            8 => Error::InvalidAccount,
+            9 => Error::NotRentExempt,
+            10 => Error::Validation,
+            11 => {
+                return Some(Error::SplReadError(match payload {
+                    0 => SplReadError::InvalidData,
+                    1 => SplReadError::InvalidOwner,
+                    2 => SplReadError::InvalidMint,
+                    _ => return None,
+                }))
+            }
+            12 => {
+                let err = TokenError::from(payload);
+                return if err as u32 == payload {
+                    Some(Error::TokenError(err))
+                } else {
+                    None
+                };
+            }
+            13 => Error::AccountAlreadyInitialized,
+            14 => Error::InvalidAmountTransferred,
+            15 => Error::NotEnoughAccountKeys,
+            16 => {
+                return Some(Error::AccountCheck(match payload {
+                    0 => AccountCheckError::InvalidOwner,
+                    1 => AccountCheckError::InvalidKey,
+                    2 => AccountCheckError::MissingSignature,
+                    3 => AccountCheckError::NotWritable,
+                    _ => return None,
+                }))
+            }
+            17 => Error::ReentrancyNotAllowed,
+            _ => return None,
+        };
+
+        if payload == 0 {
+            Some(error)
+        } else {
+            None
+        }
+    }
+}
+
+fn spl_read_error_code(err: SplReadError) -> u32 {
+    match err {
+        SplReadError::InvalidData => 0,
+        SplReadError::InvalidOwner => 1,
+        SplReadError::InvalidMint => 2,
+    }
+}
+
+fn account_check_error_code(err: AccountCheckError) -> u32 {
+    match err {
+        AccountCheckError::InvalidOwner => 0,
+        AccountCheckError::InvalidKey => 1,
+        AccountCheckError::MissingSignature => 2,
+        AccountCheckError::NotWritable => 3,
+    }
+}
+
+impl Loggable for Error {
+    fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>) {
+        logger.push_str(self.into());
+
+        match self {
+            Error::SplReadError(err) => {
+                logger.push_str("(");
+                err.push_to_logger(logger);
+                logger.push_str(")");
+            }
+            Error::TokenError(err) => {
+                logger.push_str("(");
+                err.push_to_logger(logger);
+                logger.push_str(")");
+            }
+            Error::AccountCheck(err) => {
+                logger.push_str("(");
+                err.push_to_logger(logger);
+                logger.push_str(")");
+            }
+            _ => {}
         }
     }
 }
@@ -42,6 +166,12 @@ impl From<SplReadError> for Error {
     }
 }
 
+impl From<AccountCheckError> for Error {
+    fn from(other: AccountCheckError) -> Self {
+        Self::AccountCheck(other)
+    }
+}
+
 impl From<TokenError> for Error {
     fn from(other: TokenError) -> Self {
         Self::TokenError(other)
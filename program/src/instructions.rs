@@ -0,0 +1,226 @@
+//! Builders for the instructions of the stake program.
+//!
+//! Accounts are passed by name and laid out in the order `from_program_input` of the matching
+//! `*ArgsAccounts` parses them, checked against its account count in debug builds.
+
+use parity_scale_codec::Encode;
+use solana_api_types::{AccountMeta, AccountMetas, Instruction, Pubkey};
+use solar::spl;
+
+use crate::{
+    simple_stake::{self, InitializeArgs},
+    Method, TokenAmount,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CreatePoolAccounts {
+    pub administrator: Pubkey,
+    /// As returned by [`simple_stake::derive_pool_authority`], along with the salt in the args.
+    pub program_authority: Pubkey,
+    pub pool: Pubkey,
+    pub stake_mint: Pubkey,
+    /// Token wallet of `stake_mint` owned by `program_authority`.
+    pub stake_vault: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StakeAccounts {
+    pub pool: Pubkey,
+    pub staker: Pubkey,
+    /// Allocated for the program ahead of the first stake, it is initialized on the fly.
+    pub ticket: Pubkey,
+    pub stake_vault: Pubkey,
+    /// Signs the transfer out of `source_wallet`.
+    pub source_authority: Pubkey,
+    pub source_wallet: Pubkey,
+}
+
+/// One stake of a `StakeBatch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchStake {
+    pub staker: Pubkey,
+    /// Allocated for the program ahead of the first stake, it is initialized on the fly.
+    pub ticket: Pubkey,
+    /// Signs the transfer out of `source_wallet`.
+    pub source_authority: Pubkey,
+    pub source_wallet: Pubkey,
+    pub amount: TokenAmount,
+}
+
+/// Accounts of `Unstake`, `UnstakePercent` and `ClaimReward`, which share the same layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawAccounts {
+    pub pool: Pubkey,
+    pub ticket: Pubkey,
+    /// Owner of `ticket`, signs the withdrawal.
+    pub staker: Pubkey,
+    pub program_authority: Pubkey,
+    pub stake_vault: Pubkey,
+    pub target_wallet: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddRewardAccounts {
+    pub pool: Pubkey,
+    pub stake_vault: Pubkey,
+    /// Signs the transfer out of `source_wallet`.
+    pub source_authority: Pubkey,
+    pub source_wallet: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetAdministratorAccounts {
+    pub pool: Pubkey,
+    /// Current administrator of the pool, signs the hand-over.
+    pub administrator: Pubkey,
+    pub new_administrator: Pubkey,
+}
+
+fn instruction(
+    program_id: Pubkey,
+    accounts: Vec<AccountMeta>,
+    method: simple_stake::Method,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts,
+        data: Method::Simple(method).encode(),
+    }
+}
+
+pub fn create_pool(
+    program_id: Pubkey,
+    accounts: &CreatePoolAccounts,
+    args: InitializeArgs,
+) -> Instruction {
+    instruction(
+        program_id,
+        AccountMetas::new(simple_stake::CREATE_POOL_ACCOUNTS)
+            .readonly(accounts.administrator)
+            .readonly(accounts.program_authority)
+            .writable(accounts.pool)
+            .readonly(accounts.stake_mint)
+            .readonly(accounts.stake_vault)
+            .build(),
+        simple_stake::Method::CreatePool(args),
+    )
+}
+
+pub fn stake(program_id: Pubkey, accounts: &StakeAccounts, amount: TokenAmount) -> Instruction {
+    instruction(
+        program_id,
+        AccountMetas::new(simple_stake::STAKE_ACCOUNTS)
+            .readonly(*spl::ID)
+            .writable(accounts.pool)
+            .readonly(accounts.staker)
+            .writable(accounts.ticket)
+            .writable(accounts.stake_vault)
+            .signer_readonly(accounts.source_authority)
+            .writable(accounts.source_wallet)
+            .build(),
+        simple_stake::Method::Stake { amount },
+    )
+}
+
+/// Stake on every ticket of `stakes` in one instruction, at most
+/// [`simple_stake::MAX_STAKE_BATCH`] of them.
+pub fn stake_batch(
+    program_id: Pubkey,
+    pool: Pubkey,
+    stake_vault: Pubkey,
+    stakes: &[BatchStake],
+) -> Instruction {
+    let mut accounts = AccountMetas::new(
+        simple_stake::STAKE_BATCH_ACCOUNTS
+            + stakes.len() * simple_stake::STAKE_BATCH_ENTRY_ACCOUNTS,
+    )
+    .readonly(*spl::ID)
+    .writable(pool)
+    .writable(stake_vault);
+    for stake in stakes {
+        accounts = accounts
+            .readonly(stake.staker)
+            .writable(stake.ticket)
+            .signer_readonly(stake.source_authority)
+            .writable(stake.source_wallet);
+    }
+
+    instruction(
+        program_id,
+        accounts.build(),
+        simple_stake::Method::StakeBatch {
+            amounts: stakes.iter().map(|stake| stake.amount).collect(),
+        },
+    )
+}
+
+pub fn unstake(
+    program_id: Pubkey,
+    accounts: &WithdrawAccounts,
+    amount: TokenAmount,
+) -> Instruction {
+    instruction(
+        program_id,
+        withdraw_metas(accounts),
+        simple_stake::Method::Unstake { amount },
+    )
+}
+
+/// Unstake `bps` basis points of the ticket, 10000 withdraws all of it.
+pub fn unstake_percent(program_id: Pubkey, accounts: &WithdrawAccounts, bps: u16) -> Instruction {
+    instruction(
+        program_id,
+        withdraw_metas(accounts),
+        simple_stake::Method::UnstakePercent { bps },
+    )
+}
+
+pub fn claim_reward(program_id: Pubkey, accounts: &WithdrawAccounts) -> Instruction {
+    instruction(
+        program_id,
+        withdraw_metas(accounts),
+        simple_stake::Method::ClaimReward,
+    )
+}
+
+fn withdraw_metas(accounts: &WithdrawAccounts) -> Vec<AccountMeta> {
+    AccountMetas::new(simple_stake::WITHDRAW_ACCOUNTS)
+        .readonly(*spl::ID)
+        .writable(accounts.pool)
+        .writable(accounts.ticket)
+        .signer_writable(accounts.staker)
+        .readonly(accounts.program_authority)
+        .writable(accounts.stake_vault)
+        .writable(accounts.target_wallet)
+        .build()
+}
+
+pub fn add_reward(
+    program_id: Pubkey,
+    accounts: &AddRewardAccounts,
+    amount: TokenAmount,
+) -> Instruction {
+    instruction(
+        program_id,
+        AccountMetas::new(simple_stake::ADD_REWARD_ACCOUNTS)
+            .readonly(*spl::ID)
+            .writable(accounts.pool)
+            .writable(accounts.stake_vault)
+            .signer_readonly(accounts.source_authority)
+            .writable(accounts.source_wallet)
+            .build(),
+        simple_stake::Method::AddReward { amount },
+    )
+}
+
+pub fn set_administrator(program_id: Pubkey, accounts: &SetAdministratorAccounts) -> Instruction {
+    instruction(
+        program_id,
+        AccountMetas::new(simple_stake::SET_ADMINISTRATOR_ACCOUNTS)
+            .writable(accounts.pool)
+            .signer_readonly(accounts.administrator)
+            .readonly(accounts.new_administrator)
+            .build(),
+        simple_stake::Method::SetAdministrator,
+    )
+}
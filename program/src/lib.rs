@@ -7,10 +7,11 @@ use simple_stake::StakePoolEntity;
 #[cfg(feature = "onchain")]
 use solana_api_types::program::ProgramResult;
 #[cfg(feature = "onchain")]
-use solar::input::{BpfProgramInput, Entrypoint, ProgramInput};
-use solar::math::Checked;
-#[cfg(feature = "onchain")]
-use solar::util::ResultExt;
+use solar::input::{decode_instruction, BpfProgramInput, Entrypoint, ProgramInput};
+use solar::{
+    log::{Loggable, Logger},
+    math::Checked,
+};
 
 #[macro_use]
 extern crate static_assertions;
@@ -20,6 +21,9 @@ extern crate strum;
 pub mod complex;
 pub mod data;
 pub mod error;
+pub mod instructions;
+#[cfg(not(target_arch = "bpf"))]
+pub mod query;
 pub mod simple_stake;
 
 pub type TokenAmount = Checked<u64>;
@@ -30,37 +34,69 @@ pub enum Method {
     Simple(simple_stake::Method),
 }
 
+impl Method {
+    /// Decode instruction data, rejecting trailing bytes.
+    pub fn decode(data: &[u8]) -> Result<Self, parity_scale_codec::Error> {
+        parity_scale_codec::DecodeAll::decode_all(data)
+    }
+}
+
+impl Loggable for Method {
+    fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>) {
+        match self {
+            Method::Simple(method) => {
+                logger.push_str("Simple::");
+                method.push_to_logger(logger);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Method::Simple(method) => write!(f, "Simple::{}", method),
+        }
+    }
+}
+
 #[cfg(feature = "onchain")]
 #[allow(unused)]
 pub fn main(mut input: BpfProgramInput) -> ProgramResult {
-    use solar::qlog;
-
-    let mut data = input.data();
-    let method: Method = parity_scale_codec::Decode::decode(&mut data)
-        .ok()
-        .bpf_expect("could not parse method");
-
-    let result = match method {
-        Method::Simple(method) => match method {
-            simple_stake::Method::CreatePool(args) => StakePoolEntity::initialize(&mut input, args),
-            simple_stake::Method::Stake { amount } => {
-                StakePoolEntity::add_stake(&mut input, amount)
+    use solar::{entrypoint::handle_instruction, log::with_compute_units};
+
+    let method: Method = decode_instruction(input.data())?;
+
+    handle_instruction(&method, || match &method {
+        Method::Simple(simple) => match simple {
+            simple_stake::Method::CreatePool(args) => {
+                StakePoolEntity::initialize(&mut input, args.clone())
             }
+            simple_stake::Method::Stake { amount } => with_compute_units("add_stake", || {
+                StakePoolEntity::add_stake(&mut input, *amount)
+            }),
             simple_stake::Method::Unstake { amount } => {
-                StakePoolEntity::remove_stake(&mut input, amount)
+                StakePoolEntity::remove_stake(&mut input, *amount)
+            }
+            simple_stake::Method::ClaimReward => {
+                with_compute_units("claim_reward", || StakePoolEntity::claim_reward(&mut input))
             }
-            simple_stake::Method::ClaimReward => StakePoolEntity::claim_reward(&mut input),
             simple_stake::Method::AddReward { amount } => {
-                StakePoolEntity::add_reward(&mut input, amount)
+                StakePoolEntity::add_reward(&mut input, *amount)
+            }
+            simple_stake::Method::UnstakePercent { bps } => {
+                StakePoolEntity::remove_stake_percent(&mut input, *bps)
+            }
+            simple_stake::Method::SetAdministrator => {
+                StakePoolEntity::set_administrator(&mut input)
+            }
+            simple_stake::Method::StakeBatch { amounts } => {
+                with_compute_units("add_stake_batch", || {
+                    StakePoolEntity::add_stake_batch(&mut input, amounts)
+                })
             }
         },
-    };
-
-    if result.is_err() {
-        dbg!(result);
-    }
-
-    Ok(())
+    })
 }
 
 pub struct Program;
@@ -80,397 +116,1829 @@ mod test {
     use parity_scale_codec::Encode;
     use solana_program_test::builtin_process_instruction;
     use solar::{
-        input::wrapped_entrypoint,
-        spl::{create_mint, create_wallet, mint_to, Mint, Wallet},
+        account::{AccountCheckError, AccountFields},
+        authority::{Authority, AuthorityError, MultisigAuthority},
+        input::{decode_instruction, wrapped_entrypoint},
+        log::{Loggable, Logger},
+        spl::{
+            self, create_mint, create_wallet, mint_to, mint_to_cap, Mint, MintAccount,
+            SplReadError, TokenError, Wallet, WalletAccount, MAX_SIGNERS,
+        },
+        sysvar::{EpochScheduleSysvar, RentSysvar, SlotHashesSysvar, SysvarError},
         util::minimum_balance,
     };
 
     use solana_api_types::{
-        program_test::ProgramTest, system::create_account, AccountMeta, Instruction, Keypair,
-        Pubkey, Signer, Transaction,
+        fixture::AccountFixture,
+        program::ProgramError,
+        program_test::{ProgramTest, Runtime},
+        system::{self, create_account},
+        sysvar::{epoch_schedule, rent, slot_hashes},
+        Account, AccountMeta, AccountSlice, Hash, Instruction, Keypair, Pubkey, Signer,
+        Transaction,
     };
 
     use crate::{
-        data::AccountType,
-        simple_stake::{self, InitializeArgs, StakePool, StakePoolEntity, StakerTicket},
-        Method,
+        data::{
+            AccountType, Entity, EntityAllocator, EntityId, EntityKind, FREE_ID_CAPACITY,
+            HEADER_RESERVED,
+        },
+        error::Error,
+        instructions::{
+            self, AddRewardAccounts, BatchStake, CreatePoolAccounts, SetAdministratorAccounts,
+            StakeAccounts, WithdrawAccounts,
+        },
+        simple_stake::{
+            self, derive_pool_authority, pool_authority, verify_deposit, verify_withdrawal,
+            ActivationState, BalanceGuard, InitializeArgs, RewardTranche, StakePool,
+            StakePoolEntity, StakerTicket, StakerTicketEntity, StakerTicketState,
+            MAX_REWARD_TRANCHES, MAX_STAKE_BATCH, POOL_FLAG_FEE_ON_TRANSFER,
+            POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE, STAKE_BATCH_ENTRY_ACCOUNTS,
+        },
+        Method, TokenAmount,
     };
 
-    #[tokio::test]
-    async fn create_test() -> anyhow::Result<()> {
-        let mut program_test = ProgramTest::default();
-        let program_id = Pubkey::new_unique();
+    #[test]
+    fn rent_sysvar_test() {
+        // mainnet parameters: 3480 lamports per byte-year, exempt after 2 years, 50% burned
+        let mut data = vec![];
+        data.extend_from_slice(&3480u64.to_le_bytes());
+        data.extend_from_slice(&2.0f64.to_le_bytes());
+        data.push(50);
+
+        let account = Box::new(Account {
+            data,
+            pubkey: *rent::ID,
+            ..Default::default()
+        });
+        let sysvar = RentSysvar::any(account.clone()).unwrap();
 
-        program_test.add_program(
-            "x5margin",
-            program_id,
-            Some(|a, b, c| {
-                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
-            }),
+        assert_eq!(sysvar.rent().burn_percent, 50);
+        assert_eq!(sysvar.minimum_balance(0), 890_880);
+        assert_eq!(
+            sysvar.minimum_balance(size_of::<Wallet>() as u64),
+            2_039_280
         );
+        assert!(sysvar.is_exempt(2_039_280, size_of::<Wallet>() as u64));
+        assert!(!sysvar.is_exempt(2_039_279, size_of::<Wallet>() as u64));
 
-        let pool_key = Keypair::new();
-        let pool_administrator_key = Keypair::new();
+        let wrong_key = Box::new(Account {
+            pubkey: Pubkey::new_unique(),
+            ..*account
+        });
+        assert_eq!(RentSysvar::any(wrong_key), Err(SysvarError::InvalidKey));
+    }
 
-        let mut salt: u64 = 0;
-        let pool_program_authority = loop {
-            let pool_program_authority = Pubkey::create_program_address(
-                &[
-                    pool_key.pubkey().as_ref(),
-                    pool_administrator_key.pubkey().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
-                &program_id,
-            );
+    #[test]
+    fn epoch_schedule_sysvar_test() {
+        // mainnet schedule: 432000 slots per epoch, with warmup epochs starting at 32 slots
+        let mut data = vec![];
+        data.extend_from_slice(&432_000u64.to_le_bytes());
+        data.extend_from_slice(&432_000u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&14u64.to_le_bytes());
+        data.extend_from_slice(&524_256u64.to_le_bytes());
+
+        let account = Box::new(Account {
+            data,
+            pubkey: *epoch_schedule::ID,
+            ..Default::default()
+        });
+        let schedule = EpochScheduleSysvar::any(account).unwrap();
+
+        assert_eq!(*schedule, epoch_schedule::EpochSchedule::default());
+
+        assert_eq!(schedule.get_epoch(0), 0);
+        assert_eq!(schedule.get_epoch(31), 0);
+        assert_eq!(schedule.get_epoch(32), 1);
+        assert_eq!(schedule.get_epoch(95), 1);
+        assert_eq!(schedule.get_epoch(96), 2);
+        assert_eq!(schedule.get_epoch(524_255), 13);
+        assert_eq!(schedule.get_epoch(524_256), 14);
+        assert_eq!(schedule.get_epoch(956_255), 14);
+        assert_eq!(schedule.get_epoch(956_256), 15);
+
+        assert_eq!(schedule.get_first_slot_in_epoch(1), 32);
+        assert_eq!(schedule.get_first_slot_in_epoch(2), 96);
+        assert_eq!(schedule.get_first_slot_in_epoch(14), 524_256);
+        assert_eq!(schedule.get_first_slot_in_epoch(15), 956_256);
+        assert_eq!(schedule.get_last_slot_in_epoch(14), 956_255);
+        assert_eq!(schedule.get_epoch_and_slot_index(956_300), (15, 44));
+    }
+
+    #[test]
+    fn slot_hashes_sysvar_test() {
+        let entries = vec![
+            (105u64, Hash::new_unique()),
+            (104, Hash::new_unique()),
+            (102, Hash::new_unique()),
+        ];
 
-            match pool_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
+        let mut data = vec![];
+        data.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (slot, hash) in &entries {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(hash.as_ref());
+        }
+
+        let account = Box::new(Account {
+            data,
+            pubkey: *slot_hashes::ID,
+            ..Default::default()
+        });
+        let slot_hashes = SlotHashesSysvar::any(account.clone()).unwrap();
+
+        assert_eq!(slot_hashes.entries().len(), 3);
+        assert_eq!(slot_hashes.most_recent().unwrap().slot(), 105);
+        assert_eq!(slot_hashes.get(104), Some(&entries[1].1));
+        assert_eq!(slot_hashes.get(102), Some(&entries[2].1));
+        assert_eq!(slot_hashes.get(103), None);
+        assert_eq!(slot_hashes.get(106), None);
+
+        let mut truncated = account.clone();
+        truncated.data.truncate(8 + 2 * 40);
+        assert_eq!(
+            SlotHashesSysvar::any(truncated),
+            Err(SysvarError::InvalidData)
+        );
+    }
+
+    /// Offchain accounts never sign, so signer checks need their own account type.
+    struct TestSigner {
+        account: Account,
+        is_signer: bool,
+    }
+
+    impl TestSigner {
+        fn new(key: Pubkey, is_signer: bool) -> Self {
+            Self {
+                account: Account {
+                    pubkey: key,
+                    ..Default::default()
+                },
+                is_signer,
             }
-        };
+        }
+    }
 
-        let (mut client, payer, hash) = program_test.start().await;
+    impl AccountFields for TestSigner {
+        fn key(&self) -> &Pubkey {
+            self.account.key()
+        }
 
-        let stake_mint_key = Keypair::new();
-        let stake_vault_key = Keypair::new();
-        let aux_wallet_key = Keypair::new();
+        fn owner(&self) -> &Pubkey {
+            self.account.owner()
+        }
 
-        let staker_key = Keypair::new();
-        let staker_ticket_key = Keypair::new();
+        fn is_signer(&self) -> bool {
+            self.is_signer
+        }
 
-        let mut instrs = vec![];
-        instrs.extend(create_mint(
-            &payer.pubkey(),
-            &stake_mint_key.pubkey(),
-            &pool_administrator_key.pubkey(),
-            6,
-        ));
-        instrs.extend(create_wallet(
-            &payer.pubkey(),
-            &stake_vault_key.pubkey(),
-            &stake_mint_key.pubkey(),
-            &pool_program_authority,
-        ));
-        instrs.extend(create_wallet(
-            &payer.pubkey(),
-            &aux_wallet_key.pubkey(),
-            &stake_mint_key.pubkey(),
-            &pool_administrator_key.pubkey(),
-        ));
-        instrs.push(mint_to(
-            &stake_mint_key.pubkey(),
-            &aux_wallet_key.pubkey(),
-            &pool_administrator_key.pubkey(),
-            1_000_000,
-        ));
-        instrs.push(create_account(
-            &payer.pubkey(),
-            &pool_key.pubkey(),
-            minimum_balance(StakePool::default_size() as u64),
-            StakePool::default_size() as u64,
-            &program_id,
-        ));
-        instrs.push(create_account(
-            &payer.pubkey(),
-            &staker_ticket_key.pubkey(),
-            minimum_balance(StakerTicket::default_size() as u64),
-            StakerTicket::default_size() as u64,
-            &program_id,
-        ));
+        fn is_writable(&self) -> bool {
+            self.account.is_writable()
+        }
 
-        instrs.push(Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
-                AccountMeta::new_readonly(pool_program_authority, false),
-                AccountMeta::new(pool_key.pubkey(), false),
-                AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
-                AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
-            ],
-            data: Method::Simple(simple_stake::Method::CreatePool(InitializeArgs {
-                program_authority_salt: salt,
-                lockup_duration: 1000.into(),
-                topup_duration: 200.into(),
-                reward_amount: 1000.into(),
-                target_amount: 10000.into(),
-            }))
-            .encode(),
-        });
+        fn is_executable(&self) -> bool {
+            self.account.is_executable()
+        }
 
-        instrs.push(Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new_readonly(*solar::spl::ID, false),
-                AccountMeta::new(pool_key.pubkey(), false),
-                AccountMeta::new_readonly(staker_key.pubkey(), false),
-                AccountMeta::new(staker_ticket_key.pubkey(), false),
-                AccountMeta::new(stake_vault_key.pubkey(), false),
-                AccountMeta::new_readonly(pool_administrator_key.pubkey(), true),
-                AccountMeta::new(aux_wallet_key.pubkey(), false),
-            ],
-            data: Method::Simple(simple_stake::Method::Stake {
-                amount: 10000.into(),
-            })
-            .encode(),
-        });
+        fn lamports(&self) -> u64 {
+            self.account.lamports()
+        }
 
-        let trx = Transaction::new_signed_with_payer(
-            &instrs,
-            Some(&payer.pubkey()),
-            &vec![
-                payer,
-                stake_mint_key,
-                stake_vault_key,
-                pool_key.clone(),
-                aux_wallet_key,
-                pool_administrator_key,
-                staker_ticket_key.clone(),
-            ],
-            hash,
-        );
+        fn rent_epoch(&self) -> u64 {
+            self.account.rent_epoch()
+        }
 
-        let result = client.process_transaction(trx).await;
-        println!("{:?}", result);
+        fn data(&self) -> &[u8] {
+            self.account.data()
+        }
+    }
 
-        let stake_pool = client
-            .get_account(&pool_key.pubkey())
-            .await
-            .unwrap()
-            .unwrap();
+    #[test]
+    fn multisig_authority_test() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+
+        // 2 of 3
+        let mut data = vec![2, 3, 1];
+        for index in 0..MAX_SIGNERS {
+            let key = keys.get(index).copied().unwrap_or_default();
+            data.extend_from_slice(key.as_ref());
+        }
+
+        let multisig = Box::new(Account {
+            data,
+            owner: *spl::ID,
+            pubkey: Pubkey::new_unique(),
+            ..Default::default()
+        });
+        let expected = multisig.pubkey;
 
-        let staker_ticket = client
-            .get_account(&staker_ticket_key.pubkey())
-            .await
-            .unwrap()
-            .unwrap();
+        let met = [
+            TestSigner::new(keys[0], true),
+            TestSigner::new(keys[2], true),
+        ];
+        assert!(MultisigAuthority::expected_signed(multisig.clone(), &expected, &met[..]).is_ok());
 
-        let stake_pool = StakePoolEntity::load(&program_id, Box::new(stake_pool)).unwrap();
-        let staker_ticket = stake_pool.load_ticket(Box::new(staker_ticket)).unwrap();
+        let unsigned = [
+            TestSigner::new(keys[0], true),
+            TestSigner::new(keys[1], false),
+        ];
+        let duplicated = [
+            TestSigner::new(keys[0], true),
+            TestSigner::new(keys[0], true),
+        ];
+        let outsider = [
+            TestSigner::new(keys[0], true),
+            TestSigner::new(Pubkey::new_unique(), true),
+        ];
+        for signers in [unsigned, duplicated, outsider].iter() {
+            assert_eq!(
+                MultisigAuthority::expected_signed(multisig.clone(), &expected, &signers[..]),
+                Err(AuthorityError::ThresholdNotMet)
+            );
+        }
 
-        assert!(stake_pool.stake_acquired_amount == 10000.into());
-        assert!(stake_pool.stake_target_amount == 10000.into());
-        assert!(staker_ticket.staked_amount == 10000.into());
-        Ok(())
+        assert_eq!(
+            MultisigAuthority::expected_signed(multisig, &Pubkey::new_unique(), &met[..]),
+            Err(AuthorityError::InvalidKey)
+        );
     }
 
-    #[tokio::test]
-    async fn stake_test() -> anyhow::Result<()> {
-        let mut program_test = ProgramTest::default();
+    #[test]
+    fn pda_authority_test() {
         let program_id = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
 
-        program_test.add_program(
-            "x5margin",
-            program_id,
-            Some(|a, b, c| {
-                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
-            }),
-        );
+        let (salt, address) = (0u64..)
+            .find_map(|salt| {
+                let salt = salt.to_le_bytes();
+                let address = Pubkey::create_program_address(&[pool.as_ref(), &salt], &program_id)?;
+                Some((salt, address))
+            })
+            .unwrap();
+        let seeds: [&[u8]; 2] = [pool.as_ref(), &salt];
 
-        let pool_key = Keypair::new();
-        let pool_administrator_key = Keypair::new();
+        let account = Box::new(Account {
+            pubkey: address,
+            ..Default::default()
+        });
+        let authority = Authority::expected_pda(account.clone(), &seeds, &program_id).unwrap();
+        assert_eq!(authority.account().pubkey, address);
 
-        let mut salt: u64 = 0;
-        let pool_program_authority = loop {
-            let pool_program_authority = Pubkey::create_program_address(
-                &[
-                    pool_key.pubkey().as_ref(),
-                    pool_administrator_key.pubkey().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
-                &program_id,
-            );
+        let other_program = Pubkey::new_unique();
+        assert_eq!(
+            Authority::expected_pda(account, &seeds, &other_program),
+            Err(AuthorityError::InvalidKey)
+        );
 
-            match pool_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
-            }
-        };
+        let impostor = Box::new(Account {
+            pubkey: Pubkey::new_unique(),
+            ..Default::default()
+        });
+        assert_eq!(
+            Authority::expected_pda(impostor, &seeds, &program_id),
+            Err(AuthorityError::InvalidKey)
+        );
+    }
 
-        let (mut client, payer, hash) = program_test.start().await;
+    #[test]
+    fn staked_amount_slice_test() {
+        let slice = StakerTicketState::staked_amount_slice();
+        assert_eq!(slice, AccountSlice::new(HEADER_RESERVED + 32, 8));
 
-        let stake_mint_key = Keypair::new();
-        let stake_vault_key = Keypair::new();
+        let size = StakerTicket::default_size();
+        assert!(slice.fits(size));
+        assert!(!slice.fits(HEADER_RESERVED + 39));
 
-        let amount: super::TokenAmount = solar::math::Checked::from(100);
+        let mut data = vec![0; size];
+        data[HEADER_RESERVED + 32..HEADER_RESERVED + 40].copy_from_slice(&77u64.to_le_bytes());
+        assert_eq!(slice.apply(&data), Some(&77u64.to_le_bytes()[..]));
+        assert_eq!(AccountSlice::new(usize::MAX, 1).apply(&data), None);
+    }
 
-        let instrs = vec![
-            create_account(
-                &payer.pubkey(),
-                &stake_mint_key.pubkey(),
-                minimum_balance(size_of::<Mint>() as u64),
-                size_of::<Mint>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &stake_vault_key.pubkey(),
-                minimum_balance(size_of::<Wallet>() as u64),
-                size_of::<Wallet>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &pool_key.pubkey(),
-                StakePool::default_lamports(),
-                StakePool::default_size() as u64,
-                &program_id,
-            ),
-            spl_token::instruction::initialize_mint(
-                &solar::spl::ID.compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_administrator_key.pubkey().compat(),
-                None,
-                6,
-            )
-            .unwrap()
-            .into(),
-            spl_token::instruction::initialize_account(
-                &solar::spl::ID.compat(),
-                &stake_vault_key.pubkey().compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_program_authority.compat(),
-            )
-            .unwrap()
-            .into(),
-            Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
-                    AccountMeta::new_readonly(pool_program_authority, false),
-                    AccountMeta::new(pool_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
-                ],
-                data: Method::Simple(simple_stake::Method::Stake { amount: amount }).encode(),
-            },
-        ];
+    #[test]
+    fn decode_instruction_test() {
+        let method = Method::Simple(simple_stake::Method::CreatePool(InitializeArgs {
+            program_authority_salt: 3,
+            lockup_duration: 1000.into(),
+            topup_duration: 200.into(),
+            reward_amount: 1000.into(),
+            target_amount: 10000.into(),
+            flags: 0,
+            reward_tranches: vec![],
+        }));
+        let data = method.encode();
+
+        assert_eq!(decode_instruction::<Method>(&data), Ok(method));
+
+        let truncated = &data[..data.len() - 1];
+        assert_eq!(
+            decode_instruction::<Method>(truncated),
+            Err(ProgramError::InvalidInstructionData)
+        );
 
-        let trx = Transaction::new_signed_with_payer(
-            &instrs,
-            Some(&payer.pubkey()),
-            &vec![payer, stake_mint_key, stake_vault_key, pool_key],
-            hash,
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(
+            decode_instruction::<Method>(&trailing),
+            Err(ProgramError::InvalidInstructionData)
         );
 
-        let result = client.process_transaction(trx).await;
-        println!("{:?}", result);
+        assert_eq!(
+            decode_instruction::<Method>(&[]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
 
-        Ok(())
+    fn test_entity<T: AccountType>(
+        owner: Pubkey,
+        root: Pubkey,
+        id: u64,
+        parent_id: u64,
+    ) -> Entity<Box<Account>, T> {
+        let account = Box::new(Account {
+            data: vec![0; T::default_size()],
+            owner,
+            pubkey: Pubkey::new_unique(),
+            ..Default::default()
+        });
+
+        let mut entity = Entity::<_, T>::raw_any(&owner, account).unwrap();
+        let header = entity.header_mut();
+        header.kind = T::KIND;
+        header.root = root;
+        header.id = EntityId::new(id);
+        header.parent_id = EntityId::new(parent_id);
+        entity
     }
 
-    #[tokio::test]
-    async fn unstake_test() -> anyhow::Result<()> {
-        let mut program_test = ProgramTest::default();
-        let program_id = Pubkey::new_unique();
+    /// Answers `getProgramAccounts` from a fixed set of accounts, the way an RPC node would.
+    mod fixture_client {
+        use async_trait::async_trait;
+        use solana_api_types::*;
+
+        pub struct FixtureClient {
+            pub accounts: Vec<Account>,
+        }
+
+        #[async_trait(?Send)]
+        impl Client for FixtureClient {
+            async fn get_account_info(
+                &self,
+                _: Pubkey,
+                _: Option<RpcAccountInfoConfig>,
+            ) -> Result<Account, ClientError> {
+                unimplemented!()
+            }
 
-        program_test.add_program(
-            "x5margin",
-            program_id,
-            Some(|a, b, c| {
-                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
-            }),
-        );
+            async fn get_program_accounts(
+                &self,
+                program: Pubkey,
+                cfg: Option<RpcProgramAccountsConfig>,
+            ) -> Result<Vec<Account>, ClientError> {
+                let filters = cfg.and_then(|cfg| cfg.filters).unwrap_or_default();
+                let matches = |account: &Account| {
+                    filters.iter().all(|filter| match filter {
+                        RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
+                        RpcFilterType::Memcmp(Memcmp {
+                            offset,
+                            bytes: MemcmpEncodedBytes::Binary(bytes),
+                            ..
+                        }) => {
+                            let bytes = bs58::decode(bytes).into_vec().unwrap();
+                            account.data.get(*offset..*offset + bytes.len()) == Some(&bytes[..])
+                        }
+                    })
+                };
+
+                Ok(self
+                    .accounts
+                    .iter()
+                    .filter(|account| account.owner == program && matches(account))
+                    .cloned()
+                    .collect())
+            }
 
-        let pool_key = Keypair::new();
-        let pool_administrator_key = Keypair::new();
+            async fn get_multiple_accounts(
+                &self,
+                _: &[Pubkey],
+                _: Option<RpcAccountInfoConfig>,
+            ) -> Result<Vec<Account>, ClientError> {
+                unimplemented!()
+            }
 
-        let mut salt: u64 = 0;
-        let pool_program_authority = loop {
-            let pool_program_authority = Pubkey::create_program_address(
-                &[
-                    pool_key.pubkey().as_ref(),
-                    pool_administrator_key.pubkey().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
-                &program_id,
-            );
+            async fn get_signature_statuses(
+                &self,
+                _: &[Signature],
+                _: Option<RpcSignatureStatusConfig>,
+            ) -> Result<Vec<Option<TransactionStatus>>, ClientError> {
+                unimplemented!()
+            }
 
-            match pool_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
+            async fn get_signatures_for_address(
+                &self,
+                _: &Pubkey,
+                _: Option<RpcSignaturesForAddressConfig>,
+            ) -> Result<Vec<SignatureInfo>, ClientError> {
+                unimplemented!()
             }
-        };
 
-        let (mut client, payer, hash) = program_test.start().await;
+            async fn get_slot(&self, _: Option<RpcSlotConfig>) -> Result<Slot, ClientError> {
+                unimplemented!()
+            }
 
-        let stake_mint_key = Keypair::new();
-        let stake_vault_key = Keypair::new();
+            async fn get_transaction(
+                &self,
+                _: Signature,
+                _: Option<RpcTransactionConfig>,
+            ) -> Result<Option<EncodedConfirmedTransaction>, ClientError> {
+                unimplemented!()
+            }
 
-        let amount: super::TokenAmount = solar::math::Checked::from(100);
+            async fn get_block(
+                &self,
+                _: Slot,
+                _: Option<RpcBlockConfig>,
+            ) -> Result<Option<ConfirmedBlock>, ClientError> {
+                unimplemented!()
+            }
 
-        let instrs = vec![
-            create_account(
-                &payer.pubkey(),
-                &stake_mint_key.pubkey(),
-                minimum_balance(size_of::<Mint>() as u64),
-                size_of::<Mint>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &stake_vault_key.pubkey(),
-                minimum_balance(size_of::<Wallet>() as u64),
-                size_of::<Wallet>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &pool_key.pubkey(),
-                StakePool::default_lamports(),
-                StakePool::default_size() as u64,
-                &program_id,
-            ),
-            spl_token::instruction::initialize_mint(
-                &solar::spl::ID.compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_administrator_key.pubkey().compat(),
-                None,
+            async fn request_airdrop(
+                &self,
+                _: &Pubkey,
+                _: u64,
+                _: Option<CommitmentConfig>,
+            ) -> Result<Signature, ClientError> {
+                unimplemented!()
+            }
+
+            async fn send_transaction(
+                &self,
+                _: &Transaction,
+                _: RpcSendTransactionConfig,
+            ) -> Result<Signature, ClientError> {
+                unimplemented!()
+            }
+
+            async fn simulate_transaction(
+                &self,
+                _: &Transaction,
+                _: RpcSimulateTransactionConfig,
+            ) -> Result<RpcSimulateTransactionResult, ClientError> {
+                unimplemented!()
+            }
+
+            async fn get_recent_blockhash(
+                &self,
+                _: Option<CommitmentConfig>,
+            ) -> Result<RpcRecentBlockhash, ClientError> {
+                unimplemented!()
+            }
+
+            async fn get_health(&self) -> Result<(), ClientError> {
+                unimplemented!()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn entity_query_test() {
+        let program_id = Pubkey::new_unique();
+
+        let mut pools = vec![];
+        for _ in 0..2 {
+            let mut pool = test_entity::<StakePool>(program_id, Pubkey::default(), 0, 0);
+            pool.header_mut().root = *pool.account().key();
+            pools.push(pool);
+        }
+        let pool_key = *pools[0].account().key();
+        let other_pool_key = *pools[1].account().key();
+
+        let tickets = vec![
+            test_entity::<StakerTicket>(program_id, pool_key, 1, 0),
+            test_entity::<StakerTicket>(program_id, pool_key, 2, 0),
+            test_entity::<StakerTicket>(program_id, other_pool_key, 1, 0),
+            // claims to belong to the pool, but not as a child of it
+            test_entity::<StakerTicket>(program_id, pool_key, 3, 7),
+        ];
+        let foreign_pool = test_entity::<StakePool>(Pubkey::new_unique(), pool_key, 0, 0);
+
+        let mut accounts: Vec<Account> = pools
+            .iter()
+            .map(|pool| (**pool.account()).clone())
+            .collect();
+        accounts.extend(tickets.iter().map(|ticket| (**ticket.account()).clone()));
+        accounts.push((**foreign_pool.account()).clone());
+        let client = fixture_client::FixtureClient { accounts };
+
+        let found = StakePoolEntity::all(&client, &program_id).await.unwrap();
+        let keys: Vec<Pubkey> = found.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![pool_key, other_pool_key]);
+
+        let pool = match found.into_iter().next() {
+            Some((_, Ok(pool))) => pool,
+            _ => panic!("pool failed to load"),
+        };
+
+        let found = StakerTicketEntity::all_for_pool(&client, &pool)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].0, *tickets[0].account().key());
+        assert!(found[0].1.is_ok());
+        assert_eq!(found[1].0, *tickets[1].account().key());
+        assert!(found[1].1.is_ok());
+        assert_eq!(found[2].0, *tickets[3].account().key());
+        assert!(matches!(found[2].1, Err(Error::InvalidParent)));
+    }
+
+    #[test]
+    fn verify_child_of_test() {
+        let program_id = Pubkey::new_unique();
+        let root = Pubkey::new_unique();
+
+        let pool = test_entity::<StakePool>(program_id, root, 0, 0);
+        assert!(pool.header().kind == EntityKind::SimpleStakePool);
+
+        let child = test_entity::<StakerTicket>(program_id, root, 1, 0);
+        assert_eq!(child.verify_child_of(&pool), Ok(()));
+
+        let wrong_root = test_entity::<StakerTicket>(program_id, Pubkey::new_unique(), 1, 0);
+        assert_eq!(wrong_root.verify_child_of(&pool), Err(Error::InvalidParent));
+
+        let wrong_parent = test_entity::<StakerTicket>(program_id, root, 1, 7);
+        assert_eq!(
+            wrong_parent.verify_child_of(&pool),
+            Err(Error::InvalidParent)
+        );
+
+        let other_program = Pubkey::new_unique();
+        let wrong_owner = test_entity::<StakerTicket>(other_program, root, 1, 0);
+        assert_eq!(
+            wrong_owner.verify_child_of(&pool),
+            Err(Error::InvalidParent)
+        );
+    }
+
+    #[test]
+    fn entity_allocator_reuse_test() {
+        let mut allocator = EntityAllocator::default();
+
+        let ids: Vec<u64> = (0..4).map(|_| allocator.allocate_id().value()).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+
+        assert!(allocator.release_id(EntityId::new(1)));
+        assert!(allocator.release_id(EntityId::new(2)));
+        // double release is ignored, ids never handed out are refused
+        assert!(allocator.release_id(EntityId::new(2)));
+        assert!(!allocator.release_id(EntityId::new(4)));
+        assert!(!allocator.release_id(EntityId::new(100)));
+
+        assert_eq!(allocator.allocate_id().value(), 2);
+        assert_eq!(allocator.allocate_id().value(), 1);
+        assert_eq!(allocator.allocate_id().value(), 4);
+
+        let ids: Vec<EntityId> = (0..FREE_ID_CAPACITY + 1)
+            .map(|_| allocator.allocate_id())
+            .collect();
+        for id in &ids[..FREE_ID_CAPACITY] {
+            assert!(allocator.release_id(*id));
+        }
+        assert!(!allocator.release_id(ids[FREE_ID_CAPACITY]));
+
+        let reused: Vec<EntityId> = (0..FREE_ID_CAPACITY)
+            .map(|_| allocator.allocate_id())
+            .collect();
+        for id in &reused {
+            assert!(ids[..FREE_ID_CAPACITY].contains(id));
+        }
+        assert_eq!(
+            allocator.allocate_id().value(),
+            ids[FREE_ID_CAPACITY].value() + 1
+        );
+    }
+
+    #[test]
+    fn error_code_roundtrip_test() {
+        let mut errors = vec![
+            Error::InvalidData,
+            Error::InvalidAlignment,
+            Error::InvalidOwner,
+            Error::InvalidParent,
+            Error::InvalidKind,
+            Error::InvalidAuthority,
+            Error::InvalidMint,
+            Error::InvalidAccount,
+            Error::NotRentExempt,
+            Error::Validation,
+            Error::AccountAlreadyInitialized,
+            Error::InvalidAmountTransferred,
+            Error::NotEnoughAccountKeys,
+            Error::SplReadError(SplReadError::InvalidData),
+            Error::SplReadError(SplReadError::InvalidOwner),
+            Error::SplReadError(SplReadError::InvalidMint),
+            Error::AccountCheck(AccountCheckError::InvalidOwner),
+            Error::AccountCheck(AccountCheckError::InvalidKey),
+            Error::AccountCheck(AccountCheckError::MissingSignature),
+            Error::AccountCheck(AccountCheckError::NotWritable),
+            Error::ReentrancyNotAllowed,
+        ];
+        errors.extend((0..=19).map(|code| Error::TokenError(TokenError::from(code))));
+
+        let mut codes = vec![];
+        for error in errors {
+            let code = error.code();
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+            assert_eq!(Error::from_code(code), Some(error));
+            codes.push(code);
+        }
+
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), 41);
+
+        assert_eq!(Error::from_code(0), None);
+        assert_eq!(Error::from_code(18), None);
+        assert_eq!(Error::from_code(16 | (4 << 8)), None);
+        assert_eq!(Error::from_code(10 | (1 << 8)), None);
+        assert_eq!(Error::from_code(11 | (3 << 8)), None);
+        assert_eq!(Error::from_code(12 | (20 << 8)), None);
+    }
+
+    #[test]
+    fn verify_transfer_test() {
+        assert_eq!(verify_deposit(100.into(), 200.into(), 100.into()), Ok(()));
+        assert_eq!(
+            verify_withdrawal(200.into(), 100.into(), 100.into()),
+            Ok(())
+        );
+
+        // a fee-on-transfer mint delivers less than was sent
+        assert_eq!(
+            verify_deposit(100.into(), 199.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+        assert_eq!(
+            verify_withdrawal(200.into(), 101.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+
+        // balances moving the wrong way are reported instead of overflowing
+        assert_eq!(
+            verify_deposit(200.into(), 100.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+        assert_eq!(
+            verify_withdrawal(100.into(), 200.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+    }
+
+    #[test]
+    fn balance_guard_test() {
+        let wallet = |amount: u64| {
+            let mut data = vec![0; size_of::<Wallet>()];
+            data[64..72].copy_from_slice(&amount.to_le_bytes());
+            data[108] = 1;
+
+            WalletAccount::any(Box::new(Account {
+                data,
+                owner: *spl::ID,
+                ..Default::default()
+            }))
+            .unwrap()
+        };
+
+        let vault = BalanceGuard::new(&wallet(1000));
+        assert_eq!(vault.before(), 1000.into());
+
+        assert_eq!(vault.assert_delta(&wallet(1100), 100), Ok(()));
+        assert_eq!(vault.assert_delta(&wallet(900), -100), Ok(()));
+        assert_eq!(vault.assert_delta(&wallet(1000), 0), Ok(()));
+
+        // a fee withheld by the mint or a transfer going the wrong way is reported
+        assert_eq!(
+            vault.assert_delta(&wallet(1099), 100),
+            Err(Error::InvalidAmountTransferred)
+        );
+        assert_eq!(
+            vault.assert_delta(&wallet(900), 100),
+            Err(Error::InvalidAmountTransferred)
+        );
+        assert_eq!(
+            vault.assert_delta(&wallet(u64::MAX), u64::MAX as i128 - 1000),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn reentrancy_guard_test() {
+        let program_id = Pubkey::new_unique();
+        let mut pool = test_entity::<StakePool>(program_id, Pubkey::default(), 0, 0);
+        assert!(!pool.is_in_flight());
+
+        pool.enter().unwrap();
+        assert!(pool.is_in_flight());
+
+        // a token program calling back into the stake program halfway through the transfer,
+        // handing it the pool as it was passed down the CPI
+        let mut reentered = StakePoolEntity::load(&program_id, pool.account().clone()).unwrap();
+        assert_eq!(reentered.enter(), Err(Error::ReentrancyNotAllowed));
+
+        pool.exit();
+        assert!(!pool.is_in_flight());
+
+        // the next instruction isn't affected
+        let mut next = StakePoolEntity::load(&program_id, pool.account().clone()).unwrap();
+        assert_eq!(next.enter(), Ok(()));
+    }
+
+    #[test]
+    fn derive_pool_authority_test() {
+        let program_id = Pubkey::new_unique();
+        let administrator = Pubkey::new_unique();
+
+        for _ in 0..16 {
+            let pool = Pubkey::new_unique();
+            let (authority, salt) = derive_pool_authority(&program_id, &pool, &administrator);
+
+            assert_eq!(
+                Pubkey::create_program_address(
+                    &[pool.as_ref(), administrator.as_ref(), &salt.to_le_bytes()],
+                    &program_id,
+                ),
+                Some(authority)
+            );
+            assert!(!authority.is_on_curve());
+            assert!((0..salt).all(|salt| {
+                pool_authority(&program_id, &pool, &administrator, salt).is_none()
+            }));
+        }
+    }
+
+    #[test]
+    fn method_roundtrip_test() {
+        let methods = vec![
+            Method::Simple(simple_stake::Method::CreatePool(InitializeArgs {
+                program_authority_salt: 3,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            })),
+            Method::Simple(simple_stake::Method::Stake { amount: 100.into() }),
+            Method::Simple(simple_stake::Method::Unstake { amount: 50.into() }),
+            Method::Simple(simple_stake::Method::ClaimReward),
+            Method::Simple(simple_stake::Method::AddReward { amount: 10.into() }),
+            Method::Simple(simple_stake::Method::UnstakePercent { bps: 5000 }),
+            Method::Simple(simple_stake::Method::SetAdministrator),
+            Method::Simple(simple_stake::Method::StakeBatch {
+                amounts: vec![100.into(), 200.into()],
+            }),
+        ];
+
+        for method in methods {
+            let data = method.encode();
+            assert_eq!(Method::decode(&data).unwrap(), method);
+
+            let mut trailing = data.clone();
+            trailing.push(0);
+            assert!(Method::decode(&trailing).is_err());
+        }
+
+        assert_eq!(
+            Method::Simple(simple_stake::Method::Stake { amount: 100.into() }).to_string(),
+            "Simple::Stake(amount: 100)"
+        );
+    }
+
+    fn render<T: Loggable>(item: &T) -> String {
+        let mut logger = Logger::<64>::default();
+        item.push_to_logger(&mut logger);
+        logger.as_str().to_string()
+    }
+
+    #[test]
+    fn loggable_test() {
+        assert_eq!(
+            render(&Method::Simple(simple_stake::Method::Stake {
+                amount: 100.into()
+            })),
+            "Simple::Stake(100)"
+        );
+        assert_eq!(
+            render(&Method::Simple(simple_stake::Method::ClaimReward)),
+            "Simple::ClaimReward"
+        );
+        assert_eq!(render(&Error::Validation), "Validation");
+        assert_eq!(
+            render(&Error::SplReadError(SplReadError::InvalidOwner)),
+            "SplReadError(InvalidOwner)"
+        );
+        assert_eq!(
+            render(&Error::TokenError(TokenError::InsufficientFunds)),
+            "TokenError(InsufficientFunds)"
+        );
+    }
+
+    #[test]
+    fn instruction_builders_test() {
+        let program_id = Pubkey::new_unique();
+        let withdraw = WithdrawAccounts {
+            pool: Pubkey::new_unique(),
+            ticket: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            program_authority: Pubkey::new_unique(),
+            stake_vault: Pubkey::new_unique(),
+            target_wallet: Pubkey::new_unique(),
+        };
+
+        let unstake = instructions::unstake(program_id, &withdraw, 50.into());
+        let claim = instructions::claim_reward(program_id, &withdraw);
+        let unstake_percent = instructions::unstake_percent(program_id, &withdraw, 2500);
+        for instruction in [&unstake, &claim, &unstake_percent] {
+            assert_eq!(instruction.check_accounts(), Ok(()));
+        }
+        assert_eq!(unstake.accounts, claim.accounts);
+        assert_eq!(unstake_percent.accounts, claim.accounts);
+        assert_eq!(
+            Method::decode(&unstake_percent.data).unwrap(),
+            Method::Simple(simple_stake::Method::UnstakePercent { bps: 2500 })
+        );
+        assert_eq!(
+            unstake.accounts,
+            vec![
+                AccountMeta::new_readonly(*spl::ID, false),
+                AccountMeta::new(withdraw.pool, false),
+                AccountMeta::new(withdraw.ticket, false),
+                AccountMeta::new(withdraw.staker, true),
+                AccountMeta::new_readonly(withdraw.program_authority, false),
+                AccountMeta::new(withdraw.stake_vault, false),
+                AccountMeta::new(withdraw.target_wallet, false),
+            ]
+        );
+        assert_eq!(
+            Method::decode(&unstake.data).unwrap(),
+            Method::Simple(simple_stake::Method::Unstake { amount: 50.into() })
+        );
+        assert_eq!(
+            Method::decode(&claim.data).unwrap(),
+            Method::Simple(simple_stake::Method::ClaimReward)
+        );
+
+        let add_reward = AddRewardAccounts {
+            pool: Pubkey::new_unique(),
+            stake_vault: Pubkey::new_unique(),
+            source_authority: Pubkey::new_unique(),
+            source_wallet: Pubkey::new_unique(),
+        };
+        let instruction = instructions::add_reward(program_id, &add_reward, 10.into());
+        assert_eq!(instruction.check_accounts(), Ok(()));
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new_readonly(*spl::ID, false),
+                AccountMeta::new(add_reward.pool, false),
+                AccountMeta::new(add_reward.stake_vault, false),
+                AccountMeta::new_readonly(add_reward.source_authority, true),
+                AccountMeta::new(add_reward.source_wallet, false),
+            ]
+        );
+        assert_eq!(
+            Method::decode(&instruction.data).unwrap(),
+            Method::Simple(simple_stake::Method::AddReward { amount: 10.into() })
+        );
+
+        let pool = Pubkey::new_unique();
+        let stake_vault = Pubkey::new_unique();
+        let source_authority = Pubkey::new_unique();
+        let stakes: Vec<BatchStake> = [100u64, 200]
+            .iter()
+            .map(|amount| BatchStake {
+                staker: Pubkey::new_unique(),
+                ticket: Pubkey::new_unique(),
+                source_authority,
+                source_wallet: Pubkey::new_unique(),
+                amount: (*amount).into(),
+            })
+            .collect();
+        let instruction = instructions::stake_batch(program_id, pool, stake_vault, &stakes);
+        assert_eq!(instruction.check_accounts(), Ok(()));
+        assert_eq!(
+            instruction.accounts[..3],
+            [
+                AccountMeta::new_readonly(*spl::ID, false),
+                AccountMeta::new(pool, false),
+                AccountMeta::new(stake_vault, false),
+            ]
+        );
+        assert_eq!(
+            instruction.accounts[3 + STAKE_BATCH_ENTRY_ACCOUNTS..],
+            [
+                AccountMeta::new_readonly(stakes[1].staker, false),
+                AccountMeta::new(stakes[1].ticket, false),
+                AccountMeta::new_readonly(source_authority, true),
+                AccountMeta::new(stakes[1].source_wallet, false),
+            ]
+        );
+        assert_eq!(
+            Method::decode(&instruction.data).unwrap(),
+            Method::Simple(simple_stake::Method::StakeBatch {
+                amounts: vec![100.into(), 200.into()],
+            })
+        );
+
+        let set_administrator = SetAdministratorAccounts {
+            pool: Pubkey::new_unique(),
+            administrator: Pubkey::new_unique(),
+            new_administrator: Pubkey::new_unique(),
+        };
+        let instruction = instructions::set_administrator(program_id, &set_administrator);
+        assert_eq!(instruction.check_accounts(), Ok(()));
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::new(set_administrator.pool, false),
+                AccountMeta::new_readonly(set_administrator.administrator, true),
+                AccountMeta::new_readonly(set_administrator.new_administrator, false),
+            ]
+        );
+        assert_eq!(
+            Method::decode(&instruction.data).unwrap(),
+            Method::Simple(simple_stake::Method::SetAdministrator)
+        );
+    }
+
+    /// Runs `CreatePool` with each of `args` against a freshly created mint and vault, one
+    /// transaction per call, and returns the result of the last call along with the resulting
+    /// pool account.
+    ///
+    /// The accounts are created up front in a transaction of their own, so the pool account
+    /// exists even if it's never initialized. All but the last `CreatePool` must succeed. The
+    /// pool account is funded with `pool_lamports`.
+    async fn create_pool_with(
+        pool_lamports: u64,
+        args: Vec<InitializeArgs>,
+    ) -> anyhow::Result<(Pubkey, anyhow::Result<()>, Account)> {
+        let mut program_test = ProgramTest::default();
+        let program_id = Pubkey::new_unique();
+
+        program_test.add_program(
+            "x5margin",
+            program_id,
+            Some(|a, b, c| {
+                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+            }),
+        );
+
+        let pool_key = Keypair::new();
+        let pool_administrator_key = Keypair::new();
+
+        let (pool_program_authority, salt) = derive_pool_authority(
+            &program_id,
+            &pool_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+        );
+
+        let (mut client, payer, hash) = program_test.start().await;
+
+        let stake_mint_key = Keypair::new();
+        let stake_vault_key = Keypair::new();
+
+        let mut instrs = vec![];
+        instrs.extend(create_mint(
+            &payer.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+            None,
+            6,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &stake_vault_key.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_program_authority,
+        ));
+        instrs.push(create_account(
+            &payer.pubkey(),
+            &pool_key.pubkey(),
+            pool_lamports,
+            StakePool::default_size() as u64,
+            &program_id,
+        ));
+        let create_pool = |args: InitializeArgs| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
+                AccountMeta::new_readonly(pool_program_authority, false),
+                AccountMeta::new(pool_key.pubkey(), false),
+                AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
+                AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
+            ],
+            data: Method::Simple(simple_stake::Method::CreatePool(InitializeArgs {
+                program_authority_salt: salt,
+                ..args
+            }))
+            .encode(),
+        };
+
+        let trx = Transaction::new_signed_with_payer(
+            &instrs,
+            Some(&payer.pubkey()),
+            &vec![
+                &payer as &dyn Signer,
+                &stake_mint_key,
+                &stake_vault_key,
+                &pool_key,
+            ],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let mut result = Ok(());
+        for args in args {
+            result?;
+
+            let trx = Transaction::new_signed_with_payer(
+                &[create_pool(args)],
+                Some(&payer.pubkey()),
+                &vec![&payer as &dyn Signer],
+                hash,
+            );
+            result = client.process_transaction(trx).await;
+        }
+
+        let pool = client.get_account(&pool_key.pubkey()).await?.unwrap();
+
+        Ok((program_id, result, pool))
+    }
+
+    #[tokio::test]
+    async fn create_rejects_negative_duration_test() -> anyhow::Result<()> {
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: (-1000).into(),
+                topup_duration: (-2000).into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+
+        assert_custom_error(result, Error::InvalidData.code());
+        assert!(StakePoolEntity::load(&program_id, Box::new(pool)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_rejects_zero_target_test() -> anyhow::Result<()> {
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 0.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+
+        assert_custom_error(result, Error::InvalidData.code());
+        assert!(StakePoolEntity::load(&program_id, Box::new(pool)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_rejects_reinitialization_test() -> anyhow::Result<()> {
+        let args = InitializeArgs {
+            program_authority_salt: 0,
+            lockup_duration: 1000.into(),
+            topup_duration: 200.into(),
+            reward_amount: 1000.into(),
+            target_amount: 10000.into(),
+            flags: 0,
+            reward_tranches: vec![],
+        };
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![
+                args.clone(),
+                InitializeArgs {
+                    target_amount: 20000.into(),
+                    ..args
+                },
+            ],
+        )
+        .await?;
+        assert_custom_error(result, Error::AccountAlreadyInitialized.code());
+
+        let pool = StakePoolEntity::load(&program_id, Box::new(pool)).unwrap();
+        assert!(pool.stake_target_amount == 10000.into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_rejects_underfunded_pool_test() -> anyhow::Result<()> {
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports() - 1,
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+
+        assert_custom_error(result, Error::NotRentExempt.code());
+        assert!(StakePoolEntity::load(&program_id, Box::new(pool)).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fee_on_transfer_accounting_test() -> anyhow::Result<()> {
+        let args = InitializeArgs {
+            program_authority_salt: 0,
+            lockup_duration: 1000.into(),
+            topup_duration: 200.into(),
+            reward_amount: 1000.into(),
+            target_amount: 10000.into(),
+            flags: POOL_FLAG_FEE_ON_TRANSFER,
+            reward_tranches: vec![],
+        };
+
+        let (program_id, result, pool) =
+            create_pool_with(StakePool::default_lamports(), vec![args.clone()]).await?;
+        result?;
+        let pool = StakePoolEntity::load(&program_id, Box::new(pool)).unwrap();
+        assert!(pool.fee_on_transfer());
+
+        // 1% of a 100 token stake is withheld by the mint
+        assert_eq!(
+            pool.deposited(1000.into(), 1099.into(), 100.into()),
+            Ok(99.into())
+        );
+        assert_eq!(
+            pool.withdrawn(1099.into(), 1000.into(), 99.into()),
+            Ok(99.into())
+        );
+        assert_eq!(
+            pool.deposited(1000.into(), 1101.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+
+        let (program_id, result, strict) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                flags: 0,
+                ..args.clone()
+            }],
+        )
+        .await?;
+        result?;
+        let strict = StakePoolEntity::load(&program_id, Box::new(strict)).unwrap();
+        assert!(!strict.fee_on_transfer());
+        assert_eq!(
+            strict.deposited(1000.into(), 1099.into(), 100.into()),
+            Err(Error::InvalidAmountTransferred)
+        );
+        assert_eq!(
+            strict.deposited(1000.into(), 1100.into(), 100.into()),
+            Ok(100.into())
+        );
+
+        let (program_id, result, unknown) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs { flags: 4, ..args }],
+        )
+        .await?;
+        assert_custom_error(result, Error::InvalidData.code());
+        assert!(StakePoolEntity::load(&program_id, Box::new(unknown)).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reward_rate_test() -> anyhow::Result<()> {
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: (30 * 24 * 60 * 60).into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+        result?;
+
+        let mut pool = StakePoolEntity::load(&program_id, Box::new(pool)).unwrap();
+
+        // 10% per 30 days
+        let apr = pool.apr().unwrap().to_num::<f64>();
+        assert!((apr - 0.1 * 365.0 / 30.0).abs() < 1e-9);
+        let apy = pool.apy().unwrap().to_num::<f64>();
+        assert!((apy - (1.1f64.powf(365.0 / 30.0) - 1.0)).abs() < 1e-9);
+        assert!(apy > apr);
+
+        pool.lockup_duration = 0.into();
+        assert_eq!(pool.apr(), None);
+        assert_eq!(pool.apy(), None);
+
+        pool.lockup_duration = (30 * 24 * 60 * 60).into();
+        pool.stake_target_amount = 0.into();
+        assert_eq!(pool.apr(), None);
+        assert_eq!(pool.apy(), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn activation_state_test() -> anyhow::Result<()> {
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+        result?;
+        let pool = StakePoolEntity::load(&program_id, Box::new(pool)).unwrap();
+        let at = |offset: i64| pool.activation_state((pool.genesis().value() + offset).into());
+
+        assert_eq!(at(0), ActivationState::Topup);
+        assert_eq!(at(199), ActivationState::Topup);
+        assert_eq!(at(200), ActivationState::Locked);
+        assert_eq!(at(1000), ActivationState::Locked);
+        assert_eq!(at(1001), ActivationState::Expired);
+
+        // the state agrees with the checks the instructions use
+        for offset in &[0, 199, 200, 1000, 1001] {
+            let now = (pool.genesis().value() + offset).into();
+            let state = pool.activation_state(now);
+            assert_eq!(pool.can_topup(now), state == ActivationState::Topup);
+            assert_eq!(pool.is_expired(now), state == ActivationState::Expired);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_amount_test() -> anyhow::Result<()> {
+        use az::CheckedAs;
+        use solar::math::ToF64;
+
+        let (program_id, result, pool) = create_pool_with(
+            StakePool::default_lamports(),
+            vec![InitializeArgs {
+                program_authority_salt: 0,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            }],
+        )
+        .await?;
+        result?;
+        let mut pool = StakePoolEntity::load(&program_id, Box::new(pool)).unwrap();
+
+        for &(staked, acquired, reward) in &[
+            (10000, 10000, 1000),
+            (1, 3, 1000),
+            (2, 3, 1000),
+            (333, 1000, 7),
+            (1, 7, 1),
+            (u64::MAX / 2, u64::MAX / 2 + 1, 12345),
+        ] {
+            pool.stake_acquired_amount = acquired.into();
+            pool.reward_amount = reward.into();
+
+            // what `claim_reward` computed before the math was shared with offchain previews
+            let staked_amount = super::TokenAmount::from(staked).to_u64f64();
+            let share = staked_amount / pool.stake_acquired_amount.to_u64f64();
+            let expected = (staked_amount + share * pool.reward_amount.to_u64f64())
+                .checked_as::<super::TokenAmount>();
+
+            assert_eq!(pool.claim_amount(staked.into()), expected);
+        }
+
+        pool.stake_acquired_amount = 0.into();
+        assert_eq!(pool.claim_amount(0.into()), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_mint_decimals_test() -> anyhow::Result<()> {
+        let (mut client, payer, hash) = ProgramTest::default().start().await;
+        let mint_key = Keypair::new();
+        let authority = Pubkey::new_unique();
+
+        let trx = Transaction::new_signed_with_payer(
+            &create_mint(&payer.pubkey(), &mint_key.pubkey(), &authority, None, 9),
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &mint_key],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let mint = client.get_account(&mint_key.pubkey()).await?.unwrap();
+        let mint = MintAccount::any(Box::new(mint)).unwrap();
+        assert!(mint.is_initialized());
+        assert_eq!(mint.decimals(), 9);
+        assert_eq!(mint.mint_authority(), Some(&authority));
+        assert_eq!(mint.freeze_authority(), None);
+        assert_eq!(mint.supply().value(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_mint_freeze_authority_test() -> anyhow::Result<()> {
+        let (mut client, payer, hash) = ProgramTest::default().start().await;
+        let mint_key = Keypair::new();
+        let authority = Pubkey::new_unique();
+        let freeze_authority = Pubkey::new_unique();
+
+        let trx = Transaction::new_signed_with_payer(
+            &create_mint(
+                &payer.pubkey(),
+                &mint_key.pubkey(),
+                &authority,
+                Some(&freeze_authority),
                 6,
-            )
+            ),
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &mint_key],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let mint = client.get_account(&mint_key.pubkey()).await?.unwrap();
+        let mint = MintAccount::any(Box::new(mint)).unwrap();
+        assert_eq!(mint.mint_authority(), Some(&authority));
+        assert_eq!(mint.freeze_authority(), Some(&freeze_authority));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mint_to_cap_test() -> anyhow::Result<()> {
+        let (mut client, payer, hash) = ProgramTest::default().start().await;
+        let mint_key = Keypair::new();
+        let wallet_key = Keypair::new();
+
+        let mut instrs = vec![];
+        instrs.extend(create_mint(
+            &payer.pubkey(),
+            &mint_key.pubkey(),
+            &payer.pubkey(),
+            None,
+            6,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &wallet_key.pubkey(),
+            &mint_key.pubkey(),
+            &payer.pubkey(),
+        ));
+        instrs.push(mint_to(
+            &mint_key.pubkey(),
+            &wallet_key.pubkey(),
+            &payer.pubkey(),
+            400,
+        ));
+        let trx = Transaction::new_signed_with_payer(
+            &instrs,
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &mint_key, &wallet_key],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        // a script minting 1000 towards a cap of 1000, the second run finds the cap reached
+        let mut minted = vec![];
+        for _ in 0..2 {
+            let mint = client.get_account(&mint_key.pubkey()).await?.unwrap();
+            let mint = MintAccount::any(Box::new(mint)).unwrap();
+            let instruction = mint_to_cap(
+                &mint_key.pubkey(),
+                &mint,
+                &wallet_key.pubkey(),
+                &payer.pubkey(),
+                1000,
+                1000,
+            );
+            minted.push(instruction.is_some());
+
+            if let Some(instruction) = instruction {
+                let trx = Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&payer.pubkey()),
+                    &vec![&payer as &dyn Signer],
+                    hash,
+                );
+                client.process_transaction(trx).await?;
+            }
+        }
+        assert_eq!(minted, vec![true, false]);
+
+        let mint = client.get_account(&mint_key.pubkey()).await?.unwrap();
+        assert_eq!(
+            MintAccount::any(Box::new(mint)).unwrap().supply().value(),
+            1000
+        );
+        let wallet = client.get_account(&wallet_key.pubkey()).await?.unwrap();
+        assert_eq!(
+            WalletAccount::any(Box::new(wallet)).unwrap().amount(),
+            1000.into()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_test() -> anyhow::Result<()> {
+        let mut program_test = ProgramTest::default();
+        let program_id = Pubkey::new_unique();
+
+        program_test.add_program(
+            "x5margin",
+            program_id,
+            Some(|a, b, c| {
+                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+            }),
+        );
+
+        let pool_key = Keypair::new();
+        let pool_administrator_key = Keypair::new();
+
+        let (pool_program_authority, salt) = derive_pool_authority(
+            &program_id,
+            &pool_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+        );
+
+        let (mut client, payer, hash) = program_test.start().await;
+
+        let stake_mint_key = Keypair::new();
+        let stake_vault_key = Keypair::new();
+        let aux_wallet_key = Keypair::new();
+
+        let staker_key = Keypair::new();
+        let staker_ticket_key = Keypair::new();
+
+        let mut instrs = vec![];
+        instrs.extend(create_mint(
+            &payer.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+            None,
+            6,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &stake_vault_key.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_program_authority,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &aux_wallet_key.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+        ));
+        instrs.push(mint_to(
+            &stake_mint_key.pubkey(),
+            &aux_wallet_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+            1_000_000,
+        ));
+        instrs.push(create_account(
+            &payer.pubkey(),
+            &pool_key.pubkey(),
+            minimum_balance(StakePool::default_size() as u64),
+            StakePool::default_size() as u64,
+            &program_id,
+        ));
+        instrs.push(create_account(
+            &payer.pubkey(),
+            &staker_ticket_key.pubkey(),
+            minimum_balance(StakerTicket::default_size() as u64),
+            StakerTicket::default_size() as u64,
+            &program_id,
+        ));
+
+        instrs.push(instructions::create_pool(
+            program_id,
+            &CreatePoolAccounts {
+                administrator: pool_administrator_key.pubkey(),
+                program_authority: pool_program_authority,
+                pool: pool_key.pubkey(),
+                stake_mint: stake_mint_key.pubkey(),
+                stake_vault: stake_vault_key.pubkey(),
+            },
+            InitializeArgs {
+                program_authority_salt: salt,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: 0,
+                reward_tranches: vec![],
+            },
+        ));
+
+        instrs.push(instructions::stake(
+            program_id,
+            &StakeAccounts {
+                pool: pool_key.pubkey(),
+                staker: staker_key.pubkey(),
+                ticket: staker_ticket_key.pubkey(),
+                stake_vault: stake_vault_key.pubkey(),
+                source_authority: pool_administrator_key.pubkey(),
+                source_wallet: aux_wallet_key.pubkey(),
+            },
+            10000.into(),
+        ));
+
+        let trx = Transaction::new_signed_with_payer(
+            &instrs,
+            Some(&payer.pubkey()),
+            &vec![
+                payer,
+                stake_mint_key.clone(),
+                stake_vault_key.clone(),
+                pool_key.clone(),
+                aux_wallet_key,
+                pool_administrator_key,
+                staker_ticket_key.clone(),
+            ],
+            hash,
+        );
+
+        client.process_transaction(trx).await?;
+
+        let stake_pool = client
+            .get_account(&pool_key.pubkey())
+            .await
             .unwrap()
-            .into(),
-            spl_token::instruction::initialize_account(
-                &solar::spl::ID.compat(),
-                &stake_vault_key.pubkey().compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_program_authority.compat(),
-            )
+            .unwrap();
+
+        let staker_ticket = client
+            .get_account(&staker_ticket_key.pubkey())
+            .await
             .unwrap()
-            .into(),
-            Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
-                    AccountMeta::new_readonly(pool_program_authority, false),
-                    AccountMeta::new(pool_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
-                ],
-                data: Method::Simple(simple_stake::Method::Unstake { amount: amount }).encode(),
+            .unwrap();
+
+        let stake_pool = StakePoolEntity::load(&program_id, Box::new(stake_pool)).unwrap();
+        let staker_ticket = stake_pool.load_ticket(Box::new(staker_ticket)).unwrap();
+
+        assert_eq!(stake_pool.program_authority, pool_program_authority);
+        assert!(stake_pool.stake_acquired_amount == 10000.into());
+        assert!(stake_pool.stake_target_amount == 10000.into());
+        assert!(staker_ticket.staked_amount == 10000.into());
+
+        let expiry = stake_pool.genesis() + stake_pool.lockup_duration();
+        assert_eq!(staker_ticket.claimable(&stake_pool, expiry), None);
+        assert_eq!(
+            staker_ticket.claimable(&stake_pool, expiry + 1.into()),
+            Some((10000.into(), 1000.into()))
+        );
+
+        // the pool replays from a fixture as it was left
+        let keys = [
+            pool_key.pubkey(),
+            staker_ticket_key.pubkey(),
+            stake_mint_key.pubkey(),
+            stake_vault_key.pubkey(),
+        ];
+        let fixture = client.capture_fixture(&keys).await?;
+        let fixture = AccountFixture::from_json(&fixture.to_json())?;
+
+        let mut program_test = ProgramTest::default();
+        program_test.add_program(
+            "x5margin",
+            program_id,
+            Some(|a, b, c| {
+                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+            }),
+        );
+        program_test.add_fixture(&fixture);
+        let (mut replay, _, _) = program_test.start().await;
+
+        for account in &fixture.accounts {
+            assert_eq!(
+                replay.get_account(&account.pubkey).await?.as_ref(),
+                Some(account)
+            );
+        }
+
+        let stake_pool = replay.get_account(&pool_key.pubkey()).await?.unwrap();
+        let stake_pool = StakePoolEntity::load(&program_id, Box::new(stake_pool)).unwrap();
+        assert!(stake_pool.stake_acquired_amount == 10000.into());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stake_insufficient_funds_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+
+        let result = fixture.stake((PoolFixture::SOURCE_AMOUNT + 1).into()).await;
+        assert_custom_error(result, Error::Validation.code());
+
+        assert!(fixture.pool().await?.stake_acquired_amount == 0.into());
+        let aux_wallet = fixture.aux_wallet_key.pubkey();
+        assert_eq!(
+            fixture.wallet_amount(aux_wallet).await?,
+            PoolFixture::SOURCE_AMOUNT.into()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stake_requires_funded_pool_test() -> anyhow::Result<()> {
+        let mut program_test = ProgramTest::default();
+        let program_id = Pubkey::new_unique();
+
+        program_test.add_program(
+            "x5margin",
+            program_id,
+            Some(|a, b, c| {
+                builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+            }),
+        );
+
+        let pool_key = Keypair::new();
+        let pool_administrator_key = Keypair::new();
+
+        let (pool_program_authority, salt) = derive_pool_authority(
+            &program_id,
+            &pool_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+        );
+
+        let (mut client, payer, hash) = program_test.start().await;
+
+        let stake_mint_key = Keypair::new();
+        let stake_vault_key = Keypair::new();
+        let aux_wallet_key = Keypair::new();
+
+        let staker_key = Keypair::new();
+        let staker_ticket_key = Keypair::new();
+
+        let mut instrs = vec![];
+        instrs.extend(create_mint(
+            &payer.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+            None,
+            6,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &stake_vault_key.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_program_authority,
+        ));
+        instrs.extend(create_wallet(
+            &payer.pubkey(),
+            &aux_wallet_key.pubkey(),
+            &stake_mint_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+        ));
+        instrs.push(mint_to(
+            &stake_mint_key.pubkey(),
+            &aux_wallet_key.pubkey(),
+            &pool_administrator_key.pubkey(),
+            2000,
+        ));
+        instrs.push(create_account(
+            &payer.pubkey(),
+            &pool_key.pubkey(),
+            minimum_balance(StakePool::default_size() as u64),
+            StakePool::default_size() as u64,
+            &program_id,
+        ));
+        instrs.push(create_account(
+            &payer.pubkey(),
+            &staker_ticket_key.pubkey(),
+            minimum_balance(StakerTicket::default_size() as u64),
+            StakerTicket::default_size() as u64,
+            &program_id,
+        ));
+        instrs.push(instructions::create_pool(
+            program_id,
+            &CreatePoolAccounts {
+                administrator: pool_administrator_key.pubkey(),
+                program_authority: pool_program_authority,
+                pool: pool_key.pubkey(),
+                stake_mint: stake_mint_key.pubkey(),
+                stake_vault: stake_vault_key.pubkey(),
             },
-        ];
+            InitializeArgs {
+                program_authority_salt: salt,
+                lockup_duration: 1000.into(),
+                topup_duration: 200.into(),
+                reward_amount: 1000.into(),
+                target_amount: 10000.into(),
+                flags: POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE,
+                reward_tranches: vec![],
+            },
+        ));
 
         let trx = Transaction::new_signed_with_payer(
             &instrs,
             Some(&payer.pubkey()),
-            &vec![payer, stake_mint_key, stake_vault_key, pool_key],
+            &vec![
+                &payer as &dyn Signer,
+                &stake_mint_key,
+                &stake_vault_key,
+                &pool_key,
+                &aux_wallet_key,
+                &pool_administrator_key,
+                &staker_ticket_key,
+            ],
+            hash,
+        );
+        client.process_transaction(trx).await?;
+
+        let stake_accounts = StakeAccounts {
+            pool: pool_key.pubkey(),
+            staker: staker_key.pubkey(),
+            ticket: staker_ticket_key.pubkey(),
+            stake_vault: stake_vault_key.pubkey(),
+            source_authority: pool_administrator_key.pubkey(),
+            source_wallet: aux_wallet_key.pubkey(),
+        };
+
+        let trx = Transaction::new_signed_with_payer(
+            &[instructions::stake(program_id, &stake_accounts, 100.into())],
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &pool_administrator_key],
             hash,
         );
+        let err = client.process_transaction(trx).await.unwrap_err();
+        let err = err
+            .downcast_ref::<solana_sdk::transport::TransportError>()
+            .expect("transaction error");
+        assert!(matches!(
+            err,
+            solana_sdk::transport::TransportError::TransactionError(
+                solana_sdk::transaction::TransactionError::InstructionError(
+                    0,
+                    solana_sdk::instruction::InstructionError::Custom(10)
+                )
+            )
+        ));
 
-        let result = client.process_transaction(trx).await;
-        println!("{:?}", result);
+        let add_reward = instructions::add_reward(
+            program_id,
+            &AddRewardAccounts {
+                pool: pool_key.pubkey(),
+                stake_vault: stake_vault_key.pubkey(),
+                source_authority: pool_administrator_key.pubkey(),
+                source_wallet: aux_wallet_key.pubkey(),
+            },
+            1000.into(),
+        );
+        let stake = instructions::stake(program_id, &stake_accounts, 200.into());
+        let trx = Transaction::new_signed_with_payer(
+            &[add_reward, stake],
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer, &pool_administrator_key],
+            hash,
+        );
+        client.process_transaction(trx).await?;
 
+        let stake_pool = client.get_account(&pool_key.pubkey()).await?.unwrap();
+        let stake_pool = StakePoolEntity::load(&program_id, Box::new(stake_pool)).unwrap();
+        assert!(stake_pool.is_funded());
+        assert!(stake_pool.stake_acquired_amount == 200.into());
         Ok(())
     }
 
     #[tokio::test]
-    async fn claim_reward_test() -> anyhow::Result<()> {
+    async fn not_enough_accounts_test() -> anyhow::Result<()> {
         let mut program_test = ProgramTest::default();
         let program_id = Pubkey::new_unique();
 
@@ -482,201 +1950,836 @@ mod test {
             }),
         );
 
-        let pool_key = Keypair::new();
-        let pool_administrator_key = Keypair::new();
+        let (mut client, payer, hash) = program_test.start().await;
 
-        let mut salt: u64 = 0;
-        let pool_program_authority = loop {
-            let pool_program_authority = Pubkey::create_program_address(
-                &[
-                    pool_key.pubkey().as_ref(),
-                    pool_administrator_key.pubkey().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
+        // only the token program is passed, the pool is missing
+        let mut instruction = instructions::claim_reward(
+            program_id,
+            &WithdrawAccounts {
+                pool: Pubkey::new_unique(),
+                ticket: Pubkey::new_unique(),
+                staker: Pubkey::new_unique(),
+                program_authority: Pubkey::new_unique(),
+                stake_vault: Pubkey::new_unique(),
+                target_wallet: Pubkey::new_unique(),
+            },
+        );
+        instruction.accounts.truncate(1);
+
+        let trx = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &vec![&payer as &dyn Signer],
+            hash,
+        );
+        let err = client.process_transaction(trx).await.unwrap_err();
+        let err = err
+            .downcast_ref::<solana_sdk::transport::TransportError>()
+            .expect("transaction error");
+        assert!(matches!(
+            err,
+            solana_sdk::transport::TransportError::TransactionError(
+                solana_sdk::transaction::TransactionError::InstructionError(
+                    0,
+                    solana_sdk::instruction::InstructionError::Custom(15)
+                )
+            )
+        ));
+        assert_eq!(Error::from_code(15), Some(Error::NotEnoughAccountKeys));
+        Ok(())
+    }
+
+    /// A pool with a lockup of 1000 seconds, of which the first 200 are open for staking, paying
+    /// a reward of 1000 for a target of 10000.
+    ///
+    /// The administrator owns the mint and a source wallet holding `SOURCE_AMOUNT`, the staker
+    /// has an empty wallet and an allocated ticket account.
+    struct PoolFixture {
+        client: Runtime,
+        payer: Keypair,
+        hash: Hash,
+        program_id: Pubkey,
+        pool_key: Keypair,
+        pool_administrator_key: Keypair,
+        pool_program_authority: Pubkey,
+        stake_vault_key: Keypair,
+        aux_wallet_key: Keypair,
+        staker_key: Keypair,
+        staker_ticket_key: Keypair,
+        staker_wallet_key: Keypair,
+    }
+
+    impl PoolFixture {
+        const SOURCE_AMOUNT: u64 = 20000;
+
+        async fn new() -> anyhow::Result<Self> {
+            Self::with_reward_tranches(vec![]).await
+        }
+
+        /// A pool whose 1000 token reward unlocks on `reward_tranches`.
+        async fn with_reward_tranches(reward_tranches: Vec<RewardTranche>) -> anyhow::Result<Self> {
+            let mut program_test = ProgramTest::default();
+            let program_id = Pubkey::new_unique();
+
+            program_test.add_program(
+                "x5margin",
+                program_id,
+                Some(|a, b, c| {
+                    builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
+                }),
+            );
+
+            let pool_key = Keypair::new();
+            let pool_administrator_key = Keypair::new();
+
+            let (pool_program_authority, salt) = derive_pool_authority(
                 &program_id,
+                &pool_key.pubkey(),
+                &pool_administrator_key.pubkey(),
             );
 
-            match pool_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
-            }
-        };
+            let (mut client, payer, hash) = program_test.start().await;
 
-        let (mut client, payer, hash) = program_test.start().await;
+            let stake_mint_key = Keypair::new();
+            let stake_vault_key = Keypair::new();
+            let aux_wallet_key = Keypair::new();
 
-        let stake_mint_key = Keypair::new();
-        let stake_vault_key = Keypair::new();
+            let staker_key = Keypair::new();
+            let staker_ticket_key = Keypair::new();
+            let staker_wallet_key = Keypair::new();
 
-        let instrs = vec![
-            create_account(
+            let mut instrs = vec![];
+            instrs.extend(create_mint(
                 &payer.pubkey(),
                 &stake_mint_key.pubkey(),
-                minimum_balance(size_of::<Mint>() as u64),
-                size_of::<Mint>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
+                &pool_administrator_key.pubkey(),
+                None,
+                6,
+            ));
+            instrs.extend(create_wallet(
                 &payer.pubkey(),
                 &stake_vault_key.pubkey(),
-                minimum_balance(size_of::<Wallet>() as u64),
-                size_of::<Wallet>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
+                &stake_mint_key.pubkey(),
+                &pool_program_authority,
+            ));
+            instrs.extend(create_wallet(
+                &payer.pubkey(),
+                &aux_wallet_key.pubkey(),
+                &stake_mint_key.pubkey(),
+                &pool_administrator_key.pubkey(),
+            ));
+            instrs.extend(create_wallet(
+                &payer.pubkey(),
+                &staker_wallet_key.pubkey(),
+                &stake_mint_key.pubkey(),
+                &staker_key.pubkey(),
+            ));
+            instrs.push(mint_to(
+                &stake_mint_key.pubkey(),
+                &aux_wallet_key.pubkey(),
+                &pool_administrator_key.pubkey(),
+                Self::SOURCE_AMOUNT,
+            ));
+            instrs.push(create_account(
                 &payer.pubkey(),
                 &pool_key.pubkey(),
-                StakePool::default_lamports(),
+                minimum_balance(StakePool::default_size() as u64),
                 StakePool::default_size() as u64,
                 &program_id,
-            ),
-            spl_token::instruction::initialize_mint(
-                &solar::spl::ID.compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_administrator_key.pubkey().compat(),
-                None,
-                6,
-            )
-            .unwrap()
-            .into(),
-            spl_token::instruction::initialize_account(
-                &solar::spl::ID.compat(),
-                &stake_vault_key.pubkey().compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_program_authority.compat(),
-            )
-            .unwrap()
-            .into(),
-            Instruction {
+            ));
+            instrs.push(create_account(
+                &payer.pubkey(),
+                &staker_ticket_key.pubkey(),
+                minimum_balance(StakerTicket::default_size() as u64),
+                StakerTicket::default_size() as u64,
+                &program_id,
+            ));
+            instrs.push(instructions::create_pool(
                 program_id,
-                accounts: vec![
-                    AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
-                    AccountMeta::new_readonly(pool_program_authority, false),
-                    AccountMeta::new(pool_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
+                &CreatePoolAccounts {
+                    administrator: pool_administrator_key.pubkey(),
+                    program_authority: pool_program_authority,
+                    pool: pool_key.pubkey(),
+                    stake_mint: stake_mint_key.pubkey(),
+                    stake_vault: stake_vault_key.pubkey(),
+                },
+                InitializeArgs {
+                    program_authority_salt: salt,
+                    lockup_duration: 1000.into(),
+                    topup_duration: 200.into(),
+                    reward_amount: 1000.into(),
+                    target_amount: 10000.into(),
+                    flags: 0,
+                    reward_tranches,
+                },
+            ));
+
+            let trx = Transaction::new_signed_with_payer(
+                &instrs,
+                Some(&payer.pubkey()),
+                &vec![
+                    &payer as &dyn Signer,
+                    &stake_mint_key,
+                    &stake_vault_key,
+                    &pool_key,
+                    &aux_wallet_key,
+                    &staker_wallet_key,
+                    &pool_administrator_key,
+                    &staker_ticket_key,
                 ],
-                data: Method::Simple(simple_stake::Method::ClaimReward).encode(),
-            },
-        ];
+                hash,
+            );
+            client.process_transaction(trx).await?;
+
+            Ok(Self {
+                client,
+                payer,
+                hash,
+                program_id,
+                pool_key,
+                pool_administrator_key,
+                pool_program_authority,
+                stake_vault_key,
+                aux_wallet_key,
+                staker_key,
+                staker_ticket_key,
+                staker_wallet_key,
+            })
+        }
+
+        /// Process `instruction` signed by the payer and `signer`.
+        async fn process(
+            &mut self,
+            instruction: Instruction,
+            signer: &Keypair,
+        ) -> anyhow::Result<()> {
+            let trx = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&self.payer.pubkey()),
+                &vec![&self.payer as &dyn Signer, signer],
+                self.hash,
+            );
+            self.client.process_transaction(trx).await
+        }
+
+        async fn stake(&mut self, amount: TokenAmount) -> anyhow::Result<()> {
+            let stake = instructions::stake(
+                self.program_id,
+                &StakeAccounts {
+                    pool: self.pool_key.pubkey(),
+                    staker: self.staker_key.pubkey(),
+                    ticket: self.staker_ticket_key.pubkey(),
+                    stake_vault: self.stake_vault_key.pubkey(),
+                    source_authority: self.pool_administrator_key.pubkey(),
+                    source_wallet: self.aux_wallet_key.pubkey(),
+                },
+                amount,
+            );
+            let administrator = self.pool_administrator_key.clone();
+            self.process(stake, &administrator).await
+        }
+
+        async fn add_reward(&mut self, amount: TokenAmount) -> anyhow::Result<()> {
+            let add_reward = instructions::add_reward(
+                self.program_id,
+                &AddRewardAccounts {
+                    pool: self.pool_key.pubkey(),
+                    stake_vault: self.stake_vault_key.pubkey(),
+                    source_authority: self.pool_administrator_key.pubkey(),
+                    source_wallet: self.aux_wallet_key.pubkey(),
+                },
+                amount,
+            );
+            let administrator = self.pool_administrator_key.clone();
+            self.process(add_reward, &administrator).await
+        }
+
+        /// Hand the pool over to `new_administrator`, signed by `administrator`.
+        async fn set_administrator(
+            &mut self,
+            administrator: &Keypair,
+            new_administrator: Pubkey,
+        ) -> anyhow::Result<()> {
+            let set_administrator = instructions::set_administrator(
+                self.program_id,
+                &SetAdministratorAccounts {
+                    pool: self.pool_key.pubkey(),
+                    administrator: administrator.pubkey(),
+                    new_administrator,
+                },
+            );
+            self.process(set_administrator, administrator).await
+        }
+
+        fn withdraw_accounts(&self) -> WithdrawAccounts {
+            WithdrawAccounts {
+                pool: self.pool_key.pubkey(),
+                ticket: self.staker_ticket_key.pubkey(),
+                staker: self.staker_key.pubkey(),
+                program_authority: self.pool_program_authority,
+                stake_vault: self.stake_vault_key.pubkey(),
+                target_wallet: self.staker_wallet_key.pubkey(),
+            }
+        }
+
+        async fn unstake(&mut self, amount: TokenAmount) -> anyhow::Result<()> {
+            let unstake = instructions::unstake(self.program_id, &self.withdraw_accounts(), amount);
+            let staker = self.staker_key.clone();
+            self.process(unstake, &staker).await
+        }
+
+        async fn unstake_percent(&mut self, bps: u16) -> anyhow::Result<()> {
+            let unstake =
+                instructions::unstake_percent(self.program_id, &self.withdraw_accounts(), bps);
+            let staker = self.staker_key.clone();
+            self.process(unstake, &staker).await
+        }
+
+        async fn claim_reward(&mut self) -> anyhow::Result<()> {
+            let claim = instructions::claim_reward(self.program_id, &self.withdraw_accounts());
+            let staker = self.staker_key.clone();
+            self.process(claim, &staker).await
+        }
+
+        async fn pool(&mut self) -> anyhow::Result<StakePoolEntity<Box<Account>>> {
+            let pool = self
+                .client
+                .get_account(&self.pool_key.pubkey())
+                .await?
+                .unwrap();
+            Ok(StakePoolEntity::load(&self.program_id, Box::new(pool)).unwrap())
+        }
+
+        /// The staker's ticket, `None` once it was closed.
+        async fn ticket(&mut self) -> anyhow::Result<Option<StakerTicketEntity<Box<Account>>>> {
+            let pool = self.pool().await?;
+            let ticket = self
+                .client
+                .get_account(&self.staker_ticket_key.pubkey())
+                .await?;
+
+            match ticket {
+                Some(ticket) => Ok(Some(pool.load_ticket(Box::new(ticket)).unwrap())),
+                None => Ok(None),
+            }
+        }
+
+        async fn wallet_amount(&mut self, wallet: Pubkey) -> anyhow::Result<TokenAmount> {
+            let wallet = self.client.get_account(&wallet).await?.unwrap();
+            Ok(WalletAccount::any(Box::new(wallet)).unwrap().amount())
+        }
+    }
+
+    /// Assert that `result` failed with the custom program error `code`.
+    fn assert_custom_error(result: anyhow::Result<()>, code: u32) {
+        let err = result.unwrap_err();
+        let err = err
+            .downcast_ref::<solana_sdk::transport::TransportError>()
+            .expect("transaction error");
+        assert!(
+            matches!(
+                err,
+                solana_sdk::transport::TransportError::TransactionError(
+                    solana_sdk::transaction::TransactionError::InstructionError(
+                        0,
+                        solana_sdk::instruction::InstructionError::Custom(actual)
+                    )
+                ) if *actual == code
+            ),
+            "expected custom error {}, got {:?}",
+            code,
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn stake_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+
+        fixture.stake(100.into()).await?;
+        fixture.stake(50.into()).await?;
+
+        let pool = fixture.pool().await?;
+        assert_eq!(pool.stake_acquired_amount, 150.into());
+
+        let ticket = fixture.ticket().await?.unwrap();
+        assert_eq!(ticket.staked_amount, 150.into());
+        assert_eq!(ticket.authority, fixture.staker_key.pubkey());
+
+        let vault = fixture.stake_vault_key.pubkey();
+        let source = fixture.aux_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(vault).await?, 150.into());
+        assert_eq!(
+            fixture.wallet_amount(source).await?,
+            (PoolFixture::SOURCE_AMOUNT - 150).into()
+        );
+
+        // staking is capped at the pool target
+        fixture.stake(10000.into()).await?;
+        assert_eq!(fixture.pool().await?.stake_acquired_amount, 10000.into());
+        assert_eq!(fixture.wallet_amount(vault).await?, 10000.into());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stake_batch_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        let program_id = fixture.program_id;
+        let pool = fixture.pool_key.pubkey();
+        let stake_vault = fixture.stake_vault_key.pubkey();
+        let administrator = fixture.pool_administrator_key.clone();
+
+        let tickets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let stakes: Vec<BatchStake> = tickets
+            .iter()
+            .zip([100u64, 200, 300])
+            .map(|(ticket, amount)| BatchStake {
+                staker: Pubkey::new_unique(),
+                ticket: ticket.pubkey(),
+                source_authority: administrator.pubkey(),
+                source_wallet: fixture.aux_wallet_key.pubkey(),
+                amount: amount.into(),
+            })
+            .collect();
+
+        let mut instrs: Vec<Instruction> = tickets
+            .iter()
+            .map(|ticket| {
+                create_account(
+                    &fixture.payer.pubkey(),
+                    &ticket.pubkey(),
+                    StakerTicket::default_lamports(),
+                    StakerTicket::default_size() as u64,
+                    &program_id,
+                )
+            })
+            .collect();
+        instrs.push(instructions::stake_batch(
+            program_id,
+            pool,
+            stake_vault,
+            &stakes,
+        ));
 
+        let mut signers = vec![&fixture.payer as &dyn Signer, &administrator];
+        signers.extend(tickets.iter().map(|ticket| ticket as &dyn Signer));
         let trx = Transaction::new_signed_with_payer(
             &instrs,
-            Some(&payer.pubkey()),
-            &vec![payer, stake_mint_key, stake_vault_key, pool_key],
-            hash,
+            Some(&fixture.payer.pubkey()),
+            &signers,
+            fixture.hash,
+        );
+        fixture.client.process_transaction(trx).await?;
+
+        let stake_pool = fixture.pool().await?;
+        assert_eq!(stake_pool.stake_acquired_amount, 600.into());
+        for stake in &stakes {
+            let ticket = fixture.client.get_account(&stake.ticket).await?.unwrap();
+            let ticket = stake_pool.load_ticket(Box::new(ticket)).unwrap();
+            assert_eq!(ticket.staked_amount, stake.amount);
+            assert_eq!(ticket.authority, stake.staker);
+        }
+        assert_eq!(fixture.wallet_amount(stake_vault).await?, 600.into());
+
+        // the batch is capped to fit the input
+        let too_many = vec![stakes[0]; MAX_STAKE_BATCH + 1];
+        let instruction = instructions::stake_batch(program_id, pool, stake_vault, &too_many);
+        assert_custom_error(
+            fixture.process(instruction, &administrator).await,
+            Error::InvalidData.code(),
         );
 
-        let result = client.process_transaction(trx).await;
-        println!("{:?}", result);
+        // amounts without their accounts are rejected
+        let mut instruction = instructions::stake_batch(program_id, pool, stake_vault, &stakes);
+        instruction
+            .accounts
+            .truncate(3 + STAKE_BATCH_ENTRY_ACCOUNTS);
+        assert_custom_error(
+            fixture.process(instruction, &administrator).await,
+            Error::InvalidData.code(),
+        );
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn add_reward_test() -> anyhow::Result<()> {
-        let mut program_test = ProgramTest::default();
-        let program_id = Pubkey::new_unique();
+    async fn unstake_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        fixture.stake(200.into()).await?;
+
+        fixture.unstake(50.into()).await?;
+
+        let pool = fixture.pool().await?;
+        assert_eq!(pool.stake_acquired_amount, 150.into());
+        assert_eq!(fixture.ticket().await?.unwrap().staked_amount, 150.into());
+
+        let vault = fixture.stake_vault_key.pubkey();
+        let staker_wallet = fixture.staker_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(vault).await?, 150.into());
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 50.into());
+
+        // unstaking more than the ticket holds withdraws the rest and closes the ticket
+        fixture.unstake(1000.into()).await?;
+        assert_eq!(fixture.pool().await?.stake_acquired_amount, 0.into());
+        assert!(fixture.ticket().await?.is_none());
+        assert_eq!(fixture.wallet_amount(vault).await?, 0.into());
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 200.into());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unstake_percent_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        fixture.stake(201.into()).await?;
+
+        // half of an odd stake rounds down
+        fixture.unstake_percent(5000).await?;
+        assert_eq!(fixture.pool().await?.stake_acquired_amount, 101.into());
+        assert_eq!(fixture.ticket().await?.unwrap().staked_amount, 101.into());
+
+        let staker_wallet = fixture.staker_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 100.into());
+
+        assert_custom_error(fixture.unstake_percent(10001).await, 10);
+
+        // 10000 bps withdraws everything and closes the ticket
+        fixture.unstake_percent(10000).await?;
+        assert_eq!(fixture.pool().await?.stake_acquired_amount, 0.into());
+        assert!(fixture.ticket().await?.is_none());
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 201.into());
+
+        let vault = fixture.stake_vault_key.pubkey();
+        assert_eq!(fixture.wallet_amount(vault).await?, 0.into());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn closed_ticket_revival_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        fixture.stake(100.into()).await?;
+        let closed_id = fixture.ticket().await?.unwrap().header().id;
+
+        // close the ticket, fund its address again and stake on it within one transaction, before
+        // the runtime purges it
+        let ticket = fixture.staker_ticket_key.pubkey();
+        let unstake =
+            instructions::unstake(fixture.program_id, &fixture.withdraw_accounts(), 100.into());
+        let refund = system::transfer(
+            &fixture.payer.pubkey(),
+            &ticket,
+            StakerTicket::default_lamports(),
+        );
+        let stake = instructions::stake(
+            fixture.program_id,
+            &StakeAccounts {
+                pool: fixture.pool_key.pubkey(),
+                staker: fixture.staker_key.pubkey(),
+                ticket,
+                stake_vault: fixture.stake_vault_key.pubkey(),
+                source_authority: fixture.pool_administrator_key.pubkey(),
+                source_wallet: fixture.aux_wallet_key.pubkey(),
+            },
+            50.into(),
+        );
+        let trx = Transaction::new_signed_with_payer(
+            &[unstake, refund, stake],
+            Some(&fixture.payer.pubkey()),
+            &vec![
+                &fixture.payer as &dyn Signer,
+                &fixture.staker_key,
+                &fixture.pool_administrator_key,
+            ],
+            fixture.hash,
+        );
+        fixture.client.process_transaction(trx).await?;
+
+        // the ticket was initialized from scratch, taking the id its closed state released
+        let ticket = fixture.ticket().await?.unwrap();
+        assert_eq!(ticket.staked_amount, 50.into());
+        assert_eq!(ticket.authority, fixture.staker_key.pubkey());
+        assert_eq!(ticket.header().id.value(), closed_id.value());
+
+        let mut pool = fixture.pool().await?;
+        assert_eq!(pool.stake_acquired_amount, 50.into());
+        assert_ne!(pool.allocator.allocate_id().value(), closed_id.value());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_reward_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        fixture.add_reward(1000.into()).await?;
+        fixture.stake(200.into()).await?;
+
+        let pool = fixture.pool().await?;
+        let expected_amount = pool.claim_amount(200.into()).unwrap();
+        assert_eq!(expected_amount, 1200.into());
+
+        // the pool is still locked
+        assert_custom_error(fixture.claim_reward().await, 10);
+
+        fixture.hash = fixture.client.warp_clock(1001).await?;
+        let now = fixture.client.clock().await?.unix_timestamp;
+        assert!(pool.is_expired(now.into()));
 
+        fixture.claim_reward().await?;
+
+        let staker_wallet = fixture.staker_wallet_key.pubkey();
+        let vault = fixture.stake_vault_key.pubkey();
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, expected_amount);
+        assert_eq!(fixture.wallet_amount(vault).await?, 0.into());
+
+        // the ticket is closed once claimed
+        assert!(fixture.ticket().await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reentrancy_rejected_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        fixture.add_reward(1000.into()).await?;
+        fixture.stake(200.into()).await?;
+
+        let vault = fixture.stake_vault_key.pubkey();
+        let vault_account = fixture.client.get_account(&vault).await?.unwrap();
+        let mint = *WalletAccount::any(Box::new(vault_account)).unwrap().mint();
+        let keys = [
+            fixture.pool_key.pubkey(),
+            fixture.staker_ticket_key.pubkey(),
+            vault,
+            fixture.aux_wallet_key.pubkey(),
+            fixture.staker_wallet_key.pubkey(),
+            mint,
+        ];
+        let mut accounts = fixture.client.capture_fixture(&keys).await?;
+
+        // replay the pool as a token program calling back into the stake program halfway
+        // through a transfer would find it
+        let mut pool =
+            StakePoolEntity::load(&fixture.program_id, Box::new(accounts.accounts[0].clone()))
+                .unwrap();
+        pool.enter().unwrap();
+        accounts.accounts[0] = (**pool.account()).clone();
+
+        let mut program_test = ProgramTest::default();
         program_test.add_program(
             "x5margin",
-            program_id,
+            fixture.program_id,
             Some(|a, b, c| {
                 builtin_process_instruction(wrapped_entrypoint::<super::Program>, a, b, c)
             }),
         );
+        program_test.add_fixture(&accounts);
+        let (client, payer, hash) = program_test.start().await;
+        fixture.client = client;
+        fixture.payer = payer;
+        fixture.hash = hash;
 
-        let pool_key = Keypair::new();
-        let pool_administrator_key = Keypair::new();
+        assert_custom_error(fixture.stake(100.into()).await, 17);
+        assert_custom_error(fixture.unstake(100.into()).await, 17);
 
-        let mut salt: u64 = 0;
-        let pool_program_authority = loop {
-            let pool_program_authority = Pubkey::create_program_address(
-                &[
-                    pool_key.pubkey().as_ref(),
-                    pool_administrator_key.pubkey().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
-                &program_id,
-            );
+        fixture.hash = fixture.client.warp_clock(1001).await?;
+        assert_custom_error(fixture.claim_reward().await, 17);
 
-            match pool_program_authority {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
-            }
-        };
+        // nothing moved
+        assert_eq!(fixture.ticket().await?.unwrap().staked_amount, 200.into());
+        assert_eq!(fixture.wallet_amount(vault).await?, 1200.into());
 
-        let (mut client, payer, hash) = program_test.start().await;
+        Ok(())
+    }
 
-        let stake_mint_key = Keypair::new();
-        let stake_vault_key = Keypair::new();
+    fn tranche(unlock_offset: i64, amount: u64) -> RewardTranche {
+        RewardTranche {
+            unlock_offset: unlock_offset.into(),
+            amount: amount.into(),
+        }
+    }
 
-        let amount: super::TokenAmount = solar::math::Checked::from(100);
+    #[tokio::test]
+    async fn reward_tranches_test() -> anyhow::Result<()> {
+        let tranches = vec![tranche(500, 300), tranche(1000, 300), tranche(1500, 400)];
+
+        let mut fixture = PoolFixture::with_reward_tranches(tranches.clone()).await?;
+        let pool = fixture.pool().await?;
+        assert_eq!(&*pool.reward_tranches(), &tranches[..]);
+
+        let genesis = pool.genesis().value();
+        let matured = |offset: i64| pool.matured_reward((genesis + offset).into());
+        assert_eq!(matured(499), 0.into());
+        assert_eq!(matured(500), 300.into());
+        assert_eq!(matured(1000), 600.into());
+        assert_eq!(matured(1499), 600.into());
+        assert_eq!(matured(1500), 1000.into());
+
+        // claimed right after the lockup, the last tranche hasn't matured yet
+        fixture.add_reward(1000.into()).await?;
+        fixture.stake(200.into()).await?;
+        fixture.hash = fixture.client.warp_clock(1001).await?;
+        fixture.claim_reward().await?;
+
+        let staker_wallet = fixture.staker_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 800.into());
+
+        // the ticket stays open for the last tranche, claiming again before it matures fails
+        fixture.hash = fixture.client.warp_clock(1).await?;
+        let ticket = fixture.ticket().await?.unwrap();
+        assert_eq!(ticket.claimed_amount, 800.into());
+        let now = fixture.client.clock().await?.unix_timestamp;
+        assert_eq!(
+            ticket.claimable(&fixture.pool().await?, now.into()),
+            Some((0.into(), 0.into()))
+        );
+        assert_custom_error(fixture.claim_reward().await, 10);
 
-        let instrs = vec![
-            create_account(
-                &payer.pubkey(),
-                &stake_mint_key.pubkey(),
-                minimum_balance(size_of::<Mint>() as u64),
-                size_of::<Mint>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &stake_vault_key.pubkey(),
-                minimum_balance(size_of::<Wallet>() as u64),
-                size_of::<Wallet>() as u64,
-                &solar::spl::ID,
-            ),
-            create_account(
-                &payer.pubkey(),
-                &pool_key.pubkey(),
+        fixture.hash = fixture.client.warp_clock(499).await?;
+        let now = fixture.client.clock().await?.unix_timestamp;
+        assert_eq!(
+            ticket.claimable(&fixture.pool().await?, now.into()),
+            Some((0.into(), 400.into()))
+        );
+        fixture.claim_reward().await?;
+
+        // nothing is left behind in the vault once the last tranche has been claimed
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 1200.into());
+        let vault = fixture.stake_vault_key.pubkey();
+        assert_eq!(fixture.wallet_amount(vault).await?, 0.into());
+        assert!(fixture.ticket().await?.is_none());
+
+        // claimed once every tranche matured, the whole reward is paid out
+        let mut fixture = PoolFixture::with_reward_tranches(tranches).await?;
+        fixture.add_reward(1000.into()).await?;
+        fixture.stake(200.into()).await?;
+        fixture.hash = fixture.client.warp_clock(1501).await?;
+        fixture.claim_reward().await?;
+
+        let staker_wallet = fixture.staker_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(staker_wallet).await?, 1200.into());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_rejects_invalid_reward_tranches_test() -> anyhow::Result<()> {
+        let args = InitializeArgs {
+            program_authority_salt: 0,
+            lockup_duration: 1000.into(),
+            topup_duration: 200.into(),
+            reward_amount: 1000.into(),
+            target_amount: 10000.into(),
+            flags: 0,
+            reward_tranches: vec![],
+        };
+
+        let mut too_many = vec![tranche(0, 100); MAX_REWARD_TRANCHES];
+        too_many.push(tranche(0, 1000 - 100 * MAX_REWARD_TRANCHES as u64));
+
+        for reward_tranches in vec![
+            // doesn't add up to the reward
+            vec![tranche(500, 300), tranche(1000, 300)],
+            vec![tranche(-1, 1000)],
+            vec![tranche(i64::MAX, 1000)],
+            vec![tranche(0, u64::MAX), tranche(0, 1001)],
+            too_many,
+        ] {
+            let (program_id, result, pool) = create_pool_with(
                 StakePool::default_lamports(),
-                StakePool::default_size() as u64,
-                &program_id,
-            ),
-            spl_token::instruction::initialize_mint(
-                &solar::spl::ID.compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_administrator_key.pubkey().compat(),
-                None,
-                6,
-            )
-            .unwrap()
-            .into(),
-            spl_token::instruction::initialize_account(
-                &solar::spl::ID.compat(),
-                &stake_vault_key.pubkey().compat(),
-                &stake_mint_key.pubkey().compat(),
-                &pool_program_authority.compat(),
+                vec![InitializeArgs {
+                    reward_tranches,
+                    ..args.clone()
+                }],
             )
-            .unwrap()
-            .into(),
-            Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new_readonly(pool_administrator_key.pubkey(), false),
-                    AccountMeta::new_readonly(pool_program_authority, false),
-                    AccountMeta::new(pool_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_mint_key.pubkey(), false),
-                    AccountMeta::new_readonly(stake_vault_key.pubkey(), false),
-                ],
-                data: Method::Simple(simple_stake::Method::AddReward { amount: amount }).encode(),
-            },
-        ];
+            .await?;
+            assert_custom_error(result, Error::InvalidData.code());
+            assert!(StakePoolEntity::load(&program_id, Box::new(pool)).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_reward_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+
+        fixture.add_reward(400.into()).await?;
+        assert_eq!(fixture.pool().await?.deposited_reward_amount, 400.into());
+        assert!(!fixture.pool().await?.is_funded());
+
+        // deposits are capped at the pool reward
+        fixture.add_reward(1000.into()).await?;
+        let pool = fixture.pool().await?;
+        assert_eq!(pool.deposited_reward_amount, 1000.into());
+        assert!(pool.is_funded());
+
+        let vault = fixture.stake_vault_key.pubkey();
+        let source = fixture.aux_wallet_key.pubkey();
+        assert_eq!(fixture.wallet_amount(vault).await?, 1000.into());
+        assert_eq!(
+            fixture.wallet_amount(source).await?,
+            (PoolFixture::SOURCE_AMOUNT - 1000).into()
+        );
+
+        // nothing is left to deposit
+        assert_custom_error(fixture.add_reward(1.into()).await, 10);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_administrator_test() -> anyhow::Result<()> {
+        let mut fixture = PoolFixture::new().await?;
+        let initial = fixture.pool_administrator_key.clone();
+        let next = Keypair::new();
+
+        fixture.stake(100.into()).await?;
+        fixture.set_administrator(&initial, next.pubkey()).await?;
+
+        let pool = fixture.pool().await?;
+        assert_eq!(pool.administrator_authority, next.pubkey());
+        assert_eq!(pool.initial_administrator, initial.pubkey());
+        assert_eq!(pool.program_authority, fixture.pool_program_authority);
+
+        // the previous administrator lost control of the pool
+        let invalid_key = Error::AccountCheck(AccountCheckError::InvalidKey).code();
+        assert_custom_error(
+            fixture.set_administrator(&initial, initial.pubkey()).await,
+            invalid_key,
+        );
 
+        // the new one has to sign
+        let mut unsigned = instructions::set_administrator(
+            fixture.program_id,
+            &SetAdministratorAccounts {
+                pool: fixture.pool_key.pubkey(),
+                administrator: next.pubkey(),
+                new_administrator: initial.pubkey(),
+            },
+        );
+        unsigned.accounts[1].is_signer = false;
         let trx = Transaction::new_signed_with_payer(
-            &instrs,
-            Some(&payer.pubkey()),
-            &vec![payer, stake_mint_key, stake_vault_key, pool_key],
-            hash,
+            &[unsigned],
+            Some(&fixture.payer.pubkey()),
+            &vec![&fixture.payer as &dyn Signer],
+            fixture.hash,
+        );
+        assert_custom_error(
+            fixture.client.process_transaction(trx).await,
+            Error::AccountCheck(AccountCheckError::MissingSignature).code(),
         );
 
-        let result = client.process_transaction(trx).await;
-        println!("{:?}", result);
+        // the vault authority is unchanged, so withdrawals keep working
+        fixture.unstake(40.into()).await?;
+        assert_eq!(fixture.pool().await?.stake_acquired_amount, 60.into());
+
+        fixture.set_administrator(&next, initial.pubkey()).await?;
+        assert_eq!(
+            fixture.pool().await?.administrator_authority,
+            initial.pubkey()
+        );
 
         Ok(())
     }
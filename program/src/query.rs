@@ -0,0 +1,96 @@
+//! Off-chain queries for the entities of the program.
+//!
+//! Entities are found with `getProgramAccounts`, filtered on the size of the entity and on the
+//! fields of its [`EntityHeader`](crate::data::EntityHeader). Every matching account is loaded
+//! on its own, so an account failing to load is reported next to its key instead of failing the
+//! whole query.
+
+use solana_api_types::{
+    Account, Client, ClientError, Memcmp, MemcmpEncodedBytes, Pubkey, RpcAccountInfoConfig,
+    RpcFilterType, RpcProgramAccountsConfig, UiAccountEncoding,
+};
+use solar::account::AccountFields;
+
+use crate::{
+    data::AccountType,
+    error::Error,
+    simple_stake::{StakePool, StakePoolEntity, StakerTicket, StakerTicketEntity},
+};
+
+/// Offset of `EntityHeader::root` in the account data.
+pub const HEADER_ROOT_OFFSET: usize = 0;
+/// Offset of `EntityHeader::kind` in the account data.
+pub const HEADER_KIND_OFFSET: usize = 48;
+
+/// Entities of a query along with their keys, or the error they failed to load with.
+pub type QueryResult<T> = Vec<(Pubkey, Result<T, Error>)>;
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp {
+        offset,
+        bytes: MemcmpEncodedBytes::Binary(bs58::encode(bytes).into_string()),
+        encoding: None,
+    })
+}
+
+/// `getProgramAccounts` config matching every entity of type `T`, narrowed down by `filters`.
+pub fn entity_accounts_config<T: AccountType>(
+    filters: Vec<RpcFilterType>,
+) -> RpcProgramAccountsConfig {
+    let mut entity_filters = vec![
+        RpcFilterType::DataSize(T::default_size() as u64),
+        memcmp(HEADER_KIND_OFFSET, &[T::KIND as u8]),
+    ];
+    entity_filters.extend(filters);
+
+    RpcProgramAccountsConfig {
+        filters: Some(entity_filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        with_context: None,
+    }
+}
+
+impl StakePoolEntity<Box<Account>> {
+    /// Every stake pool of `program_id`.
+    pub async fn all<C: Client>(
+        client: &C,
+        program_id: &Pubkey,
+    ) -> Result<QueryResult<Self>, ClientError> {
+        let accounts = client
+            .get_program_accounts(
+                *program_id,
+                Some(entity_accounts_config::<StakePool>(vec![])),
+            )
+            .await?;
+
+        Ok(accounts
+            .into_iter()
+            .map(|account| (account.pubkey, Self::load(program_id, Box::new(account))))
+            .collect())
+    }
+}
+
+impl StakerTicketEntity<Box<Account>> {
+    /// Every ticket staked in `pool`.
+    pub async fn all_for_pool<C: Client>(
+        client: &C,
+        pool: &StakePoolEntity<Box<Account>>,
+    ) -> Result<QueryResult<Self>, ClientError> {
+        let filters = vec![memcmp(HEADER_ROOT_OFFSET, pool.account().key().as_ref())];
+
+        let accounts = client
+            .get_program_accounts(
+                *pool.account().owner(),
+                Some(entity_accounts_config::<StakerTicket>(filters)),
+            )
+            .await?;
+
+        Ok(accounts
+            .into_iter()
+            .map(|account| (account.pubkey, pool.load_ticket(Box::new(account))))
+            .collect())
+    }
+}
@@ -1,26 +1,31 @@
 use std::mem::size_of;
 
-#[cfg(feature = "onchain")]
 use az::CheckedAs;
-use solana_api_types::Pubkey;
+use fixed::types::U64F64;
+use solana_api_types::{field_slice, AccountSlice, Pubkey};
 #[cfg(feature = "onchain")]
 use solar::{
     account::onchain::Account,
+    collections::VecViewMut,
     input::AccountSource,
-    math::ToF64,
+    math::{mul_bps, BPS_DENOMINATOR},
     qlog,
-    util::{is_zeroed, timestamp_now, ResultExt},
+    util::{close_account, is_zeroed, minimum_balance, timestamp_now, ResultExt},
 };
 use solar::{
     account::{AccountFields, AccountFieldsMut},
+    authority::Authority,
+    collections::VecView,
+    input::MAX_ACCOUNTS,
+    log::{Loggable, Logger},
     math::Checked,
     prelude::AccountBackend,
     reinterpret::as_bytes,
-    spl::{MintAccount, TokenProgram, WalletAccount},
+    spl::{MintAccount, TokenProgram, Wallet, WalletAccount},
     util::pubkey_eq,
 };
 #[cfg(feature = "onchain")]
-use solar_macros::parse_accounts;
+use solar_macros::try_parse_accounts;
 
 use crate::{
     data::{AccountType, Entity, EntityAllocator, EntityKind, HEADER_RESERVED},
@@ -31,6 +36,201 @@ use crate::{
 pub type StakePoolEntity<B> = Entity<B, StakePool>;
 pub type StakerTicketEntity<B> = Entity<B, StakerTicket>;
 
+/// Program authority of `pool` for a given salt, `None` if the salt doesn't yield a valid
+/// program address.
+pub fn pool_authority(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    administrator: &Pubkey,
+    salt: u64,
+) -> Option<Pubkey> {
+    Pubkey::create_program_address(
+        &[pool.as_ref(), administrator.as_ref(), &salt.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Find the program authority of a new `pool` along with the lowest salt deriving it, which is
+/// what `CreatePool` expects as `program_authority_salt`.
+pub fn derive_pool_authority(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    administrator: &Pubkey,
+) -> (Pubkey, u64) {
+    (0..)
+        .find_map(|salt| {
+            pool_authority(program_id, pool, administrator, salt).map(|authority| (authority, salt))
+        })
+        .expect("no valid program authority salt")
+}
+
+/// Check that a transfer into the vault raised its balance from `before` to `after` by exactly
+/// `expected`.
+pub fn verify_deposit(
+    before: TokenAmount,
+    after: TokenAmount,
+    expected: TokenAmount,
+) -> Result<(), Error> {
+    match after.value().checked_sub(before.value()) {
+        Some(received) if received == expected.value() => Ok(()),
+        _ => Err(Error::InvalidAmountTransferred),
+    }
+}
+
+/// Check that a transfer out of the vault lowered its balance from `before` to `after` by
+/// exactly `expected`.
+pub fn verify_withdrawal(
+    before: TokenAmount,
+    after: TokenAmount,
+    expected: TokenAmount,
+) -> Result<(), Error> {
+    verify_deposit(after, before, expected)
+}
+
+/// Balance of a wallet taken before a CPI moves tokens in or out of it, to check the transfer
+/// against once the call returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceGuard {
+    before: TokenAmount,
+}
+
+impl BalanceGuard {
+    pub fn new(wallet: &Wallet) -> Self {
+        Self {
+            before: wallet.amount(),
+        }
+    }
+
+    pub fn before(&self) -> TokenAmount {
+        self.before
+    }
+
+    /// Check that the balance of `wallet` changed by exactly `expected` since the snapshot,
+    /// negative for tokens leaving it.
+    pub fn assert_delta(&self, wallet: &Wallet, expected: i128) -> Result<(), Error> {
+        let delta = wallet.amount().value() as i128 - self.before.value() as i128;
+        if delta == expected {
+            Ok(())
+        } else {
+            Err(Error::InvalidAmountTransferred)
+        }
+    }
+
+    /// Tokens `pool` credits for a transfer of `sent` tokens into `wallet`, see
+    /// [`StakePoolState::deposited`].
+    pub fn deposited(
+        &self,
+        pool: &StakePoolState,
+        wallet: &Wallet,
+        sent: TokenAmount,
+    ) -> Result<TokenAmount, Error> {
+        pool.deposited(self.before, wallet.amount(), sent)
+    }
+
+    /// Tokens `pool` debits for a transfer of `sent` tokens out of `wallet`, see
+    /// [`StakePoolState::withdrawn`].
+    pub fn withdrawn(
+        &self,
+        pool: &StakePoolState,
+        wallet: &Wallet,
+        sent: TokenAmount,
+    ) -> Result<TokenAmount, Error> {
+        pool.withdrawn(self.before, wallet.amount(), sent)
+    }
+}
+
+/// Check that `tranches` add up to `reward_amount` and unlock after a genesis of `now`.
+#[cfg(feature = "onchain")]
+fn verify_reward_tranches(
+    now: Checked<i64>,
+    tranches: &[RewardTranche],
+    reward_amount: TokenAmount,
+) -> Result<(), Error> {
+    if tranches.len() > MAX_REWARD_TRANCHES {
+        qlog!("too many reward tranches");
+        return Err(Error::InvalidData);
+    }
+
+    let mut total = 0u64;
+    for tranche in tranches {
+        if tranche.unlock_offset < 0.into()
+            || now
+                .value()
+                .checked_add(tranche.unlock_offset.value())
+                .is_none()
+        {
+            qlog!("invalid reward tranche unlock_offset");
+            return Err(Error::InvalidData);
+        }
+
+        total = match total.checked_add(tranche.amount.value()) {
+            Some(total) => total,
+            None => {
+                qlog!("reward tranches overflow");
+                return Err(Error::InvalidData);
+            }
+        };
+    }
+
+    if total != reward_amount.value() {
+        qlog!("reward tranches should add up to reward_amount");
+        return Err(Error::InvalidData);
+    }
+
+    Ok(())
+}
+
+/// Credit stakes and rewards with the tokens that reached the vault rather than the amount sent,
+/// for mints that take a fee on transfer.
+pub const POOL_FLAG_FEE_ON_TRANSFER: u64 = 1;
+
+/// Reject stakes until the whole reward has been deposited, so stakers can't commit to a pool
+/// that never gets funded.
+pub const POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE: u64 = 2;
+
+const POOL_FLAGS: u64 = POOL_FLAG_FEE_ON_TRANSFER | POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE;
+
+/// Length of the year [`StakePoolEntity::apr`] and [`StakePoolEntity::apy`] annualize over.
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Reward tranches a pool can be created with.
+pub const MAX_REWARD_TRANCHES: usize = 8;
+
+/// Accounts parsed by [`InitializeArgsAccounts`].
+pub const CREATE_POOL_ACCOUNTS: usize = 5;
+
+/// Accounts parsed by [`StakeArgsAccounts`].
+pub const STAKE_ACCOUNTS: usize = 7;
+
+/// Accounts parsed by [`UnStakeArgsAccounts`], shared by `Unstake`, `UnstakePercent` and
+/// `ClaimReward`.
+pub const WITHDRAW_ACCOUNTS: usize = 7;
+
+/// Accounts parsed by [`StakeBatchArgsAccounts`], ahead of the stakes.
+pub const STAKE_BATCH_ACCOUNTS: usize = 3;
+
+/// Accounts of every stake of a `StakeBatch`, see [`StakeBatchEntryAccounts`].
+pub const STAKE_BATCH_ENTRY_ACCOUNTS: usize = 4;
+
+/// Stakes a `StakeBatch` can carry, as many as fit the input next to the token program, the pool
+/// and the vault.
+pub const MAX_STAKE_BATCH: usize =
+    (MAX_ACCOUNTS - STAKE_BATCH_ACCOUNTS) / STAKE_BATCH_ENTRY_ACCOUNTS;
+
+/// Accounts parsed by [`AddRewardArgsAccounts`].
+pub const ADD_REWARD_ACCOUNTS: usize = 5;
+
+/// Accounts parsed by [`SetAdministratorArgsAccounts`].
+pub const SET_ADMINISTRATOR_ACCOUNTS: usize = 3;
+
+/// Space after [`StakePoolState`] holding the reward tranches, a `u64` length followed by
+/// [`MAX_REWARD_TRANCHES`] slots.
+///
+/// Pools created before tranches were added don't have this space and fail
+/// [`AccountType::is_valid_size`], there is no migration for them.
+pub const REWARD_TRANCHES_SIZE: usize =
+    size_of::<u64>() + MAX_REWARD_TRANCHES * size_of::<RewardTranche>();
+
 #[derive(Debug, PartialEq, Eq, Clone, parity_scale_codec::Encode, parity_scale_codec::Decode)]
 pub enum Method {
     CreatePool(InitializeArgs),
@@ -38,6 +238,76 @@ pub enum Method {
     Unstake { amount: TokenAmount },
     ClaimReward,
     AddReward { amount: TokenAmount },
+    UnstakePercent { bps: u16 },
+    SetAdministrator,
+    StakeBatch { amounts: Vec<TokenAmount> },
+}
+
+impl std::fmt::Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Method::CreatePool(args) => write!(
+                f,
+                "CreatePool(lockup_duration: {}, topup_duration: {}, target_amount: {}, reward_amount: {})",
+                args.lockup_duration.value(),
+                args.topup_duration.value(),
+                args.target_amount.value(),
+                args.reward_amount.value()
+            ),
+            Method::Stake { amount } => write!(f, "Stake(amount: {})", amount.value()),
+            Method::Unstake { amount } => write!(f, "Unstake(amount: {})", amount.value()),
+            Method::ClaimReward => write!(f, "ClaimReward"),
+            Method::AddReward { amount } => write!(f, "AddReward(amount: {})", amount.value()),
+            Method::UnstakePercent { bps } => write!(f, "UnstakePercent(bps: {})", bps),
+            Method::SetAdministrator => write!(f, "SetAdministrator"),
+            Method::StakeBatch { amounts } => write!(f, "StakeBatch(stakes: {})", amounts.len()),
+        }
+    }
+}
+
+impl Loggable for Method {
+    fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>) {
+        let (name, amount) = match self {
+            Method::CreatePool(_) => ("CreatePool", None),
+            Method::Stake { amount } => ("Stake", Some(amount.value())),
+            Method::Unstake { amount } => ("Unstake", Some(amount.value())),
+            Method::ClaimReward => ("ClaimReward", None),
+            Method::AddReward { amount } => ("AddReward", Some(amount.value())),
+            Method::UnstakePercent { bps } => ("UnstakePercent", Some(*bps as u64)),
+            Method::SetAdministrator => ("SetAdministrator", None),
+            Method::StakeBatch { amounts } => ("StakeBatch", Some(amounts.len() as u64)),
+        };
+
+        logger.push_str(name);
+        if let Some(amount) = amount {
+            logger.push_str("(");
+            logger.push_int(amount);
+            logger.push_str(")");
+        }
+    }
+}
+
+/// Phase of a pool at a given time, as returned by [`StakePoolEntity::activation_state`].
+///
+/// These are phases of a token staking pool, there is no native stake account behind it.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum ActivationState {
+    /// Stakes are accepted and can be withdrawn again.
+    Topup,
+    /// Stakes are locked until the lockup ends.
+    Locked,
+    /// Stakes can be withdrawn along with their reward.
+    Expired,
+}
+
+/// Part of the pool reward that unlocks `unlock_offset` seconds after genesis.
+#[repr(C)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, parity_scale_codec::Encode, parity_scale_codec::Decode,
+)]
+pub struct RewardTranche {
+    pub unlock_offset: Checked<i64>,
+    pub amount: TokenAmount,
 }
 
 #[derive(Debug)]
@@ -45,6 +315,11 @@ pub struct StakePool;
 #[derive(Debug)]
 pub struct StakerTicket;
 
+/// On-chain layout of a pool, followed by its reward tranches.
+///
+/// The layout is not versioned. It grew with the pool flags, the free list of the id allocator,
+/// the reward tranches and `initial_administrator`, so pools created by an earlier build of the
+/// program fail [`AccountType::is_valid_size`] and have to be recreated after an upgrade.
 #[repr(C)]
 pub struct StakePoolState {
     pub administrator_authority: Pubkey,
@@ -63,23 +338,91 @@ pub struct StakePoolState {
     pub genesis: Checked<i64>,
     pub lockup_duration: Checked<i64>,
     pub topup_duration: Checked<i64>,
+
+    pub flags: u64,
+
+    /// Administrator the pool was created with.
+    ///
+    /// `program_authority` is derived from it, so it stays fixed when `administrator_authority`
+    /// changes hands and the vault keeps its authority.
+    pub initial_administrator: Pubkey,
+}
+
+impl StakePoolState {
+    pub fn fee_on_transfer(&self) -> bool {
+        self.flags & POOL_FLAG_FEE_ON_TRANSFER != 0
+    }
+
+    pub fn require_funded_before_stake(&self) -> bool {
+        self.flags & POOL_FLAG_REQUIRE_FUNDED_BEFORE_STAKE != 0
+    }
+
+    /// Whether the whole reward has been deposited.
+    pub fn is_funded(&self) -> bool {
+        self.deposited_reward_amount == self.reward_amount
+    }
+
+    /// Tokens credited by a transfer of `sent` tokens into the vault, which raised its balance
+    /// from `before` to `after`.
+    ///
+    /// Pools of mints taking a fee on transfer credit what actually arrived, other pools require
+    /// the full amount.
+    pub fn deposited(
+        &self,
+        before: TokenAmount,
+        after: TokenAmount,
+        sent: TokenAmount,
+    ) -> Result<TokenAmount, Error> {
+        if !self.fee_on_transfer() {
+            return verify_deposit(before, after, sent).map(|_| sent);
+        }
+
+        match after.value().checked_sub(before.value()) {
+            Some(received) if received <= sent.value() => Ok(received.into()),
+            _ => Err(Error::InvalidAmountTransferred),
+        }
+    }
+
+    /// Tokens debited by a transfer of `sent` tokens out of the vault, which lowered its balance
+    /// from `before` to `after`.
+    pub fn withdrawn(
+        &self,
+        before: TokenAmount,
+        after: TokenAmount,
+        sent: TokenAmount,
+    ) -> Result<TokenAmount, Error> {
+        self.deposited(after, before, sent)
+    }
 }
 
 #[repr(C)]
 pub struct StakerTicketState {
     pub authority: Pubkey,
     pub staked_amount: TokenAmount,
+    /// Principal and reward paid out by claims so far. The ticket stays open until the last
+    /// reward tranche matured, so later tranches are paid on top of this.
+    pub claimed_amount: TokenAmount,
+}
+
+const_assert_eq!(size_of::<StakerTicketState>(), 48);
+
+impl StakerTicketState {
+    /// Slice of a ticket account holding just `staked_amount`, so clients can fetch the stake
+    /// of many tickets without downloading whole accounts.
+    pub fn staked_amount_slice() -> AccountSlice {
+        field_slice!(HEADER_RESERVED, StakerTicketState, staked_amount)
+    }
 }
 
 impl AccountType for StakePool {
     const KIND: EntityKind = EntityKind::SimpleStakePool;
 
     fn is_valid_size(size: usize) -> bool {
-        size == size_of::<StakePoolState>()
+        size == size_of::<StakePoolState>() + REWARD_TRANCHES_SIZE
     }
 
     fn default_size() -> usize {
-        size_of::<StakePoolState>() + HEADER_RESERVED
+        size_of::<StakePoolState>() + REWARD_TRANCHES_SIZE + HEADER_RESERVED
     }
 }
 
@@ -111,7 +454,7 @@ impl<B: AccountBackend> InitializeArgsAccounts<B> {
     #[cfg(feature = "onchain")]
     #[inline]
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
-        parse_accounts! {
+        try_parse_accounts! {
             &administrator_authority,
             &program_authority,
             &mut pool,
@@ -136,6 +479,11 @@ pub struct InitializeArgs {
     pub topup_duration: Checked<i64>,
     pub target_amount: TokenAmount,
     pub reward_amount: TokenAmount,
+    /// `POOL_FLAG_*` bits.
+    pub flags: u64,
+    /// Schedule `reward_amount` unlocks on, adding up to it. The whole reward unlocks when the
+    /// pool expires if empty.
+    pub reward_tranches: Vec<RewardTranche>,
 }
 
 #[derive(Debug)]
@@ -157,11 +505,28 @@ pub struct UnStakeArgsAccounts<B: AccountBackend> {
     pub pool: Entity<B, StakePool>,
     pub ticket: Entity<B, StakerTicket>,
     pub staker: B,
-    pub program_authority: B,
+    pub program_authority: Authority<B>,
     pub stake_vault: WalletAccount<B>,
     pub target_wallet: WalletAccount<B>,
 }
 
+/// Accounts ahead of the stakes of a `StakeBatch`.
+#[derive(Debug)]
+pub struct StakeBatchArgsAccounts<B: AccountBackend> {
+    pub token_program: TokenProgram<B>,
+    pub pool: Entity<B, StakePool>,
+    pub stake_vault: WalletAccount<B>,
+}
+
+/// Accounts of one stake of a `StakeBatch`, laid out like the tail of [`StakeArgsAccounts`].
+#[derive(Debug)]
+pub struct StakeBatchEntryAccounts<B: AccountBackend> {
+    pub staker: B,
+    pub ticket: Entity<B, StakerTicket>,
+    pub source_authority: B,
+    pub source_wallet: WalletAccount<B>,
+}
+
 #[derive(Debug)]
 pub struct AddRewardArgsAccounts<B: AccountBackend> {
     pub token_program: TokenProgram<B>,
@@ -180,7 +545,7 @@ impl<B: AccountBackend> StakeArgsAccounts<B> {
     {
         let program_id = *input.program_id();
 
-        parse_accounts!(
+        try_parse_accounts!(
             &token_program = TokenProgram::load(this)?,
             &mut pool = <Entity<B, StakePool>>::load(&program_id, this)?,
             &staker,
@@ -209,12 +574,14 @@ impl<B: AccountBackend> UnStakeArgsAccounts<B> {
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
 
-        parse_accounts!(
+        try_parse_accounts!(
             &token_program = TokenProgram::load(this)?,
             &mut pool = <Entity<B, StakePool>>::load(&program_id, this)?,
             &mut ticket = pool.load_ticket(this)?,
             &mut staker,
-            &program_authority,
+            &program_authority =
+                Authority::expected_pda(this, &pool.authority_seeds(), &program_id)
+                    .map_err(|_| Error::InvalidAuthority)?,
             &mut stake_vault = pool.stake_vault(this)?,
             &mut target_wallet = pool.stake_wallet(this)?
         );
@@ -231,13 +598,60 @@ impl<B: AccountBackend> UnStakeArgsAccounts<B> {
     }
 }
 
+impl<B: AccountBackend> StakeBatchArgsAccounts<B> {
+    #[cfg(feature = "onchain")]
+    #[inline]
+    pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
+        let program_id = *input.program_id();
+
+        try_parse_accounts!(
+            &token_program = TokenProgram::load(this)?,
+            &mut pool = <Entity<B, StakePool>>::load(&program_id, this)?,
+            &mut stake_vault = pool.stake_vault(this)?
+        );
+
+        Ok(Self {
+            token_program,
+            pool,
+            stake_vault,
+        })
+    }
+}
+
+impl<B: AccountBackend> StakeBatchEntryAccounts<B> {
+    /// Parse the accounts of the next stake from the remaining accounts of `input`.
+    #[cfg(feature = "onchain")]
+    #[inline]
+    pub fn from_program_input<T: AccountSource<B>>(
+        input: &mut T,
+        pool: &mut Entity<B, StakePool>,
+    ) -> Result<Self, Error>
+    where
+        B::Impl: AccountFieldsMut,
+    {
+        try_parse_accounts!(
+            &staker,
+            &mut ticket = pool.load_or_init_ticket(&staker, this)?,
+            &source_authority,
+            &mut source_wallet = pool.stake_wallet(this)?
+        );
+
+        Ok(Self {
+            staker,
+            ticket,
+            source_authority,
+            source_wallet,
+        })
+    }
+}
+
 impl<B: AccountBackend> AddRewardArgsAccounts<B> {
     #[cfg(feature = "onchain")]
     #[inline]
     pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
         let program_id = *input.program_id();
 
-        parse_accounts!(
+        try_parse_accounts!(
             &token_program = TokenProgram::load(this)?,
             &mut pool = <Entity<B, StakePool>>::load(&program_id, this)?,
             &mut stake_vault = pool.stake_vault(this)?,
@@ -255,6 +669,33 @@ impl<B: AccountBackend> AddRewardArgsAccounts<B> {
     }
 }
 
+#[derive(Debug)]
+pub struct SetAdministratorArgsAccounts<B: AccountBackend> {
+    pub pool: Entity<B, StakePool>,
+    pub administrator: B,
+    pub new_administrator: B,
+}
+
+impl<B: AccountBackend> SetAdministratorArgsAccounts<B> {
+    #[cfg(feature = "onchain")]
+    #[inline]
+    pub fn from_program_input<T: AccountSource<B>>(input: &mut T) -> Result<Self, Error> {
+        let program_id = *input.program_id();
+
+        try_parse_accounts!(
+            &mut pool = <Entity<B, StakePool>>::load(&program_id, this)?,
+            &administrator,
+            &new_administrator
+        );
+
+        Ok(Self {
+            pool,
+            administrator,
+            new_administrator,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StakeArgs {
     pub amount: TokenAmount,
@@ -281,13 +722,24 @@ where
 
         let mut entity = Self::raw_any(input.program_id(), pool)?;
 
-        let expected_program_authority = Pubkey::create_program_address(
-            &[
-                entity.account().key().as_ref(),
-                administrator_authority.key().as_ref(),
-                &args.program_authority_salt.to_le_bytes(),
-            ],
+        if entity.header().kind != EntityKind::None || !is_zeroed(entity.account().data()) {
+            qlog!("pool is already initialized");
+            return Err(Error::AccountAlreadyInitialized);
+        }
+
+        let minimum_balance = minimum_balance(entity.account().data().len() as u64);
+        if entity.account().lamports() < minimum_balance {
+            qlog!("pool account is not rent-exempt");
+            return Err(Error::NotRentExempt);
+        }
+
+        // program addresses are off the curve, so matching one also rules out an authority
+        // somebody holds the secret key of
+        let expected_program_authority = pool_authority(
             input.program_id(),
+            entity.account().key(),
+            administrator_authority.key(),
+            args.program_authority_salt,
         )
         .bpf_expect("couldn't derive program authority");
 
@@ -313,8 +765,38 @@ where
             return Err(Error::InvalidData);
         }
 
+        if args.topup_duration < 0.into() || args.lockup_duration < 0.into() {
+            qlog!("pool durations can't be negative");
+            return Err(Error::InvalidData);
+        }
+
+        if args.flags & !POOL_FLAGS != 0 {
+            qlog!("unknown pool flags");
+            return Err(Error::InvalidData);
+        }
+
+        if args.target_amount == 0.into() || args.reward_amount == 0.into() {
+            qlog!("target_amount and reward_amount should be positive");
+            return Err(Error::InvalidData);
+        }
+
+        // `is_expired` adds the lockup to genesis on every call, make sure it can't overflow later
+        if now
+            .value()
+            .checked_add(args.lockup_duration.value())
+            .is_none()
+        {
+            qlog!("lockup_duration is too large");
+            return Err(Error::InvalidData);
+        }
+
+        if !args.reward_tranches.is_empty() {
+            verify_reward_tranches(now, &args.reward_tranches, args.reward_amount)?;
+        }
+
         entity.program_authority = *program_authority.key();
         entity.administrator_authority = *administrator_authority.key();
+        entity.initial_administrator = *administrator_authority.key();
         entity.genesis = now;
         entity.topup_duration = args.topup_duration;
         entity.lockup_duration = args.lockup_duration;
@@ -322,10 +804,18 @@ where
         entity.stake_acquired_amount = 0.into();
         entity.stake_target_amount = args.target_amount;
         entity.reward_amount = args.reward_amount;
+        entity.flags = args.flags;
 
         entity.stake_mint = *stake_mint.key();
         entity.stake_vault = *stake_vault.key();
 
+        {
+            let mut reward_tranches = entity.reward_tranches_mut();
+            for tranche in args.reward_tranches {
+                reward_tranches.push(tranche);
+            }
+        }
+
         let id = entity.allocator.allocate_id();
         let entity_key = *entity.account().key();
         let header = entity.header_mut();
@@ -367,11 +857,137 @@ where
         now > self.genesis() + self.lockup_duration()
     }
 
+    /// Phase of the pool at `now`, following [`Entity::can_topup`] and [`Entity::is_expired`].
+    pub fn activation_state(&self, now: Checked<i64>) -> ActivationState {
+        if self.is_expired(now) {
+            ActivationState::Expired
+        } else if self.can_topup(now) {
+            ActivationState::Topup
+        } else {
+            ActivationState::Locked
+        }
+    }
+
+    /// Annualized reward rate of a full pool, i.e. `reward / target` scaled from the lockup
+    /// duration to a year.
+    ///
+    /// `None` if the pool has no target or lockup duration.
+    #[cfg(not(target_arch = "bpf"))]
+    pub fn apr(&self) -> Option<U64F64> {
+        let (target, duration) = self.reward_terms()?;
+
+        U64F64::checked_from_num(self.reward_amount.value())?
+            .checked_div_int(target)?
+            .checked_mul_int(SECONDS_PER_YEAR)?
+            .checked_div_int(duration)
+    }
+
+    /// Like [`Entity::apr`], but assumes the stake and reward are restaked into an identical
+    /// pool every time the lockup ends.
+    ///
+    /// Offchain only, like [`Entity::apr`]: compounding takes floating point math, which isn't
+    /// deterministic onchain.
+    #[cfg(not(target_arch = "bpf"))]
+    pub fn apy(&self) -> Option<U64F64> {
+        let (target, duration) = self.reward_terms()?;
+
+        let rate = self.reward_amount.value() as f64 / target as f64;
+        let periods = SECONDS_PER_YEAR as f64 / duration as f64;
+
+        U64F64::checked_from_num((1.0 + rate).powf(periods) - 1.0)
+    }
+
+    /// Reward tranches the pool was created with, empty if the whole reward unlocks at once.
+    pub fn reward_tranches(&self) -> VecView<'_, RewardTranche> {
+        VecView::load(&self.body()[size_of::<StakePoolState>()..])
+            .expect("pool size is checked on load")
+    }
+
+    #[cfg(feature = "onchain")]
+    fn reward_tranches_mut(&mut self) -> VecViewMut<'_, RewardTranche>
+    where
+        B::Impl: AccountFieldsMut,
+    {
+        VecViewMut::load(&mut self.body_mut()[size_of::<StakePoolState>()..])
+            .expect("pool size is checked on load")
+    }
+
+    /// Part of the reward unlocked at `now`: the tranches that matured by then, or the whole
+    /// reward once the pool expires if it has no tranches.
+    pub fn matured_reward(&self, now: Checked<i64>) -> TokenAmount {
+        let tranches = self.reward_tranches();
+
+        if tranches.is_empty() {
+            return if self.is_expired(now) {
+                self.reward_amount
+            } else {
+                0.into()
+            };
+        }
+
+        tranches
+            .iter()
+            .filter(|tranche| self.genesis() + tranche.unlock_offset <= now)
+            .fold(TokenAmount::from(0), |matured, tranche| {
+                matured + tranche.amount
+            })
+    }
+
+    /// Stake plus its share of the reward paid out for `staked_amount` once the pool expires
+    /// and every reward tranche matured.
+    ///
+    /// Claims and offchain previews both go through here, so they round the same way.
+    pub fn claim_amount(&self, staked_amount: TokenAmount) -> Option<TokenAmount> {
+        self.claim_amount_of(staked_amount, self.reward_amount)
+    }
+
+    /// Like [`Entity::claim_amount`], but only shares out the reward matured at `now`.
+    ///
+    /// This is what a ticket is owed in total by then, claims pay out the difference to its
+    /// [`StakerTicketState::claimed_amount`].
+    pub fn matured_claim_amount(
+        &self,
+        staked_amount: TokenAmount,
+        now: Checked<i64>,
+    ) -> Option<TokenAmount> {
+        self.claim_amount_of(staked_amount, self.matured_reward(now))
+    }
+
+    fn claim_amount_of(
+        &self,
+        staked_amount: TokenAmount,
+        reward_amount: TokenAmount,
+    ) -> Option<TokenAmount> {
+        let staked_amount = U64F64::checked_from_num(staked_amount.value())?;
+        let stake_acquired_amount = U64F64::checked_from_num(self.stake_acquired_amount.value())?;
+        let reward_amount = U64F64::checked_from_num(reward_amount.value())?;
+
+        let share = staked_amount.checked_div(stake_acquired_amount)?;
+        let reward_share = share.checked_mul(reward_amount)?;
+
+        staked_amount
+            .checked_add(reward_share)?
+            .checked_as::<u64>()
+            .map(TokenAmount::from)
+    }
+
+    #[cfg(not(target_arch = "bpf"))]
+    fn reward_terms(&self) -> Option<(u64, u64)> {
+        let target = self.stake_target_amount.value();
+        let duration = self.lockup_duration.value();
+
+        if target == 0 || duration <= 0 {
+            None
+        } else {
+            Some((target, duration as u64))
+        }
+    }
+
     #[inline]
     pub fn authority_seeds(&self) -> [&[u8]; 3] {
         [
             self.account().key().as_ref(),
-            self.administrator_authority.as_ref(),
+            self.initial_administrator.as_ref(),
             as_bytes(&self.program_authority_salt),
         ]
     }
@@ -413,11 +1029,8 @@ where
     pub fn load_ticket(&self, ticket: B) -> Result<Entity<B, StakerTicket>, Error> {
         let ticket = Entity::<B, StakerTicket>::raw_any(self.account().owner(), ticket)?;
         if ticket.header().kind == EntityKind::SimpleStakeTicket {
-            if !ticket.is_child(self) {
-                Err(Error::InvalidParent)
-            } else {
-                Ok(ticket)
-            }
+            ticket.verify_child_of(self)?;
+            Ok(ticket)
         } else {
             Err(Error::InvalidKind)
         }
@@ -436,11 +1049,8 @@ where
         let mut ticket = Entity::<B, StakerTicket>::raw_any(self.account().owner(), ticket)?;
 
         if ticket.header().kind == EntityKind::SimpleStakeTicket {
-            if !ticket.is_child(self) {
-                Err(Error::InvalidParent)
-            } else {
-                Ok(ticket)
-            }
+            ticket.verify_child_of(self)?;
+            Ok(ticket)
         } else if ticket.header().kind == EntityKind::None {
             if !is_zeroed(ticket.account().data()) {
                 Err(Error::InvalidData)
@@ -477,42 +1087,128 @@ where
             ..
         } = StakeArgsAccounts::from_program_input(input)?;
 
-        if source_wallet.amount() < amount {
-            qlog!("not enough funds in wallet");
-            return Err(Error::Validation);
+        pool.require_stakes_accepted()?;
+        pool.stake_from(
+            &token_program,
+            &mut ticket,
+            &mut stake_vault,
+            &source_authority,
+            &mut source_wallet,
+            amount,
+        )
+    }
+
+    /// Stake `amounts[i]` on the `i`-th ticket of the batch, failing as a whole if any of them
+    /// fails.
+    #[cfg(feature = "onchain")]
+    #[inline(never)]
+    pub fn add_stake_batch<T>(input: &mut T, amounts: &[TokenAmount]) -> Result<(), Error>
+    where
+        B: AccountBackend<Impl = Account>,
+        T: AccountSource<B>,
+    {
+        if amounts.is_empty() || amounts.len() > MAX_STAKE_BATCH {
+            qlog!("stake batch should hold 1 to ", MAX_STAKE_BATCH, " stakes");
+            return Err(Error::InvalidData);
+        }
+
+        let StakeBatchArgsAccounts {
+            token_program,
+            mut pool,
+            mut stake_vault,
+        } = StakeBatchArgsAccounts::from_program_input(input)?;
+
+        if input.remaining() != amounts.len() * STAKE_BATCH_ENTRY_ACCOUNTS {
+            qlog!("stake batch accounts don't match its amounts");
+            return Err(Error::InvalidData);
+        }
+
+        pool.require_stakes_accepted()?;
+
+        for amount in amounts {
+            let StakeBatchEntryAccounts {
+                mut ticket,
+                source_authority,
+                mut source_wallet,
+                ..
+            } = StakeBatchEntryAccounts::from_program_input(input, &mut pool)?;
+
+            pool.stake_from(
+                &token_program,
+                &mut ticket,
+                &mut stake_vault,
+                &source_authority,
+                &mut source_wallet,
+                *amount,
+            )?;
         }
 
+        Ok(())
+    }
+
+    /// Fail unless the pool takes stakes at the moment.
+    #[cfg(feature = "onchain")]
+    fn require_stakes_accepted(&self) -> Result<(), Error> {
         let now = timestamp_now();
 
-        if !pool.can_topup(now) {
+        if !self.can_topup(now) {
             qlog!("pool is locked and funds can no longer be added");
             return Err(Error::Validation);
         }
 
-        let transfer_amount = amount.min(pool.stake_target_amount - pool.stake_acquired_amount);
+        if self.require_funded_before_stake() && !self.is_funded() {
+            qlog!("pool reward is not fully deposited yet");
+            return Err(Error::Validation);
+        }
+
+        Ok(())
+    }
+
+    /// Move `amount` tokens, capped at what the pool still takes, from `source_wallet` into the
+    /// vault and credit what arrived to `ticket`.
+    #[cfg(feature = "onchain")]
+    fn stake_from(
+        &mut self,
+        token_program: &TokenProgram<B>,
+        ticket: &mut Entity<B, StakerTicket>,
+        stake_vault: &mut WalletAccount<B>,
+        source_authority: &B,
+        source_wallet: &mut WalletAccount<B>,
+        amount: TokenAmount,
+    ) -> Result<(), Error>
+    where
+        B: AccountBackend<Impl = Account>,
+    {
+        if source_wallet.amount() < amount {
+            qlog!("not enough funds in wallet");
+            return Err(Error::Validation);
+        }
+
+        let transfer_amount = amount.min(self.stake_target_amount - self.stake_acquired_amount);
 
         if transfer_amount == 0.into() {
             qlog!("pool is full");
             return Err(Error::Validation);
         }
 
-        let amount_before = stake_vault.amount();
+        self.enter()?;
+        let vault_balance = BalanceGuard::new(stake_vault);
         token_program
             .transfer(
-                &mut source_wallet,
-                &mut stake_vault,
+                source_wallet,
+                stake_vault,
                 transfer_amount.value(),
-                &source_authority,
+                source_authority,
                 &[],
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
-        let amount_after = stake_vault.amount();
+        self.exit();
 
-        assert!(amount_after - amount_before == transfer_amount);
+        let received = vault_balance.deposited(self, stake_vault, transfer_amount)?;
 
-        pool.stake_acquired_amount += transfer_amount;
-        ticket.staked_amount += transfer_amount;
+        self.stake_acquired_amount += received;
+        ticket.staked_amount += received;
 
         Ok(())
     }
@@ -523,6 +1219,35 @@ where
     where
         B: AccountBackend<Impl = Account>,
         T: AccountSource<B>,
+    {
+        Self::remove_stake_with(input, |_| amount)
+    }
+
+    /// Unstake `bps` basis points of the ticket, rounded down.
+    #[cfg(feature = "onchain")]
+    #[inline(never)]
+    pub fn remove_stake_percent<T>(input: &mut T, bps: u16) -> Result<(), Error>
+    where
+        B: AccountBackend<Impl = Account>,
+        T: AccountSource<B>,
+    {
+        if bps as u64 > BPS_DENOMINATOR {
+            qlog!("can't unstake more than 10000 bps");
+            return Err(Error::Validation);
+        }
+
+        Self::remove_stake_with(input, |staked_amount| {
+            mul_bps(staked_amount.value(), bps).bpf_unwrap().into()
+        })
+    }
+
+    /// Unstake the amount `amount` picks given the stake of the ticket.
+    #[cfg(feature = "onchain")]
+    fn remove_stake_with<T, F>(input: &mut T, amount: F) -> Result<(), Error>
+    where
+        B: AccountBackend<Impl = Account>,
+        T: AccountSource<B>,
+        F: FnOnce(TokenAmount) -> TokenAmount,
     {
         let UnStakeArgsAccounts {
             token_program,
@@ -534,15 +1259,7 @@ where
             mut target_wallet,
         } = UnStakeArgsAccounts::from_program_input(input)?;
 
-        if !pubkey_eq(&ticket.authority, staker.key()) {
-            qlog!("wrong staker provided");
-            return Err(Error::Validation);
-        }
-
-        if !staker.is_signer() {
-            qlog!("the staker is expected to sign");
-            return Err(Error::Validation);
-        }
+        staker.require_key(&ticket.authority)?.require_signer()?;
 
         let now = timestamp_now();
 
@@ -551,27 +1268,31 @@ where
             return Err(Error::Validation);
         }
 
-        let transfer_amount = amount.min(ticket.staked_amount);
+        let transfer_amount = amount(ticket.staked_amount).min(ticket.staked_amount);
 
+        pool.enter()?;
         let seeds = pool.authority_seeds();
-        let amount_before = stake_vault.amount();
+        let vault_balance = BalanceGuard::new(&stake_vault);
         token_program
             .transfer(
                 &mut stake_vault,
                 &mut target_wallet,
                 transfer_amount.value(),
-                &program_authority,
+                program_authority.account(),
                 &[&seeds],
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
-        let amount_after = stake_vault.amount();
+        pool.exit();
 
-        assert!(amount_before - amount_after == transfer_amount);
+        let withdrawn = vault_balance.withdrawn(&pool, &stake_vault, transfer_amount)?;
 
-        pool.stake_acquired_amount -= transfer_amount;
-        ticket.staked_amount -= transfer_amount;
-        ticket.collect(&mut staker)?;
+        pool.stake_acquired_amount -= withdrawn;
+        ticket.staked_amount -= withdrawn;
+        let ticket_id = ticket.header().id;
+        if ticket.collect(&mut staker)? {
+            pool.allocator.release_id(ticket_id);
+        }
 
         Ok(())
     }
@@ -585,7 +1306,7 @@ where
     {
         let UnStakeArgsAccounts {
             token_program,
-            pool,
+            mut pool,
             mut staker,
             mut ticket,
             program_authority,
@@ -593,15 +1314,7 @@ where
             mut target_wallet,
         } = UnStakeArgsAccounts::from_program_input(input)?;
 
-        if !pubkey_eq(&ticket.authority, staker.key()) {
-            qlog!("wrong staker provided");
-            return Err(Error::Validation);
-        }
-
-        if !staker.is_signer() {
-            qlog!("the staker is expected to sign");
-            return Err(Error::Validation);
-        }
+        staker.require_key(&ticket.authority)?.require_signer()?;
 
         let now = timestamp_now();
 
@@ -610,35 +1323,42 @@ where
             return Err(Error::Validation);
         }
 
-        let staked_amount = ticket.staked_amount.to_u64f64();
-        let stake_acquired_amount = pool.stake_acquired_amount.to_u64f64();
-        let reward_amount = pool.reward_amount.to_u64f64();
-
-        let share = staked_amount / stake_acquired_amount;
-        let reward_share = share * reward_amount;
-
-        let transfer_amount = (staked_amount + reward_share)
-            .checked_as::<TokenAmount>()
+        let owed_amount = pool
+            .matured_claim_amount(ticket.staked_amount, now)
             .bpf_unwrap();
+        let transfer_amount = owed_amount - ticket.claimed_amount;
 
+        if transfer_amount == 0.into() {
+            qlog!("no reward matured since the last claim");
+            return Err(Error::Validation);
+        }
+
+        pool.enter()?;
         let seeds = pool.authority_seeds();
-        let amount_before = stake_vault.amount();
+        let vault_balance = BalanceGuard::new(&stake_vault);
         token_program
             .transfer(
                 &mut stake_vault,
                 &mut target_wallet,
                 transfer_amount.value(),
-                &program_authority,
+                program_authority.account(),
                 &[&seeds],
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
-        let amount_after = stake_vault.amount();
+        pool.exit();
 
-        assert!(amount_before - amount_after == transfer_amount);
+        vault_balance.withdrawn(&pool, &stake_vault, transfer_amount)?;
 
-        ticket.staked_amount = 0.into();
-        assert!(ticket.collect(&mut staker)?);
+        ticket.claimed_amount += transfer_amount;
+
+        // the ticket is kept until every tranche matured and its whole share has been paid
+        if owed_amount == pool.claim_amount(ticket.staked_amount).bpf_unwrap() {
+            ticket.staked_amount = 0.into();
+            let ticket_id = ticket.header().id;
+            assert!(ticket.collect(&mut staker)?);
+            pool.allocator.release_id(ticket_id);
+        }
 
         Ok(())
     }
@@ -674,7 +1394,8 @@ where
             return Err(Error::Validation);
         }
 
-        let amount_before = stake_vault.amount();
+        pool.enter()?;
+        let vault_balance = BalanceGuard::new(&stake_vault);
         token_program
             .transfer(
                 &mut source_wallet,
@@ -685,28 +1406,80 @@ where
             )
             .bpf_expect("call failed")
             .bpf_expect("transfer failed");
-        let amount_after = stake_vault.amount();
-        assert!(amount_after - amount_before == transfer_amount);
+        pool.exit();
+        let received = vault_balance.deposited(&pool, &stake_vault, transfer_amount)?;
 
-        pool.deposited_reward_amount += transfer_amount;
+        pool.deposited_reward_amount += received;
         assert!(pool.deposited_reward_amount <= pool.reward_amount);
 
         Ok(())
     }
 
+    /// Hand the pool over to `new_administrator`, signed by the current administrator.
+    ///
+    /// The program authority keeps being derived from the initial administrator, see
+    /// [`StakePoolState::initial_administrator`].
+    #[cfg(feature = "onchain")]
+    #[inline(never)]
+    pub fn set_administrator<T>(input: &mut T) -> Result<(), Error>
+    where
+        B::Impl: AccountFieldsMut,
+        T: AccountSource<B>,
+    {
+        let SetAdministratorArgsAccounts {
+            mut pool,
+            administrator,
+            new_administrator,
+        } = SetAdministratorArgsAccounts::from_program_input(input)?;
+
+        administrator
+            .require_key(&pool.administrator_authority)?
+            .require_signer()?;
+
+        pool.administrator_authority = *new_administrator.key();
+
+        Ok(())
+    }
+
     pub fn load(program_id: &Pubkey, account: B) -> Result<Self, Error> {
         Self::raw_initialized(program_id, account)
     }
 }
 
 impl<B: AccountBackend> Entity<B, StakerTicket> {
+    /// Principal and reward the ticket would receive if claimed at `now`, `None` while the pool
+    /// is still locked.
+    ///
+    /// The principal is paid by the first claim, later claims only pay reward tranches that
+    /// matured since.
+    pub fn claimable<P: AccountBackend>(
+        &self,
+        pool: &Entity<P, StakePool>,
+        now: Checked<i64>,
+    ) -> Option<(TokenAmount, TokenAmount)> {
+        if !pool.is_expired(now) {
+            return None;
+        }
+
+        let owed = pool.matured_claim_amount(self.staked_amount, now)?;
+        let principal = self
+            .staked_amount
+            .value()
+            .saturating_sub(self.claimed_amount.value());
+        let payout = owed.value().checked_sub(self.claimed_amount.value())?;
+
+        Some((principal.into(), (payout - principal).into()))
+    }
+
+    /// Close the ticket once nothing is staked on it, its rent goes to `beneficiary`.
+    ///
+    /// The data is zeroed along with the balance, read the header before if its id is needed.
     pub fn collect(&mut self, beneficiary: &mut B) -> Result<bool, Error>
     where
         B: AccountFieldsMut,
     {
         if self.staked_amount == 0.into() {
-            beneficiary.set_lamports(beneficiary.lamports() + self.account().lamports());
-            self.account_mut().set_lamports(0);
+            close_account(self.account_mut(), beneficiary);
             Ok(true)
         } else {
             Ok(false)
@@ -0,0 +1,216 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CommitmentConfig, EncodedTransactionWithStatusMeta, Hash, Rewards, RpcError, Signature, Slot,
+    Transaction, UiTransactionEncoding, UiTransactionStatusMeta, UnixTimestamp,
+};
+
+/// How much of its transactions `getBlock` returns.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionDetails {
+    Full,
+    /// Only the signatures, which is much cheaper for blocks with many transactions.
+    Signatures,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlockConfig {
+    /// Only binary encodings can be decoded, base64 is requested if `None`.
+    pub encoding: Option<UiTransactionEncoding>,
+    /// Full transactions if `None`.
+    pub transaction_details: Option<TransactionDetails>,
+    pub rewards: Option<bool>,
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+}
+
+impl RpcBlockConfig {
+    /// `cfg` with the transaction encoding filled in.
+    pub fn with_binary_encoding(cfg: Option<RpcBlockConfig>) -> Self {
+        let cfg = cfg.unwrap_or_default();
+
+        Self {
+            encoding: Some(cfg.encoding.unwrap_or(UiTransactionEncoding::Base64)),
+            ..cfg
+        }
+    }
+}
+
+/// A block as returned by `getBlock`, before its transactions are decoded.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiConfirmedBlock {
+    pub previous_blockhash: String,
+    pub blockhash: String,
+    pub parent_slot: Slot,
+    #[serde(default)]
+    pub transactions: Option<Vec<EncodedTransactionWithStatusMeta>>,
+    #[serde(default)]
+    pub signatures: Option<Vec<String>>,
+    #[serde(default)]
+    pub rewards: Option<Rewards>,
+    pub block_time: Option<UnixTimestamp>,
+    #[serde(default)]
+    pub block_height: Option<u64>,
+}
+
+/// A transaction of a [`ConfirmedBlock`] along with its status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmedTransaction {
+    pub transaction: Transaction,
+    pub meta: Option<UiTransactionStatusMeta>,
+}
+
+impl TryFrom<EncodedTransactionWithStatusMeta> for ConfirmedTransaction {
+    type Error = RpcError;
+
+    fn try_from(encoded: EncodedTransactionWithStatusMeta) -> Result<Self, Self::Error> {
+        let transaction = encoded
+            .transaction
+            .decode()
+            .ok_or_else(|| RpcError::ParseError("binary encoded transaction".to_string()))?;
+
+        Ok(Self {
+            transaction,
+            meta: encoded.meta,
+        })
+    }
+}
+
+/// A block with its transactions decoded.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "UiConfirmedBlock")]
+pub struct ConfirmedBlock {
+    pub previous_blockhash: Hash,
+    pub blockhash: Hash,
+    pub parent_slot: Slot,
+    /// Empty unless the block was fetched with [`TransactionDetails::Full`].
+    pub transactions: Vec<ConfirmedTransaction>,
+    /// First signature of every transaction, empty for [`TransactionDetails::None`].
+    pub signatures: Vec<Signature>,
+    pub rewards: Rewards,
+    pub block_time: Option<UnixTimestamp>,
+    pub block_height: Option<u64>,
+}
+
+impl TryFrom<UiConfirmedBlock> for ConfirmedBlock {
+    type Error = RpcError;
+
+    fn try_from(block: UiConfirmedBlock) -> Result<Self, Self::Error> {
+        let hash = |hash: &str| {
+            Hash::from_str(hash).map_err(|_| RpcError::ParseError("blockhash".to_string()))
+        };
+
+        let transactions = block
+            .transactions
+            .unwrap_or_default()
+            .into_iter()
+            .map(ConfirmedTransaction::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let signatures = match block.signatures {
+            Some(signatures) => signatures
+                .iter()
+                .map(|signature| {
+                    Signature::from_str(signature)
+                        .map_err(|_| RpcError::ParseError("signature".to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => transactions
+                .iter()
+                .filter_map(|confirmed| confirmed.transaction.signatures.first().copied())
+                .collect(),
+        };
+
+        Ok(Self {
+            previous_blockhash: hash(&block.previous_blockhash)?,
+            blockhash: hash(&block.blockhash)?,
+            parent_slot: block.parent_slot,
+            transactions,
+            signatures,
+            rewards: block.rewards.unwrap_or_default(),
+            block_time: block.block_time,
+            block_height: block.block_height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::*;
+
+    fn block_json(details: serde_json::Value) -> serde_json::Value {
+        let mut block = json!({
+            "previousBlockhash": Hash([1; 32]).to_string(),
+            "blockhash": Hash([2; 32]).to_string(),
+            "parentSlot": 41,
+            "blockTime": 1_630_000_000,
+            "blockHeight": 40,
+        });
+        block
+            .as_object_mut()
+            .unwrap()
+            .extend(details.as_object().unwrap().clone());
+        block
+    }
+
+    #[test]
+    fn confirmed_block_test() {
+        let payer = Pubkey::new([3; 32]);
+        let mut transaction = Transaction::new_with_payer(
+            &[system::transfer(&payer, &Pubkey::new([4; 32]), 1)],
+            Some(&payer),
+        );
+        transaction.signatures = vec![Signature::new([7; 64])];
+        let encoded = transaction.encode(UiTransactionEncoding::Base64).unwrap();
+
+        let full = block_json(json!({
+            "transactions": [{ "transaction": [encoded, "base64"], "meta": null }],
+        }));
+        let block: Option<ConfirmedBlock> = serde_json::from_value(full).unwrap();
+        let block = block.unwrap();
+        assert_eq!(block.previous_blockhash, Hash([1; 32]));
+        assert_eq!(block.blockhash, Hash([2; 32]));
+        assert_eq!(block.parent_slot, 41);
+        assert_eq!(block.block_time, Some(1_630_000_000));
+        assert_eq!(block.transactions[0].transaction, transaction);
+        assert_eq!(block.signatures, transaction.signatures);
+
+        let signatures = block_json(json!({ "signatures": [Signature::new([7; 64]).to_string()] }));
+        let block: ConfirmedBlock = serde_json::from_value(signatures).unwrap();
+        assert!(block.transactions.is_empty());
+        assert_eq!(block.signatures, transaction.signatures);
+
+        // skipped slots have no block
+        let skipped: Option<ConfirmedBlock> = serde_json::from_value(json!(null)).unwrap();
+        assert_eq!(skipped, None);
+
+        // json encoded transactions can't be decoded
+        let json_encoded = block_json(json!({
+            "transactions": [{
+                "transaction": {
+                    "signatures": [],
+                    "message": {
+                        "header": {
+                            "numRequiredSignatures": 0,
+                            "numReadonlySignedAccounts": 0,
+                            "numReadonlyUnsignedAccounts": 0,
+                        },
+                        "accountKeys": [],
+                        "recentBlockhash": Hash::default().to_string(),
+                        "instructions": [],
+                    },
+                },
+                "meta": null,
+            }],
+        }));
+        assert!(serde_json::from_value::<ConfirmedBlock>(json_encoded).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+use std::convert::TryInto;
+
+use solar_macros::parse_base58;
+
+use crate::{instruction::Instruction, Pubkey};
+
+pub const ID: &Pubkey = &Pubkey::new(parse_base58!("ComputeBudget111111111111111111111111111111"));
+
+/// Most compute units a transaction can request.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+
+/// Limit the compute units the whole transaction may consume to `units`.
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+    data.extend_from_slice(&units.to_le_bytes());
+
+    Instruction {
+        program_id: *ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Limit set by `instruction`, `None` if it isn't a `set_compute_unit_limit` instruction.
+pub fn compute_unit_limit(instruction: &Instruction) -> Option<u32> {
+    match instruction.data.split_first() {
+        Some((&SET_COMPUTE_UNIT_LIMIT, units)) if instruction.program_id == *ID => {
+            Some(u32::from_le_bytes(units.try_into().ok()?))
+        }
+        _ => None,
+    }
+}
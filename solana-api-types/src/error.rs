@@ -90,6 +90,9 @@ pub enum RpcRequest {
     SignVote,
 }
 
+/// Code of the error a node answers with when it is behind the `minContextSlot` of a request.
+pub const JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
 #[derive(Debug)]
 pub enum RpcResponseErrorData {
     Empty,
@@ -160,15 +163,26 @@ pub enum ClientErrorKind {
     Custom(String),
 }
 
-#[derive(Error, Debug)]
-#[error("{kind}")]
+#[derive(Debug)]
 pub struct ClientError {
     pub request: Option<RpcRequest>,
-
-    #[source]
     pub kind: ClientErrorKind,
 }
 
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for ClientError {
+    /// Most kinds display the error they wrap, so the chain continues with the source of that
+    /// error instead of repeating its message, e.g. when reported through `anyhow`.
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&self.kind)
+    }
+}
+
 impl From<ClientErrorKind> for ClientError {
     fn from(kind: ClientErrorKind) -> Self {
         Self {
@@ -266,3 +280,24 @@ impl Into<JsValue> for ClientError {
         JsValue::from_str(s.as_str())
     }
 }
+
+#[cfg(all(test, feature = "rpc"))]
+mod tests {
+    use super::ClientError;
+
+    #[test]
+    fn anyhow_chain_test() {
+        let transport = reqwest::Client::new().get("not a url").build().unwrap_err();
+        let mut expected = vec![transport.to_string()];
+        let mut source = std::error::Error::source(&transport);
+        while let Some(err) = source {
+            expected.push(err.to_string());
+            source = err.source();
+        }
+
+        let err = anyhow::Error::from(ClientError::from(transport));
+        let chain: Vec<_> = err.chain().map(ToString::to_string).collect();
+        assert_eq!(chain, expected);
+        assert!(format!("{:#}", err).starts_with(&expected.join(": ")));
+    }
+}
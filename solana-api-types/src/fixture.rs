@@ -0,0 +1,180 @@
+//! Sets of accounts captured from a cluster or a test, so the state a bug shows up in can be
+//! attached to the report and replayed with `ProgramTest::add_fixture`.
+//!
+//! Fixtures are JSON, keys in base58 and data in base64, e.g.
+//!
+//! ```json
+//! {
+//!     "accounts": [
+//!         {
+//!             "pubkey": "6Bho5aBGh5ppwxXYfLQGsNbVn2tnpCmcrvVpDbMvTtvD",
+//!             "owner": "11111111111111111111111111111111",
+//!             "lamports": 1000000,
+//!             "data": "AQID"
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! `executable` and `rentEpoch` may be left out, they default to `false` and 0.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Account, Epoch, Pubkey};
+
+#[derive(Debug, Error)]
+pub enum FixtureError {
+    #[error("invalid fixture: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid pubkey {0}")]
+    Pubkey(String),
+    #[error("invalid data of account {0}: {1}")]
+    Data(Pubkey, base64::DecodeError),
+}
+
+/// An account as written in a fixture.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawAccount {
+    pubkey: String,
+    owner: String,
+    lamports: u64,
+    data: String,
+    #[serde(default)]
+    executable: bool,
+    #[serde(default)]
+    rent_epoch: Epoch,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFixture {
+    accounts: Vec<RawAccount>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountFixture {
+    pub accounts: Vec<Account>,
+}
+
+impl AccountFixture {
+    pub fn new(accounts: Vec<Account>) -> Self {
+        Self { accounts }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, FixtureError> {
+        let raw: RawFixture = serde_json::from_str(json)?;
+        let pubkey =
+            |key: &str| Pubkey::from_str(key).map_err(|_| FixtureError::Pubkey(key.into()));
+
+        let accounts = raw
+            .accounts
+            .into_iter()
+            .map(|account| {
+                let key = pubkey(&account.pubkey)?;
+                Ok(Account {
+                    lamports: account.lamports,
+                    data: base64::decode(&account.data)
+                        .map_err(|err| FixtureError::Data(key, err))?,
+                    owner: pubkey(&account.owner)?,
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                    pubkey: key,
+                })
+            })
+            .collect::<Result<_, FixtureError>>()?;
+
+        Ok(Self { accounts })
+    }
+
+    pub fn to_json(&self) -> String {
+        let raw = RawFixture {
+            accounts: self
+                .accounts
+                .iter()
+                .map(|account| RawAccount {
+                    pubkey: account.pubkey.to_string(),
+                    owner: account.owner.to_string(),
+                    lamports: account.lamports,
+                    data: base64::encode(&account.data),
+                    executable: account.executable,
+                    rent_epoch: account.rent_epoch,
+                })
+                .collect(),
+        };
+
+        serde_json::to_string_pretty(&raw).expect("fixtures always serialize")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountFixture, FixtureError};
+    use crate::{Account, Pubkey};
+
+    #[test]
+    fn account_fixture_test() {
+        let fixture = AccountFixture::new(vec![
+            Account {
+                lamports: 1_000_000,
+                data: vec![1, 2, 3],
+                owner: Pubkey::new([1; 32]),
+                executable: false,
+                rent_epoch: 7,
+                pubkey: Pubkey::new([2; 32]),
+            },
+            Account {
+                lamports: 1,
+                data: vec![],
+                owner: Pubkey::new([3; 32]),
+                executable: true,
+                rent_epoch: 0,
+                pubkey: Pubkey::new([4; 32]),
+            },
+        ]);
+
+        assert_eq!(
+            AccountFixture::from_json(&fixture.to_json()).unwrap(),
+            fixture
+        );
+
+        let minimal = AccountFixture::from_json(
+            r#"{
+                "accounts": [
+                    {
+                        "pubkey": "6Bho5aBGh5ppwxXYfLQGsNbVn2tnpCmcrvVpDbMvTtvD",
+                        "owner": "11111111111111111111111111111111",
+                        "lamports": 5,
+                        "data": "AQID"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(minimal.accounts[0].data, vec![1, 2, 3]);
+        assert_eq!(minimal.accounts[0].owner, Pubkey::default());
+        assert!(!minimal.accounts[0].executable);
+
+        let invalid_data = r#"{ "accounts": [{
+            "pubkey": "11111111111111111111111111111111",
+            "owner": "11111111111111111111111111111111",
+            "lamports": 5,
+            "data": "not base64!"
+        }] }"#;
+        assert!(matches!(
+            AccountFixture::from_json(invalid_data),
+            Err(FixtureError::Data(..))
+        ));
+        assert!(matches!(
+            AccountFixture::from_json(&invalid_data.replacen("111", "0OI", 1)),
+            Err(FixtureError::Pubkey(_))
+        ));
+        assert!(matches!(
+            AccountFixture::from_json(r#"{ "accounts": [], "slot": 1 }"#),
+            Err(FixtureError::Json(_))
+        ));
+    }
+}
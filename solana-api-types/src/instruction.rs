@@ -263,6 +263,103 @@ impl Instruction {
             data: data.to_vec(),
         }
     }
+
+    /// Check that an account passed more than once has the same flags every time.
+    ///
+    /// [`Message::new`](crate::Message::new) merges duplicates into a single key, promoting it to
+    /// writable if any of them is, so a mismatch usually means one of the metas was built wrong.
+    pub fn check_accounts(&self) -> Result<(), ConflictingAccountMeta> {
+        for (second, meta) in self.accounts.iter().enumerate() {
+            let first = self.accounts[..second]
+                .iter()
+                .position(|other| other.pubkey == meta.pubkey);
+
+            if let Some(first) = first {
+                let other = &self.accounts[first];
+                if other.is_signer != meta.is_signer || other.is_writable != meta.is_writable {
+                    return Err(ConflictingAccountMeta {
+                        pubkey: meta.pubkey,
+                        first,
+                        second,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An account passed to an instruction twice, as `first` and `second`, with different flags.
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+#[error(
+    "account {pubkey} is passed as #{first} and #{second} with different signer or writable flags"
+)]
+pub struct ConflictingAccountMeta {
+    pub pubkey: Pubkey,
+    pub first: usize,
+    pub second: usize,
+}
+
+/// Accounts of an instruction, added by role in the order the program parses them.
+///
+/// `expected` is the length of the account schema of the instruction. Debug builds check it
+/// against the accounts added once they're built, so a builder which drifted from the program's
+/// parser fails its tests instead of the transactions it builds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountMetas {
+    metas: Vec<AccountMeta>,
+    expected: usize,
+}
+
+impl AccountMetas {
+    pub fn new(expected: usize) -> Self {
+        Self {
+            metas: Vec::with_capacity(expected),
+            expected,
+        }
+    }
+
+    pub fn signer_writable(self, pubkey: Pubkey) -> Self {
+        self.push(AccountMeta::new(pubkey, true))
+    }
+
+    pub fn signer_readonly(self, pubkey: Pubkey) -> Self {
+        self.push(AccountMeta::new_readonly(pubkey, true))
+    }
+
+    pub fn writable(self, pubkey: Pubkey) -> Self {
+        self.push(AccountMeta::new(pubkey, false))
+    }
+
+    pub fn readonly(self, pubkey: Pubkey) -> Self {
+        self.push(AccountMeta::new_readonly(pubkey, false))
+    }
+
+    fn push(mut self, meta: AccountMeta) -> Self {
+        self.metas.push(meta);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.metas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metas.is_empty()
+    }
+
+    pub fn build(self) -> Vec<AccountMeta> {
+        debug_assert_eq!(
+            self.metas.len(),
+            self.expected,
+            "expected {} accounts, got {}",
+            self.expected,
+            self.metas.len()
+        );
+
+        self.metas
+    }
 }
 
 #[cfg(feature = "runtime-test")]
@@ -275,3 +372,89 @@ impl From<solana_program::instruction::Instruction> for Instruction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountMetas, ConflictingAccountMeta};
+    use crate::{AccountMeta, Instruction, Pubkey};
+
+    #[test]
+    fn check_accounts_test() {
+        let program_id = Pubkey::new([1; 32]);
+        let pool = Pubkey::new([2; 32]);
+        let authority = Pubkey::new([3; 32]);
+
+        // the same account twice with matching flags is fine
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(pool, false),
+            ],
+        );
+        assert_eq!(instruction.check_accounts(), Ok(()));
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(pool, false),
+            ],
+        );
+        assert_eq!(
+            instruction.check_accounts(),
+            Err(ConflictingAccountMeta {
+                pubkey: pool,
+                first: 0,
+                second: 2,
+            })
+        );
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(authority, false),
+            ],
+        );
+        assert!(instruction.check_accounts().is_err());
+    }
+
+    #[test]
+    fn account_metas_test() {
+        let pool = Pubkey::new([2; 32]);
+        let authority = Pubkey::new([3; 32]);
+        let wallet = Pubkey::new([4; 32]);
+
+        let metas = AccountMetas::new(4)
+            .writable(pool)
+            .signer_readonly(authority)
+            .signer_writable(wallet)
+            .readonly(pool);
+        assert_eq!(metas.len(), 4);
+        assert_eq!(
+            metas.build(),
+            vec![
+                AccountMeta::new(pool, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new(wallet, true),
+                AccountMeta::new_readonly(pool, false),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "expected 3 accounts, got 2")]
+    fn account_metas_missing_account_test() {
+        AccountMetas::new(3)
+            .writable(Pubkey::new([2; 32]))
+            .signer_readonly(Pubkey::new([3; 32]))
+            .build();
+    }
+}
@@ -1,3 +1,5 @@
+use async_trait::async_trait;
+
 use crate::{signature::SignerError, Pubkey, Signature};
 
 #[cfg(feature = "crypto")]
@@ -42,6 +44,29 @@ pub trait Signer {
     fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError>;
 }
 
+/// A signer that can't sign on the spot, such as a hardware wallet or a remote key service.
+///
+/// Every [`Signer`] is also an `AsyncSigner`, so in-memory keypairs can be mixed with external
+/// signers in [`Transaction::try_sign_async`](crate::Transaction::try_sign_async).
+#[async_trait(?Send)]
+pub trait AsyncSigner {
+    /// Fallibly gets the implementor's public key.
+    async fn try_pubkey_async(&self) -> Result<Pubkey, SignerError>;
+    /// Fallibly produces an Ed25519 signature over the provided `message` bytes.
+    async fn try_sign_message_async(&self, message: &[u8]) -> Result<Signature, SignerError>;
+}
+
+#[async_trait(?Send)]
+impl<T: Signer + ?Sized> AsyncSigner for T {
+    async fn try_pubkey_async(&self) -> Result<Pubkey, SignerError> {
+        self.try_pubkey()
+    }
+
+    async fn try_sign_message_async(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        self.try_sign_message(message)
+    }
+}
+
 #[cfg(feature = "crypto")]
 impl Keypair {
     /// Constructs a new, random `Keypair` using a caller-proveded RNG
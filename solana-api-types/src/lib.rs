@@ -7,9 +7,12 @@ use serde_json::Value;
 
 use serde::{Deserialize, Serialize};
 
+mod block;
+pub mod compute_budget;
 pub mod entrypoint;
 mod error;
 mod faucet;
+pub mod fixture;
 mod hash;
 mod instruction;
 mod message;
@@ -32,11 +35,17 @@ pub mod sdk_proxy;
 mod key;
 #[cfg(feature = "crypto")]
 pub use key::Keypair;
-pub use key::Signer;
-
-pub use error::{ClientError, ClientErrorKind, RpcError};
+pub use key::{AsyncSigner, Signer};
+
+pub use block::{
+    ConfirmedBlock, ConfirmedTransaction, RpcBlockConfig, TransactionDetails, UiConfirmedBlock,
+};
+pub use error::{
+    ClientError, ClientErrorKind, RpcError, RpcResponseErrorData,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+};
 pub use hash::Hash;
-pub use instruction::{Instruction, InstructionError};
+pub use instruction::{AccountMetas, ConflictingAccountMeta, Instruction, InstructionError};
 pub use pubkey::Pubkey;
 pub use signature::{Signature, SignerError};
 pub use signers::Signers;
@@ -310,6 +319,49 @@ pub struct UiDataSliceConfig {
     pub length: usize,
 }
 
+/// The part of an account's data requested through `dataSlice`.
+pub type AccountSlice = UiDataSliceConfig;
+
+impl UiDataSliceConfig {
+    pub const fn new(offset: usize, length: usize) -> Self {
+        Self { offset, length }
+    }
+
+    /// First byte after the slice, `None` on overflow.
+    pub fn end(&self) -> Option<usize> {
+        self.offset.checked_add(self.length)
+    }
+
+    /// Whether the slice lies within an account with `data_len` bytes of data.
+    pub fn fits(&self, data_len: usize) -> bool {
+        matches!(self.end(), Some(end) if end <= data_len)
+    }
+
+    /// Bytes of `data` covered by the slice.
+    pub fn apply<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+        data.get(self.offset..self.end()?)
+    }
+}
+
+/// Builds the [`AccountSlice`] covering a single field of a `#[repr(C)]` struct stored `base`
+/// bytes into the account data, e.g. `field_slice!(HEADER_RESERVED, StakerTicketState, staked_amount)`.
+#[macro_export]
+macro_rules! field_slice {
+    ($base:expr, $type:ty, $field:ident) => {{
+        fn size_of_pointee<T>(_: *const T) -> usize {
+            std::mem::size_of::<T>()
+        }
+
+        let uninit = std::mem::MaybeUninit::<$type>::uninit();
+        let base_ptr = uninit.as_ptr();
+        // no reference to uninitialized memory is created, only raw pointers
+        let field_ptr = unsafe { std::ptr::addr_of!((*base_ptr).$field) };
+        let offset = field_ptr as usize - base_ptr as usize;
+
+        $crate::AccountSlice::new($base + offset, size_of_pointee(field_ptr))
+    }};
+}
+
 /// Configuration object for
 /// [getAccountInfo](https://docs.solana.com/developing/clients/jsonrpc-api#getaccountinfo) request.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
@@ -319,6 +371,9 @@ pub struct RpcAccountInfoConfig {
     pub data_slice: Option<UiDataSliceConfig>,
     #[serde(flatten)]
     pub commitment: Option<CommitmentConfig>,
+    /// Don't read the account from a node that is behind this slot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_context_slot: Option<Slot>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -499,6 +554,25 @@ pub enum EncodedTransaction {
     Json(UiTransaction),
 }
 
+impl EncodedTransaction {
+    /// Decode a binary-encoded transaction. Json-encoded transactions are not supported.
+    pub fn decode(&self) -> Option<Transaction> {
+        let data = match self {
+            EncodedTransaction::Json(_) => None,
+            EncodedTransaction::LegacyBinary(blob) => bs58::decode(blob).into_vec().ok(),
+            EncodedTransaction::Binary(blob, encoding) => match encoding {
+                UiTransactionEncoding::Binary | UiTransactionEncoding::Base58 => {
+                    bs58::decode(blob).into_vec().ok()
+                }
+                UiTransactionEncoding::Base64 => base64::decode(blob).ok(),
+                _ => None,
+            },
+        }?;
+
+        bincode::deserialize(&data).ok()
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EncodedTransactionWithStatusMeta {
@@ -586,6 +660,13 @@ pub struct RpcSendTransactionConfig {
     pub skip_preflight: bool,
     pub preflight_commitment: Option<CommitmentLevel>,
     pub encoding: Option<UiTransactionEncoding>,
+    /// How many times the node rebroadcasts the transaction, left to the node if `None`.
+    /// `Some(0)` makes the caller responsible for resending it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<usize>,
+    /// Don't process the transaction on a node that is behind this slot.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_context_slot: Option<Slot>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
@@ -614,6 +695,9 @@ pub struct RpcSimulateTransactionResult {
     pub err: Option<TransactionError>,
     pub logs: Option<Vec<String>>,
     pub accounts: Option<Vec<Option<UiAccount>>>,
+    /// Only reported by newer nodes.
+    #[serde(default)]
+    pub units_consumed: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
@@ -688,6 +772,15 @@ pub trait Client {
         cfg: Option<RpcTransactionConfig>,
     ) -> Result<Option<EncodedConfirmedTransaction>, ClientError>;
 
+    /// https://docs.solana.com/developing/clients/jsonrpc-api#getblock
+    ///
+    /// `None` if the slot was skipped.
+    async fn get_block(
+        &self,
+        slot: Slot,
+        cfg: Option<RpcBlockConfig>,
+    ) -> Result<Option<ConfirmedBlock>, ClientError>;
+
     /// https://docs.solana.com/developing/clients/jsonrpc-api#requestairdrop
     async fn request_airdrop(
         &self,
@@ -715,4 +808,7 @@ pub trait Client {
         &self,
         commitment: Option<CommitmentConfig>,
     ) -> Result<RpcRecentBlockhash, ClientError>;
+
+    /// https://docs.solana.com/developing/clients/jsonrpc-api#gethealth
+    async fn get_health(&self) -> Result<(), ClientError>;
 }
@@ -1,6 +1,11 @@
+use std::convert::TryFrom;
+
 use itertools::Itertools;
 
-use crate::{AccountMeta, CompiledInstruction, Hash, Instruction, Message, MessageHeader, Pubkey};
+use crate::{
+    short_vec, AccountMeta, CompiledInstruction, Hash, Instruction, Message, MessageHeader, Pubkey,
+    TransactionError,
+};
 
 fn position(keys: &[Pubkey], key: &Pubkey) -> u8 {
     keys.iter().position(|k| k == key).unwrap() as u8
@@ -175,7 +180,181 @@ impl Message {
         compile_instruction(ix, &self.account_keys)
     }
 
+    /// The message in the layout bincode gives it, the one signatures are made over.
     pub fn serialize(&self) -> Vec<u8> {
-        bincode::serialize(self).unwrap()
+        let mut out = vec![];
+        self.serialize_into(&mut out);
+        out
+    }
+
+    /// Append [`Message::serialize`] to `out`.
+    ///
+    /// The layout is written by hand rather than through bincode, so messages can be assembled
+    /// on-chain as well.
+    pub fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.push(self.header.num_required_signatures);
+        out.push(self.header.num_readonly_signed_accounts);
+        out.push(self.header.num_readonly_unsigned_accounts);
+
+        write_len(out, self.account_keys.len());
+        for key in &self.account_keys {
+            out.extend_from_slice(key.as_ref());
+        }
+
+        out.extend_from_slice(self.recent_blockhash.as_ref());
+
+        write_len(out, self.instructions.len());
+        for instruction in &self.instructions {
+            instruction.serialize_into(out);
+        }
+    }
+
+    /// Check that the header agrees with `account_keys`, that keys are unique and that every
+    /// instruction only references existing accounts, like the runtime does before executing
+    /// the message.
+    pub fn sanitize(&self) -> Result<(), TransactionError> {
+        let header = &self.header;
+        let num_keys = self.account_keys.len();
+
+        if usize::from(header.num_required_signatures)
+            + usize::from(header.num_readonly_unsigned_accounts)
+            > num_keys
+        {
+            return Err(TransactionError::SanitizeFailure);
+        }
+
+        // the fee payer has to be writable
+        if header.num_readonly_signed_accounts >= header.num_required_signatures {
+            return Err(TransactionError::SanitizeFailure);
+        }
+
+        if self.account_keys.iter().unique().count() != num_keys {
+            return Err(TransactionError::SanitizeFailure);
+        }
+
+        for instruction in &self.instructions {
+            // the fee payer can't be a program
+            let program_id_index = usize::from(instruction.program_id_index);
+            if program_id_index == 0 || program_id_index >= num_keys {
+                return Err(TransactionError::SanitizeFailure);
+            }
+
+            if instruction
+                .accounts
+                .iter()
+                .any(|index| usize::from(*index) >= num_keys)
+            {
+                return Err(TransactionError::SanitizeFailure);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CompiledInstruction {
+    /// Append the bincode layout of the instruction to `out`, see [`Message::serialize_into`].
+    pub fn serialize_into(&self, out: &mut Vec<u8>) {
+        out.push(self.program_id_index);
+
+        write_len(out, self.accounts.len());
+        out.extend_from_slice(&self.accounts);
+
+        write_len(out, self.data.len());
+        out.extend_from_slice(&self.data);
+    }
+}
+
+/// Length prefix of a `short_vec`, which can't hold more than `u16::MAX` elements.
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    let len = u16::try_from(len).expect("short_vec is limited to u16::MAX elements");
+    short_vec::write_len(out, len);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AccountMeta, CompiledInstruction, Hash, Instruction, Message, Pubkey, TransactionError,
+    };
+
+    fn message() -> Message {
+        let payer = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(Pubkey::new_unique(), true),
+            ],
+            data: vec![1, 2, 3],
+        };
+
+        Message::new(&[instruction], Some(&payer))
+    }
+
+    #[test]
+    fn serialize_matches_bincode_test() {
+        let mut message = message();
+        message.recent_blockhash = Hash::new_unique();
+        // long enough for multi-byte length prefixes
+        message.instructions.push(CompiledInstruction {
+            program_id_index: 3,
+            accounts: (0..200).map(|i| (i % 4) as u8).collect(),
+            data: vec![7; 300],
+        });
+
+        assert_eq!(message.serialize(), bincode::serialize(&message).unwrap());
+
+        let instruction = &message.instructions[1];
+        let mut out = vec![];
+        instruction.serialize_into(&mut out);
+        assert_eq!(out, bincode::serialize(instruction).unwrap());
+
+        let empty = Message::default();
+        assert_eq!(empty.serialize(), bincode::serialize(&empty).unwrap());
+    }
+
+    #[test]
+    fn sanitize_test() {
+        assert_eq!(message().sanitize(), Ok(()));
+
+        let mut out_of_range = message();
+        out_of_range.instructions[0].accounts[1] = out_of_range.account_keys.len() as u8;
+        assert_eq!(
+            out_of_range.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut bad_program = message();
+        bad_program.instructions[0].program_id_index = 200;
+        assert_eq!(
+            bad_program.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut payer_program = message();
+        payer_program.instructions[0].program_id_index = 0;
+        assert_eq!(
+            payer_program.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut duplicate = message();
+        duplicate.account_keys[1] = duplicate.account_keys[0];
+        assert_eq!(duplicate.sanitize(), Err(TransactionError::SanitizeFailure));
+
+        let mut bad_header = message();
+        bad_header.header.num_required_signatures = bad_header.account_keys.len() as u8 + 1;
+        assert_eq!(
+            bad_header.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
+
+        let mut readonly_payer = message();
+        readonly_payer.header.num_readonly_signed_accounts =
+            readonly_payer.header.num_required_signatures;
+        assert_eq!(
+            readonly_payer.sanitize(),
+            Err(TransactionError::SanitizeFailure)
+        );
     }
 }
@@ -1,8 +1,13 @@
-use crate::{Account, Hash, Keypair, Pubkey, Transaction};
-use solana_program_test::BanksClient;
-use solana_sdk::process_instruction::ProcessInstructionWithContext;
+use crate::{
+    fixture::AccountFixture, sysvar::clock::Clock, Account, Hash, Keypair, Pubkey, Transaction,
+};
+use solana_program_test::{BanksClient, ProgramTestContext};
+use solana_sdk::{clock::DEFAULT_MS_PER_SLOT, process_instruction::ProcessInstructionWithContext};
 
-use crate::sdk_proxy::ToSdk;
+use crate::sdk_proxy::{FromSdk, ToSdk};
+
+/// Warps tried by [`Runtime::warp_clock`] before giving up on the clock catching up.
+const MAX_CLOCK_WARPS: usize = 8;
 
 #[derive(Default)]
 pub struct ProgramTest {
@@ -19,21 +24,90 @@ impl ProgramTest {
         self.inner.add_program(name, pk.to_sdk(), handler)
     }
 
+    /// Start the validator with `account` already in place.
+    pub fn add_account(&mut self, account: &Account) {
+        self.inner.add_account(
+            account.pubkey.to_sdk(),
+            solana_sdk::account::Account {
+                lamports: account.lamports,
+                data: account.data.clone(),
+                owner: account.owner.to_sdk(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+            },
+        )
+    }
+
+    /// Start the validator with every account of `fixture` in place, e.g. to replay the state a
+    /// bug was reported with.
+    pub fn add_fixture(&mut self, fixture: &AccountFixture) {
+        for account in &fixture.accounts {
+            self.add_account(account);
+        }
+    }
+
     pub async fn start(self) -> (Runtime, Keypair, Hash) {
-        let (client, keypair, hash) = self.inner.start().await;
+        let context = self.inner.start_with_context().await;
 
-        let keypair = Keypair::from_bytes(&keypair.to_bytes()).unwrap();
-        let hash = Hash(hash.0);
+        let keypair = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+        let hash = Hash(context.last_blockhash.0);
+        let client = context.banks_client.clone();
 
-        (Runtime { client }, keypair, hash)
+        (Runtime { client, context }, keypair, hash)
     }
 }
 
 pub struct Runtime {
     client: BanksClient,
+    context: ProgramTestContext,
 }
 
 impl Runtime {
+    pub async fn clock(&mut self) -> Result<Clock, anyhow::Error> {
+        let clock = self
+            .client
+            .get_sysvar::<solana_program::sysvar::clock::Clock>()
+            .await?;
+
+        Ok(Clock::from_sdk(&clock))
+    }
+
+    /// Warp the validator to a later slot, until its clock is at least `seconds` ahead.
+    ///
+    /// This lets tests reach deadlines such as the expiry of a pool without waiting for them.
+    /// Blockhashes from before the warp may be rejected afterwards, so transactions should be
+    /// signed with the returned one.
+    pub async fn warp_clock(&mut self, seconds: i64) -> Result<Hash, anyhow::Error> {
+        let mut clock = self.clock().await?;
+        let target = clock.unix_timestamp + seconds;
+
+        // the clock is estimated from the slot, so it may take a few warps to get there
+        for _ in 0..MAX_CLOCK_WARPS {
+            if clock.unix_timestamp >= target {
+                break;
+            }
+
+            let remaining_ms = (target - clock.unix_timestamp) as u64 * 1000;
+            let slots = remaining_ms / DEFAULT_MS_PER_SLOT + 1;
+            self.context
+                .warp_to_slot(clock.slot + slots)
+                .map_err(|err| anyhow::anyhow!("warp failed: {:?}", err))?;
+
+            clock = self.clock().await?;
+        }
+
+        if clock.unix_timestamp < target {
+            anyhow::bail!(
+                "clock is at {} after warping, expected at least {}",
+                clock.unix_timestamp,
+                target
+            );
+        }
+
+        let hash = self.client.get_recent_blockhash().await?;
+        Ok(Hash(hash.0))
+    }
+
     pub async fn process_transaction(
         &mut self,
         transaction: Transaction,
@@ -60,4 +134,21 @@ impl Runtime {
             })
             .map_err(|err| err.into())
     }
+
+    /// Capture `pubkeys` as they are now, to be replayed with [`ProgramTest::add_fixture`].
+    pub async fn capture_fixture(
+        &mut self,
+        pubkeys: &[Pubkey],
+    ) -> Result<AccountFixture, anyhow::Error> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for pubkey in pubkeys {
+            let account = self
+                .get_account(pubkey)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("account {} not found", pubkey))?;
+            accounts.push(account);
+        }
+
+        Ok(AccountFixture::new(accounts))
+    }
 }
@@ -93,6 +93,28 @@ impl Pubkey {
         }
     }
 
+    /// Program address of `seeds` followed by the highest bump seed that derives one, along with
+    /// that bump seed, the same search the runtime's `find_program_address` does.
+    #[cfg(any(feature = "extended", target_arch = "bpf"))]
+    pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+        Self::try_find_program_address(seeds, program_id).expect("no viable bump seed")
+    }
+
+    /// Like [`Pubkey::find_program_address`], `None` if no bump seed derives a program address.
+    #[cfg(any(feature = "extended", target_arch = "bpf"))]
+    pub fn try_find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Option<(Pubkey, u8)> {
+        (0..=u8::MAX).rev().find_map(|bump| {
+            let bump_seed = [bump];
+            let mut seeds_with_bump = seeds.to_vec();
+            seeds_with_bump.push(&bump_seed);
+
+            Self::create_program_address(&seeds_with_bump, program_id)
+                .map(|address| (address, bump))
+        })
+    }
+
+    /// Whether the key is a point of the ed25519 curve, i.e. could have a secret key. Program
+    /// addresses never are, which is what keeps anyone but their program from signing for them.
     #[cfg(feature = "extended")]
     pub fn is_on_curve(&self) -> bool {
         curve25519_dalek::edwards::CompressedEdwardsY::from_slice(self.0.as_ref())
@@ -168,3 +190,27 @@ impl TryFrom<&str> for Pubkey {
         Pubkey::from_str(s)
     }
 }
+
+#[cfg(all(test, feature = "extended"))]
+mod tests {
+    use super::Pubkey;
+
+    #[test]
+    fn is_on_curve_test() {
+        // a wallet address, which has a secret key
+        let wallet: Pubkey = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"
+            .parse()
+            .unwrap();
+        assert!(wallet.is_on_curve());
+
+        // its associated wallet for USDC, a program address of the associated token program
+        let associated: Pubkey = "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B"
+            .parse()
+            .unwrap();
+        assert!(!associated.is_on_curve());
+
+        let program_id = Pubkey::new([1; 32]);
+        let (address, _) = Pubkey::find_program_address(&[b"authority"], &program_id);
+        assert!(!address.is_on_curve());
+    }
+}
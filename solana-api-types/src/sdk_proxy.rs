@@ -1,6 +1,6 @@
 use crate::{
     program::ProgramError,
-    sysvar::{clock::Clock, rent::Rent},
+    sysvar::{clock::Clock, epoch_schedule::EpochSchedule, rent::Rent},
     AccountMeta, CompiledInstruction, Hash, Instruction, Message, Pubkey, Signature, Transaction,
 };
 
@@ -132,6 +132,20 @@ impl FromSdk for Rent {
     }
 }
 
+impl FromSdk for EpochSchedule {
+    type Original = solana_program::sysvar::epoch_schedule::EpochSchedule;
+
+    fn from_sdk(sdk: &Self::Original) -> Self {
+        Self {
+            slots_per_epoch: sdk.slots_per_epoch,
+            leader_schedule_slot_offset: sdk.leader_schedule_slot_offset,
+            warmup: sdk.warmup,
+            first_normal_epoch: sdk.first_normal_epoch,
+            first_normal_slot: sdk.first_normal_slot,
+        }
+    }
+}
+
 impl FromSdk for ProgramError {
     type Original = solana_program::program_error::ProgramError;
 
@@ -38,6 +38,20 @@ impl Serialize for ShortU16 {
     }
 }
 
+/// Append `len` to `out` in the encoding of [`ShortU16`], without going through serde.
+pub fn write_len(out: &mut Vec<u8>, len: u16) {
+    let mut rem_val = len;
+    loop {
+        let elem = (rem_val & 0x7f) as u8;
+        rem_val >>= 7;
+        if rem_val == 0 {
+            out.push(elem);
+            break;
+        }
+        out.push(elem | 0x80);
+    }
+}
+
 enum VisitStatus {
     Done(u16),
     More(u16),
@@ -179,6 +193,9 @@ pub fn serialize<S: Serializer, T: Serialize>(
     seq.end()
 }
 
+/// Upper bound on the capacity reserved up front when decoding a [`ShortVec`].
+const MAX_PREALLOCATED_ELEMENTS: usize = 1024;
+
 struct ShortVecVisitor<T> {
     _t: PhantomData<T>,
 }
@@ -202,7 +219,8 @@ where
             .ok_or_else(|| de::Error::invalid_length(0, &self))?;
         let len = short_len.0 as usize;
 
-        let mut result = Vec::with_capacity(len);
+        // the length comes from untrusted input, so don't trust it for allocations
+        let mut result = Vec::with_capacity(len.min(MAX_PREALLOCATED_ELEMENTS));
         for i in 0..len {
             let elem = seq
                 .next_element()?
@@ -275,6 +293,9 @@ mod tests {
 
     fn assert_len_encoding(len: u16, bytes: &[u8]) {
         assert_eq!(encode_len(len), bytes, "unexpected usize encoding");
+        let mut written = vec![];
+        write_len(&mut written, len);
+        assert_eq!(written, bytes, "unexpected write_len encoding");
         assert_eq!(
             decode_shortu16_len(bytes).unwrap(),
             (usize::from(len), bytes.len()),
@@ -379,4 +400,64 @@ mod tests {
         ];
         assert!(deserialize::<ShortVec<u8>>(&bytes).is_err());
     }
+
+    #[test]
+    fn test_short_u16_roundtrip_all_values() {
+        for value in 0..=std::u16::MAX {
+            let bytes = encode_len(value);
+            let expected_len = match value {
+                0..=0x7f => 1,
+                0x80..=0x3fff => 2,
+                _ => 3,
+            };
+
+            assert_eq!(
+                bytes.len(),
+                expected_len,
+                "non-minimal encoding of {}",
+                value
+            );
+            assert_eq!(deserialize::<ShortU16>(&bytes).unwrap().0, value);
+
+            // truncated encodings are rejected, not misread
+            for end in 0..bytes.len() {
+                assert!(deserialize::<ShortU16>(&bytes[..end]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_u16_only_canonical_encodings_decode() {
+        let last_bytes = [0x00, 0x01, 0x02, 0x03, 0x04, 0x7f, 0x80, 0xff];
+
+        for b0 in 0..=0xffu8 {
+            for b1 in 0..=0xffu8 {
+                for &b2 in &last_bytes {
+                    let bytes = [b0, b1, b2];
+
+                    // whatever decodes has to be exactly the encoding the value serializes to
+                    if let Ok((len, consumed)) = decode_shortu16_len(&bytes) {
+                        assert_eq!(deserialize::<ShortU16>(&bytes).unwrap().0 as usize, len);
+                        assert_eq!(encode_len(len as u16), &bytes[..consumed]);
+                    } else {
+                        assert!(deserialize::<ShortU16>(&bytes).is_err());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_short_vec_truncated_elements() {
+        // claims u16::MAX elements, but carries only a few
+        let bytes = [0xff, 0xff, 0x03, 0x01, 0x02, 0x03];
+        assert!(deserialize::<ShortVec<u8>>(&bytes).is_err());
+        assert!(deserialize::<ShortVec<[u8; 32]>>(&bytes).is_err());
+
+        let vec = ShortVec(vec![7u8; 300]);
+        let bytes = serialize(&vec).unwrap();
+        for end in 0..bytes.len() {
+            assert!(deserialize::<ShortVec<u8>>(&bytes[..end]).is_err());
+        }
+    }
 }
@@ -11,3 +11,7 @@ pub fn sol_get_clock_sysvar(_var_addr: *mut u8) -> u64 {
 pub fn sol_get_rent_sysvar(_var_addr: *mut u8) -> u64 {
     UNSUPPORTED_SYSVAR
 }
+
+pub fn sol_get_epoch_schedule_sysvar(_var_addr: *mut u8) -> u64 {
+    UNSUPPORTED_SYSVAR
+}
@@ -153,3 +153,169 @@ pub mod rent {
         impl_sysvar_get!(Rent, rent, sol_get_rent_sysvar);
     }
 }
+
+pub mod epoch_schedule {
+    use super::Sysvar;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Epoch, Slot};
+
+    crate::declare_sysvar_id!("SysvarEpochSchedu1e111111111111111111111111", EpochSchedule);
+
+    /// The default number of slots per epoch, roughly 2 days at 400ms per slot.
+    pub const DEFAULT_SLOTS_PER_EPOCH: u64 = 432_000;
+    pub const DEFAULT_LEADER_SCHEDULE_SLOT_OFFSET: u64 = DEFAULT_SLOTS_PER_EPOCH;
+
+    /// The length of the first epoch when warmup is enabled, every following warmup epoch
+    /// doubles in length until `slots_per_epoch` is reached.
+    pub const MINIMUM_SLOTS_PER_EPOCH: u64 = 32;
+
+    #[repr(C)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    pub struct EpochSchedule {
+        /// The maximum number of slots in each epoch.
+        pub slots_per_epoch: u64,
+
+        /// A number of slots before beginning of an epoch to calculate a leader schedule for
+        /// that epoch.
+        pub leader_schedule_slot_offset: u64,
+
+        /// Whether epochs start short and grow.
+        pub warmup: bool,
+
+        /// The first epoch with `slots_per_epoch` slots, equal to
+        /// `log2(slots_per_epoch) - log2(MINIMUM_SLOTS_PER_EPOCH)`.
+        pub first_normal_epoch: Epoch,
+
+        /// The first slot of `first_normal_epoch`, equal to
+        /// `MINIMUM_SLOTS_PER_EPOCH * (2.pow(first_normal_epoch) - 1)`.
+        pub first_normal_slot: Slot,
+    }
+
+    impl Default for EpochSchedule {
+        fn default() -> Self {
+            Self::custom(
+                DEFAULT_SLOTS_PER_EPOCH,
+                DEFAULT_LEADER_SCHEDULE_SLOT_OFFSET,
+                true,
+            )
+        }
+    }
+
+    impl EpochSchedule {
+        pub fn custom(
+            slots_per_epoch: u64,
+            leader_schedule_slot_offset: u64,
+            warmup: bool,
+        ) -> Self {
+            assert!(slots_per_epoch >= MINIMUM_SLOTS_PER_EPOCH);
+
+            let (first_normal_epoch, first_normal_slot) = if warmup {
+                let next_power_of_two = slots_per_epoch.next_power_of_two();
+                let log2_slots_per_epoch = next_power_of_two
+                    .trailing_zeros()
+                    .saturating_sub(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros());
+
+                (
+                    u64::from(log2_slots_per_epoch),
+                    next_power_of_two.saturating_sub(MINIMUM_SLOTS_PER_EPOCH),
+                )
+            } else {
+                (0, 0)
+            };
+
+            Self {
+                slots_per_epoch,
+                leader_schedule_slot_offset,
+                warmup,
+                first_normal_epoch,
+                first_normal_slot,
+            }
+        }
+
+        /// Number of slots in `epoch`.
+        pub fn get_slots_in_epoch(&self, epoch: Epoch) -> u64 {
+            if epoch < self.first_normal_epoch {
+                2u64.saturating_pow(
+                    (epoch as u32).saturating_add(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()),
+                )
+            } else {
+                self.slots_per_epoch
+            }
+        }
+
+        /// Epoch containing `slot`.
+        pub fn get_epoch(&self, slot: Slot) -> Epoch {
+            self.get_epoch_and_slot_index(slot).0
+        }
+
+        /// Epoch containing `slot` and the offset of `slot` within that epoch.
+        pub fn get_epoch_and_slot_index(&self, slot: Slot) -> (Epoch, u64) {
+            if slot < self.first_normal_slot {
+                let epoch = slot
+                    .saturating_add(MINIMUM_SLOTS_PER_EPOCH)
+                    .saturating_add(1)
+                    .next_power_of_two()
+                    .trailing_zeros()
+                    .saturating_sub(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros())
+                    .saturating_sub(1);
+
+                let epoch_len = 2u64
+                    .saturating_pow(epoch.saturating_add(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()));
+
+                (
+                    u64::from(epoch),
+                    slot.saturating_sub(epoch_len.saturating_sub(MINIMUM_SLOTS_PER_EPOCH)),
+                )
+            } else {
+                let normal_slot_index = slot.saturating_sub(self.first_normal_slot);
+                let normal_epoch_index = normal_slot_index
+                    .checked_div(self.slots_per_epoch)
+                    .unwrap_or(0);
+                let epoch = self.first_normal_epoch.saturating_add(normal_epoch_index);
+                let slot_index = normal_slot_index
+                    .checked_rem(self.slots_per_epoch)
+                    .unwrap_or(0);
+
+                (epoch, slot_index)
+            }
+        }
+
+        /// First slot of `epoch`.
+        pub fn get_first_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+            if epoch <= self.first_normal_epoch {
+                2u64.saturating_pow(epoch as u32)
+                    .saturating_sub(1)
+                    .saturating_mul(MINIMUM_SLOTS_PER_EPOCH)
+            } else {
+                epoch
+                    .saturating_sub(self.first_normal_epoch)
+                    .saturating_mul(self.slots_per_epoch)
+                    .saturating_add(self.first_normal_slot)
+            }
+        }
+
+        /// Last slot of `epoch`.
+        pub fn get_last_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+            self.get_first_slot_in_epoch(epoch)
+                .saturating_add(self.get_slots_in_epoch(epoch))
+                .saturating_sub(1)
+        }
+    }
+
+    impl Sysvar for EpochSchedule {
+        crate::impl_sysvar_get!(EpochSchedule, epoch_schedule, sol_get_epoch_schedule_sysvar);
+    }
+}
+
+pub mod slot_hashes {
+    /// SlotHashes isn't exposed through a syscall, since it's too big to copy. Programs read it
+    /// from the account data instead.
+    pub const ID: &crate::Pubkey = &crate::Pubkey::new(solar_macros::parse_base58!(
+        "SysvarS1otHashes111111111111111111111111111"
+    ));
+
+    /// Number of recent slots kept by the sysvar.
+    pub const MAX_ENTRIES: usize = 512;
+}
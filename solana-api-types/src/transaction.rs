@@ -2,9 +2,9 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    error::ClientErrorKind, short_vec, signature::SignerError, ClientError, CompiledInstruction,
-    Instruction, InstructionError, Message, Pubkey, Signature, Signers, Slot,
-    UiTransactionEncoding,
+    error::ClientErrorKind, short_vec, signature::SignerError, AsyncSigner, ClientError,
+    CommitmentConfig, CommitmentLevel, CompiledInstruction, Instruction, InstructionError, Message,
+    Pubkey, Signature, Signers, Slot, UiTransactionEncoding,
 };
 
 use super::Hash;
@@ -108,6 +108,32 @@ pub struct TransactionStatus {
     pub confirmation_status: Option<TransactionConfirmationStatus>,
 }
 
+impl TransactionStatus {
+    /// How far the transaction got. Nodes older than 1.5.5 don't report `confirmation_status`,
+    /// so it's derived from the number of confirmations for them.
+    pub fn confirmation_status(&self) -> TransactionConfirmationStatus {
+        match (&self.confirmation_status, self.confirmations) {
+            (Some(status), _) => status.clone(),
+            (None, None) => TransactionConfirmationStatus::Finalized,
+            (None, Some(confirmations)) if confirmations > 1 => {
+                TransactionConfirmationStatus::Confirmed
+            }
+            (None, Some(_)) => TransactionConfirmationStatus::Processed,
+        }
+    }
+
+    /// Whether the transaction reached the `commitment` level.
+    pub fn is_confirmed_at(&self, commitment: CommitmentConfig) -> bool {
+        let status = self.confirmation_status();
+
+        match commitment.commitment {
+            CommitmentLevel::Finalized => status == TransactionConfirmationStatus::Finalized,
+            CommitmentLevel::Confirmed => status != TransactionConfirmationStatus::Processed,
+            CommitmentLevel::Processed => true,
+        }
+    }
+}
+
 /// An atomic transaction
 #[derive(Debug, PartialEq, Default, Eq, Clone, Serialize, Deserialize)]
 pub struct Transaction {
@@ -165,6 +191,18 @@ impl Transaction {
         self.message().serialize()
     }
 
+    /// Account paying the fees of the transaction, i.e. the first signer.
+    pub fn fee_payer(&self) -> Option<&Pubkey> {
+        self.required_signers().first()
+    }
+
+    /// Accounts that have to sign the transaction, in the order of `signatures`.
+    pub fn required_signers(&self) -> &[Pubkey] {
+        let keys = &self.message.account_keys;
+        let count = self.message.header.num_required_signatures as usize;
+        &keys[..count.min(keys.len())]
+    }
+
     pub fn is_signed(&self) -> bool {
         self.signatures
             .iter()
@@ -176,7 +214,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails.
-    pub fn new_signed_with_payer<T: Signers>(
+    pub fn new_signed_with_payer<T: Signers + ?Sized>(
         instructions: &[Instruction],
         payer: Option<&Pubkey>,
         signing_keypairs: &T,
@@ -191,7 +229,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails.
-    pub fn new<T: Signers>(
+    pub fn new<T: Signers + ?Sized>(
         from_keypairs: &T,
         message: Message,
         recent_blockhash: Hash,
@@ -212,7 +250,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails.
-    pub fn new_with_compiled_instructions<T: Signers>(
+    pub fn new_with_compiled_instructions<T: Signers + ?Sized>(
         from_keypairs: &T,
         keys: &[Pubkey],
         recent_blockhash: Hash,
@@ -239,7 +277,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails, use [`Transaction::try_sign`] to handle the error.
-    pub fn sign<T: Signers>(&mut self, keypairs: &T, recent_blockhash: Hash) {
+    pub fn sign<T: Signers + ?Sized>(&mut self, keypairs: &T, recent_blockhash: Hash) {
         if let Err(e) = self.try_sign(keypairs, recent_blockhash) {
             panic!("Transaction::sign failed with error {:?}", e);
         }
@@ -252,7 +290,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails, use [`Transaction::try_partial_sign`] to handle the error.
-    pub fn partial_sign<T: Signers>(&mut self, keypairs: &T, recent_blockhash: Hash) {
+    pub fn partial_sign<T: Signers + ?Sized>(&mut self, keypairs: &T, recent_blockhash: Hash) {
         if let Err(e) = self.try_partial_sign(keypairs, recent_blockhash) {
             panic!("Transaction::partial_sign failed with error {:?}", e);
         }
@@ -264,7 +302,7 @@ impl Transaction {
     /// # Panics
     ///
     /// Panics when signing fails, use [`Transaction::try_partial_sign_unchecked`] to handle the error.
-    pub fn partial_sign_unchecked<T: Signers>(
+    pub fn partial_sign_unchecked<T: Signers + ?Sized>(
         &mut self,
         keypairs: &T,
         positions: Vec<usize>,
@@ -280,7 +318,7 @@ impl Transaction {
 
     /// Check keys and keypair lengths, then sign this transaction, returning any signing errors
     /// encountered
-    pub fn try_sign<T: Signers>(
+    pub fn try_sign<T: Signers + ?Sized>(
         &mut self,
         keypairs: &T,
         recent_blockhash: Hash,
@@ -297,11 +335,13 @@ impl Transaction {
     ///  Sign using some subset of required keys, returning any signing errors encountered. If
     ///  recent_blockhash is not the same as currently in the transaction, clear any prior
     ///  signatures and update recent_blockhash
-    pub fn try_partial_sign<T: Signers>(
+    pub fn try_partial_sign<T: Signers + ?Sized>(
         &mut self,
         keypairs: &T,
         recent_blockhash: Hash,
     ) -> std::result::Result<(), SignerError> {
+        self.message.sanitize()?;
+
         let positions = self.get_signing_keypair_positions(&keypairs.pubkeys())?;
         if positions.iter().any(|pos| pos.is_none()) {
             return Err(SignerError::KeypairPubkeyMismatch);
@@ -313,12 +353,58 @@ impl Transaction {
     /// Sign the transaction, returning any signing errors encountered, and place the
     /// signatures in their associated positions in `signatures` without checking that the
     /// positions are correct.
-    pub fn try_partial_sign_unchecked<T: Signers>(
+    ///
+    /// A keypair may be passed more than once, e.g. when the fee payer is also an authority of
+    /// an instruction, its position is filled only once.
+    pub fn try_partial_sign_unchecked<T: Signers + ?Sized>(
         &mut self,
         keypairs: &T,
         positions: Vec<usize>,
         recent_blockhash: Hash,
     ) -> std::result::Result<(), SignerError> {
+        self.reset_recent_blockhash(recent_blockhash);
+
+        let signatures = keypairs.try_sign_message(&self.message_data())?;
+        self.place_signatures(positions, signatures)
+    }
+
+    /// Like [`Transaction::try_sign`], but collects the signatures from [`AsyncSigner`]s, e.g.
+    /// hardware wallets. Signers are asked one after another, in order.
+    pub async fn try_sign_async(
+        &mut self,
+        signers: &[&dyn AsyncSigner],
+        recent_blockhash: Hash,
+    ) -> std::result::Result<(), SignerError> {
+        self.message.sanitize()?;
+
+        let mut pubkeys = Vec::with_capacity(signers.len());
+        for signer in signers {
+            pubkeys.push(signer.try_pubkey_async().await?);
+        }
+
+        let positions = self
+            .get_signing_keypair_positions(&pubkeys)?
+            .into_iter()
+            .collect::<Option<Vec<usize>>>()
+            .ok_or(SignerError::KeypairPubkeyMismatch)?;
+
+        self.reset_recent_blockhash(recent_blockhash);
+
+        let message = self.message_data();
+        let mut signatures = Vec::with_capacity(signers.len());
+        for signer in signers {
+            signatures.push(signer.try_sign_message_async(&message).await?);
+        }
+        self.place_signatures(positions, signatures)?;
+
+        if !self.is_signed() {
+            Err(SignerError::NotEnoughSigners)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn reset_recent_blockhash(&mut self, recent_blockhash: Hash) {
         // if you change the blockhash, you're re-signing...
         if recent_blockhash != self.message.recent_blockhash {
             self.message.recent_blockhash = recent_blockhash;
@@ -326,11 +412,39 @@ impl Transaction {
                 .iter_mut()
                 .for_each(|signature| *signature = Signature::default());
         }
+    }
 
-        let signatures = keypairs.try_sign_message(&self.message_data())?;
-        for i in 0..positions.len() {
-            self.signatures[positions[i]] = signatures[i];
+    fn place_signatures(
+        &mut self,
+        positions: Vec<usize>,
+        signatures: Vec<Signature>,
+    ) -> std::result::Result<(), SignerError> {
+        if signatures.len() != positions.len() {
+            return Err(SignerError::InvalidInput(format!(
+                "{} signatures for {} positions",
+                signatures.len(),
+                positions.len()
+            )));
+        }
+
+        let mut filled = Vec::with_capacity(positions.len());
+        for (position, signature) in positions.into_iter().zip(signatures) {
+            if position >= self.signatures.len() {
+                return Err(TransactionError::InvalidAccountIndex.into());
+            }
+
+            // signatures are deterministic, so a repeated keypair produces the same one again
+            if filled.contains(&position) {
+                if self.signatures[position] != signature {
+                    return Err(SignerError::KeypairPubkeyMismatch);
+                }
+                continue;
+            }
+
+            self.signatures[position] = signature;
+            filled.push(position);
         }
+
         Ok(())
     }
 
@@ -377,3 +491,245 @@ impl Transaction {
             .collect()
     }
 }
+
+#[cfg(all(test, feature = "crypto"))]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use async_trait::async_trait;
+
+    use crate::{
+        AccountMeta, AsyncSigner, Hash, Instruction, Keypair, Pubkey, Signature, Signer,
+        SignerError, Transaction, TransactionStatus,
+    };
+
+    /// Stands in for a hardware wallet, which hands out its key and signatures asynchronously.
+    struct MockDevice {
+        keypair: Keypair,
+        requests: Cell<usize>,
+        connected: bool,
+    }
+
+    #[async_trait(?Send)]
+    impl AsyncSigner for MockDevice {
+        async fn try_pubkey_async(&self) -> Result<Pubkey, SignerError> {
+            Ok(self.keypair.pubkey())
+        }
+
+        async fn try_sign_message_async(&self, message: &[u8]) -> Result<Signature, SignerError> {
+            self.requests.set(self.requests.get() + 1);
+
+            if !self.connected {
+                return Err(SignerError::Connection("device unplugged".to_string()));
+            }
+            Ok(self.keypair.sign_message(message))
+        }
+    }
+
+    #[test]
+    fn duplicate_signer_test() {
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let blockhash = Hash::new_unique();
+
+        let mut once = Transaction::new_with_payer(&[instruction.clone()], Some(&payer.pubkey()));
+        once.try_sign(&[&payer as &dyn Signer, &authority], blockhash)
+            .unwrap();
+
+        let mut twice = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        twice
+            .try_sign(&[&payer as &dyn Signer, &authority, &payer], blockhash)
+            .unwrap();
+
+        assert_eq!(twice.signatures.len(), 2);
+        assert!(twice.is_signed());
+        assert_eq!(twice.verify(), Ok(()));
+        assert_eq!(twice.signatures, once.signatures);
+
+        // signing again with the same keypairs changes nothing
+        twice
+            .try_partial_sign(&[&authority as &dyn Signer, &authority], blockhash)
+            .unwrap();
+        assert_eq!(twice.signatures, once.signatures);
+
+        // two different keypairs can't claim the same position
+        let mut mismatched = once.clone();
+        assert_eq!(
+            mismatched.try_partial_sign_unchecked(
+                &[&payer as &dyn Signer, &authority],
+                vec![0, 0],
+                blockhash
+            ),
+            Err(SignerError::KeypairPubkeyMismatch)
+        );
+    }
+
+    #[test]
+    fn is_confirmed_at_test() {
+        use crate::{CommitmentConfig, CommitmentLevel, TransactionConfirmationStatus};
+
+        let at = |commitment| CommitmentConfig { commitment };
+        let status = |confirmation_status, confirmations| TransactionStatus {
+            slot: 1,
+            confirmations,
+            status: Ok(()),
+            err: None,
+            confirmation_status,
+        };
+
+        let cases = [
+            (
+                TransactionConfirmationStatus::Processed,
+                Some(0),
+                [true, false, false],
+            ),
+            (
+                TransactionConfirmationStatus::Confirmed,
+                Some(5),
+                [true, true, false],
+            ),
+            (
+                TransactionConfirmationStatus::Finalized,
+                None,
+                [true, true, true],
+            ),
+        ];
+
+        for (confirmation_status, confirmations, expected) in cases.iter().cloned() {
+            let reported = status(Some(confirmation_status.clone()), confirmations);
+            // older nodes only report the confirmations
+            let legacy = status(None, confirmations);
+
+            for candidate in &[reported, legacy] {
+                assert_eq!(candidate.confirmation_status(), confirmation_status);
+
+                let levels = [
+                    CommitmentLevel::Processed,
+                    CommitmentLevel::Confirmed,
+                    CommitmentLevel::Finalized,
+                ];
+                for (level, expected) in levels.iter().zip(&expected) {
+                    assert_eq!(
+                        candidate.is_confirmed_at(at(*level)),
+                        *expected,
+                        "{:?} at {:?}",
+                        candidate,
+                        level
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn required_signers_test() {
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountMeta::new_readonly(authority, true),
+            ],
+            data: vec![],
+        };
+
+        let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+        assert_eq!(transaction.fee_payer(), Some(&payer));
+        assert_eq!(transaction.required_signers(), &[payer, authority]);
+        assert_eq!(
+            transaction.required_signers().len(),
+            transaction.signatures.len()
+        );
+
+        let unsigned = Transaction::default();
+        assert_eq!(unsigned.fee_payer(), None);
+        assert!(unsigned.required_signers().is_empty());
+    }
+
+    #[test]
+    fn boxed_signers_test() {
+        let payer = Keypair::new();
+        let authority = Keypair::new();
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(authority.pubkey(), true),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let payer_key = payer.pubkey();
+        let blockhash = Hash::new_unique();
+
+        let signers: Vec<Box<dyn Signer>> = vec![Box::new(payer), Box::new(Rc::new(authority))];
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction.clone()],
+            Some(&payer_key),
+            &signers,
+            blockhash,
+        );
+        assert!(transaction.is_signed());
+        assert_eq!(transaction.verify(), Ok(()));
+
+        let mut from_slice = Transaction::new_with_payer(&[instruction], Some(&payer_key));
+        from_slice.try_sign(&signers[..], blockhash).unwrap();
+        assert_eq!(from_slice.signatures, transaction.signatures);
+    }
+
+    #[test]
+    fn async_signer_test() {
+        let payer = Keypair::new();
+        let device = MockDevice {
+            keypair: Keypair::new(),
+            requests: Cell::new(0),
+            connected: true,
+        };
+        let instruction = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(device.keypair.pubkey(), true),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let blockhash = Hash::new_unique();
+
+        let mut transaction =
+            Transaction::new_with_payer(&[instruction.clone()], Some(&payer.pubkey()));
+        futures::executor::block_on(
+            transaction.try_sign_async(&[&payer as &dyn AsyncSigner, &device], blockhash),
+        )
+        .unwrap();
+        assert_eq!(device.requests.get(), 1);
+        assert_eq!(transaction.verify(), Ok(()));
+
+        let mut expected =
+            Transaction::new_with_payer(&[instruction.clone()], Some(&payer.pubkey()));
+        expected
+            .try_sign(&[&payer as &dyn Signer, &device.keypair], blockhash)
+            .unwrap();
+        assert_eq!(transaction.signatures, expected.signatures);
+
+        let unplugged = MockDevice {
+            connected: false,
+            ..device
+        };
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        assert_eq!(
+            futures::executor::block_on(
+                transaction.try_sign_async(&[&payer as &dyn AsyncSigner, &unplugged], blockhash)
+            ),
+            Err(SignerError::Connection("device unplugged".to_string()))
+        );
+    }
+}
@@ -8,6 +8,11 @@ pub fn parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     parse_accounts::parse_accounts(input)
 }
 
+#[proc_macro]
+pub fn try_parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    parse_accounts::try_parse_accounts(input)
+}
+
 #[proc_macro]
 pub fn parse_base58(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as LitStr);
@@ -82,6 +82,30 @@ impl Parse for LoadStatements {
 pub fn parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let stmts = parse_macro_input!(input as LoadStatements).stmts;
 
+    expand(stmts, false)
+}
+
+/// Like [`parse_accounts`], but returns `Error::NotEnoughAccountKeys` or `Error::Validation`
+/// from the enclosing function instead of panicking. `Error` is resolved at the call site.
+pub fn try_parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let stmts = parse_macro_input!(input as LoadStatements).stmts;
+
+    expand(stmts, true)
+}
+
+fn expand(stmts: Vec<LoadStatement>, fallible: bool) -> proc_macro::TokenStream {
+    let (not_enough_accounts, not_writable): (Stmt, Stmt) = if fallible {
+        (
+            parse_quote! { return Err(Error::NotEnoughAccountKeys.into()); },
+            parse_quote! { return Err(Error::Validation.into()); },
+        )
+    } else {
+        (
+            parse_quote! { panic!("cannot load"); },
+            parse_quote! { panic!("cannot load"); },
+        )
+    };
+
     let mut new_stmts: Vec<Stmt> = vec![];
     for stmt in stmts {
         let LoadStatement {
@@ -93,7 +117,7 @@ pub fn parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream
         new_stmts.push(parse_quote! {
             if input.is_empty() {
                 solar::qlog!("cannot load `", stringify!(#ident), "` because there are not enough accounts (len = ", input.len(), ")");
-                panic!("cannot load");
+                #not_enough_accounts
             }
         });
 
@@ -105,7 +129,7 @@ pub fn parse_accounts(input: proc_macro::TokenStream) -> proc_macro::TokenStream
             new_stmts.push(parse_quote! {
                 if !solar::account::AccountFields::is_writable(solar::account::AccountBackend::backend(&#ident)) {
                     solar::qlog!("cannot load `", stringify!(#ident), "` because it is read-only, but expected writable (len = ", input.len(), ")");
-                    panic!("cannot load");
+                    #not_writable
                 }
             })
         }
@@ -1,5 +1,10 @@
 use solana_api_types::Pubkey;
 
+use crate::{
+    log::{Loggable, Logger},
+    util::pubkey_eq,
+};
+
 pub mod offchain;
 #[cfg(feature = "onchain")]
 pub mod onchain;
@@ -15,6 +20,61 @@ pub trait AccountFields {
     fn lamports(&self) -> u64;
     fn rent_epoch(&self) -> u64;
     fn data(&self) -> &[u8];
+
+    /// Fail unless the account is owned by `owner`, returns the account so checks can be chained.
+    #[inline]
+    fn require_owner(&self, owner: &Pubkey) -> Result<&Self, AccountCheckError> {
+        if pubkey_eq(self.owner(), owner) {
+            Ok(self)
+        } else {
+            Err(AccountCheckError::InvalidOwner)
+        }
+    }
+
+    /// Fail unless the key of the account is `key`.
+    #[inline]
+    fn require_key(&self, key: &Pubkey) -> Result<&Self, AccountCheckError> {
+        if pubkey_eq(self.key(), key) {
+            Ok(self)
+        } else {
+            Err(AccountCheckError::InvalidKey)
+        }
+    }
+
+    /// Fail unless the account signed the transaction.
+    #[inline]
+    fn require_signer(&self) -> Result<&Self, AccountCheckError> {
+        if self.is_signer() {
+            Ok(self)
+        } else {
+            Err(AccountCheckError::MissingSignature)
+        }
+    }
+
+    /// Fail unless the account is writable.
+    #[inline]
+    fn require_writable(&self) -> Result<&Self, AccountCheckError> {
+        if self.is_writable() {
+            Ok(self)
+        } else {
+            Err(AccountCheckError::NotWritable)
+        }
+    }
+}
+
+/// Failed `require_*` check of [`AccountFields`].
+#[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum AccountCheckError {
+    InvalidOwner,
+    InvalidKey,
+    MissingSignature,
+    NotWritable,
+}
+
+impl Loggable for AccountCheckError {
+    fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>) {
+        logger.push_str(self.into())
+    }
 }
 
 pub trait AccountFieldsMut: AccountFields {
@@ -112,3 +172,93 @@ macro_rules! forward_account_backend {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_api_types::Pubkey;
+
+    use super::{AccountCheckError, AccountFields};
+
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        is_signer: bool,
+        is_writable: bool,
+    }
+
+    impl AccountFields for TestAccount {
+        fn key(&self) -> &Pubkey {
+            &self.key
+        }
+
+        fn owner(&self) -> &Pubkey {
+            &self.owner
+        }
+
+        fn is_signer(&self) -> bool {
+            self.is_signer
+        }
+
+        fn is_writable(&self) -> bool {
+            self.is_writable
+        }
+
+        fn is_executable(&self) -> bool {
+            false
+        }
+
+        fn lamports(&self) -> u64 {
+            0
+        }
+
+        fn rent_epoch(&self) -> u64 {
+            0
+        }
+
+        fn data(&self) -> &[u8] {
+            &[]
+        }
+    }
+
+    #[test]
+    fn require_test() {
+        let key = Pubkey::new([1; 32]);
+        let owner = Pubkey::new([2; 32]);
+        let other = Pubkey::new([3; 32]);
+
+        let account = TestAccount {
+            key,
+            owner,
+            is_signer: true,
+            is_writable: true,
+        };
+        assert!(account
+            .require_key(&key)
+            .and_then(|account| account.require_owner(&owner))
+            .and_then(|account| account.require_signer())
+            .and_then(|account| account.require_writable())
+            .is_ok());
+        assert_eq!(
+            account.require_key(&other).err(),
+            Some(AccountCheckError::InvalidKey)
+        );
+        assert_eq!(
+            account.require_owner(&other).err(),
+            Some(AccountCheckError::InvalidOwner)
+        );
+
+        let account = TestAccount {
+            is_signer: false,
+            is_writable: false,
+            ..account
+        };
+        assert_eq!(
+            account.require_signer().err(),
+            Some(AccountCheckError::MissingSignature)
+        );
+        assert_eq!(
+            account.require_writable().err(),
+            Some(AccountCheckError::NotWritable)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::mem::size_of;
+
+use solana_api_types::Pubkey;
+
+use crate::{
+    account::{AccountBackend, AccountFields},
+    forward_account_backend,
+    reinterpret::reinterpret_unchecked,
+    spl::{self, Multisig, MAX_SIGNERS},
+    util::pubkey_eq,
+};
+
+#[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorityError {
+    InvalidKey,
+    NotSigned,
+    InvalidMultisig,
+    ThresholdNotMet,
+}
+
+/// An account acting as a single-key authority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Authority<B: AccountBackend> {
+    account: B,
+}
+
+impl<B: AccountBackend> Authority<B> {
+    /// Any account that signed the transaction.
+    pub fn any_signed(account: B) -> Result<Self, AuthorityError> {
+        if !account.is_signer() {
+            Err(AuthorityError::NotSigned)
+        } else {
+            Ok(Self { account })
+        }
+    }
+
+    /// `expected` itself, which must have signed the transaction.
+    pub fn expected_signed(account: B, expected: &Pubkey) -> Result<Self, AuthorityError> {
+        if !pubkey_eq(account.key(), expected) {
+            Err(AuthorityError::InvalidKey)
+        } else {
+            Self::any_signed(account)
+        }
+    }
+
+    /// The program address derived from `seeds` and `program_id`.
+    ///
+    /// The account doesn't sign the transaction itself, `program_id` signs for it in CPIs by
+    /// passing the same `seeds` to `invoke_signed`.
+    pub fn expected_pda(
+        account: B,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> Result<Self, AuthorityError> {
+        match Pubkey::create_program_address(seeds, program_id) {
+            Some(address) if pubkey_eq(account.key(), &address) => Ok(Self { account }),
+            _ => Err(AuthorityError::InvalidKey),
+        }
+    }
+
+    pub fn account(&self) -> &B {
+        &self.account
+    }
+}
+
+/// A token program `Multisig` account acting as an authority.
+///
+/// The multisig account itself never signs, instead at least `m` of its signers have to be
+/// passed as separate signed accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultisigAuthority<B: AccountBackend> {
+    account: B,
+}
+
+impl<B: AccountBackend> MultisigAuthority<B> {
+    /// `expected` multisig, approved by at least `m` of its signers among `signers`.
+    ///
+    /// Each multisig signer is only counted once, no matter how many times it's passed.
+    pub fn expected_signed<S: AccountFields>(
+        account: B,
+        expected: &Pubkey,
+        signers: &[S],
+    ) -> Result<Self, AuthorityError> {
+        if !pubkey_eq(account.key(), expected) {
+            return Err(AuthorityError::InvalidKey);
+        }
+
+        let authority = Self::any(account)?;
+        let multisig = authority.multisig();
+
+        let mut matched = [false; MAX_SIGNERS];
+        let mut approvals = 0;
+        for signer in signers.iter().filter(|signer| signer.is_signer()) {
+            for (index, key) in multisig.signers().iter().enumerate() {
+                if !matched[index] && pubkey_eq(key, signer.key()) {
+                    matched[index] = true;
+                    approvals += 1;
+                    break;
+                }
+            }
+        }
+
+        if approvals < multisig.m() {
+            Err(AuthorityError::ThresholdNotMet)
+        } else {
+            Ok(authority)
+        }
+    }
+
+    fn any(account: B) -> Result<Self, AuthorityError> {
+        let data = account.data();
+
+        if !pubkey_eq(account.owner(), &*spl::ID) || data.len() != size_of::<Multisig>() {
+            return Err(AuthorityError::InvalidMultisig);
+        }
+
+        // m, n and is_initialized, checked before the bool is reinterpreted
+        let (m, n, is_initialized) = (data[0], data[1], data[2]);
+        if is_initialized != 1 || m == 0 || m > n || n as usize > MAX_SIGNERS {
+            return Err(AuthorityError::InvalidMultisig);
+        }
+
+        Ok(Self { account })
+    }
+
+    pub fn multisig(&self) -> &Multisig {
+        unsafe { reinterpret_unchecked(self.account.data()) }
+    }
+}
+
+forward_account_backend!(Authority, account);
+forward_account_backend!(MultisigAuthority, account);
@@ -1,10 +1,42 @@
 use std::{alloc::Layout, mem::size_of, ptr::null_mut};
 
-use solana_api_types::entrypoint::{HEAP_LENGTH, HEAP_START_ADDRESS};
+use solana_api_types::{
+    entrypoint::{HEAP_LENGTH, HEAP_START_ADDRESS},
+    program::{ProgramError, ProgramResult},
+};
 
+use crate::{log::Loggable, qlog};
+
+/// Log where the program panicked.
+///
+/// Panics abort the VM without an error code, so this is all the client gets to see. The
+/// message is left out, formatting it would pull `std::fmt` into the program.
 #[inline(always)]
-pub fn panic_handler(_: &core::panic::PanicInfo) {
-    // msg!("{}", info);
+pub fn panic_handler(info: &core::panic::PanicInfo) {
+    match info.location() {
+        Some(location) => {
+            qlog!("panicked at ", location.file(), ":", location.line());
+        }
+        None => {
+            qlog!("panicked");
+        }
+    }
+}
+
+/// Run the handler of `method`, logging the error it fails with.
+///
+/// The error is converted to a `ProgramError`, usually `ProgramError::Custom` with the program's
+/// own error code, so clients can tell which check failed.
+pub fn handle_instruction<M, E, F>(method: &M, handler: F) -> ProgramResult
+where
+    M: Loggable,
+    E: Loggable + Into<ProgramError>,
+    F: FnOnce() -> Result<(), E>,
+{
+    handler().map_err(|err| {
+        qlog!(*method, " failed: ", err);
+        err.into()
+    })
 }
 pub struct BpfAllocator {}
 
@@ -44,8 +76,11 @@ macro_rules! entrypoint {
     ($process_instruction:path) => {
         #[no_mangle]
         pub unsafe extern "C" fn entrypoint(input: *mut u8) -> u64 {
-            let input =
-                unsafe { $crate::input::BpfProgramInput::deserialize_from_bpf_entrypoint(input) };
+            let input = $crate::input::BpfProgramInput::deserialize_from_bpf_entrypoint(input);
+            let input = match input {
+                Ok(input) => input,
+                Err(error) => return error.into(),
+            };
             match $process_instruction(input) {
                 Ok(()) => solana_program::entrypoint::SUCCESS,
                 Err(error) => error.into(),
@@ -61,3 +96,37 @@ macro_rules! entrypoint {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_api_types::program::ProgramError;
+
+    use super::handle_instruction;
+    use crate::log::{Loggable, Logger};
+
+    struct Failure;
+
+    impl Loggable for Failure {
+        fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>) {
+            logger.push_str("Failure")
+        }
+    }
+
+    impl From<Failure> for ProgramError {
+        fn from(_: Failure) -> Self {
+            ProgramError::Custom(42)
+        }
+    }
+
+    #[test]
+    fn handle_instruction_test() {
+        assert_eq!(
+            handle_instruction(&"Method", || Err(Failure)),
+            Err(ProgramError::Custom(42))
+        );
+        assert_eq!(
+            handle_instruction(&"Method", || Ok::<_, Failure>(())),
+            Ok(())
+        );
+    }
+}
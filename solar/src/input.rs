@@ -1,6 +1,7 @@
-use solana_api_types::Pubkey;
+use parity_scale_codec::Decode;
+use solana_api_types::{program::ProgramError, Pubkey};
 
-use crate::prelude::AccountBackend;
+use crate::{prelude::AccountBackend, qlog};
 #[cfg(feature = "onchain")]
 pub use onchain::*;
 
@@ -20,6 +21,33 @@ pub trait AccountSource<B: AccountBackend>: ProgramInput {
     fn next_account(&mut self) -> B;
 }
 
+/// Decode the instruction data of a program as `T`.
+///
+/// Empty data, data that fails to decode and data with bytes left over after decoding are all
+/// rejected with `InvalidInstructionData`, so malformed calldata fails early and visibly.
+pub fn decode_instruction<T: Decode>(data: &[u8]) -> Result<T, ProgramError> {
+    if data.is_empty() {
+        qlog!("instruction data is empty");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    let mut rest = data;
+    let value = match T::decode(&mut rest) {
+        Ok(value) => value,
+        Err(_) => {
+            qlog!("malformed instruction data, length: ", data.len());
+            return Err(ProgramError::InvalidInstructionData);
+        }
+    };
+
+    if !rest.is_empty() {
+        qlog!("trailing bytes in instruction data: ", rest.len());
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    Ok(value)
+}
+
 #[cfg(feature = "onchain")]
 mod onchain {
     use std::{
@@ -47,6 +75,16 @@ mod onchain {
         pub(crate) cursor: usize,
     }
 
+    /// Reject inputs with more accounts than the deserializer has room for.
+    pub(crate) fn check_account_count(num_accounts: usize) -> Result<(), ProgramError> {
+        if num_accounts > MAX_ACCOUNTS {
+            qlog!("too many accounts in input: ", num_accounts, ", max: ", MAX_ACCOUNTS);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
     #[repr(C)]
     struct SerializedAccount {
         dup_info: u8,
@@ -64,23 +102,26 @@ mod onchain {
         /// Deserialize inputs to a BPF program invocation.
         ///
         /// This implementation is hand-optimized to produce minimal bytecode.
+        ///
+        /// Accounts passed more than once are deserialized once and share their memory.
+        /// Inputs with more than [`MAX_ACCOUNTS`] accounts fail with `InvalidArgument`.
         /// # Safety
         /// Must be called with a pointer to a BPF entrypoint memory region, or one that mimicks it.
-        pub unsafe fn deserialize_from_bpf_entrypoint(mut input: *mut u8) -> Self {
+        pub unsafe fn deserialize_from_bpf_entrypoint(
+            mut input: *mut u8,
+        ) -> Result<Self, ProgramError> {
             const U64_SIZE: usize = size_of::<u64>();
 
             let num_accounts = *(input as *const u64) as usize;
-
-            if num_accounts > 32 {
-                panic!("max 32 accounts supported in input");
-            }
+            check_account_count(num_accounts)?;
 
             input = input.add(U64_SIZE);
 
-            let memory = std::alloc::alloc(Layout::new::<[MaybeUninit<Account>; 32]>());
-            let accounts = &mut *memory.cast::<[MaybeUninit<Account>; 32]>();
+            let memory =
+                std::alloc::alloc(Layout::new::<[MaybeUninit<Account>; MAX_ACCOUNTS]>());
+            let accounts = &mut *memory.cast::<[MaybeUninit<Account>; MAX_ACCOUNTS]>();
 
-            (0..num_accounts).for_each(|i| {
+            for i in 0..num_accounts {
                 let dup_info = *(input as *const u8);
                 if dup_info == std::u8::MAX {
                     let serialized = &mut *(input as *mut SerializedAccount);
@@ -107,9 +148,22 @@ mod onchain {
 
                     input = data_end.add(U64_SIZE);
                 } else {
-                    panic!("duplicate account inputs are unsupported");
+                    // NB: the runtime marks repeated accounts with the index of their first
+                    // occurrence, which is always deserialized by now. The copy points to the same
+                    // memory, so writes through either of them are seen by both.
+                    let original = dup_info as usize;
+                    if original >= i {
+                        qlog!("invalid duplicate account index: ", original, ", at: ", i);
+                        return Err(ProgramError::InvalidArgument);
+                    }
+
+                    let account = accounts.get_unchecked(original).assume_init_ref().copy();
+                    accounts.get_unchecked_mut(i).as_mut_ptr().write(account);
+
+                    // the marker is padded to 8 bytes
+                    input = input.add(U64_SIZE);
                 }
-            });
+            }
 
             let data_len = *(input as *const u64) as usize;
             input = input.add(U64_SIZE);
@@ -123,11 +177,11 @@ mod onchain {
                 cursor: 0,
             };
 
-            BpfProgramInput {
+            Ok(BpfProgramInput {
                 program_id,
                 accounts,
                 data,
-            }
+            })
         }
     }
 
@@ -236,8 +290,8 @@ pub fn wrapped_entrypoint<T: Entrypoint>(
     use std::mem::MaybeUninit;
     use crate::account::onchain::Account;
 
-    if account_infos.len() > MAX_ACCOUNTS {
-        panic!("too many accounts");
+    if let Err(err) = onchain::check_account_count(account_infos.len()) {
+        return Err(solana_program::program_error::ProgramError::from(u64::from(err)));
     }
 
     let mut accounts_array: [MaybeUninit<Account>; MAX_ACCOUNTS] = MaybeUninit::uninit_array();
@@ -277,3 +331,169 @@ pub fn wrapped_entrypoint<T: Entrypoint>(
 
     T::call(input).map_err(|err| solana_program::program_error::ProgramError::from(u64::from(err)))
 }
+
+#[cfg(all(test, feature = "onchain"))]
+mod tests {
+    use solana_api_types::{
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        program::ProgramError,
+        Pubkey,
+    };
+    #[cfg(feature = "runtime-test")]
+    use solana_api_types::program::ProgramResult;
+
+    #[cfg(feature = "runtime-test")]
+    use solana_program::{account_info::AccountInfo, program_error, pubkey};
+
+    use super::{AccountSource, BpfProgramInput, ProgramInput};
+    #[cfg(feature = "runtime-test")]
+    use super::{wrapped_entrypoint, Entrypoint, MAX_ACCOUNTS};
+    use crate::account::{AccountFields, AccountFieldsMut};
+
+    enum InputAccount {
+        Account { key: Pubkey, data: Vec<u8> },
+        Duplicate(u8),
+    }
+
+    /// Lay out `accounts` and `data` the way the runtime passes them to a BPF entrypoint.
+    fn serialize_input(accounts: &[InputAccount], data: &[u8], program_id: &Pubkey) -> Vec<u8> {
+        let mut input = vec![];
+        input.extend_from_slice(&(accounts.len() as u64).to_le_bytes());
+
+        for account in accounts {
+            match account {
+                InputAccount::Account { key, data } => {
+                    input.extend_from_slice(&[u8::MAX, 0, 1, 0, 0, 0, 0, 0]);
+                    input.extend_from_slice(key.as_ref());
+                    input.extend_from_slice(Pubkey::default().as_ref());
+                    input.extend_from_slice(&100u64.to_le_bytes());
+                    input.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                    input.extend_from_slice(data);
+
+                    let data_end = input.len() + MAX_PERMITTED_DATA_INCREASE;
+                    input.resize(data_end + (8 - data_end % 8) % 8, 0);
+                    input.extend_from_slice(&0u64.to_le_bytes());
+                }
+                InputAccount::Duplicate(index) => {
+                    input.extend_from_slice(&[*index, 0, 0, 0, 0, 0, 0, 0]);
+                }
+            }
+        }
+
+        input.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        input.extend_from_slice(data);
+        input.extend_from_slice(program_id.as_ref());
+        input
+    }
+
+    /// Deserialize `input` from aligned memory that outlives the accounts referring to it.
+    fn deserialize(input: &[u8]) -> Result<BpfProgramInput, ProgramError> {
+        let words = vec![0u64; (input.len() + 7) / 8].leak();
+        let memory = words.as_mut_ptr().cast::<u8>();
+
+        unsafe {
+            memory.copy_from_nonoverlapping(input.as_ptr(), input.len());
+            BpfProgramInput::deserialize_from_bpf_entrypoint(memory)
+        }
+    }
+
+    #[test]
+    fn duplicate_accounts_test() {
+        let payer = Pubkey::new([1; 32]);
+        let pool = Pubkey::new([2; 32]);
+        let program_id = Pubkey::new([3; 32]);
+
+        let input = serialize_input(
+            &[
+                InputAccount::Account {
+                    key: payer,
+                    data: vec![0; 3],
+                },
+                InputAccount::Account {
+                    key: pool,
+                    data: vec![7; 5],
+                },
+                InputAccount::Duplicate(0),
+                InputAccount::Duplicate(1),
+            ],
+            &[42],
+            &program_id,
+        );
+
+        let mut input = deserialize(&input).unwrap();
+        assert_eq!(input.len(), 4);
+        assert_eq!(input.data(), &[42]);
+        assert_eq!(input.program_id(), &program_id);
+
+        let [payer_account, pool_account, authority_account, pool_copy] =
+            input.take_accounts::<4>();
+        assert_eq!(payer_account.key(), &payer);
+        assert_eq!(authority_account.key(), &payer);
+        assert_eq!(pool_copy.key(), &pool);
+        assert_eq!(pool_copy.data(), &[7; 5]);
+
+        // both refer to the same account
+        pool_copy.set_lamports(50);
+        pool_copy.data_mut()[0] = 8;
+        assert_eq!(pool_account.lamports(), 50);
+        assert_eq!(pool_account.data()[0], 8);
+    }
+
+    #[test]
+    fn invalid_duplicate_test() {
+        let program_id = Pubkey::new([3; 32]);
+        let input = serialize_input(&[InputAccount::Duplicate(0)], &[], &program_id);
+
+        assert!(matches!(
+            deserialize(&input),
+            Err(ProgramError::InvalidArgument)
+        ));
+    }
+
+    /// Succeeds if it sees as many accounts as the first byte of the instruction data says.
+    #[cfg(feature = "runtime-test")]
+    struct CountAccounts;
+
+    #[cfg(feature = "runtime-test")]
+    impl Entrypoint for CountAccounts {
+        fn call(input: BpfProgramInput) -> ProgramResult {
+            if input.len() == input.data()[0] as usize {
+                Ok(())
+            } else {
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+
+    #[cfg(feature = "runtime-test")]
+    fn call_with_accounts(count: usize) -> Result<(), program_error::ProgramError> {
+        let keys: Vec<_> = (0..count)
+            .map(|i| pubkey::Pubkey::new(&[i as u8; 32]))
+            .collect();
+        let owner = pubkey::Pubkey::default();
+        let mut lamports = vec![0; count];
+        let mut data = vec![[0; 8]; count];
+
+        let infos: Vec<_> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, false, true, lamports, data, &owner, false, 0)
+            })
+            .collect();
+
+        wrapped_entrypoint::<CountAccounts>(&owner, &infos, &[count as u8])
+    }
+
+    #[cfg(feature = "runtime-test")]
+    #[test]
+    fn account_count_test() {
+        assert_eq!(call_with_accounts(0), Ok(()));
+        assert_eq!(call_with_accounts(MAX_ACCOUNTS), Ok(()));
+        assert_eq!(
+            call_with_accounts(MAX_ACCOUNTS + 1),
+            Err(program_error::ProgramError::InvalidArgument)
+        );
+    }
+}
@@ -1,5 +1,6 @@
 #![cfg_attr(target_arch = "bpf", feature(test))]
 #![cfg_attr(not(target_arch = "bpf"), feature(bench_black_box))]
+#![cfg_attr(all(test, not(target_arch = "bpf")), feature(test))]
 #![allow(stable_features)]
 #![feature(min_const_generics)]
 #![feature(maybe_uninit_ref)]
@@ -10,6 +11,7 @@
 extern crate strum;
 
 pub mod account;
+pub mod authority;
 pub mod collections;
 #[cfg(feature = "onchain")]
 pub mod entrypoint;
@@ -21,6 +23,7 @@ pub mod math;
 pub mod mem;
 pub mod reinterpret;
 pub mod spl;
+pub mod sysvar;
 pub mod time;
 pub mod util;
 
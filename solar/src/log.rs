@@ -22,6 +22,8 @@ pub struct Logger<const S: usize> {
 
 extern "C" {
     fn sol_log_(src: *const u8, len: u64);
+    #[cfg(all(feature = "compute-units", target_arch = "bpf"))]
+    fn sol_log_compute_units_();
 }
 
 impl<const S: usize> Logger<S> {
@@ -45,31 +47,65 @@ impl<const S: usize> Logger<S> {
         self.cursor += unsafe { write_to_ptr(self.buf.as_mut_ptr().add(self.cursor).cast(), i) };
     }
 
+    /// Everything pushed so far.
+    pub fn as_str(&self) -> &str {
+        // only whole strings and formatted integers are ever pushed, so this is valid UTF-8
+        unsafe {
+            std::str::from_utf8_unchecked(std::slice::from_raw_parts(
+                self.buf.as_ptr().cast::<u8>(),
+                self.cursor,
+            ))
+        }
+    }
+
     pub fn log(&self) {
         if cfg!(target_arch = "bpf") {
             unsafe {
                 sol_log_(self.buf.as_ptr().cast(), self.cursor as u64);
             }
         } else {
-            let buf = unsafe {
-                std::slice::from_raw_parts(self.buf.as_ptr().cast::<u8>(), self.cursor)
-            };
-            let output = String::from_utf8_lossy(buf);
+            let output = self.as_str();
 
             #[cfg(feature = "runtime-test")]
             {
 
-                solana_program::log::sol_log(&output);
+                solana_program::log::sol_log(output);
             }
 
             #[cfg(not(feature = "runtime-test"))]
             {
-                solana_api_types::syscalls::sol_log(&output)
+                solana_api_types::syscalls::sol_log(output)
             }
         }
     }
 }
 
+/// Run `f` between two logs of the remaining compute units, so the units it consumed are the
+/// difference between the `<label> start` and `<label> end` lines.
+///
+/// Only logs on-chain with the `compute-units` feature enabled, otherwise it just calls `f`.
+#[inline(always)]
+pub fn with_compute_units<R>(label: &str, f: impl FnOnce() -> R) -> R {
+    #[cfg(all(feature = "compute-units", target_arch = "bpf"))]
+    {
+        crate::qlog!(label, " start");
+        unsafe { sol_log_compute_units_() };
+
+        let result = f();
+
+        unsafe { sol_log_compute_units_() };
+        crate::qlog!(label, " end");
+
+        result
+    }
+
+    #[cfg(not(all(feature = "compute-units", target_arch = "bpf")))]
+    {
+        let _ = label;
+        f()
+    }
+}
+
 pub trait Loggable {
     fn push_to_logger<const S: usize>(&self, logger: &mut Logger<S>);
 }
@@ -1,4 +1,5 @@
 use std::{
+    convert::TryFrom,
     ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
     panic::Location,
 };
@@ -224,3 +225,31 @@ impl<T: ToFixed> ToF64 for T {
         overflow_guard!(self.checked_to_fixed::<I64F64>()).into()
     }
 }
+
+/// Basis points in a whole, i.e. 100%.
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// `amount * bps / 10_000`, rounded down.
+///
+/// The product is taken in `u128`, so this only fails if the result doesn't fit into a `u64`,
+/// which can't happen for `bps` up to [`BPS_DENOMINATOR`].
+#[inline]
+pub fn mul_bps(amount: u64, bps: u16) -> Option<u64> {
+    let result = amount as u128 * bps as u128 / BPS_DENOMINATOR as u128;
+    u64::try_from(result).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mul_bps;
+
+    #[test]
+    fn mul_bps_test() {
+        assert_eq!(mul_bps(1000, 5000), Some(500));
+        assert_eq!(mul_bps(1001, 5000), Some(500));
+        assert_eq!(mul_bps(1, 9999), Some(0));
+        assert_eq!(mul_bps(u64::MAX, 10_000), Some(u64::MAX));
+        assert_eq!(mul_bps(u64::MAX, 10_001), None);
+        assert_eq!(mul_bps(0, u16::MAX), Some(0));
+    }
+}
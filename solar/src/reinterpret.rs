@@ -17,6 +17,7 @@
 //! no references and no other complex types with strict invariants. Using them on anything else is a *bad idea*.
 
 use std::{
+    any::type_name,
     mem::align_of,
     mem::size_of,
     slice::{from_raw_parts, from_raw_parts_mut},
@@ -30,6 +31,42 @@ pub fn is_valid_for_type<T>(data: &[u8]) -> bool {
     data.len() >= size_of::<T>() && (data.as_ptr() as usize) % align_of::<T>() == 0
 }
 
+/// Catch size and alignment mistakes of the unchecked helpers in debug builds, release and
+/// on-chain builds don't pay for it.
+#[inline(always)]
+fn debug_assert_valid_for_type<T>(data: &[u8]) {
+    debug_assert!(
+        data.len() >= size_of::<T>(),
+        "{} bytes are too short for {}",
+        data.len(),
+        type_name::<T>()
+    );
+    debug_assert_aligned::<T>(data);
+}
+
+#[inline(always)]
+fn debug_assert_aligned<T>(data: &[u8]) {
+    debug_assert!(
+        (data.as_ptr() as usize) % align_of::<T>() == 0,
+        "bytes are misaligned for {}",
+        type_name::<T>()
+    );
+}
+
+/// Reinterpret a slice of bytes as an object of a type valid for any bit pattern.
+///
+/// Returns `None` if the slice is too short or misaligned for `T`.
+#[inline(always)]
+pub fn checked_reinterpret<T: ReinterpretSafe>(data: &[u8]) -> Option<&T> {
+    unsafe { try_reinterpret(data) }
+}
+
+/// Mutable counterpart of [`checked_reinterpret`].
+#[inline(always)]
+pub fn checked_reinterpret_mut<T: ReinterpretSafe>(data: &mut [u8]) -> Option<&mut T> {
+    unsafe { try_reinterpret_mut(data) }
+}
+
 /// Reinterpret a slice of bytes as an object.
 ///
 /// The slice can be bigger than necessary.
@@ -52,9 +89,11 @@ pub unsafe fn try_reinterpret<T>(data: &[u8]) -> Option<&T> {
 /// # Safety
 /// `data` must be a valid representation of object `T`
 ///
-/// Unlike [`try_reinterpret`], this function does not check size or alignment.
+/// Unlike [`try_reinterpret`], this function does not check size or alignment, except for a
+/// debug assertion.
 #[inline(always)]
 pub unsafe fn reinterpret_unchecked<T>(data: &[u8]) -> &T {
+    debug_assert_valid_for_type::<T>(data);
     &*(data.as_ptr() as *const T)
 }
 
@@ -80,9 +119,11 @@ pub unsafe fn try_reinterpret_mut<T>(data: &mut [u8]) -> Option<&mut T> {
 /// # Safety
 /// `data` must be a valid representation of object `T`
 ///
-/// Unlike [`try_reinterpret_mut`], this function does not check size or alignment.
+/// Unlike [`try_reinterpret_mut`], this function does not check size or alignment, except for
+/// a debug assertion.
 #[inline(always)]
 pub unsafe fn reinterpret_mut_unchecked<T>(data: &mut [u8]) -> &mut T {
+    debug_assert_valid_for_type::<T>(data);
     &mut *(data.as_mut_ptr() as *mut T)
 }
 
@@ -108,9 +149,11 @@ pub unsafe fn try_reinterpret_slice<T>(data: &[u8]) -> Option<&[T]> {
 /// # Safety
 /// `data` must be a valid representation of some number of objects `T`
 ///
-/// Unlike [`try_reinterpret_slice`], this function does not check for alignment or size.
+/// Unlike [`try_reinterpret_slice`], this function does not check for alignment or size, except
+/// for a debug assertion on the alignment.
 #[inline(always)]
 pub unsafe fn reinterpret_slice_unchecked<T>(data: &[u8]) -> &[T] {
+    debug_assert_aligned::<T>(data);
     let count = data.len() / size_of::<T>();
 
     from_raw_parts(data.as_ptr() as *const T, count)
@@ -138,9 +181,11 @@ pub unsafe fn try_reinterpret_slice_mut<T>(data: &mut [u8]) -> Option<&mut [T]>
 /// # Safety
 /// `data` must be a valid representation of some number of objects `T`
 ///
-/// Unlike [`try_reinterpret_slice_mut`], this function does not check for alignment or size.
+/// Unlike [`try_reinterpret_slice_mut`], this function does not check for alignment or size,
+/// except for a debug assertion on the alignment.
 #[inline(always)]
 pub unsafe fn reinterpret_slice_mut_unchecked<T>(data: &mut [u8]) -> &mut [T] {
+    debug_assert_aligned::<T>(data);
     let count = data.len() / size_of::<T>();
 
     from_raw_parts_mut(data.as_mut_ptr() as *mut T, count)
@@ -167,3 +212,53 @@ impl_reinterpret_safe! {
 }
 
 unsafe impl<T: ReinterpretSafe, const N: usize> ReinterpretSafe for [T; N] {}
+
+#[cfg(test)]
+mod tests {
+    use super::{checked_reinterpret, checked_reinterpret_mut, reinterpret_unchecked};
+
+    fn aligned_bytes() -> Vec<u64> {
+        vec![0x0102_0304_0506_0708, 0]
+    }
+
+    fn as_bytes(words: &[u64]) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(words.as_ptr().cast(), words.len() * 8) }
+    }
+
+    #[test]
+    fn checked_reinterpret_test() {
+        let words = aligned_bytes();
+        let bytes = as_bytes(&words);
+
+        assert_eq!(checked_reinterpret::<u64>(bytes), Some(&words[0]));
+        assert_eq!(checked_reinterpret::<[u64; 2]>(bytes), Some(&[words[0], 0]));
+
+        // misaligned
+        assert_eq!(checked_reinterpret::<u64>(&bytes[1..]), None);
+        // undersized
+        assert_eq!(checked_reinterpret::<u64>(&bytes[..7]), None);
+        assert_eq!(checked_reinterpret::<[u64; 3]>(bytes), None);
+
+        let mut words = aligned_bytes();
+        let bytes = unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr().cast::<u8>(), 16) };
+        *checked_reinterpret_mut::<u64>(&mut bytes[8..]).unwrap() = 42;
+        assert!(checked_reinterpret_mut::<u64>(&mut bytes[4..]).is_none());
+        assert_eq!(words[1], 42);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "too short")]
+    fn unchecked_undersized_test() {
+        let words = aligned_bytes();
+        let _ = unsafe { reinterpret_unchecked::<[u64; 3]>(as_bytes(&words)) };
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "misaligned")]
+    fn unchecked_misaligned_test() {
+        let words = aligned_bytes();
+        let _ = unsafe { reinterpret_unchecked::<u64>(&as_bytes(&words)[1..]) };
+    }
+}
@@ -1,11 +1,16 @@
-use std::{io::Write, mem::size_of, ops::Deref};
+use std::{convert::TryInto, io::Write, mem::size_of, ops::Deref};
+
+use serde::ser::SerializeStruct;
 
 #[cfg(feature = "onchain")]
 use solana_api_types::program::ProgramError;
-use solana_api_types::{system::create_account, sysvar, AccountMeta, Instruction, Pubkey};
+use solana_api_types::{
+    system::{self, create_account},
+    sysvar, AccountMeta, Instruction, Memcmp, MemcmpEncodedBytes, Pubkey, RpcFilterType,
+};
 
 use crate::{
-    account::{AccountBackend, AccountFields},
+    account::{offchain::Offchain, AccountBackend, AccountFields},
     collections::StaticVec,
     forward_account_backend,
     log::Loggable,
@@ -16,6 +21,10 @@ use crate::{
 
 pub const ID: &Pubkey = &solar_macros::parse_pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 
+/// The associated token account program, which creates each owner's canonical wallet of a mint.
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &Pubkey =
+    &solar_macros::parse_pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
 #[repr(packed)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Mint {
@@ -74,6 +83,39 @@ pub struct Wallet {
     close_authority: Pubkey,
 }
 
+/// Maximum number of signers of a token program multisig.
+pub const MAX_SIGNERS: usize = 11;
+
+#[repr(packed)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Multisig {
+    m: u8,
+    n: u8,
+    is_initialized: bool,
+    signers: [Pubkey; MAX_SIGNERS],
+}
+
+impl Multisig {
+    /// Number of signatures required.
+    pub fn m(&self) -> u8 {
+        self.m
+    }
+
+    /// Number of valid signers.
+    pub fn n(&self) -> u8 {
+        self.n
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    /// The `n` valid signers, a malformed `n` is clamped to `MAX_SIGNERS`.
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.signers[..(self.n as usize).min(MAX_SIGNERS)]
+    }
+}
+
 #[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
 pub enum AccountState {
     Uninitialized,
@@ -135,8 +177,32 @@ impl Wallet {
             None
         }
     }
+
+    /// `getProgramAccounts` filters of the token program matching every wallet of `mint`.
+    pub fn filter_by_mint(mint: &Pubkey) -> Vec<RpcFilterType> {
+        Self::filter_by_key(WALLET_MINT_OFFSET, mint)
+    }
+
+    /// `getProgramAccounts` filters of the token program matching every wallet owned by `owner`.
+    pub fn filter_by_owner(owner: &Pubkey) -> Vec<RpcFilterType> {
+        Self::filter_by_key(WALLET_AUTHORITY_OFFSET, owner)
+    }
+
+    fn filter_by_key(offset: usize, key: &Pubkey) -> Vec<RpcFilterType> {
+        vec![
+            RpcFilterType::DataSize(size_of::<Wallet>() as u64),
+            RpcFilterType::Memcmp(Memcmp {
+                offset,
+                bytes: MemcmpEncodedBytes::Binary(key.to_string()),
+                encoding: None,
+            }),
+        ]
+    }
 }
 
+const WALLET_MINT_OFFSET: usize = 0;
+const WALLET_AUTHORITY_OFFSET: usize = 32;
+
 #[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
 pub enum SplReadError {
     InvalidData,
@@ -149,15 +215,27 @@ pub struct MintAccount<B: AccountBackend> {
     account: B,
 }
 
-impl<B> serde::Serialize for MintAccount<B>
-where
-    B: AccountBackend + serde::Serialize,
-{
+/// Serializes the decoded mint with keys as base58 strings, serialize
+/// [`MintAccount::account`] for the raw account instead.
+impl<B: AccountBackend> serde::Serialize for MintAccount<B> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        self.account.serialize(serializer)
+        let mut state = serializer.serialize_struct("MintAccount", 6)?;
+        state.serialize_field("pubkey", &self.key().to_string())?;
+        state.serialize_field(
+            "mint_authority",
+            &self.mint_authority().map(Pubkey::to_string),
+        )?;
+        state.serialize_field("supply", &self.supply().value())?;
+        state.serialize_field("decimals", &self.decimals())?;
+        state.serialize_field("is_initialized", &self.is_initialized())?;
+        state.serialize_field(
+            "freeze_authority",
+            &self.freeze_authority().map(Pubkey::to_string),
+        )?;
+        state.end()
     }
 }
 
@@ -166,15 +244,30 @@ pub struct WalletAccount<B: AccountBackend> {
     account: B,
 }
 
-impl<B> serde::Serialize for WalletAccount<B>
-where
-    B: AccountBackend + serde::Serialize,
-{
+/// Serializes the decoded wallet with keys as base58 strings, serialize
+/// [`WalletAccount::account`] for the raw account instead.
+impl<B: AccountBackend> serde::Serialize for WalletAccount<B> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        self.account.serialize(serializer)
+        let mut state = serializer.serialize_struct("WalletAccount", 9)?;
+        state.serialize_field("pubkey", &self.key().to_string())?;
+        state.serialize_field("mint", &self.mint().to_string())?;
+        state.serialize_field("authority", &self.authority().to_string())?;
+        state.serialize_field("amount", &self.amount().value())?;
+        state.serialize_field("delegate", &self.delegate().map(Pubkey::to_string))?;
+        state.serialize_field("delegated_amount", &self.delegated_amount().value())?;
+        state.serialize_field("state", <&str>::from(self.state()))?;
+        state.serialize_field(
+            "native_reserve",
+            &self.native_reserve().map(|reserve| reserve.value()),
+        )?;
+        state.serialize_field(
+            "close_authority",
+            &self.close_authority().map(Pubkey::to_string),
+        )?;
+        state.end()
     }
 }
 
@@ -196,6 +289,10 @@ impl<'a, 'b: 'a, B: AccountBackend> MintAccount<B> {
         }
     }
 
+    pub fn account(&self) -> &B {
+        &self.account
+    }
+
     pub fn wallet(&self, account: B) -> Result<WalletAccount<B>, SplReadError> {
         let wallet = WalletAccount::<B>::any(account)?;
 
@@ -227,6 +324,28 @@ impl<B: AccountBackend> WalletAccount<B> {
             Ok(Self { account })
         }
     }
+
+    pub fn account(&self) -> &B {
+        &self.account
+    }
+}
+
+impl<B: AccountBackend<Env = Offchain>> WalletAccount<B> {
+    /// Interpret any account as a token wallet, e.g. to inspect a corrupt account or one owned by
+    /// another program.
+    ///
+    /// Only the size and alignment of the data are validated, the owner is not checked. Nothing
+    /// guarantees the result is a wallet of the token program, so it must not be trusted like one
+    /// loaded with [`WalletAccount::any`], which is why this is only available off-chain.
+    pub fn interpret_unchecked(account: B) -> Result<Self, SplReadError> {
+        let data = account.data();
+
+        if data.len() != size_of::<Wallet>() || !is_valid_for_type::<Wallet>(data) {
+            Err(SplReadError::InvalidData)
+        } else {
+            Ok(Self { account })
+        }
+    }
 }
 
 impl<B: AccountBackend> Deref for WalletAccount<B> {
@@ -286,6 +405,30 @@ impl<T: AccountBackend> TokenProgram<T> {
             seeds,
         ))
     }
+
+    /// Close an empty `wallet`, moving its rent to `destination`.
+    #[inline(never)]
+    pub fn close_account(
+        &self,
+        wallet: &mut WalletAccount<T>,
+        destination: &mut T,
+        authority: &T,
+        seeds: &[&[&[u8]]],
+    ) -> Result<Result<(), TokenError>, ProgramError>
+    where
+        T: AccountBackend<Impl = crate::account::onchain::Account>,
+    {
+        let mut invoker = crate::invoke::Invoker::<4>::new();
+        invoker.push(wallet);
+        invoker.push(destination);
+        invoker.push_signed(authority);
+
+        Self::handle_result(invoker.invoke_signed(
+            self.backend(),
+            &TokenInstruction::CloseAccount.pack_static_vec(),
+            seeds,
+        ))
+    }
 }
 
 forward_account_backend!(TokenProgram, account);
@@ -396,12 +539,40 @@ fn write_pubkey<W: Write>(mut writer: W, pubkey: &Pubkey) -> std::io::Result<()>
 fn write_pubkey_option<W: Write>(mut writer: W, pubkey: &Option<Pubkey>) -> std::io::Result<()> {
     use byteorder::WriteBytesExt;
     if let Some(pubkey) = pubkey {
+        writer.write_u8(1)?;
         write_pubkey(writer, pubkey)
     } else {
         writer.write_u8(0)
     }
 }
 
+fn read_pubkey(data: &mut &[u8]) -> Option<Pubkey> {
+    let bytes = data.get(..32)?;
+    *data = &data[32..];
+    Some(Pubkey::new(bytes.try_into().ok()?))
+}
+
+fn read_pubkey_option(data: &mut &[u8]) -> Option<Option<Pubkey>> {
+    use byteorder::ReadBytesExt;
+    match data.read_u8().ok()? {
+        0 => Some(None),
+        1 => read_pubkey(data).map(Some),
+        _ => None,
+    }
+}
+
+impl AuthorityType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(AuthorityType::MintTokens),
+            1 => Some(AuthorityType::FreezeAccount),
+            2 => Some(AuthorityType::AccountOwner),
+            3 => Some(AuthorityType::CloseAccount),
+            _ => None,
+        }
+    }
+}
+
 impl TokenInstruction {
     #[inline]
     pub fn id(&self) -> u8 {
@@ -438,6 +609,73 @@ impl TokenInstruction {
         vec
     }
 
+    /// Decode instruction data written by [`TokenInstruction::write`], `None` if it isn't a
+    /// valid token instruction. Trailing bytes are ignored, like the token program does.
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        use byteorder::ReadBytesExt;
+        use byteorder::LE;
+
+        let mut rest = data;
+        let data = &mut rest;
+        let amount_and_decimals = |data: &mut &[u8]| -> Option<(u64, u8)> {
+            Some((data.read_u64::<LE>().ok()?, data.read_u8().ok()?))
+        };
+
+        let instruction = match data.read_u8().ok()? {
+            0 => TokenInstruction::InitializeMint {
+                decimals: data.read_u8().ok()?,
+                mint_authority: read_pubkey(data)?,
+                freeze_authority: read_pubkey_option(data)?,
+            },
+            1 => TokenInstruction::InitializeAccount,
+            2 => TokenInstruction::InitializeMultisig {
+                m: data.read_u8().ok()?,
+            },
+            3 => TokenInstruction::Transfer {
+                amount: data.read_u64::<LE>().ok()?,
+            },
+            4 => TokenInstruction::Approve {
+                amount: data.read_u64::<LE>().ok()?,
+            },
+            5 => TokenInstruction::Revoke,
+            6 => TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::from_u8(data.read_u8().ok()?)?,
+                new_authority: read_pubkey_option(data)?,
+            },
+            7 => TokenInstruction::MintTo {
+                amount: data.read_u64::<LE>().ok()?,
+            },
+            8 => TokenInstruction::Burn {
+                amount: data.read_u64::<LE>().ok()?,
+            },
+            9 => TokenInstruction::CloseAccount,
+            10 => TokenInstruction::FreezeAccount,
+            11 => TokenInstruction::ThawAccount,
+            12 => {
+                let (amount, decimals) = amount_and_decimals(data)?;
+                TokenInstruction::TransferChecked { amount, decimals }
+            }
+            13 => {
+                let (amount, decimals) = amount_and_decimals(data)?;
+                TokenInstruction::ApproveChecked { amount, decimals }
+            }
+            14 => {
+                let (amount, decimals) = amount_and_decimals(data)?;
+                TokenInstruction::MintToChecked { amount, decimals }
+            }
+            15 => {
+                let (amount, decimals) = amount_and_decimals(data)?;
+                TokenInstruction::BurnChecked { amount, decimals }
+            }
+            16 => TokenInstruction::InitializeAccount2 {
+                owner: read_pubkey(data)?,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
         use byteorder::WriteBytesExt;
         use byteorder::LE;
@@ -501,6 +739,7 @@ pub fn create_mint(
     payer: &Pubkey,
     mint: &Pubkey,
     authority: &Pubkey,
+    freeze_authority: Option<&Pubkey>,
     decimals: u8,
 ) -> [Instruction; 2] {
     [
@@ -511,7 +750,7 @@ pub fn create_mint(
             size_of::<Mint>() as u64,
             ID,
         ),
-        initialize_mint(mint, authority, decimals),
+        initialize_mint(mint, authority, freeze_authority, decimals),
     ]
 }
 
@@ -533,6 +772,34 @@ pub fn create_wallet(
     ]
 }
 
+/// Associated wallet of `owner` for `mint` and its bump seed, derived the way the associated token
+/// account program does.
+pub fn find_associated_wallet(owner: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), ID.as_ref(), mint.as_ref()],
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+}
+
+/// Create the associated wallet of `owner` for `mint`, funded by `payer`.
+pub fn create_associated_wallet(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    let (wallet, _) = find_associated_wallet(owner, mint);
+
+    Instruction {
+        program_id: *ASSOCIATED_TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(wallet, false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*system::ID, false),
+            AccountMeta::new_readonly(*ID, false),
+            AccountMeta::new_readonly(*sysvar::rent::ID, false),
+        ],
+        data: vec![],
+    }
+}
+
 pub fn mint_to(mint: &Pubkey, wallet: &Pubkey, authority: &Pubkey, amount: u64) -> Instruction {
     Instruction {
         program_id: *ID,
@@ -545,7 +812,32 @@ pub fn mint_to(mint: &Pubkey, wallet: &Pubkey, authority: &Pubkey, amount: u64)
     }
 }
 
-pub fn initialize_mint(mint: &Pubkey, authority: &Pubkey, decimals: u8) -> Instruction {
+/// Like [`mint_to`], but mints no more of `amount` than takes the supply `state` of `mint` up to
+/// `cap`, so re-running a script that mints towards a target supply doesn't over-issue. `None`
+/// once the supply has reached the cap.
+pub fn mint_to_cap(
+    mint: &Pubkey,
+    state: &Mint,
+    wallet: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    cap: u64,
+) -> Option<Instruction> {
+    let amount = amount.min(cap.saturating_sub(state.supply().value()));
+    if amount == 0 {
+        return None;
+    }
+
+    Some(mint_to(mint, wallet, authority, amount))
+}
+
+/// Initialize `mint`, without a freeze authority its wallets can never be frozen.
+pub fn initialize_mint(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    freeze_authority: Option<&Pubkey>,
+    decimals: u8,
+) -> Instruction {
     Instruction {
         program_id: *ID,
         accounts: vec![
@@ -555,7 +847,7 @@ pub fn initialize_mint(mint: &Pubkey, authority: &Pubkey, decimals: u8) -> Instr
         data: TokenInstruction::InitializeMint {
             decimals,
             mint_authority: *authority,
-            freeze_authority: None,
+            freeze_authority: freeze_authority.copied(),
         }
         .pack_vec(),
     }
@@ -573,3 +865,226 @@ pub fn initialize_wallet(wallet: &Pubkey, mint: &Pubkey, authority: &Pubkey) ->
         data: TokenInstruction::InitializeAccount.pack_vec(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use serde_json::json;
+    use solana_api_types::{Account, Memcmp, MemcmpEncodedBytes, Pubkey, RpcFilterType};
+
+    use super::{
+        create_associated_wallet, find_associated_wallet, AuthorityType, SplReadError,
+        TokenInstruction, Wallet, WalletAccount, ID,
+    };
+    use crate::reinterpret::reinterpret_unchecked;
+
+    /// Write the key matched by `filters` into empty wallet data and read the wallet back.
+    fn apply(filters: &[RpcFilterType]) -> Wallet {
+        assert_eq!(filters.len(), 2);
+        assert_eq!(
+            filters[0],
+            RpcFilterType::DataSize(size_of::<Wallet>() as u64)
+        );
+
+        let (offset, key) = match &filters[1] {
+            RpcFilterType::Memcmp(Memcmp {
+                offset,
+                bytes: MemcmpEncodedBytes::Binary(key),
+                encoding: None,
+            }) => (*offset, key.parse::<Pubkey>().unwrap()),
+            filter => panic!("unexpected filter {:?}", filter),
+        };
+
+        let mut data = vec![0; 165];
+        data[offset..offset + 32].copy_from_slice(key.as_ref());
+        unsafe { *reinterpret_unchecked::<Wallet>(&data) }
+    }
+
+    #[test]
+    fn wallet_filter_test() {
+        assert_eq!(size_of::<Wallet>(), 165);
+
+        let key = Pubkey::new([7; 32]);
+
+        let wallet = apply(&Wallet::filter_by_mint(&key));
+        assert_eq!(wallet.mint(), &key);
+        assert_eq!(wallet.authority(), &Pubkey::default());
+
+        let wallet = apply(&Wallet::filter_by_owner(&key));
+        assert_eq!(wallet.authority(), &key);
+        assert_eq!(wallet.mint(), &Pubkey::default());
+    }
+
+    #[test]
+    fn interpret_unchecked_test() {
+        let mint = Pubkey::new([7; 32]);
+        let mut data = vec![0; size_of::<Wallet>()];
+        data[..32].copy_from_slice(mint.as_ref());
+
+        let account = Box::new(Account {
+            data,
+            owner: Pubkey::new([1; 32]),
+            pubkey: Pubkey::new([2; 32]),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            WalletAccount::any(account.clone()).unwrap_err(),
+            SplReadError::InvalidOwner
+        );
+
+        let wallet = WalletAccount::interpret_unchecked(account.clone()).unwrap();
+        assert_eq!(wallet.mint(), &mint);
+
+        let mut truncated = account;
+        truncated.data.pop();
+        assert_eq!(
+            WalletAccount::interpret_unchecked(truncated).unwrap_err(),
+            SplReadError::InvalidData
+        );
+    }
+
+    #[test]
+    fn associated_wallet_test() {
+        let owner: Pubkey = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"
+            .parse()
+            .unwrap();
+        let vectors = [
+            (
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B",
+                254,
+            ),
+            (
+                "So11111111111111111111111111111111111111112",
+                "8LjUgMjzZuHj8VdyxzkmLLQVmW4C3gd56md1nLd76TNW",
+                255,
+            ),
+        ];
+
+        for (mint, wallet, bump) in vectors.iter() {
+            let mint: Pubkey = mint.parse().unwrap();
+            assert_eq!(
+                find_associated_wallet(&owner, &mint),
+                (wallet.parse::<Pubkey>().unwrap(), *bump)
+            );
+
+            let instruction = create_associated_wallet(&owner, &owner, &mint);
+            assert_eq!(
+                instruction.accounts[1].pubkey,
+                wallet.parse::<Pubkey>().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn wallet_json_test() {
+        let mint = Pubkey::new([7; 32]);
+        let authority = Pubkey::new([8; 32]);
+        let mut data = vec![0; size_of::<Wallet>()];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(authority.as_ref());
+        data[64..72].copy_from_slice(&1_500u64.to_le_bytes());
+        data[108] = 1;
+
+        let wallet = WalletAccount::any(Box::new(Account {
+            data,
+            owner: *ID,
+            pubkey: Pubkey::new([2; 32]),
+            ..Default::default()
+        }))
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&wallet).unwrap(),
+            json!({
+                "pubkey": Pubkey::new([2; 32]).to_string(),
+                "mint": mint.to_string(),
+                "authority": authority.to_string(),
+                "amount": 1_500,
+                "delegate": null,
+                "delegated_amount": 0,
+                "state": "Initialized",
+                "native_reserve": null,
+                "close_authority": null,
+            })
+        );
+
+        // the raw account is still available
+        let raw = serde_json::to_value(wallet.account()).unwrap();
+        assert_eq!(raw["data"].as_array().unwrap().len(), size_of::<Wallet>());
+    }
+
+    #[test]
+    fn token_instruction_unpack_test() {
+        let key = Pubkey::new([5; 32]);
+        let instructions = vec![
+            TokenInstruction::InitializeMint {
+                decimals: 6,
+                mint_authority: key,
+                freeze_authority: None,
+            },
+            TokenInstruction::InitializeMint {
+                decimals: 9,
+                mint_authority: key,
+                freeze_authority: Some(Pubkey::new([6; 32])),
+            },
+            TokenInstruction::InitializeAccount,
+            TokenInstruction::InitializeMultisig { m: 2 },
+            TokenInstruction::Transfer { amount: 100 },
+            TokenInstruction::Approve { amount: 200 },
+            TokenInstruction::Revoke,
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::CloseAccount,
+                new_authority: Some(key),
+            },
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::MintTokens,
+                new_authority: None,
+            },
+            TokenInstruction::MintTo { amount: 1000 },
+            TokenInstruction::Burn { amount: u64::MAX },
+            TokenInstruction::CloseAccount,
+            TokenInstruction::FreezeAccount,
+            TokenInstruction::ThawAccount,
+            TokenInstruction::TransferChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::ApproveChecked {
+                amount: 3,
+                decimals: 4,
+            },
+            TokenInstruction::MintToChecked {
+                amount: 5,
+                decimals: 6,
+            },
+            TokenInstruction::BurnChecked {
+                amount: 7,
+                decimals: 8,
+            },
+            TokenInstruction::InitializeAccount2 { owner: key },
+        ];
+
+        for instruction in instructions {
+            let data = instruction.pack_vec();
+            assert_eq!(TokenInstruction::unpack(&data), Some(instruction.clone()));
+            assert_eq!(&data[..], &instruction.pack_static_vec()[..]);
+        }
+
+        // the layout the token program expects
+        assert_eq!(
+            TokenInstruction::Transfer { amount: 100 }.pack_vec(),
+            [&[3][..], &100u64.to_le_bytes()].concat()
+        );
+
+        assert_eq!(TokenInstruction::unpack(&[]), None);
+        assert_eq!(TokenInstruction::unpack(&[17]), None);
+        // truncated amount
+        assert_eq!(TokenInstruction::unpack(&[7, 1, 0, 0]), None);
+        // invalid option tag and authority type
+        assert_eq!(TokenInstruction::unpack(&[6, 0, 2]), None);
+        assert_eq!(TokenInstruction::unpack(&[6, 4, 0]), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{convert::TryInto, mem::size_of, ops::Deref};
+
+use solana_api_types::{
+    sysvar::{
+        epoch_schedule::{self, EpochSchedule},
+        rent::{self, Rent},
+        slot_hashes,
+    },
+    Hash, Slot,
+};
+
+use crate::{
+    account::{AccountBackend, AccountFields},
+    forward_account_backend,
+    reinterpret::reinterpret_slice_unchecked,
+    util::{minimum_balance_for, pubkey_eq},
+};
+
+#[derive(IntoStaticStr, Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum SysvarError {
+    InvalidKey,
+    InvalidData,
+}
+
+/// The `Rent` sysvar account, as passed to an instruction.
+///
+/// Unlike [`crate::util::minimum_balance`], which assumes the default rent parameters, this uses
+/// the values currently configured on the cluster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RentSysvar<B: AccountBackend> {
+    account: B,
+    rent: Rent,
+}
+
+impl<B: AccountBackend> RentSysvar<B> {
+    /// Size of the bincode encoded `Rent` struct.
+    pub const DATA_LEN: usize = 17;
+
+    pub fn any(account: B) -> Result<Self, SysvarError> {
+        if !pubkey_eq(account.key(), &*rent::ID) {
+            return Err(SysvarError::InvalidKey);
+        }
+
+        let data = account.data();
+        if data.len() < Self::DATA_LEN {
+            return Err(SysvarError::InvalidData);
+        }
+
+        let rent = Rent {
+            lamports_per_byte_year: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+            exemption_threshold: f64::from_le_bytes(data[8..16].try_into().unwrap()),
+            burn_percent: data[16],
+        };
+
+        Ok(Self { account, rent })
+    }
+
+    pub fn rent(&self) -> &Rent {
+        &self.rent
+    }
+
+    /// Minimum balance for an account with `data_len` bytes of data to be rent exempt.
+    pub fn minimum_balance(&self, data_len: u64) -> u64 {
+        minimum_balance_for(&self.rent, data_len)
+    }
+
+    pub fn is_exempt(&self, lamports: u64, data_len: u64) -> bool {
+        lamports >= self.minimum_balance(data_len)
+    }
+}
+
+/// The `EpochSchedule` sysvar account, as passed to an instruction.
+///
+/// Derefs to the parsed [`EpochSchedule`], so `get_epoch(slot)` and
+/// `get_first_slot_in_epoch(epoch)` can be called on it directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochScheduleSysvar<B: AccountBackend> {
+    account: B,
+    schedule: EpochSchedule,
+}
+
+impl<B: AccountBackend> EpochScheduleSysvar<B> {
+    /// Size of the bincode encoded `EpochSchedule` struct.
+    pub const DATA_LEN: usize = 33;
+
+    pub fn any(account: B) -> Result<Self, SysvarError> {
+        if !pubkey_eq(account.key(), &*epoch_schedule::ID) {
+            return Err(SysvarError::InvalidKey);
+        }
+
+        let data = account.data();
+        if data.len() < Self::DATA_LEN {
+            return Err(SysvarError::InvalidData);
+        }
+
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let warmup = match data[16] {
+            0 => false,
+            1 => true,
+            _ => return Err(SysvarError::InvalidData),
+        };
+
+        let schedule = EpochSchedule {
+            slots_per_epoch: u64_at(0),
+            leader_schedule_slot_offset: u64_at(8),
+            warmup,
+            first_normal_epoch: u64_at(17),
+            first_normal_slot: u64_at(25),
+        };
+
+        Ok(Self { account, schedule })
+    }
+}
+
+impl<B: AccountBackend> Deref for EpochScheduleSysvar<B> {
+    type Target = EpochSchedule;
+
+    fn deref(&self) -> &Self::Target {
+        &self.schedule
+    }
+}
+
+/// A single entry of the `SlotHashes` sysvar, laid out as in the account data.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotHash {
+    slot: [u8; 8],
+    pub hash: Hash,
+}
+
+impl SlotHash {
+    pub fn slot(&self) -> Slot {
+        Slot::from_le_bytes(self.slot)
+    }
+}
+
+/// The `SlotHashes` sysvar account, as passed to an instruction.
+///
+/// Entries are read in place from the account data, most recent slot first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotHashesSysvar<B: AccountBackend> {
+    account: B,
+    len: usize,
+}
+
+impl<B: AccountBackend> SlotHashesSysvar<B> {
+    pub fn any(account: B) -> Result<Self, SysvarError> {
+        if !pubkey_eq(account.key(), &*slot_hashes::ID) {
+            return Err(SysvarError::InvalidKey);
+        }
+
+        let data = account.data();
+        let len = data
+            .get(0..8)
+            .map(|len| u64::from_le_bytes(len.try_into().unwrap()) as usize)
+            .ok_or(SysvarError::InvalidData)?;
+
+        let entries_len = len
+            .checked_mul(size_of::<SlotHash>())
+            .ok_or(SysvarError::InvalidData)?;
+        if len > slot_hashes::MAX_ENTRIES || data.len() - 8 < entries_len {
+            return Err(SysvarError::InvalidData);
+        }
+
+        Ok(Self { account, len })
+    }
+
+    /// All entries, most recent slot first.
+    pub fn entries(&self) -> &[SlotHash] {
+        let data = &self.account.data()[8..8 + self.len * size_of::<SlotHash>()];
+
+        // SlotHash is byte aligned and `any` checked the data holds `len` entries
+        unsafe { reinterpret_slice_unchecked(data) }
+    }
+
+    pub fn most_recent(&self) -> Option<&SlotHash> {
+        self.entries().first()
+    }
+
+    /// Hash of `slot`, if it's still among the recent slots.
+    pub fn get(&self, slot: Slot) -> Option<&Hash> {
+        self.entries()
+            .binary_search_by(|entry| slot.cmp(&entry.slot()))
+            .ok()
+            .map(|index| &self.entries()[index].hash)
+    }
+}
+
+forward_account_backend!(RentSysvar, account);
+forward_account_backend!(EpochScheduleSysvar, account);
+forward_account_backend!(SlotHashesSysvar, account);
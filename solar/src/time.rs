@@ -1,4 +1,9 @@
+use std::fmt;
+
 use chrono::{DateTime, Duration, TimeZone, Utc};
+use solana_api_types::UnixTimestamp;
+
+use crate::util::timestamp_now;
 
 #[repr(C)]
 #[derive(
@@ -17,6 +22,86 @@ pub struct SolTimestamp {
     ts: i64,
 }
 
+impl SolTimestamp {
+    pub const fn from_unix_timestamp(ts: UnixTimestamp) -> Self {
+        Self { ts }
+    }
+
+    pub const fn unix_timestamp(&self) -> UnixTimestamp {
+        self.ts
+    }
+
+    /// Current cluster time plus `duration`, read from the `Clock` sysvar.
+    pub fn now_plus(duration: SolDuration) -> Option<Self> {
+        Self::from(timestamp_now().value()).checked_add(duration)
+    }
+
+    pub fn checked_add(self, duration: SolDuration) -> Option<Self> {
+        self.ts.checked_add(duration.value).map(Self::from)
+    }
+
+    pub fn checked_sub(self, duration: SolDuration) -> Option<Self> {
+        self.ts.checked_sub(duration.value).map(Self::from)
+    }
+
+    /// Time elapsed from `earlier` to `self`, negative if `earlier` is actually later.
+    pub fn duration_since(self, earlier: SolTimestamp) -> Option<SolDuration> {
+        self.ts.checked_sub(earlier.ts).map(SolDuration::from)
+    }
+
+    pub fn is_before(&self, other: SolTimestamp) -> bool {
+        self.ts < other.ts
+    }
+
+    pub fn is_after(&self, other: SolTimestamp) -> bool {
+        self.ts > other.ts
+    }
+
+    /// Whether `self` is at or before `now`.
+    pub fn has_passed(&self, now: SolTimestamp) -> bool {
+        self.ts <= now.ts
+    }
+}
+
+/// Formats as an ISO-8601 UTC date, e.g. `2023-11-14T22:13:20Z`.
+///
+/// Implemented by hand so it doesn't pull chrono's formatting into on-chain builds.
+impl fmt::Display for SolTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.ts.div_euclid(86400);
+        let secs = self.ts.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60
+        )
+    }
+}
+
+/// Converts days since the unix epoch into a proleptic Gregorian `(year, month, day)`.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
 impl From<i64> for SolTimestamp {
     fn from(ts: i64) -> Self {
         Self { ts }
@@ -1,4 +1,4 @@
-use std::{borrow::Borrow, mem::size_of};
+use std::borrow::Borrow;
 
 use chrono::{DateTime, TimeZone, Utc};
 use fixed::{traits::ToFixed, types::U64F64};
@@ -8,7 +8,7 @@ use solana_api_types::{
     Pubkey,
 };
 
-use crate::{log::Loggable, math::Checked, mem::memcmp, qlog};
+use crate::{account::AccountFieldsMut, log::Loggable, math::Checked, mem::memset, qlog};
 
 #[macro_export]
 macro_rules! bytecode_marker {
@@ -41,15 +41,22 @@ impl<T: Borrow<Pubkey>> AsPubkey for T {
     }
 }
 
+/// Compare two pubkeys, meant for every key check of a program such as authorities, owners and
+/// mints.
+///
+/// The keys are compared as four words and the differences are accumulated without branching,
+/// so the time taken does not depend on where they differ.
 #[cfg_attr(target_arch = "bpf", inline(never))]
 pub fn pubkey_eq<A: AsPubkey, B: AsPubkey>(a: A, b: B) -> bool {
-    unsafe {
-        memcmp(
-            a.as_pubkey().as_ref().as_ptr(),
-            b.as_pubkey().as_ref().as_ptr(),
-            32,
-        ) == 0
+    let (a, _) = a.as_pubkey().as_ref().as_chunks::<8>();
+    let (b, _) = b.as_pubkey().as_ref().as_chunks::<8>();
+
+    let mut diff = 0;
+    for (a, b) in a.iter().zip(b) {
+        diff |= u64::from_ne_bytes(*a) ^ u64::from_ne_bytes(*b);
     }
+
+    diff == 0
 }
 
 pub trait ResultExt<T, E> {
@@ -165,53 +172,56 @@ impl<T> ResultExt<T, ()> for Option<T> {
     }
 }
 
+/// Whether every byte of `slice` is zero.
+///
+/// Accounts are zero-filled when they are created, so this is what tells an account allocated
+/// for the program apart from one that is already in use. Call it before treating an account as
+/// uninitialized, an empty entity header alone doesn't rule out data left in the body.
+///
+/// The aligned part of the slice is scanned a `u64` at a time without branching, which takes 8
+/// times less opcodes on BPF and lets the compiler vectorize the loop elsewhere.
 #[cfg_attr(target_arch = "bpf", inline(never))]
 pub fn is_zeroed(slice: &[u8]) -> bool {
-    if cfg!(target_arch = "bpf") {
-        const ALIGNMENT: usize = size_of::<u64>();
-        const BATCH_SIZE: usize = 64;
-
-        unsafe {
-            let mut acc: u64 = 0;
-            let start_misalign = slice.as_ptr() as usize % ALIGNMENT;
-            let mut ptr = slice.as_ptr();
-            let end_ptr = slice.as_ptr().add(slice.len());
-
-            if start_misalign > 0 {
-                for _ in 0..(ALIGNMENT - start_misalign) {
-                    acc |= ptr.read() as u64;
-                    ptr = ptr.add(1);
-                }
-            }
-
-            // compare using 64-bit operations, takes 8 times less opcodes than if we were using u8's
-            while end_ptr as usize - ptr as usize >= BATCH_SIZE {
-                let aligned_ptr = ptr.cast::<u64>();
-
-                // loop will be unrolled by optimizer, less overhead for branching
-                for i in 0..BATCH_SIZE / 8 {
-                    acc |= aligned_ptr.add(i).read();
-                }
+    const BATCH_WORDS: usize = 8;
 
-                ptr = ptr.add(BATCH_SIZE);
-            }
+    // any bit pattern is a valid u64
+    let (prefix, words, suffix) = unsafe { slice.align_to::<u64>() };
+    let (batches, rest) = words.as_chunks::<BATCH_WORDS>();
 
-            while end_ptr as usize - ptr as usize > 0 {
-                acc |= ptr.read() as u64;
-                ptr = ptr.add(1);
-            }
+    let mut acc = prefix
+        .iter()
+        .chain(suffix)
+        .fold(0, |acc, byte| acc | *byte as u64);
 
-            acc == 0
-        }
-    } else {
-        for b in slice {
-            if *b != 0 {
-                return false;
-            }
+    for batch in batches {
+        // loop will be unrolled by optimizer, less overhead for branching
+        for word in batch {
+            acc |= word;
         }
+    }
 
-        true
+    for word in rest {
+        acc |= word;
     }
+
+    acc == 0
+}
+
+/// Close `account` by moving all of its lamports to `beneficiary` and zeroing its data.
+///
+/// The runtime only purges an account without lamports once the transaction is over, until then
+/// a later instruction can fund it again. Zeroed data makes such an account pass [`is_zeroed`] as
+/// if it was just allocated, instead of reviving the state it was closed with.
+pub fn close_account<A, B>(account: &mut A, beneficiary: &mut B)
+where
+    A: AccountFieldsMut,
+    B: AccountFieldsMut,
+{
+    beneficiary.set_lamports(beneficiary.lamports() + account.lamports());
+    account.set_lamports(0);
+
+    let data = account.data_mut();
+    unsafe { memset(data.as_mut_ptr(), 0, data.len()) };
 }
 
 pub fn timestamp_now() -> Checked<i64> {
@@ -223,8 +233,12 @@ pub fn datetime_now() -> DateTime<Utc> {
 }
 
 pub fn minimum_balance(size: u64) -> u64 {
+    minimum_balance_for(&Rent::default(), size)
+}
+
+/// Rent exempt balance of an account with `size` bytes of data, given the `rent` parameters.
+pub fn minimum_balance_for(rent: &Rent, size: u64) -> u64 {
     pub const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
-    let rent = Rent::default();
     let exemption_threshold = rent.exemption_threshold.to_fixed::<U64F64>();
     let per_year_cost =
         ((ACCOUNT_STORAGE_OVERHEAD + size) * rent.lamports_per_byte_year).to_fixed::<U64F64>();
@@ -234,11 +248,95 @@ pub fn minimum_balance(size: u64) -> u64 {
 }
 
 pub fn is_rent_exempt_fixed_arithmetic(rent: &Rent, lamports: u64, size: u64) -> bool {
-    pub const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
-    let exemption_threshold = rent.exemption_threshold.to_fixed::<U64F64>();
-    let per_year_cost =
-        ((ACCOUNT_STORAGE_OVERHEAD + size) * rent.lamports_per_byte_year).to_fixed::<U64F64>();
-    let minimum_balance: u64 = (exemption_threshold * per_year_cost).to_num::<u64>();
+    lamports >= minimum_balance_for(rent, size)
+}
 
-    lamports >= minimum_balance
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use solana_api_types::Pubkey;
+    use test::{black_box, Bencher};
+
+    use super::{is_zeroed, pubkey_eq};
+
+    /// Zeroed bytes backed by `u64`s, so slicing at offsets 0..8 covers every misalignment.
+    fn aligned_zeroes(len: usize) -> Vec<u64> {
+        vec![0; (len + 7) / 8]
+    }
+
+    fn as_bytes_mut(words: &mut [u64]) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(words.as_mut_ptr().cast(), words.len() * 8) }
+    }
+
+    #[test]
+    fn is_zeroed_test() {
+        let mut words = aligned_zeroes(256);
+        let bytes = as_bytes_mut(&mut words);
+
+        for start in 1..9 {
+            for len in [0, 1, 7, 8, 9, 63, 64, 65, 127, 200] {
+                let end = start + len;
+                assert!(is_zeroed(&bytes[start..end]), "{}..{}", start, end);
+
+                // neighbours outside of the slice must not be read
+                bytes[start - 1] = 1;
+                bytes[end] = 1;
+                assert!(is_zeroed(&bytes[start..end]), "{}..{}", start, end);
+                bytes[start - 1] = 0;
+                bytes[end] = 0;
+
+                for i in start..end {
+                    bytes[i] = 0x80;
+                    assert!(
+                        !is_zeroed(&bytes[start..end]),
+                        "{}..{} at {}",
+                        start,
+                        end,
+                        i
+                    );
+                    bytes[i] = 0;
+                }
+            }
+        }
+    }
+
+    #[bench]
+    fn is_zeroed_aligned_bench(b: &mut Bencher) {
+        let mut words = aligned_zeroes(10 * 1024);
+        let bytes = as_bytes_mut(&mut words);
+
+        b.iter(|| is_zeroed(black_box(&bytes[..])));
+    }
+
+    #[bench]
+    fn is_zeroed_unaligned_bench(b: &mut Bencher) {
+        let mut words = aligned_zeroes(10 * 1024 + 8);
+        let bytes = as_bytes_mut(&mut words);
+
+        b.iter(|| is_zeroed(black_box(&bytes[3..10 * 1024 + 3])));
+    }
+
+    #[test]
+    fn pubkey_eq_test() {
+        let key = Pubkey::new([7; 32]);
+        assert!(pubkey_eq(&key, &Pubkey::new([7; 32])));
+        assert!(pubkey_eq(Pubkey::default(), Pubkey::default()));
+        assert!(!pubkey_eq(&key, &Pubkey::new([8; 32])));
+
+        // a difference in any byte is caught, including the first and last of each word
+        for i in 0..32 {
+            let mut bytes = *key.as_bytes();
+            bytes[i] ^= 1;
+            assert!(!pubkey_eq(&key, &Pubkey::new(bytes)), "byte {}", i);
+        }
+    }
+
+    #[bench]
+    fn pubkey_eq_bench(b: &mut Bencher) {
+        let key = Pubkey::new([7; 32]);
+        let other = key;
+
+        b.iter(|| pubkey_eq(black_box(&key), black_box(&other)));
+    }
 }
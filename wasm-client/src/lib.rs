@@ -14,13 +14,11 @@ use wasm_bindgen_futures::future_to_promise;
 
 use solana_api_types::{
     Account, AccountMeta, Client, ClientError, ClientErrorKind, EncodedConfirmedTransaction,
-    Instruction, Memcmp, MemcmpEncodedBytes, Pubkey, RpcAccountInfoConfig, RpcError, RpcFilterType,
-    RpcKeyedAccount, RpcProgramAccountsConfig, RpcRecentBlockhash, RpcResponse,
-    RpcSendTransactionConfig, RpcSignaturesForAddressConfig, RpcSimulateTransactionConfig,
-    RpcSimulateTransactionResult, Signature, SignatureInfo, Signer, Slot, Transaction,
-    TransactionStatus, UiAccount,
+    Instruction, Pubkey, RpcAccountInfoConfig, RpcError, RpcKeyedAccount, RpcProgramAccountsConfig,
+    RpcRecentBlockhash, RpcResponse, RpcSendTransactionConfig, RpcSignaturesForAddressConfig,
+    RpcSimulateTransactionConfig, RpcSimulateTransactionResult, Signature, SignatureInfo, Signer,
+    Slot, Transaction, TransactionStatus, UiAccount,
 };
-use x5margin_program::data::EntityKind;
 
 pub trait ResultExt<T> {
     fn into_js_value(self) -> Result<T, JsValue>;
@@ -282,6 +280,20 @@ impl Client for RawApiClient {
         Ok(r)
     }
 
+    async fn get_block(
+        &self,
+        slot: Slot,
+        cfg: Option<solana_api_types::RpcBlockConfig>,
+    ) -> Result<Option<solana_api_types::ConfirmedBlock>, solana_api_types::ClientError> {
+        let cfg = solana_api_types::RpcBlockConfig::with_binary_encoding(cfg);
+
+        self.mk_request(Request {
+            method: "getBlock",
+            params: serde_json::json!([slot, serde_json::to_value(&cfg)?]),
+        })
+        .await
+    }
+
     async fn request_airdrop(
         &self,
         pubkey: &Pubkey,
@@ -371,6 +383,21 @@ impl Client for RawApiClient {
 
         Ok(r.value)
     }
+
+    async fn get_health(&self) -> Result<(), ClientError> {
+        let r: String = self
+            .mk_request(Request {
+                method: "getHealth",
+                params: serde_json::json!([]),
+            })
+            .await?;
+
+        if r == "ok" {
+            Ok(())
+        } else {
+            Err(RpcError::ForUser(format!("node is unhealthy: {}", r)).into())
+        }
+    }
 }
 
 fn return_promise<T>(fut: impl Future<Output = Result<T, ClientError>> + 'static) -> Promise
@@ -564,6 +591,14 @@ impl ApiClient {
 
         return_promise(fut)
     }
+
+    pub fn get_health(&self) -> Promise {
+        let client = self.inner.clone();
+
+        let fut = async move { client.get_health().await };
+
+        return_promise(fut)
+    }
 }
 
 #[wasm_bindgen]
@@ -610,37 +645,16 @@ pub struct RawPoolClient {
 
 impl RawPoolClient {
     async fn get_pools(&self, program: Pubkey) -> Result<Vec<StakePoolEntity>, ClientError> {
-        let accounts = self
-            .inner
-            .get_program_accounts(
-                program,
-                Some(RpcProgramAccountsConfig {
-                    filters: Some(vec![
-                        // header size up to kind
-                        RpcFilterType::DataSize(49),
-                        // skip all the parts that are not kind
-                        RpcFilterType::Memcmp(Memcmp {
-                            offset: 48,
-                            // and filter out pools only
-                            bytes: MemcmpEncodedBytes::Binary(
-                                bs58::encode([EntityKind::SimpleStakePool as u8]).into_string(),
-                            ),
-                            encoding: None,
-                        }),
-                    ]),
-                    account_config: RpcAccountInfoConfig::default(),
-                    with_context: None,
-                }),
-            )
-            .await?;
+        let found =
+            x5margin_program::simple_stake::StakePoolEntity::all(&self.inner, &program).await?;
 
         let mut pools = vec![];
 
-        for account in accounts.into_iter() {
-            let pool = StakePoolEntity::load(&program, Box::new(account))
-                .map_err(|err| ClientError::from(ClientErrorKind::Custom(err.to_string())))?;
+        for (_, pool) in found.into_iter() {
+            let entity =
+                pool.map_err(|err| ClientError::from(ClientErrorKind::Custom(err.to_string())))?;
 
-            pools.push(pool);
+            pools.push(StakePoolEntity { entity, program });
         }
 
         Ok(pools)
@@ -975,8 +989,21 @@ impl Instructions {
 }
 
 #[wasm_bindgen]
-pub fn create_mint(payer: Pk, mint: Pk, authority: Pk, decimals: u8) -> Instructions {
-    solar::spl::create_mint(payer.as_ref(), mint.as_ref(), authority.as_ref(), decimals).into()
+pub fn create_mint(
+    payer: Pk,
+    mint: Pk,
+    authority: Pk,
+    freeze_authority: Option<Pk>,
+    decimals: u8,
+) -> Instructions {
+    solar::spl::create_mint(
+        payer.as_ref(),
+        mint.as_ref(),
+        authority.as_ref(),
+        freeze_authority.as_ref().map(Pk::as_ref),
+        decimals,
+    )
+    .into()
 }
 
 #[wasm_bindgen]
@@ -1021,24 +1048,11 @@ pub struct ProgramAuthority {
 
 impl ProgramAuthority {
     pub fn new(key: Pk, administrator_key: Pk, program_id: Pk) -> Self {
-        let mut salt: u64 = 0;
-        let pk = loop {
-            let pk = Pubkey::create_program_address(
-                &[
-                    key.as_ref().as_ref(),
-                    administrator_key.as_ref().as_ref(),
-                    &salt.to_le_bytes(),
-                ],
-                program_id.as_ref(),
-            );
-
-            match pk {
-                Some(s) => break s,
-                None => {
-                    salt += 1;
-                }
-            }
-        };
+        let (pk, salt) = x5margin_program::simple_stake::derive_pool_authority(
+            program_id.as_ref(),
+            key.as_ref(),
+            administrator_key.as_ref(),
+        );
 
         Self { salt, pk: Pk(pk) }
     }
@@ -1095,6 +1109,8 @@ pub fn create_pool_instruction(
                 topup_duration: args.topup_duration.into(),
                 reward_amount: args.reward_amount.into(),
                 target_amount: args.target_amount.into(),
+                flags: 0,
+                reward_tranches: vec![],
             },
         ))
         .encode(),
@@ -1241,6 +1257,7 @@ mod tests {
                     encoding: Some(UiAccountEncoding::Base58),
                     data_slice: None,
                     commitment: None,
+                    min_context_slot: None,
                 }),
             )
             .await